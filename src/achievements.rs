@@ -0,0 +1,123 @@
+use std::collections::HashSet;
+use specs::prelude::*;
+use specs::shrev::ReaderId;
+use rltk::RGB;
+use serde::{Serialize, Deserialize};
+use super::{gamelog::{GameLog, LogCategory}, events::{GameEvents, GameEvent}, replay::Replay, storage};
+
+pub struct Achievement {
+    pub name : &'static str,
+    pub description : &'static str
+}
+
+pub const FIRST_BLOOD : &str = "First Blood";
+pub const DEEP_DELVER : &str = "Deep Delver";
+pub const TEETOTALLER : &str = "Teetotaller";
+
+/// Every achievement there is, in the order the viewer lists them.
+pub const ACHIEVEMENTS : [Achievement; 3] = [
+    Achievement{ name: FIRST_BLOOD, description: "Slay your first foe." },
+    Achievement{ name: DEEP_DELVER, description: "Reach depth 5." },
+    Achievement{ name: TEETOTALLER, description: "Escape with the Amulet without drinking a single potion." }
+];
+
+const DEEP_DELVER_DEPTH : i32 = 5;
+
+/// What the player has achieved over all their runs. It belongs to the player rather than the run,
+/// so it lives in its own file instead of the save game.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Profile {
+    pub unlocked : HashSet<String>,
+    /// Whether item kinds identified on one run are already known at the start of the next.
+    #[serde(default)]
+    pub remember_identifications : bool,
+    /// Every item kind identified so far, over all runs.
+    #[serde(default)]
+    pub identified : HashSet<String>
+}
+
+impl Profile {
+    /// Reads profile.json, starting afresh if there isn't one yet.
+    pub fn load() -> Profile {
+        storage::read("profile.json")
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let data = serde_json::to_string(self).expect("Unable to serialize profile");
+        storage::write("profile.json", &data)
+    }
+
+    pub fn is_unlocked(&self, name : &str) -> bool {
+        self.unlocked.contains(name)
+    }
+
+    /// Unlocks an achievement; true if it wasn't already.
+    fn unlock(&mut self, name : &str) -> bool {
+        self.unlocked.insert(name.to_string())
+    }
+}
+
+/// The things about this run that achievements and the score table depend on but the world doesn't
+/// otherwise remember. Saved with the game.
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+pub struct RunRecord {
+    pub potions_drunk : i32,
+    pub kills : i32,
+    /// The deepest level reached, in whichever branch.
+    pub deepest : i32
+}
+
+/// Keeps the run record up to date, and unlocks achievements as the events that earn them come in.
+#[derive(Default)]
+pub struct AchievementSystem {
+    reader : Option<ReaderId<GameEvent>>
+}
+
+impl<'a> System<'a> for AchievementSystem {
+    type SystemData = ( ReadExpect<'a, GameEvents>,
+                        WriteExpect<'a, Profile>,
+                        WriteExpect<'a, RunRecord>,
+                        WriteExpect<'a, GameLog>,
+                        ReadExpect<'a, Replay> );
+
+    fn setup(&mut self, world : &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(world.write_resource::<GameEvents>().register_reader());
+    }
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (events, mut profile, mut record, mut log, replay) = data;
+
+        let mut earned : Vec<&'static str> = Vec::new();
+        for event in events.read(self.reader.as_mut().expect("AchievementSystem was not set up")) {
+            match event {
+                GameEvent::Died{ foe: true, .. } => {
+                    record.kills += 1;
+                    earned.push(FIRST_BLOOD);
+                }
+                GameEvent::LevelEntered{ depth } => {
+                    record.deepest = i32::max(record.deepest, *depth);
+                    if *depth >= DEEP_DELVER_DEPTH { earned.push(DEEP_DELVER); }
+                }
+                GameEvent::ItemConsumed{ name, by_player: true, .. } if name.contains("Potion") => record.potions_drunk += 1,
+                GameEvent::Victory if record.potions_drunk == 0 => earned.push(TEETOTALLER),
+                _ => {}
+            }
+        }
+
+        // Watching a replay earns nothing
+        if replay.is_playing() { return; }
+        let mut changed = false;
+        for name in earned.iter() {
+            if profile.unlock(name) {
+                log.push_colored(LogCategory::System, format!("Achievement unlocked: {}!", name), RGB::named(rltk::GOLD));
+                changed = true;
+            }
+        }
+        if changed {
+            if let Err(err) = profile.save() { log.push(LogCategory::System, err); }
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use specs::prelude::*;
+use super::{Afflictions, Affliction, AfflictionKind, Attributes, Pools, Name, Purse, RunState, gamesystem::{stamina_at_fitness, mana_at_intelligence},
+            turn_counter::TurnCounter, gamelog::{GameLog, LogCategory}};
+
+/// A disease runs its course after this many turns, if nothing cures it sooner.
+const DISEASE_TURNS : i32 = 1500;
+/// Turns between each point of Fitness a disease wastes away.
+const DISEASE_INTERVAL : i32 = 100;
+/// Turns between a curse's bouts of weakness.
+const CURSE_INTERVAL : i32 = 150;
+const WEAKNESS_TURNS : i32 = 20;
+/// Might lost for the length of a bout of weakness.
+const WEAKNESS_MIGHT : i32 = 4;
+/// No affliction takes an attribute below this.
+const MIN_ATTRIBUTE : i32 = 3;
+
+impl AfflictionKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AfflictionKind::Disease => "Wasting Fever",
+            AfflictionKind::Curse => "Curse of Weakness"
+        }
+    }
+
+    pub fn from_name(name : &str) -> Option<AfflictionKind> {
+        match name {
+            "disease" => Some(AfflictionKind::Disease),
+            "curse" => Some(AfflictionKind::Curse),
+            _ => None
+        }
+    }
+
+    /// The attribute it eats away at.
+    fn attribute<'a>(&self, attributes : &'a mut Attributes) -> &'a mut i32 {
+        match self {
+            AfflictionKind::Disease => &mut attributes.fitness,
+            AfflictionKind::Curse => &mut attributes.might
+        }
+    }
+}
+
+/// Lets diseases waste their victims away and curses sap their strength now and then, once a turn.
+pub struct AfflictionSystem {}
+
+impl<'a> System<'a> for AfflictionSystem {
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, TurnCounter>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, Afflictions>,
+                        WriteStorage<'a, Attributes>,
+                        WriteStorage<'a, Pools> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, player_entity, runstate, turn_counter, mut log, mut afflictions, mut attributes, mut pools) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        let mut recovered : Vec<Entity> = Vec::new();
+        for (entity, afflicted, attr) in (&entities, &mut afflictions, &mut attributes).join() {
+            let is_player = entity == *player_entity;
+            for affliction in afflicted.list.iter_mut() {
+                affliction.countdown -= turn_counter.elapsed();
+                if let Some(turns) = affliction.turns.as_mut() { *turns -= turn_counter.elapsed(); }
+                if affliction.countdown > 0 { continue; }
+
+                let attribute = affliction.kind.attribute(attr);
+                match affliction.kind {
+                    AfflictionKind::Disease => {
+                        affliction.countdown = DISEASE_INTERVAL;
+                        if *attribute > MIN_ATTRIBUTE {
+                            *attribute -= 1;
+                            affliction.drained += 1;
+                            if let Some(pools) = pools.get_mut(entity) { fit_pools(pools, attr); }
+                            if is_player { log.push(LogCategory::Combat, "The fever leaves you weaker.".to_string()); }
+                        }
+                    }
+                    // A curse comes and goes in bouts, giving back what it took at the end of each
+                    AfflictionKind::Curse if affliction.drained > 0 => {
+                        *attribute += affliction.drained;
+                        affliction.drained = 0;
+                        affliction.countdown = CURSE_INTERVAL;
+                        if is_player { log.push(LogCategory::Combat, "Your strength returns.".to_string()); }
+                    }
+                    AfflictionKind::Curse => {
+                        affliction.drained = i32::max(0, i32::min(WEAKNESS_MIGHT, *attribute - MIN_ATTRIBUTE));
+                        *attribute -= affliction.drained;
+                        if affliction.drained > 0 {
+                            affliction.countdown = WEAKNESS_TURNS;
+                            if is_player { log.push(LogCategory::Combat, "A wave of weakness washes over you.".to_string()); }
+                        } else {
+                            affliction.countdown = CURSE_INTERVAL;
+                        }
+                    }
+                }
+            }
+
+            // A disease that has run its course goes, but what it wasted away doesn't come back
+            let before = afflicted.list.len();
+            afflicted.list.retain(|a| a.turns.map_or(true, |t| t > 0));
+            if afflicted.list.len() < before && is_player {
+                log.push(LogCategory::Combat, "Your fever breaks at last.".to_string());
+            }
+            if afflicted.list.is_empty() { recovered.push(entity); }
+        }
+
+        for entity in recovered {
+            afflictions.remove(entity);
+        }
+    }
+}
+
+/// Keeps stamina and mana in line with a Fitness or Intelligence that an affliction has changed.
+fn fit_pools(pools : &mut Pools, attributes : &Attributes) {
+    pools.stamina.max = stamina_at_fitness(attributes.fitness);
+    pools.stamina.current = i32::min(pools.stamina.current, pools.stamina.max);
+    pools.mana.max = mana_at_intelligence(attributes.intelligence);
+    pools.mana.current = i32::min(pools.mana.current, pools.mana.max);
+}
+
+/// Gives the target an affliction, unless they're already suffering from one of its kind.
+pub fn afflict(ecs : &mut World, target : Entity, kind : AfflictionKind) {
+    if ecs.read_storage::<Attributes>().get(target).is_none() { return; }
+    let mut afflictions = ecs.write_storage::<Afflictions>();
+    if afflictions.get(target).map_or(false, |a| a.list.iter().any(|a| a.kind == kind)) { return; }
+
+    let (turns, countdown) = match kind {
+        AfflictionKind::Disease => (Some(DISEASE_TURNS), DISEASE_INTERVAL),
+        AfflictionKind::Curse => (None, CURSE_INTERVAL)
+    };
+    let affliction = Affliction{ kind, turns, countdown, drained: 0 };
+    match afflictions.get_mut(target) {
+        Some(afflicted) => afflicted.list.push(affliction),
+        None => { afflictions.insert(target, Afflictions{ list: vec![affliction] }).expect("Unable to insert afflictions"); }
+    }
+    if let Some(name) = ecs.read_storage::<Name>().get(target) {
+        ecs.write_resource::<GameLog>().push(LogCategory::Combat, format!("{} is afflicted with {}!", name.name, kind.name()));
+    }
+}
+
+/// Lifts an affliction of the given kind, or every one if `kind` is None, giving back whatever they
+/// drained. A temple charges `price` gold for the service, and turns away those who can't pay.
+pub fn cure(ecs : &mut World, target : Entity, kind : Option<AfflictionKind>, price : i32) {
+    let mut afflictions = ecs.write_storage::<Afflictions>();
+    let mut log = ecs.write_resource::<GameLog>();
+    let cured : Vec<Affliction> = match afflictions.get_mut(target) {
+        Some(afflicted) => {
+            let (cured, kept) : (Vec<Affliction>, Vec<Affliction>) = afflicted.list.drain(..).partition(|a| kind.map_or(true, |k| a.kind == k));
+            afflicted.list = kept;
+            cured
+        }
+        None => Vec::new()
+    };
+    if cured.is_empty() {
+        let message = if price > 0 { "The priests find nothing ailing you." } else { "You feel no different." };
+        log.push(LogCategory::Items, message.to_string());
+        return;
+    }
+
+    if price > 0 {
+        let mut purses = ecs.write_storage::<Purse>();
+        match purses.get_mut(target) {
+            Some(purse) if purse.gold >= price => purse.gold -= price,
+            _ => {
+                if let Some(afflicted) = afflictions.get_mut(target) { afflicted.list.extend(cured); }
+                log.push(LogCategory::Items, format!("The priests ask {} gold for their prayers, which you don't have.", price));
+                return;
+            }
+        }
+    }
+
+    let mut attributes = ecs.write_storage::<Attributes>();
+    if let Some(attr) = attributes.get_mut(target) {
+        for affliction in cured.iter() {
+            *affliction.kind.attribute(attr) += affliction.drained;
+            log.push(LogCategory::Items, format!("The {} is lifted from you.", affliction.kind.name()));
+        }
+        if let Some(pools) = ecs.write_storage::<Pools>().get_mut(target) { fit_pools(pools, attr); }
+    }
+    if afflictions.get(target).map_or(false, |a| a.list.is_empty()) {
+        afflictions.remove(target);
+    }
+}
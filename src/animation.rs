@@ -0,0 +1,115 @@
+use std::collections::HashMap;
+use specs::prelude::*;
+use rltk::{Point, PointF, RGB};
+use super::{Position, Renderable, ParticleLifetime, options::Options};
+
+/// How long an entity takes to slide from one tile to the next.
+const SLIDE_MS : f32 = 80.0;
+/// How long a missile takes to cross each tile of its flight.
+const FLIGHT_MS_PER_TILE : f32 = 25.0;
+
+/// An entity part way through a step, drawn between the tile it left and the one it's entering.
+struct Slide {
+    from : PointF,
+    to : Point,
+    elapsed_ms : f32
+}
+
+/// Something thrown or shot, drawn travelling along its flight path.
+struct Missile {
+    path : Vec<Point>,
+    glyph : rltk::FontCharType,
+    fg : RGB,
+    elapsed_ms : f32
+}
+
+/// Purely cosmetic motion laid over the turn-based positions. None of it affects the game; turning
+/// smooth movement off in the options leaves everything drawn where it stands.
+#[derive(Default)]
+pub struct Animations {
+    last_seen : HashMap<Entity, Point>,
+    slides : HashMap<Entity, Slide>,
+    missiles : Vec<Missile>
+}
+
+fn lerp(from : PointF, to : Point, t : f32) -> PointF {
+    PointF::new(from.x + (to.x as f32 - from.x) * t, from.y + (to.y as f32 - from.y) * t)
+}
+
+impl Animations {
+    /// Sends a missile along the path, which begins where it was thrown from.
+    pub fn launch(&mut self, path : Vec<Point>, glyph : rltk::FontCharType, fg : RGB) {
+        if path.len() > 1 {
+            self.missiles.push(Missile{ path, glyph, fg, elapsed_ms: 0.0 });
+        }
+    }
+
+    /// Where the entity should be drawn right now, if it is mid-step.
+    pub fn slide_position(&self, entity : Entity) -> Option<PointF> {
+        self.slides.get(&entity).map(|s| lerp(s.from, s.to, s.elapsed_ms / SLIDE_MS))
+    }
+
+    /// Every missile in flight, with where it is drawn and the tile it is currently over.
+    pub fn missiles(&self) -> Vec<(PointF, Point, rltk::FontCharType, RGB)> {
+        self.missiles.iter().map(|m| {
+            let progress = m.elapsed_ms / FLIGHT_MS_PER_TILE;
+            let step = usize::min(progress as usize, m.path.len() - 2);
+            let at = lerp(PointF::new(m.path[step].x as f32, m.path[step].y as f32), m.path[step + 1], progress - step as f32);
+            (at, m.path[step + 1], m.glyph, m.fg)
+        }).collect()
+    }
+}
+
+/// The glyph for a missile flying from one point to another.
+pub fn missile_glyph(from : Point, to : Point) -> rltk::FontCharType {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let glyph = if dx == 0 { '|' } else if dy == 0 { '-' } else if (dx > 0) == (dy > 0) { '\\' } else { '/' };
+    rltk::to_cp437(glyph)
+}
+
+/// Moves every animation on by a frame, and starts slides for anything that stepped to a
+/// neighbouring tile since the last one. Anything that moved further than that just appears.
+pub fn update(ecs : &World, frame_time_ms : f32) {
+    let enabled = ecs.fetch::<Options>().smooth_movement;
+    let entities = ecs.entities();
+    let positions = ecs.read_storage::<Position>();
+    let renderables = ecs.read_storage::<Renderable>();
+    let particles = ecs.read_storage::<ParticleLifetime>();
+    let mut animations = ecs.write_resource::<Animations>();
+    let animations = &mut *animations;
+
+    if !enabled {
+        animations.slides.clear();
+        animations.missiles.clear();
+    }
+
+    let mut seen = HashMap::new();
+    for (entity, pos, _render) in (&entities, &positions, &renderables).join() {
+        if particles.get(entity).is_some() { continue; }
+        let now = Point::new(pos.x, pos.y);
+        seen.insert(entity, now);
+        match animations.last_seen.get(&entity) {
+            Some(before) if *before != now && enabled => {
+                let adjacent = i32::abs(before.x - now.x) <= 1 && i32::abs(before.y - now.y) <= 1;
+                if adjacent {
+                    let from = animations.slide_position(entity).unwrap_or_else(|| PointF::new(before.x as f32, before.y as f32));
+                    animations.slides.insert(entity, Slide{ from, to: now, elapsed_ms: 0.0 });
+                } else {
+                    animations.slides.remove(&entity);
+                }
+            }
+            _ => {}
+        }
+    }
+    animations.last_seen = seen;
+
+    let last_seen = &animations.last_seen;
+    animations.slides.retain(|entity, slide| {
+        slide.elapsed_ms += frame_time_ms;
+        slide.elapsed_ms < SLIDE_MS && last_seen.contains_key(entity)
+    });
+    animations.missiles.retain_mut(|missile| {
+        missile.elapsed_ms += frame_time_ms;
+        missile.elapsed_ms < FLIGHT_MS_PER_TILE * (missile.path.len() - 1) as f32
+    });
+}
@@ -0,0 +1,33 @@
+use specs::prelude::*;
+use rltk::RGB;
+use super::{Artifact, Name, Position, Viewshed, gamelog::{GameLog, LogCategory}, unique_items::UniqueItems};
+
+/// Announces an artifact the first time the player lays eyes on it.
+pub struct ArtifactSystem {}
+
+impl<'a> System<'a> for ArtifactSystem {
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        WriteExpect<'a, UniqueItems>,
+                        ReadStorage<'a, Viewshed>,
+                        ReadStorage<'a, Artifact>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Position> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut log, mut uniques, viewsheds, artifacts, names, positions) = data;
+
+        let viewshed = match viewsheds.get(*player_entity) {
+            Some(viewshed) => viewshed,
+            None => return
+        };
+
+        for (_artifact, name, pos) in (&artifacts, &names, &positions).join() {
+            if uniques.seen.contains(&name.name) { continue; }
+            if viewshed.visible_tiles.iter().any(|p| p.x == pos.x && p.y == pos.y) {
+                uniques.seen.insert(name.name.clone());
+                log.push_colored(LogCategory::Items, format!("\u{263C} You glimpse {}, an artifact of legend!", name.name), RGB::named(rltk::ORANGE));
+            }
+        }
+    }
+}
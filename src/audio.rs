@@ -0,0 +1,132 @@
+use specs::prelude::*;
+use specs::shrev::ReaderId;
+use super::{Map, Branch, events::{GameEvents, GameEvent}, options::Options};
+
+/// The short sounds played as things happen.
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum Sound { Hit, LevelUp, Pickup }
+
+impl Sound {
+    #[cfg_attr(not(all(feature = "audio", not(target_arch = "wasm32"))), allow(dead_code))]
+    fn file(self) -> &'static str {
+        match self {
+            Sound::Hit => "hit.ogg",
+            Sound::LevelUp => "level_up.ogg",
+            Sound::Pickup => "pickup.ogg"
+        }
+    }
+}
+
+/// The tune that loops while the player is in a branch.
+#[cfg_attr(not(all(feature = "audio", not(target_arch = "wasm32"))), allow(dead_code))]
+fn music_file(branch : Branch) -> &'static str {
+    match branch {
+        Branch::Dungeon => "music_dungeon.ogg",
+        Branch::Caverns => "music_caverns.ogg",
+        Branch::Sewers => "music_sewers.ogg",
+        Branch::Fortress => "music_fortress.ogg",
+        Branch::Overworld => "music_overworld.ogg"
+    }
+}
+
+/// Plays sounds for the events published each tick, and music for the branch the player is in.
+/// The sound device can't be shared between threads, so this lives with the state rather than in
+/// the world. Without the `audio` feature, or on the web, it only keeps up with the events.
+#[derive(Default)]
+pub struct Audio {
+    reader : Option<ReaderId<GameEvent>>,
+    /// Opened the first time there's something to play; None inside if there's no device.
+    #[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
+    device : Option<Option<Device>>
+}
+
+impl Audio {
+    /// Starts listening to a world's events. Has to be done again whenever the world is replaced.
+    pub fn listen(&mut self, ecs : &mut World) {
+        self.reader = Some(ecs.write_resource::<GameEvents>().register_reader());
+    }
+
+    /// Plays whatever this tick's events call for, and changes the music if the player changed branch.
+    pub fn update(&mut self, ecs : &World) {
+        // There's no player yet while a character is being made
+        let player_entity = ecs.try_fetch::<Entity>().map(|player| *player);
+        let reader = self.reader.as_mut().expect("Audio is not listening to the world");
+        let mut sounds : Vec<Sound> = Vec::new();
+        for event in ecs.fetch::<GameEvents>().read(reader) {
+            let sound = match event {
+                GameEvent::Damaged{ target, from_player, .. } if *from_player || Some(*target) == player_entity => Sound::Hit,
+                GameEvent::LevelledUp{ .. } => Sound::LevelUp,
+                GameEvent::ItemPickedUp{ by_player: true, .. } => Sound::Pickup,
+                _ => continue
+            };
+            if !sounds.contains(&sound) { sounds.push(sound); }
+        }
+
+        let options = ecs.fetch::<Options>();
+        let branch = ecs.fetch::<Map>().branch;
+        self.play(&sounds, branch, options.sound_volume, options.music_volume);
+    }
+
+    #[cfg(not(all(feature = "audio", not(target_arch = "wasm32"))))]
+    fn play(&mut self, _sounds : &[Sound], _branch : Branch, _sound_volume : i32, _music_volume : i32) {}
+
+    #[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
+    fn play(&mut self, sounds : &[Sound], branch : Branch, sound_volume : i32, music_volume : i32) {
+        let device = match self.device.get_or_insert_with(Device::open) {
+            Some(device) => device,
+            None => return
+        };
+        if sound_volume > 0 {
+            for sound in sounds.iter() {
+                device.effect(sound.file(), sound_volume as f32 / 100.0);
+            }
+        }
+        device.music(branch, music_volume as f32 / 100.0);
+    }
+}
+
+/// The open sound output, and the music playing on it.
+#[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
+struct Device {
+    _stream : rodio::OutputStream,
+    handle : rodio::OutputStreamHandle,
+    music : Option<(Branch, rodio::Sink)>
+}
+
+#[cfg(all(feature = "audio", not(target_arch = "wasm32")))]
+impl Device {
+    fn open() -> Option<Device> {
+        let (stream, handle) = rodio::OutputStream::try_default().ok()?;
+        Some(Device{ _stream: stream, handle, music: None })
+    }
+
+    /// Reads one of the files in resources/audio. A missing or broken file just stays silent.
+    fn source(name : &str) -> Option<rodio::Decoder<std::io::BufReader<std::fs::File>>> {
+        let file = std::fs::File::open(format!("./resources/audio/{}", name)).ok()?;
+        rodio::Decoder::new(std::io::BufReader::new(file)).ok()
+    }
+
+    fn effect(&self, name : &str, volume : f32) {
+        if let (Some(source), Ok(sink)) = (Device::source(name), rodio::Sink::try_new(&self.handle)) {
+            sink.set_volume(volume);
+            sink.append(source);
+            sink.detach();
+        }
+    }
+
+    fn music(&mut self, branch : Branch, volume : f32) {
+        use rodio::Source;
+        if let Some((playing, sink)) = &self.music {
+            if *playing == branch {
+                sink.set_volume(volume);
+                return;
+            }
+        }
+        self.music = None;
+        if let (Some(source), Ok(sink)) = (Device::source(music_file(branch)), rodio::Sink::try_new(&self.handle)) {
+            sink.set_volume(volume);
+            sink.append(source.repeat_infinite());
+            self.music = Some((branch, sink));
+        }
+    }
+}
@@ -0,0 +1,173 @@
+use std::collections::{HashMap, HashSet};
+use specs::prelude::*;
+use specs::shrev::ReaderId;
+use serde::{Serialize, Deserialize};
+use super::{Monster, Name, Position, Viewshed, Invisible, CanSeeInvisible, AbilityKind, invisibility_system::can_perceive,
+            events::{GameEvents, GameEvent}, gamelog::{GameLog, LogCategory}, replay::Replay, raws, storage};
+
+/// Kills it takes before the bestiary shows a creature's health, its attack and defense, and what
+/// it resists.
+const KILLS_FOR_HEALTH : i32 = 1;
+const KILLS_FOR_COMBAT : i32 = 3;
+const KILLS_FOR_RESISTANCES : i32 = 5;
+
+/// What the player has learned about one kind of creature.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct BeastRecord {
+    pub seen : i32,
+    pub killed : i32,
+    /// How many times this kind of creature has killed the player.
+    pub killed_by : i32,
+    /// The abilities the player has watched it use.
+    pub abilities : Vec<String>
+}
+
+/// Every kind of creature the player has come across, over all their runs. Like the achievements
+/// it belongs to the player rather than the run, so it lives in its own file.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct KnownBeasts {
+    pub beasts : HashMap<String, BeastRecord>
+}
+
+impl KnownBeasts {
+    /// Reads bestiary.json, starting afresh if there isn't one yet.
+    pub fn load() -> KnownBeasts {
+        storage::read("bestiary.json")
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let data = serde_json::to_string(self).expect("Unable to serialize bestiary");
+        storage::write("bestiary.json", &data)
+    }
+
+    /// The creatures met so far, in alphabetical order.
+    pub fn sorted(&self) -> Vec<(&String, &BeastRecord)> {
+        let mut beasts : Vec<(&String, &BeastRecord)> = self.beasts.iter().collect();
+        beasts.sort_by(|a, b| a.0.cmp(b.0));
+        beasts
+    }
+
+    fn record(&mut self, name : &str) -> &mut BeastRecord {
+        self.beasts.entry(name.to_string()).or_default()
+    }
+}
+
+fn ability_name(kind : AbilityKind) -> &'static str {
+    match kind {
+        AbilityKind::Web => "Spits webs",
+        AbilityKind::Summon => "Calls for help",
+        AbilityKind::SelfDestruct => "Explodes on death",
+        AbilityKind::LifeDrain => "Drains life",
+        AbilityKind::Steal => "Steals from your pack",
+        AbilityKind::Split => "Splits when struck",
+        AbilityKind::Breed => "Breeds"
+    }
+}
+
+/// The bestiary's notes on a creature: more of its raws are revealed the more of them the player
+/// has killed.
+pub fn describe(name : &str, record : &BeastRecord) -> Vec<String> {
+    let mut lines = vec![format!("Seen {}, killed {}, killed you {}", record.seen, record.killed, record.killed_by)];
+    let raws = raws::raws();
+    if let Some(mob) = raws::get_mob(&raws, name) {
+        if record.killed >= KILLS_FOR_HEALTH {
+            lines.push(format!("Health {}", mob.stats.max_hp));
+        }
+        if record.killed >= KILLS_FOR_COMBAT {
+            lines.push(format!("Power {}, defense {}", mob.stats.power, mob.stats.defense));
+        }
+        if record.killed >= KILLS_FOR_RESISTANCES {
+            let mut resists : Vec<String> = mob.resistances.iter().flatten().map(|(kind, percent)| format!("{} {}%", kind, percent)).collect();
+            resists.extend(mob.vulnerabilities.iter().flatten().map(|(kind, percent)| format!("{} -{}%", kind, percent)));
+            resists.sort();
+            if !resists.is_empty() {
+                lines.push(format!("Resists {}", resists.join(", ")));
+            }
+        }
+    }
+    lines.extend(record.abilities.iter().cloned());
+    lines
+}
+
+/// Fills in the bestiary as the player meets, fights and falls to creatures.
+#[derive(Default)]
+pub struct BestiarySystem {
+    reader : Option<ReaderId<GameEvent>>,
+    /// Creatures on this level already counted as seen.
+    sighted : HashSet<Entity>,
+    /// The kind of creature that last hurt the player, which gets the blame if they die.
+    last_attacker : Option<String>
+}
+
+impl<'a> System<'a> for BestiarySystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, GameEvents>,
+                        WriteExpect<'a, KnownBeasts>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, Replay>,
+                        WriteExpect<'a, GameLog>,
+                        ReadStorage<'a, Viewshed>,
+                        ReadStorage<'a, Monster>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Invisible>,
+                        ReadStorage<'a, CanSeeInvisible> );
+
+    fn setup(&mut self, world : &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(world.write_resource::<GameEvents>().register_reader());
+    }
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, events, mut beasts, player_entity, replay, mut log, viewsheds, monsters, positions, names, invisible, see_invisible) = data;
+
+        // Watching a replay teaches nothing
+        let learning = !replay.is_playing();
+        let mut changed = false;
+        for event in events.read(self.reader.as_mut().expect("BestiarySystem was not set up")) {
+            match event {
+                GameEvent::Died{ name, foe: true, .. } if learning => {
+                    beasts.record(name).killed += 1;
+                    changed = true;
+                }
+                GameEvent::Damaged{ target, by, .. } if *target == *player_entity => {
+                    self.last_attacker = by.and_then(|by| names.get(by)).map(|n| n.name.clone());
+                }
+                GameEvent::AbilityUsed{ name, ability, .. } if learning => {
+                    let record = beasts.record(name);
+                    let ability = ability_name(*ability).to_string();
+                    if !record.abilities.contains(&ability) {
+                        record.abilities.push(ability);
+                        changed = true;
+                    }
+                }
+                GameEvent::PlayerDied if learning => {
+                    if let Some(killer) = self.last_attacker.take() {
+                        beasts.record(&killer).killed_by += 1;
+                        changed = true;
+                    }
+                }
+                GameEvent::LevelEntered{ .. } => self.sighted.clear(),
+                _ => {}
+            }
+        }
+
+        if learning {
+            if let Some(viewshed) = viewsheds.get(*player_entity) {
+                for (entity, _monster, pos, name) in (&entities, &monsters, &positions, &names).join() {
+                    if self.sighted.contains(&entity) || !viewshed.visible_tiles.contains(&rltk::Point::new(pos.x, pos.y)) { continue; }
+                    if !can_perceive(*player_entity, entity, &invisible, &see_invisible) { continue; }
+                    self.sighted.insert(entity);
+                    beasts.record(&name.name).seen += 1;
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            if let Err(err) = beasts.save() { log.push(LogCategory::System, err); }
+        }
+    }
+}
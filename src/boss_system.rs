@@ -0,0 +1,76 @@
+use specs::prelude::*;
+use rltk::{Point, RandomNumberGenerator, RGB};
+use super::{Boss, CombatStats, Position, SpecialAbilities, Name, Map, gamelog::{GameLog, LogCategory}, fire_system::Ignitions,
+            effects::{EffectQueue, EffectType, Targets}};
+
+const ARENA_RADIUS : i32 = 6;
+const ARENA_FIRES : i32 = 10;
+const ARENA_FIRE_TURNS : i32 = 6;
+
+/// Moves bosses into their next phase as their health drops past each threshold.
+pub struct BossSystem {}
+
+impl<'a> System<'a> for BossSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteExpect<'a, GameLog>,
+                        WriteExpect<'a, EffectQueue>,
+                        WriteExpect<'a, Ignitions>,
+                        WriteStorage<'a, Boss>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, SpecialAbilities> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, map, mut rng, mut log, mut effects, mut ignitions, mut bosses, combat_stats, positions, names,
+            mut abilities) = data;
+
+        for (entity, boss, stats, pos) in (&entities, &mut bosses, &combat_stats, &positions).join() {
+            if stats.hp < 1 { continue; }
+            let hp_percent = stats.hp * 100 / stats.max_hp;
+
+            while boss.phase < boss.phases.len() && hp_percent <= boss.phases[boss.phase].hp_percent {
+                let phase = boss.phases[boss.phase].clone();
+                boss.phase += 1;
+
+                let name = names.get(entity).map_or("The boss", |n| n.name.as_str());
+                log.push_colored(LogCategory::Combat, format!("{} {}", name, phase.message), RGB::named(rltk::MAGENTA));
+
+                if let Some(new_abilities) = phase.abilities {
+                    abilities.insert(entity, SpecialAbilities{ abilities: new_abilities }).expect("Unable to insert abilities");
+                }
+
+                if let Some(summon) = &phase.summon {
+                    let mut free_tiles : Vec<usize> = Vec::new();
+                    for y in pos.y - 2 ..= pos.y + 2 {
+                        for x in pos.x - 2 ..= pos.x + 2 {
+                            if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 { continue; }
+                            let idx = map.xy_idx(x, y);
+                            if !map.is_blocked(idx) { free_tiles.push(idx); }
+                        }
+                    }
+                    for tile in free_tiles.iter().take(phase.summon_count as usize) {
+                        effects.add_effect(Some(entity), EffectType::Summon{ name: summon.clone(), turns: None }, Targets::Tile{ tile: *tile });
+                    }
+                }
+
+                if let Some(script) = &phase.script {
+                    effects.add_effect(Some(entity), EffectType::Script{ source: script.clone() }, Targets::Single{ target: entity });
+                }
+
+                if phase.arena.as_deref() == Some("fire") {
+                    for _i in 0..ARENA_FIRES {
+                        let x = pos.x + rng.roll_dice(1, ARENA_RADIUS * 2 + 1) - ARENA_RADIUS - 1;
+                        let y = pos.y + rng.roll_dice(1, ARENA_RADIUS * 2 + 1) - ARENA_RADIUS - 1;
+                        if x > 0 && x < map.width - 1 && y > 0 && y < map.height - 1 && (x != pos.x || y != pos.y) {
+                            ignitions.ignite(Point::new(x, y), ARENA_FIRE_TURNS);
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
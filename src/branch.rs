@@ -0,0 +1,75 @@
+use serde::{Serialize, Deserialize};
+use super::MAX_DEPTH;
+
+/// Number of levels in each side branch of the dungeon.
+const BRANCH_LEVELS : i32 = 3;
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Default, Serialize, Deserialize)]
+pub enum Branch {
+    #[default]
+    Dungeon,
+    Caverns,
+    Sewers,
+    Fortress,
+    Overworld
+}
+
+const ALL_BRANCHES : [Branch; 5] = [Branch::Dungeon, Branch::Caverns, Branch::Sewers, Branch::Fortress, Branch::Overworld];
+
+impl Branch {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Branch::Dungeon => "Dungeon",
+            Branch::Caverns => "Caverns",
+            Branch::Sewers => "Sewers",
+            Branch::Fortress => "Fortress",
+            Branch::Overworld => "Overworld"
+        }
+    }
+
+    /// Human readable name of a level, e.g. "Caverns 2".
+    pub fn level_name(&self, depth : i32) -> String {
+        match self {
+            Branch::Overworld => self.name().to_string(),
+            _ => format!("{} {}", self.name(), depth)
+        }
+    }
+
+    /// The branch holding the entrance to this one.
+    pub fn parent(&self) -> Option<Branch> {
+        match self {
+            Branch::Overworld => None,
+            Branch::Dungeon | Branch::Caverns | Branch::Sewers => Some(Branch::Overworld),
+            Branch::Fortress => Some(Branch::Dungeon)
+        }
+    }
+
+    /// Depth of the parent level holding the entrance to this branch.
+    pub fn entry_depth(&self) -> i32 {
+        match self {
+            Branch::Fortress => 5,
+            _ => 0
+        }
+    }
+
+    pub fn max_depth(&self) -> i32 {
+        match self {
+            Branch::Overworld => 0,
+            Branch::Dungeon => MAX_DEPTH,
+            _ => self.entry_depth() + BRANCH_LEVELS
+        }
+    }
+
+    /// The branches whose entrances lie on the given level of this branch.
+    pub fn children_at(&self, depth : i32) -> Vec<Branch> {
+        ALL_BRANCHES.iter()
+            .filter(|branch| branch.parent() == Some(*self) && branch.entry_depth() == depth)
+            .copied()
+            .collect()
+    }
+
+    /// Unique key of a level, used to file it away in the MasterDungeonMap.
+    pub fn level_key(&self, depth : i32) -> i32 {
+        (*self as i32) * 100 + depth
+    }
+}
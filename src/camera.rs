@@ -0,0 +1,284 @@
+use specs::prelude::*;
+use rltk::{Point, PointF, RGB, RGBA, ColorPair, DrawBatch, Algorithm2D};
+use super::{Map, Position, Renderable, Invisible, Monster, Hidden, CanSeeInvisible, Telepathy, MovesThroughWalls, ethereal, ParticleLifetime, tile_glyph, MARKER_GLYPH, MARKER_COLOR, raws,
+            options::Options, palette, animation::Animations, visual_effects::VisualEffects, gui::{batch_for, MAP_CONSOLE, TILE_CONSOLE, MOTION_CONSOLE, OVERLAY_CONSOLE,
+            MAP_LAYER, ENTITY_LAYER}};
+
+/// The smallest terminal the interface is laid out for; a smaller window is treated as this size.
+pub const MIN_WIDTH : i32 = 80;
+pub const MIN_HEIGHT : i32 = 50;
+/// Rows along the bottom of the screen taken by the log and status panel.
+pub const PANEL_HEIGHT : i32 = 7;
+
+/// How many cells the terminal has right now. It changes when the window is resized, and the map
+/// view grows to fill whatever the panel below it leaves.
+#[derive(Clone, Copy, PartialEq)]
+pub struct Screen {
+    pub width : i32,
+    pub height : i32
+}
+
+impl Default for Screen {
+    fn default() -> Screen {
+        Screen{ width: MIN_WIDTH, height: MIN_HEIGHT }
+    }
+}
+
+impl Screen {
+    pub fn new(width : i32, height : i32) -> Screen {
+        Screen{ width: i32::max(width, MIN_WIDTH), height: i32::max(height, MIN_HEIGHT) }
+    }
+
+    pub fn view_width(&self) -> i32 {
+        self.width
+    }
+
+    /// The map view ends where the panel starts.
+    pub fn view_height(&self) -> i32 {
+        self.height - PANEL_HEIGHT
+    }
+
+    fn in_view(&self, x : i32, y : i32) -> bool {
+        x >= 0 && x < self.view_width() && y >= 0 && y < self.view_height()
+    }
+}
+
+/// Keeps one axis of the view centred on the player, without scrolling past either edge of the map.
+/// Maps no bigger than the view don't scroll at all.
+fn clamp_axis(centre : i32, view : i32, size : i32) -> i32 {
+    if size <= view { return 0; }
+    i32::max(0, i32::min(centre - view / 2, size - view))
+}
+
+/// The map tile shown in the top left corner of the screen.
+pub fn view_origin(ecs : &World) -> Point {
+    let map = ecs.fetch::<Map>();
+    let ppos = ecs.fetch::<Point>();
+    let screen = ecs.fetch::<Screen>();
+    Point::new(clamp_axis(ppos.x, screen.view_width(), map.width), clamp_axis(ppos.y, screen.view_height(), map.height))
+}
+
+/// Whether a screen cell is part of the map view.
+pub fn on_screen(ecs : &World, x : i32, y : i32) -> bool {
+    ecs.fetch::<Screen>().in_view(x, y)
+}
+
+/// Converts a screen cell, such as the one under the mouse, to map coordinates.
+pub fn screen_to_map(ecs : &World, x : i32, y : i32) -> Point {
+    let origin = view_origin(ecs);
+    Point::new(x + origin.x, y + origin.y)
+}
+
+/// Converts map coordinates to the screen cell they are drawn in.
+pub fn map_to_screen(ecs : &World, x : i32, y : i32) -> Point {
+    let origin = view_origin(ecs);
+    Point::new(x - origin.x, y - origin.y)
+}
+
+/// What ended up in one cell of the view: the top entity there, or else the terrain.
+#[derive(Clone, Copy, PartialEq)]
+struct Cell {
+    glyph : rltk::FontCharType,
+    fg : RGB,
+    bg : RGB,
+    visible : bool,
+    sprite : Option<rltk::FontCharType>,
+    flash : bool
+}
+
+/// The cells drawn this frame, kept so the interface can highlight them afterwards, and how far
+/// screen shake knocked them out of place. The map consoles hold on to them between frames, so they
+/// are only drawn again once something in the view changes; highlights are undone cell by cell.
+#[derive(Default)]
+pub struct View {
+    cells : Vec<Option<Cell>>,
+    shake : Point,
+    drawn : bool,
+    highlighted : Vec<Point>
+}
+
+/// Puts a cell on screen: sprites go on the tile console, everything else stays ASCII. A highlight
+/// tints a sprite, or colours the background behind a glyph; a cell that was just hurt flashes red.
+/// Colours go through the palette here, on their way out.
+fn draw_cell(cell : &Cell, screen : Point, map_batch : &mut DrawBatch, tile_batch : &mut DrawBatch, highlight : Option<RGB>) {
+    let highlight = highlight.or(if cell.flash { Some(RGB::named(rltk::RED)) } else { None });
+    match cell.sprite {
+        Some(sprite) => {
+            let tint = if cell.visible { RGB::named(rltk::WHITE) } else { RGB::named(rltk::GREY) };
+            tile_batch.set(screen, ColorPair::new(palette::color(highlight.unwrap_or(tint)), palette::named(rltk::BLACK)), sprite);
+        }
+        None => { map_batch.set(screen, ColorPair::new(palette::color(cell.fg), palette::color(highlight.unwrap_or(cell.bg))), cell.glyph); }
+    }
+}
+
+/// Puts a glyph at a point between cells, on the console for things in motion.
+fn draw_moving(motion_batch : &mut DrawBatch, view : &Screen, at : PointF, origin : Point, glyph : rltk::FontCharType, fg : RGB) {
+    let screen = PointF::new(at.x - origin.x as f32, at.y - origin.y as f32);
+    if !view.in_view(screen.x as i32, screen.y as i32) { return; }
+    motion_batch.set_fancy(screen, 1, rltk::Radians::new(0.0), PointF::new(1.0, 1.0),
+        ColorPair::new(palette::color(fg), RGBA::from_f32(0., 0., 0., 0.)), glyph);
+}
+
+/// Draws the revealed part of the map that falls inside the view, and everything the player can
+/// see on it, plus whatever telepathy reveals beyond line of sight. Entities mid-step and missiles
+/// in flight go on the motion console; particles go on the overlay, above the interface. Screen
+/// shake knocks the lot a cell or so out of place.
+pub fn render(ecs : &World) {
+    let vfx = ecs.fetch::<VisualEffects>();
+    let shake = vfx.shake_offset();
+    let view_origin = view_origin(ecs);
+    let origin = Point::new(view_origin.x - shake.x, view_origin.y - shake.y);
+    let map = ecs.fetch::<Map>();
+    let tile_graphics = ecs.fetch::<Options>().tile_graphics;
+    let raws = raws::raws();
+    let sprite_for = |glyph| if tile_graphics { raws::tile_for(&raws, glyph) } else { None };
+    let screen = *ecs.fetch::<Screen>();
+    let (view_width, view_height) = (screen.view_width(), screen.view_height());
+    let mut cells : Vec<Option<Cell>> = vec![None; (view_width * view_height) as usize];
+
+    for y in 0..view_height {
+        for x in 0..view_width {
+            let (map_x, map_y) = (x + origin.x, y + origin.y);
+            if map_x < 0 || map_y < 0 || map_x >= map.width || map_y >= map.height { continue; }
+            let idx = map.xy_idx(map_x, map_y);
+            if map.revealed_tiles[idx] {
+                let (glyph, fg) = tile_glyph(idx, &map);
+                let cell = if map.markers.contains_key(&idx) {
+                    // The player's own markers show over the terrain, until something stands on them
+                    Cell{ glyph: rltk::to_cp437(MARKER_GLYPH), fg: RGB::named(MARKER_COLOR), bg: RGB::from_f32(0., 0., 0.),
+                        visible: map.visible_tiles[idx], sprite: None, flash: false }
+                } else if let (Some(ghost), false) = (map.remembered.get(&idx), map.visible_tiles[idx]) {
+                    // Whatever was last seen here lingers, dimmed, until the tile comes back into view
+                    Cell{ glyph: ghost.glyph, fg: ghost.fg * 0.5, bg: RGB::from_f32(0., 0., 0.), visible: false, sprite: sprite_for(ghost.glyph), flash: false }
+                } else {
+                    Cell{ glyph, fg, bg: RGB::from_f32(0., 0., 0.), visible: map.visible_tiles[idx], sprite: sprite_for(glyph), flash: false }
+                };
+                cells[(y * view_width + x) as usize] = Some(cell);
+            }
+        }
+    }
+
+    let entities = ecs.entities();
+    let particles = ecs.read_storage::<ParticleLifetime>();
+    let positions = ecs.read_storage::<Position>();
+    let renderables = ecs.read_storage::<Renderable>();
+    let invisible = ecs.read_storage::<Invisible>();
+    let monsters = ecs.read_storage::<Monster>();
+    let hidden = ecs.read_storage::<Hidden>();
+    let walkers = ecs.read_storage::<MovesThroughWalls>();
+    let player_entity = *ecs.fetch::<Entity>();
+    let sees_invisible = ecs.read_storage::<CanSeeInvisible>().get(player_entity).is_some();
+    let telepathic = ecs.read_storage::<Telepathy>().get(player_entity).is_some();
+
+    let animations = ecs.fetch::<Animations>();
+    let mut motion_batch = batch_for(MOTION_CONSOLE);
+    let mut overlay_batch = batch_for(OVERLAY_CONSOLE);
+    let mut data = (&entities, &positions, &renderables).join()
+        .filter(|(e, _p, _r)| *e == player_entity || sees_invisible || invisible.get(*e).is_none())
+        .filter(|(e, _p, _r)| hidden.get(*e).is_none())
+        .filter(|(_e, p, _r)| screen.in_view(p.x - origin.x, p.y - origin.y))
+        .collect::<Vec<_>>();
+    data.sort_by(|&a, &b| b.2.render_order.cmp(&a.2.render_order));
+    for (entity, pos, render) in data.iter() {
+        let idx = map.xy_idx(pos.x, pos.y);
+        let at = Point::new(pos.x - origin.x, pos.y - origin.y);
+        let cell = &mut cells[(at.y * view_width + at.x) as usize];
+        // Something inside a wall can be sensed, but not seen until it comes out
+        if map.visible_tiles[idx] && !ethereal::in_wall(&map, &walkers, *entity, pos.x, pos.y) {
+            let sprite = sprite_for(render.glyph);
+            if particles.get(*entity).is_some() {
+                overlay_batch.set(at, ColorPair::new(palette::color(render.fg), palette::color(render.bg)), render.glyph);
+            } else if let (Some(slide), None) = (animations.slide_position(*entity), sprite) {
+                // Sprites just jump; only glyphs can be drawn between cells
+                draw_moving(&mut motion_batch, &screen, slide, origin, render.glyph, render.fg);
+            } else {
+                *cell = Some(Cell{ glyph: render.glyph, fg: render.fg, bg: render.bg, visible: true, sprite, flash: vfx.flashing(*entity) });
+            }
+        } else if telepathic && monsters.get(*entity).is_some() {
+            // Sensed minds show up dimmed, without revealing the map around them
+            *cell = Some(Cell{ glyph: render.glyph, fg: render.fg * 0.5, bg: RGB::named(rltk::BLACK), visible: false,
+                sprite: sprite_for(render.glyph), flash: false });
+        }
+    }
+
+    for (at, over, glyph, fg) in animations.missiles() {
+        if map.in_bounds(over) && map.visible_tiles[map.xy_idx(over.x, over.y)] {
+            draw_moving(&mut motion_batch, &screen, at, origin, glyph, fg);
+        }
+    }
+
+    let mut view = ecs.write_resource::<View>();
+    let mut map_batch = batch_for(MAP_CONSOLE);
+    let mut tile_batch = batch_for(TILE_CONSOLE);
+    if !view.drawn || view.cells != cells {
+        map_batch.cls();
+        tile_batch.cls();
+        for (i, cell) in cells.iter().enumerate() {
+            if let Some(cell) = cell {
+                let at = Point::new(i as i32 % view_width, i as i32 / view_width);
+                draw_cell(cell, at, &mut map_batch, &mut tile_batch, None);
+            }
+        }
+        view.highlighted.clear();
+    } else {
+        // Nothing moved, so only last frame's highlights need taking off again
+        for at in std::mem::take(&mut view.highlighted) {
+            match view.cell_at(&screen, at) {
+                Some(cell) => draw_cell(&cell, at, &mut map_batch, &mut tile_batch, None),
+                None => { map_batch.set_bg(at, palette::named(rltk::BLACK)); }
+            }
+        }
+    }
+    map_batch.submit(MAP_LAYER).expect("Unable to submit draw batch");
+    tile_batch.submit(MAP_LAYER).expect("Unable to submit draw batch");
+    // A red frame around the view when the player has just been hurt
+    if vfx.border_flashing() {
+        overlay_batch.draw_hollow_box(rltk::Rect::with_size(0, 0, view_width - 1, view_height - 1),
+            ColorPair::new(palette::named(rltk::RED), palette::named(rltk::BLACK)));
+    }
+    motion_batch.submit(ENTITY_LAYER).expect("Unable to submit draw batch");
+    overlay_batch.submit(ENTITY_LAYER).expect("Unable to submit draw batch");
+
+    view.cells = cells;
+    view.shake = shake;
+    view.drawn = true;
+}
+
+impl View {
+    fn cell_at(&self, screen : &Screen, at : Point) -> Option<Cell> {
+        if !screen.in_view(at.x, at.y) { return None; }
+        self.cells.get((at.y * screen.view_width() + at.x) as usize).copied().flatten()
+    }
+}
+
+/// Blanks the map consoles on screens that don't show the map, such as the menus.
+pub fn clear(ecs : &World) {
+    let mut view = ecs.write_resource::<View>();
+    if !view.drawn { return; }
+    for console in [MAP_CONSOLE, TILE_CONSOLE] {
+        let mut draw_batch = batch_for(console);
+        draw_batch.cls();
+        draw_batch.submit(MAP_LAYER).expect("Unable to submit draw batch");
+    }
+    view.drawn = false;
+    view.highlighted.clear();
+}
+
+/// Picks out screen cells in the given colours, drawn over the view at `z_order`. Later entries win.
+pub fn highlight(ecs : &World, highlights : &[(Point, RGB)], z_order : usize) {
+    let mut view = ecs.write_resource::<View>();
+    let screen = ecs.fetch::<Screen>();
+    let mut map_batch = batch_for(MAP_CONSOLE);
+    let mut tile_batch = batch_for(TILE_CONSOLE);
+    for (at, color) in highlights.iter() {
+        // Highlights follow the map when it shakes, so they stay on the cell they mean
+        let shaken = Point::new(at.x + view.shake.x, at.y + view.shake.y);
+        view.highlighted.push(shaken);
+        match view.cell_at(&screen, shaken) {
+            Some(cell) => draw_cell(&cell, shaken, &mut map_batch, &mut tile_batch, Some(*color)),
+            None => { map_batch.set_bg(shaken, palette::color(*color)); }
+        }
+    }
+    map_batch.submit(z_order).expect("Unable to submit draw batch");
+    tile_batch.submit(z_order).expect("Unable to submit draw batch");
+}
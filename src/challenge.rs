@@ -0,0 +1,66 @@
+use rltk::RandomNumberGenerator;
+use serde::{Serialize, Deserialize};
+use super::Branch;
+
+/// Whether this run is the daily challenge: everyone playing on the same day gets the same levels
+/// and the same character, so their scores can be compared. Saved with the game.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Challenge {
+    /// The day being played, as YYYY-MM-DD; None for an ordinary run.
+    pub date : Option<String>,
+    pub seed : u64
+}
+
+impl Challenge {
+    /// Today's challenge. The web build can't tell the date, so it has none.
+    #[cfg(target_arch = "wasm32")]
+    pub fn today() -> Option<Challenge> {
+        None
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn today() -> Option<Challenge> {
+        let since_epoch = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).ok()?;
+        let days = (since_epoch.as_secs() / 86400) as i64;
+        let (year, month, day) = civil_from_days(days);
+        Some(Challenge{ date: Some(format!("{:04}-{:02}-{:02}", year, month, day)), seed: mix(days as u64) })
+    }
+
+    pub fn is_daily(&self) -> bool {
+        self.date.is_some()
+    }
+
+    /// Builds a level from its own seed, so it comes out the same however the run got there.
+    pub fn level_rng(&self, branch : Branch, depth : i32) -> Option<RandomNumberGenerator> {
+        if !self.is_daily() { return None; }
+        Some(RandomNumberGenerator::seeded(mix(self.seed ^ ((branch as u64) << 32) ^ depth as u64)))
+    }
+
+    /// The class everyone plays today.
+    pub fn class<'a>(&self, classes : &'a [(String, String)]) -> &'a str {
+        &classes[(self.seed % classes.len() as u64) as usize].0
+    }
+}
+
+/// Scrambles the bits of a number, so neighbouring days and levels get unrelated seeds.
+fn mix(value : u64) -> u64 {
+    let mut z = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// Turns a count of days since 1970-01-01 into a year, month and day.
+#[cfg(not(target_arch = "wasm32"))]
+fn civil_from_days(days : i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = (if mp < 10 { mp + 3 } else { mp - 9 }) as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -4,13 +4,26 @@ use specs_derive::*;
 use serde::{Serialize, Deserialize};
 use specs::saveload::{Marker, ConvertSaveload};
 use specs::error::NoError;
+use std::collections::HashMap;
+use crate::tile_set::TileSet;
 
+/// Flagged, so the map's spatial index can follow entities as they move.
 #[derive(Component, ConvertSaveload, Clone)]
+#[storage(FlaggedStorage)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
 
+/// Position of an entity frozen on a level the player isn't currently on.
+#[derive(Component, ConvertSaveload, Clone)]
+pub struct OtherLevelPosition {
+    pub x: i32,
+    pub y: i32,
+    pub depth: i32,
+    pub branch: super::Branch
+}
+
 #[derive(Component, Clone, ConvertSaveload)]
 pub struct Renderable {
     pub glyph: rltk::FontCharType,
@@ -22,9 +35,16 @@ pub struct Renderable {
 #[derive(Component, Serialize, Deserialize, Debug, Clone)]
 pub struct Player {}
 
+/// The items bound to the number keys, slot 1 first. Items are bound by name, so a slot carries on
+/// through a whole stack of potions rather than emptying after the first.
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Hotbar {
+    pub slots : Vec<Option<String>>
+}
+
 #[derive(Component, Clone, ConvertSaveload)]
 pub struct Viewshed {
-    pub visible_tiles : Vec<Point>,
+    pub visible_tiles : TileSet,
     pub range : i32,
     pub dirty : bool
 }
@@ -38,6 +58,7 @@ pub struct Name {
 }
 
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
+#[storage(FlaggedStorage)]
 pub struct BlocksTile {}
 
 #[derive(Component, Debug, ConvertSaveload, Clone)]
@@ -53,17 +74,50 @@ pub struct WantsToMelee {
     pub target : Entity
 }
 
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum DamageType { Physical, Magic, Fire, Cold, Poison, Bleed }
+
+impl DamageType {
+    pub fn from_name(name : &str) -> DamageType {
+        match name {
+            "fire" => DamageType::Fire,
+            "cold" | "frost" => DamageType::Cold,
+            "poison" => DamageType::Poison,
+            "bleed" => DamageType::Bleed,
+            "magic" => DamageType::Magic,
+            _ => DamageType::Physical
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            DamageType::Physical => "physical",
+            DamageType::Magic => "magic",
+            DamageType::Fire => "fire",
+            DamageType::Cold => "cold",
+            DamageType::Poison => "poison",
+            DamageType::Bleed => "bleed"
+        }
+    }
+}
+
 #[derive(Component, Debug, ConvertSaveload, Clone)]
 pub struct SufferDamage {
-    pub amount : Vec<i32>
+    pub amount : Vec<(i32, DamageType)>,
+    pub from_player : bool,
+    pub source : Option<Entity>
 }
 
 impl SufferDamage {
-    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32) {
+    /// Queues damage for the victim; `from_player` marks harm the player is to blame for, and
+    /// `source` is whoever dealt it, if anyone did.
+    pub fn new_damage(store: &mut WriteStorage<SufferDamage>, victim: Entity, amount: i32, kind: DamageType, from_player: bool, source: Option<Entity>) {
         if let Some(suffering) = store.get_mut(victim) {
-            suffering.amount.push(amount);
+            suffering.amount.push((amount, kind));
+            suffering.from_player |= from_player;
+            if source.is_some() { suffering.source = source; }
         } else {
-            let dmg = SufferDamage { amount : vec![amount] };
+            let dmg = SufferDamage { amount : vec![(amount, kind)], from_player, source };
             store.insert(victim, dmg).expect("Unable to insert damage");
         }
     }
@@ -75,6 +129,168 @@ pub struct Item {}
 #[derive(Component, Debug, Serialize, Deserialize, Clone)]
 pub struct Consumable {}
 
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum EquipmentSlot { Melee, Shield, Head, Torso, Legs, Feet, Hands, Cloak, Quiver }
+
+impl EquipmentSlot {
+    /// Every slot, in the order the character sheet lists them.
+    pub const ALL : [EquipmentSlot; 9] = [EquipmentSlot::Melee, EquipmentSlot::Shield, EquipmentSlot::Head, EquipmentSlot::Torso,
+        EquipmentSlot::Legs, EquipmentSlot::Feet, EquipmentSlot::Hands, EquipmentSlot::Cloak, EquipmentSlot::Quiver];
+
+    /// The body slot a wearable names in the raws, if it's one of them.
+    pub fn from_name(name : &str) -> Option<EquipmentSlot> {
+        match name {
+            "head" => Some(EquipmentSlot::Head),
+            "torso" => Some(EquipmentSlot::Torso),
+            "legs" => Some(EquipmentSlot::Legs),
+            "feet" => Some(EquipmentSlot::Feet),
+            "hands" => Some(EquipmentSlot::Hands),
+            "cloak" => Some(EquipmentSlot::Cloak),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EquipmentSlot::Melee => "Weapon",
+            EquipmentSlot::Shield => "Off Hand",
+            EquipmentSlot::Head => "Head",
+            EquipmentSlot::Torso => "Torso",
+            EquipmentSlot::Legs => "Legs",
+            EquipmentSlot::Feet => "Feet",
+            EquipmentSlot::Hands => "Hands",
+            EquipmentSlot::Cloak => "Cloak",
+            EquipmentSlot::Quiver => "Quiver"
+        }
+    }
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Equippable {
+    pub slot : EquipmentSlot
+}
+
+/// A bow or crossbow, which shoots ammunition of the matching kind from the quiver.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Launcher {
+    pub ammo : String,
+    pub range : i32
+}
+
+/// A stack of arrows or bolts; each one is used up as it is shot.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Ammo {
+    pub kind : String,
+    pub damage : i32,
+    pub count : i32
+}
+
+/// A weapon that needs both hands, leaving no room for a shield.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct TwoHanded {}
+
+/// A weapon light enough to be wielded in the off hand alongside another.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct OffHand {}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Equipped {
+    pub owner : Entity,
+    pub slot : EquipmentSlot
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct MeleePowerBonus {
+    pub power : i32
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct DefenseBonus {
+    pub defense : i32
+}
+
+/// Wear and tear on a weapon, shield or piece of armor; it breaks when `current` reaches zero.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Durability {
+    pub current : i32,
+    pub max : i32
+}
+
+/// A wand's remaining zaps; it does nothing once they run out.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Charges {
+    pub current : i32,
+    pub max : i32
+}
+
+/// Refills the charges of every wand the user carries.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Recharges {}
+
+/// Goes by a vague description until the player learns what it is by using it.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Unidentified {
+    pub name : String
+}
+
+/// Using this item restores `amount` durability to everything the user has equipped.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Repairs {
+    pub amount : i32
+}
+
+/// Worn equipment that adds `amount` to every point of natural healing.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Regeneration {
+    pub amount : i32
+}
+
+/// What has been added to a piece of equipment on top of its plain `base` form,
+/// whether rolled when it spawned or bought from an enchanter.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Affixed {
+    pub base : String,
+    pub prefix : Option<String>,
+    pub suffix : Option<String>,
+    pub enchantment : i32
+}
+
+/// Extra elemental damage dealt by a weapon on every hit.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct BonusDamage {
+    pub amount : i32,
+    pub element : String
+}
+
+/// Percentage chance for a weapon to confuse whatever it hits.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct ConfuseOnHit {
+    pub chance : i32,
+    pub turns : i32
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum RarityTier { Common, Rare, Legendary, Artifact }
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Rarity {
+    pub tier : RarityTier
+}
+
+/// Marks one of the hand-authored unique items from the raws.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Artifact {}
+
+/// Name of the raws loot table rolled when this creature dies.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct LootTable {
+    pub table : String
+}
+
+/// Marks the artifact the player has to bring back to the surface to win.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct MacGuffin {}
+
 #[derive(Component, Debug, ConvertSaveload, Clone)]
 pub struct Ranged {
     pub range : i32
@@ -82,7 +298,8 @@ pub struct Ranged {
 
 #[derive(Component, Debug, ConvertSaveload, Clone)]
 pub struct InflictsDamage {
-    pub damage : i32
+    pub damage : i32,
+    pub damage_type : DamageType
 }
 
 #[derive(Component, Debug, ConvertSaveload, Clone)]
@@ -122,10 +339,659 @@ pub struct WantsToDropItem {
     pub item : Entity
 }
 
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct WantsToRemoveItem {
+    pub item : Entity
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToShoot {
+    pub target : Point
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WantsToThrowItem {
+    pub item : Entity,
+    pub target : Point
+}
+
+/// Damage dealt when the item is thrown at something, instead of its weight.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Thrown {
+    pub damage : i32
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Weight {
+    pub weight : i32
+}
+
+/// A fire hazard that burns for `turns` more turns.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Burning {
+    pub turns : i32
+}
+
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Flammable {}
+
+/// Remains that can be carved up for meat.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Butcherable {}
+
+/// Sets the tiles it hits alight for `turns` turns.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct StartsFire {
+    pub turns : i32
+}
+
+/// Percentage of incoming damage ignored, per damage type. Negative values are vulnerabilities.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Resistances {
+    pub percent : HashMap<DamageType, i32>
+}
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct DotEffect {
+    pub kind : DamageType,
+    pub damage : i32,
+    pub turns : i32
+}
+
+/// Poison, bleeding and the like, ticking once per turn.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct DamageOverTime {
+    pub effects : Vec<DotEffect>
+}
+
+/// A weapon or creature whose hits have a `chance` in 100 to apply `effect`.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct InflictsDot {
+    pub effect : DotEffect,
+    pub chance : i32
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Attributes {
+    pub might : i32,
+    pub fitness : i32,
+    pub quickness : i32,
+    pub intelligence : i32
+}
+
+impl Default for Attributes {
+    fn default() -> Self {
+        Attributes{ might: 10, fitness: 10, quickness: 10, intelligence: 10 }
+    }
+}
+
+/// Widens a weapon's critical hit range by `range` and multiplies critical damage by `multiplier`.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct CriticalHit {
+    pub range : i32,
+    pub multiplier : i32
+}
+
+#[derive(Debug, Copy, Clone, Default, Serialize, Deserialize)]
+pub struct Pool {
+    pub current : i32,
+    pub max : i32
+}
+
+/// Resource pools spent on special actions. `exerted` blocks regeneration for the current turn.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Pools {
+    pub stamina : Pool,
+    #[serde(default)]
+    pub mana : Pool,
+    pub exerted : bool
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum SpecialAttack { Cleave, Knockback, Lunge }
+
+/// A weapon technique triggered with Ctrl + direction.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WeaponSpecial {
+    pub attack : SpecialAttack,
+    pub stamina_cost : i32
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum AbilityKind { Web, Summon, SelfDestruct, LifeDrain, Steal, Split, Breed }
+
+impl AbilityKind {
+    /// Abilities that happen to a creature on their own, rather than being used on its turn.
+    pub fn is_passive(&self) -> bool {
+        matches!(self, AbilityKind::SelfDestruct | AbilityKind::Split | AbilityKind::Breed)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SpecialAbility {
+    pub kind : AbilityKind,
+    pub chance : i32,
+    pub range : f32,
+    pub power : i32,
+    pub summon : Option<String>
+}
+
+/// Abilities a monster may use instead of attacking, each with a per-turn chance.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct SpecialAbilities {
+    pub abilities : Vec<SpecialAbility>
+}
+
+/// One stage of a boss fight, entered once the boss drops to `hp_percent` of its health.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BossPhase {
+    pub hp_percent : i32,
+    pub message : String,
+    pub abilities : Option<Vec<SpecialAbility>>,
+    pub summon : Option<String>,
+    pub summon_count : i32,
+    pub arena : Option<String>,
+    /// Run with the boss as its target as the phase begins.
+    #[serde(default)]
+    pub script : Option<String>
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Boss {
+    pub phases : Vec<BossPhase>,
+    pub phase : usize,
+    pub loot : Option<String>
+}
+
+/// Stuck in a web and unable to move for `turns` turns.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Webbed {
+    pub turns : i32
+}
+
+/// A summoned minion, fighting on its owner's side until `turns` run out or the owner dies.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct OwnedBy {
+    pub owner : Entity,
+    pub turns : Option<i32>
+}
+
+/// Unseen by anyone without `CanSeeInvisible`; `turns` is `None` for creatures that are always invisible.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Invisible {
+    pub turns : Option<i32>
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct CanSeeInvisible {
+    pub turns : Option<i32>
+}
+
+/// Using this item turns the user invisible for `turns` turns.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct GrantsInvisibility {
+    pub turns : i32
+}
+
+/// Using this item lets the user see invisible things for `turns` turns.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct GrantsSeeInvisible {
+    pub turns : i32
+}
+
+/// Not drawn until discovered, like a trap nobody has stepped on yet.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Hidden {}
+
+/// Fires its effects on whatever steps onto its tile.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct EntryTrigger {}
+
+/// Sends whoever triggers it to a random spot on the level.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct TeleportsRandomly {}
+
+/// Runs a script from the raws when the item is used or the trap is sprung.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Scripted {
+    pub script : String
+}
+
+/// Using this item moves the user to the targeted tile.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Blink {}
+
+/// Using this item opens a portal back to town.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct TownPortal {}
+
+/// A town portal, leading back to where it was opened.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct PortalLink {
+    pub branch : super::Branch,
+    pub depth : i32,
+    pub x : i32,
+    pub y : i32
+}
+
+/// Holds items of its own, such as a chest or a barrel.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Container {}
+
+/// An item stored inside a container rather than carried.
+#[derive(Component, Debug, Clone, ConvertSaveload)]
+pub struct InContainer {
+    pub container : Entity
+}
+
+/// Won't open without a matching key or a successful lockpicking attempt.
+/// A lock with no `key` id takes any ordinary key.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Locked {
+    pub key : Option<i32>
+}
+
+/// A shopkeeper. Their wares are the items in their backpack, replaced once `restock_turn` comes around.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Vendor {
+    pub table : String,
+    pub stock : i32,
+    pub restock_turn : i32
+}
+
+/// What an item is worth to a shopkeeper before haggling.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Value {
+    pub gold : i32
+}
+
+/// Coins carried by the player.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Purse {
+    pub gold : i32
+}
+
+/// A pile of coins; picking it up empties it into the purse.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Gold {
+    pub amount : i32
+}
+
+/// Consumed by enchanters when upgrading equipment.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Reagent {}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum ItemCategory { Gold, Ammo, Potion, Scroll }
+
+/// What sort of item this is, as tagged in the raws. The auto-pickup rules and the inventory go by it.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Category {
+    pub category : ItemCategory
+}
+
+/// Unlocks the lock with the same id, and is used up doing so.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Key {
+    pub id : Option<i32>
+}
+
+/// A door; it stops blocking the way once unlocked.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Door {}
+
+/// Can't be seen through, though it may be walked through; a closed door, until someone does.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct BlocksVisibility {}
+
+/// Pulled by the player to set off whatever it is wired to.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Lever {}
+
+/// Links a lever or pressure plate to the mechanisms it works; cleared once used.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Wired {
+    pub targets : Vec<Entity>
+}
+
+/// A gate that rises out of the way when its mechanism is triggered.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Portcullis {}
+
+/// A wall section that slides away when its mechanism is triggered.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct SecretPassage {}
+
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum PropEffect { Heal(i32), Poison(i32), WellFed(i32), Rest, Bless, Enchant, Repair, Cure(i32) }
+
+/// Scenery the player can use by walking into it, such as a fountain or an altar.
+/// `uses` runs down with each use; `None` never runs out.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Interactive {
+    pub verb : String,
+    pub effects : Vec<PropEffect>,
+    pub uses : Option<i32>
+}
+
+/// Has eaten well recently, and gets stamina back faster for `turns` turns.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct WellFed {
+    pub turns : i32
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum HungerState { WellFed, Normal, Hungry, Starving }
+
+/// How hungry the player is, and how many turns until they get hungrier. The clock stands still
+/// while they are well fed, and is wound back whenever they eat.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct HungerClock {
+    pub state : HungerState,
+    pub duration : i32
+}
+
+/// Food that leaves whoever eats it well fed for `turns` turns.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct ProvidesFood {
+    pub turns : i32
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Deity { Tyrus, Mirel }
+
+/// The god the player follows and how much it currently approves of them.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Faith {
+    pub deity : Deity,
+    pub piety : i32,
+    pub last_prayer : Option<i32>
+}
+
+/// Trained talents from the character's class: melee adds to hit, lockpicking to
+/// lockpick rolls, magic to the damage of offensive scrolls, stealth quiets the noise
+/// of a botched lock and crafting steadies the hand at repairs and enchantments.
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Skills {
+    pub melee : i32,
+    pub lockpicking : i32,
+    pub magic : i32,
+    #[serde(default)]
+    pub stealth : i32,
+    #[serde(default)]
+    pub crafting : i32
+}
+
+/// The skills that improve with use, outside of a fight.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Skill { Lockpicking, Magic, Stealth, Crafting }
+
+/// Experience put towards the next rank of each skill that improves with use.
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct SkillTraining {
+    pub lockpicking : i32,
+    pub magic : i32,
+    pub stealth : i32,
+    pub crafting : i32
+}
+
+/// Passing turns until healed; `hp` is remembered to notice being hurt mid-rest.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Resting {
+    pub turns : i32,
+    pub hp : i32
+}
+
+/// Walking to a marked tile, a step a turn; `hp` is remembered to notice being hurt on the way.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Travelling {
+    pub x : i32,
+    pub y : i32,
+    pub hp : i32
+}
+
+/// Levels gained but not yet spent on the level-up menu.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct PendingLevelUps {
+    pub count : i32
+}
+
+/// Perk: critical hits land on one more point of the d20.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct KeenEye {}
+
+/// Perk: recovers faster between fights.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct FastHealing {}
+
+/// Perk: sees further than most.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct FarSight {}
+
+/// Heard something at this spot and will go and look, unless distracted by the player.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct HeardNoise {
+    pub x : i32,
+    pub y : i32
+}
+
+/// Runs with a pack: hunts alongside the others that share its leader, and scatters once the leader falls.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct PackMember {
+    pub leader : Entity
+}
+
+/// Keeps to within `radius` tiles of home, giving up the chase once its quarry leaves.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Territory {
+    pub home_x : i32,
+    pub home_y : i32,
+    pub radius : i32
+}
+
+/// Walks from one waypoint to the next, round and round, while it has nothing better to do.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Patrol {
+    pub waypoints : Vec<(i32, i32)>,
+    pub next : usize
+}
+
+/// Keeps away from the player for `turns` more turns, only fighting when cornered.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Fleeing {
+    pub turns : i32
+}
+
+/// Drifts through walls as if they weren't there, and never blocks the tile it's on.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct MovesThroughWalls {}
+
+/// Drinks its healing potions when badly hurt, and puts on any better gear it has in its pack.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct UsesItems {}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum AfflictionKind { Disease, Curse }
+
+/// One lingering disease or curse. `turns` is how long until it runs its course, if it ever does.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Affliction {
+    pub kind : AfflictionKind,
+    pub turns : Option<i32>,
+    /// Turns until it next strikes.
+    pub countdown : i32,
+    /// Attribute points it has taken, given back when it is cured.
+    pub drained : i32
+}
+
+/// Diseases and curses that stay with their victim, from level to level, until cured.
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Afflictions {
+    pub list : Vec<Affliction>
+}
+
+/// A creature whose hits, or a trap whose trigger, has a `chance` in 100 to pass on an affliction.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Afflicts {
+    pub kind : AfflictionKind,
+    pub chance : i32
+}
+
+/// Rids whoever uses it of an affliction of this kind.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Cures {
+    pub kind : AfflictionKind
+}
+
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Mutation { Claws, ScaledHide, NightEyes, SlowMetabolism, LongLegs, WitheredArm, BrittleBones, Cataracts, Ravenous, ClubFoot }
+
+/// Permanent changes wrought by chaos: drinking it, or breathing the air too deep below.
+#[derive(Component, Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Mutations {
+    pub list : Vec<Mutation>
+}
+
+/// Mutates whoever uses it: only for the better if `beneficial` is Some(true), only for the worse
+/// if Some(false), and either way if None.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Mutates {
+    pub beneficial : Option<bool>
+}
+
+/// Senses the minds of nearby monsters, seeing them even out of line of sight.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Telepathy {
+    pub turns : i32
+}
+
+/// Using this item grants telepathy for `turns` turns.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct GrantsTelepathy {
+    pub turns : i32
+}
+
+/// Mind-controlled into fighting for the player; whoever owned it before gets it back when `turns` run out.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Charmed {
+    pub turns : i32,
+    pub previous_owner : Option<Entity>,
+    pub previous_turns : Option<i32>
+}
+
+/// Using this item charms the target for `turns` turns.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Charms {
+    pub turns : i32
+}
+
+/// What a companion has been told to do.
+#[derive(PartialEq, Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum CompanionCommand { Follow, Stay, Attack }
+
+/// A recruited follower; unlike a summoned minion it stays for good and travels between levels.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Companion {
+    pub command : CompanionCommand,
+    pub target : Option<Entity>
+}
+
+/// A friendly NPC that joins the player when bumped into.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Recruitable {}
+
+/// A townsperson with work for the player, offered when bumped into.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct QuestGiver {}
+
+/// The group a creature belongs to; harming one sours the player's reputation with all of them.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Faction {
+    pub name : String
+}
+
+/// Temporarily sides with another faction; once `turns` run out it goes back to `previous`, or to none at all.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct FactionOverride {
+    pub turns : i32,
+    pub previous : Option<String>
+}
+
+/// A townsperson with something to say; bumping into them opens the named conversation.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Talks {
+    pub dialogue : String
+}
+
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Experience {
+    pub level : i32,
+    pub xp : i32
+}
+
+/// Using this item calls up a minion for the user.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct SummonsMinion {
+    pub name : String,
+    pub turns : i32
+}
+
+/// The next melee attack is a power attack.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct PowerAttack {}
+
+/// Chance in 100 for a shield to block an incoming blow.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct BlockChance {
+    pub chance : i32
+}
+
+/// Chance in 100 for a weapon to parry an incoming blow.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct ParryChance {
+    pub chance : i32
+}
+
+/// Turns left before the entity can block, parry or dodge again.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct ReactionCooldown {
+    pub turns : i32
+}
+
+/// Lost its footing after a fumble and skips its next turn.
+#[derive(Component, Debug, Serialize, Deserialize, Clone)]
+pub struct Staggered {}
+
+/// Spent this monster turn on a special ability, so it neither moves nor attacks as well.
+#[derive(Component, Debug, Clone)]
+pub struct UsedAbility {}
+
+#[derive(Component, Debug, Clone)]
+pub struct ParticleLifetime {
+    pub lifetime_ms : f32
+}
+
+/// Entity rots away once the turn counter reaches `expires_at`.
+#[derive(Component, Debug, ConvertSaveload, Clone)]
+pub struct Decay {
+    pub expires_at : i32
+}
+
 #[derive(Component, Debug, Clone, Serialize, Deserialize)]
 pub struct SerializeMe;
 
 #[derive(Component, Serialize, Deserialize, Clone)]
 pub struct SerializationHelper {
-    pub map : super::map::Map
+    pub map : super::map::Map,
+    pub turns : super::turn_counter::TurnCounter,
+    pub dungeon : super::dungeon::MasterDungeonMap,
+    pub uniques : super::unique_items::UniqueItems,
+    pub identified : super::identified_items::IdentifiedItems,
+    pub quests : super::quests::QuestLog,
+    pub reputation : super::reputation::Reputation,
+    pub difficulty : super::difficulty::Difficulty,
+    pub record : super::achievements::RunRecord,
+    pub stats : super::run_stats::RunStats,
+    pub challenge : super::challenge::Challenge,
+    pub mode : super::undo::RunMode
 }
\ No newline at end of file
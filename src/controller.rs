@@ -0,0 +1,189 @@
+use rltk::VirtualKeyCode;
+use serde::{Serialize, Deserialize};
+
+/// How far a stick has to be pushed before it counts as a direction.
+const STICK_DEADZONE : f32 = 0.5;
+/// How long a direction has to be held before the player starts walking on by themselves.
+const REPEAT_DELAY_MS : f32 = 300.0;
+/// How often they then take a step.
+const REPEAT_RATE_MS : f32 = 120.0;
+
+/// The buttons that can be bound to an action, named as on an Xbox pad.
+#[derive(PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum PadButton { South, East, West, North, LeftBumper, RightBumper, Select, Start }
+
+impl PadButton {
+    pub fn name(self) -> &'static str {
+        match self {
+            PadButton::South => "A",
+            PadButton::East => "B",
+            PadButton::West => "X",
+            PadButton::North => "Y",
+            PadButton::LeftBumper => "LB",
+            PadButton::RightBumper => "RB",
+            PadButton::Select => "Back",
+            PadButton::Start => "Start"
+        }
+    }
+
+    fn next(self) -> PadButton {
+        match self {
+            PadButton::South => PadButton::East,
+            PadButton::East => PadButton::West,
+            PadButton::West => PadButton::North,
+            PadButton::North => PadButton::LeftBumper,
+            PadButton::LeftBumper => PadButton::RightBumper,
+            PadButton::RightBumper => PadButton::Select,
+            PadButton::Select => PadButton::Start,
+            PadButton::Start => PadButton::South
+        }
+    }
+}
+
+/// What a button can be bound to. Each stands in for the key that does the same on the keyboard, so
+/// every screen that takes those keys takes the controller too.
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum PadAction { Confirm, Cancel, Pickup, Inventory, Wait }
+
+impl PadAction {
+    pub const ALL : [PadAction; 5] = [PadAction::Confirm, PadAction::Cancel, PadAction::Pickup, PadAction::Inventory, PadAction::Wait];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PadAction::Confirm => "confirm",
+            PadAction::Cancel => "cancel",
+            PadAction::Pickup => "pick up",
+            PadAction::Inventory => "inventory",
+            PadAction::Wait => "wait"
+        }
+    }
+
+    fn key(self) -> VirtualKeyCode {
+        match self {
+            PadAction::Confirm => VirtualKeyCode::Return,
+            PadAction::Cancel => VirtualKeyCode::Escape,
+            PadAction::Pickup => VirtualKeyCode::G,
+            PadAction::Inventory => VirtualKeyCode::I,
+            PadAction::Wait => VirtualKeyCode::Period
+        }
+    }
+}
+
+/// Which button does what, in the order of PadAction::ALL. The sticks and d-pad always move.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+pub struct PadBindings {
+    buttons : [PadButton; 5]
+}
+
+impl Default for PadBindings {
+    fn default() -> PadBindings {
+        PadBindings{ buttons: [PadButton::South, PadButton::East, PadButton::West, PadButton::North, PadButton::RightBumper] }
+    }
+}
+
+impl PadBindings {
+    pub fn button(&self, action : PadAction) -> PadButton {
+        self.buttons[action as usize]
+    }
+
+    fn action(&self, button : PadButton) -> Option<PadAction> {
+        PadAction::ALL.iter().copied().find(|action| self.button(*action) == button)
+    }
+
+    /// Moves an action on to the next button. Whatever had that button before takes over the old one,
+    /// so no two actions ever share a button.
+    pub fn rebind(&mut self, action : PadAction) {
+        let old = self.button(action);
+        let new = old.next();
+        if let Some(other) = self.action(new) {
+            self.buttons[other as usize] = old;
+        }
+        self.buttons[action as usize] = new;
+    }
+}
+
+/// Reads the first connected controller and turns what it does into the keys the game already
+/// understands. Only the desktop build can see controllers.
+#[derive(Default)]
+pub struct Controller {
+    /// Opened on the first poll, so a world that never reads input doesn't go looking for devices.
+    #[cfg(not(target_arch = "wasm32"))]
+    gilrs : Option<Option<gilrs::Gilrs>>,
+    held : (i32, i32),
+    repeat_ms : f32
+}
+
+impl Controller {
+    #[cfg(target_arch = "wasm32")]
+    pub fn poll(&mut self, _bindings : &PadBindings, _frame_time_ms : f32) -> Option<VirtualKeyCode> {
+        None
+    }
+
+    /// The key the controller pressed this frame, if any: a bound button, or a step in the direction
+    /// the stick or d-pad is held.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn poll(&mut self, bindings : &PadBindings, frame_time_ms : f32) -> Option<VirtualKeyCode> {
+        use gilrs::{Axis, Button, EventType};
+        let gilrs = self.gilrs.get_or_insert_with(|| gilrs::Gilrs::new().ok()).as_mut()?;
+
+        let mut pressed = None;
+        while let Some(event) = gilrs.next_event() {
+            if let EventType::ButtonPressed(button, _) = event.event {
+                let button = match button {
+                    Button::South => PadButton::South,
+                    Button::East => PadButton::East,
+                    Button::West => PadButton::West,
+                    Button::North => PadButton::North,
+                    Button::LeftTrigger => PadButton::LeftBumper,
+                    Button::RightTrigger => PadButton::RightBumper,
+                    Button::Select => PadButton::Select,
+                    Button::Start => PadButton::Start,
+                    _ => continue
+                };
+                if pressed.is_none() {
+                    pressed = bindings.action(button).map(|action| action.key());
+                }
+            }
+        }
+
+        let mut direction = (0, 0);
+        if let Some((_, pad)) = gilrs.gamepads().next() {
+            let x = pad.value(Axis::LeftStickX);
+            let y = pad.value(Axis::LeftStickY);
+            if pad.is_pressed(Button::DPadLeft) || x < -STICK_DEADZONE { direction.0 = -1; }
+            if pad.is_pressed(Button::DPadRight) || x > STICK_DEADZONE { direction.0 = 1; }
+            // The stick counts up as positive, the map counts down
+            if pad.is_pressed(Button::DPadUp) || y > STICK_DEADZONE { direction.1 = -1; }
+            if pad.is_pressed(Button::DPadDown) || y < -STICK_DEADZONE { direction.1 = 1; }
+        }
+
+        let step = if direction != self.held {
+            self.held = direction;
+            self.repeat_ms = REPEAT_DELAY_MS;
+            direction != (0, 0)
+        } else if direction != (0, 0) {
+            self.repeat_ms -= frame_time_ms;
+            let repeat = self.repeat_ms <= 0.0;
+            if repeat { self.repeat_ms += REPEAT_RATE_MS; }
+            repeat
+        } else {
+            false
+        };
+
+        pressed.or(if step { direction_key(direction) } else { None })
+    }
+}
+
+fn direction_key(direction : (i32, i32)) -> Option<VirtualKeyCode> {
+    match direction {
+        (-1, 0) => Some(VirtualKeyCode::Left),
+        (1, 0) => Some(VirtualKeyCode::Right),
+        (0, -1) => Some(VirtualKeyCode::Up),
+        (0, 1) => Some(VirtualKeyCode::Down),
+        (-1, -1) => Some(VirtualKeyCode::Numpad7),
+        (1, -1) => Some(VirtualKeyCode::Numpad9),
+        (-1, 1) => Some(VirtualKeyCode::Numpad1),
+        (1, 1) => Some(VirtualKeyCode::Numpad3),
+        _ => None
+    }
+}
@@ -0,0 +1,47 @@
+use specs::prelude::*;
+use super::{ReactionCooldown, Webbed, RunState, turn_counter::TurnCounter, gamelog::{GameLog, LogCategory}};
+
+/// Counts down defensive reaction cooldowns and webs as turns pass, whatever the one caught did with it.
+pub struct CooldownSystem {}
+
+impl<'a> System<'a> for CooldownSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, TurnCounter>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, ReactionCooldown>,
+                        WriteStorage<'a, Webbed> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, runstate, player_entity, turns, mut log, mut cooldowns, mut webbed) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        let mut ready : Vec<Entity> = Vec::new();
+        for (entity, cooldown) in (&entities, &mut cooldowns).join() {
+            cooldown.turns -= turns.elapsed();
+            if cooldown.turns < 1 {
+                ready.push(entity);
+            }
+        }
+        for entity in ready {
+            cooldowns.remove(entity);
+        }
+
+        let mut freed : Vec<Entity> = Vec::new();
+        for (entity, web) in (&entities, &mut webbed).join() {
+            web.turns -= turns.elapsed();
+            if web.turns < 1 {
+                freed.push(entity);
+            }
+        }
+        for entity in freed {
+            webbed.remove(entity);
+            if entity == *player_entity {
+                log.push(LogCategory::Combat, "You break free of the web.".to_string());
+            }
+        }
+    }
+}
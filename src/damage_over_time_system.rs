@@ -0,0 +1,40 @@
+use specs::prelude::*;
+use super::{DamageOverTime, SufferDamage, RunState, turn_counter::TurnCounter, gamelog::{GameLog, LogCategory}};
+
+/// Ticks poison, bleeding and other lingering effects once a round, wearing them off as turns pass.
+pub struct DamageOverTimeSystem {}
+
+impl<'a> System<'a> for DamageOverTimeSystem {
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, TurnCounter>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, DamageOverTime>,
+                        WriteStorage<'a, SufferDamage> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, player_entity, runstate, turns, mut log, mut damage_over_time, mut suffer_damage) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        let mut expired : Vec<Entity> = Vec::new();
+        for (entity, dots) in (&entities, &mut damage_over_time).join() {
+            for effect in dots.effects.iter_mut() {
+                SufferDamage::new_damage(&mut suffer_damage, entity, effect.damage, effect.kind, false, None);
+                effect.turns -= turns.elapsed();
+                if entity == *player_entity {
+                    log.push(LogCategory::Combat, format!("You suffer {} {} damage.", effect.damage, effect.kind.name()));
+                }
+            }
+            dots.effects.retain(|e| e.turns > 0);
+            if dots.effects.is_empty() {
+                expired.push(entity);
+            }
+        }
+
+        for entity in expired {
+            damage_over_time.remove(entity);
+        }
+    }
+}
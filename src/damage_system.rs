@@ -1,19 +1,74 @@
-use rltk::{console};
 use specs::prelude::*;
-use crate::gamelog::GameLog;
-use super::{CombatStats, Name, Player, SufferDamage};
+use rltk::RGB;
+use crate::gamelog::{GameLog, LogCategory};
+use crate::effects::{EffectQueue, EffectType, Targets};
+use super::{CombatStats, Name, Player, SufferDamage, Resistances, DamageType, RunState, Map, Position, InBackpack, Equipped, LootTable, SpecialAbilities, AbilityKind, Boss, OwnedBy, Monster, Companion, Experience, Faith, Deity, PendingLevelUps, religion,
+            Faction, gamesystem::gain_xp, events::{self, GameEvents, GameEvent}, reputation::{Reputation, Reaction}, difficulty::Difficulty, wizard::WizardMode, visual_effects::VisualEffects, scores, spawner, raws};
 
 pub struct DamageSystem {}
 
 impl<'a> System<'a> for DamageSystem {
-    type SystemData = ( WriteStorage<'a, CombatStats>,
-                        WriteStorage<'a, SufferDamage> );
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        WriteStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        ReadStorage<'a, Resistances>,
+                        ReadStorage<'a, Faction>,
+                        WriteStorage<'a, Monster>,
+                        WriteStorage<'a, OwnedBy>,
+                        WriteStorage<'a, Companion>,
+                        ReadStorage<'a, Name>,
+                        WriteExpect<'a, Reputation>,
+                        WriteExpect<'a, GameLog>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, WizardMode>,
+                        WriteExpect<'a, VisualEffects>,
+                        WriteExpect<'a, GameEvents> );
 
     fn run(&mut self, data : Self::SystemData) {
-        let (mut stats, mut damage) = data;
+        let (entities, mut stats, mut damage, resistances, factions, mut monsters, mut owned, mut companions, names, mut reputation, mut log,
+            player_entity, wizard, mut vfx, mut events) = data;
 
-        for (mut stats, damage) in (&mut stats, &damage).join() {
-            stats.hp -= damage.amount.iter().sum::<i32>();
+        let mut changed : Vec<String> = Vec::new();
+        for (entity, mut stats, damage) in (&entities, &mut stats, &damage).join() {
+            // In god mode, nothing can touch the player
+            if wizard.god_mode && entity == *player_entity { continue; }
+            let resist = resistances.get(entity);
+            let mut total = 0;
+            for (amount, kind) in damage.amount.iter() {
+                let percent = resist.and_then(|r| r.percent.get(kind)).copied().unwrap_or(0).clamp(-100, 100);
+                total += amount * (100 - percent) / 100;
+            }
+            stats.hp -= total;
+            if total > 0 {
+                vfx.hit(entity, entity == *player_entity, total, stats.max_hp);
+                events.single_write(GameEvent::Damaged{ target: entity, amount: total, from_player: damage.from_player, by: damage.source });
+            }
+            // Harming a faction's members is remembered, whether the player did it or one of their minions
+            let by_minion = damage.source.and_then(|source| owned.get(source)).map_or(false, |o| o.owner == *player_entity);
+            if damage.from_player || by_minion {
+                if let Some(faction) = factions.get(entity) {
+                    changed.extend(reputation.offend(&faction.name));
+                }
+            }
+        }
+
+        // Factions that have had enough of the player turn on them, and their members serving the player desert
+        if !changed.is_empty() {
+            for (entity, faction) in (&entities, &factions).join() {
+                let serving = owned.get(entity).map_or(false, |o| o.owner == *player_entity);
+                if !changed.contains(&faction.name) || (monsters.get(entity).is_some() && !serving) { continue; }
+                if reputation.reaction(&faction.name) == Reaction::Hostile {
+                    if serving {
+                        owned.remove(entity);
+                        companions.remove(entity);
+                    }
+                    monsters.insert(entity, Monster{}).expect("Unable to insert monster");
+                    if let Some(name) = names.get(entity) {
+                        log.push_colored(LogCategory::Combat, format!("The {} turns on you!", name.name), RGB::named(rltk::RED));
+                    }
+                }
+            }
         }
 
         damage.clear();
@@ -22,32 +77,218 @@ impl<'a> System<'a> for DamageSystem {
 
 pub fn delete_the_dead(ecs : &mut World) {
     let mut dead : Vec<Entity> = Vec::new();
+    let mut player_died = false;
     // Using a scope to make the borrow checker happy
     {
         let combat_stats = ecs.read_storage::<CombatStats>();
         let players = ecs.read_storage::<Player>();
-        let names = ecs.read_storage::<Name>();
         let entities = ecs.entities();
-        let mut log = ecs.write_resource::<GameLog>();
 
         for (entity, stats) in (&entities, &combat_stats).join() {
             if stats.hp < 1 {
                 let player = players.get(entity);
                 match player {
-                    None => {
-                        let victim_name = names.get(entity);
-                        if let Some(victim_name) = victim_name {
-                            log.entries.push(format!("{} is dead", &victim_name.name));
+                    None => dead.push(entity),
+                    Some(_) => {
+                        let mut runstate = ecs.write_resource::<RunState>();
+                        if *runstate != RunState::GameOver {
+                            *runstate = RunState::GameOver;
+                            player_died = true;
                         }
-                        dead.push(entity)
                     }
-                    Some(_) => console::log("You are dead")
                 }
             }
         }
     }
 
+    // Summoned minions don't outlive whoever called them
+    let mut dismissed : Vec<Entity> = Vec::new();
+    {
+        let entities = ecs.entities();
+        let owned = ecs.read_storage::<OwnedBy>();
+        let names = ecs.read_storage::<Name>();
+        let mut log = ecs.write_resource::<GameLog>();
+        for (entity, minion) in (&entities, &owned).join() {
+            if dead.contains(&minion.owner) && !dead.contains(&entity) {
+                if let Some(name) = names.get(entity) {
+                    log.push(LogCategory::Combat, format!("The {} fades away.", name.name));
+                }
+                dismissed.push(entity);
+            }
+        }
+    }
+
+    // The player and any companions share the experience for every foe that falls
+    {
+        let entities = ecs.entities();
+        let player_entity = *ecs.fetch::<Entity>();
+        let monsters = ecs.read_storage::<Monster>();
+        let owned = ecs.read_storage::<OwnedBy>();
+        let companions = ecs.read_storage::<Companion>();
+        let names = ecs.read_storage::<Name>();
+        let mut combat_stats = ecs.write_storage::<CombatStats>();
+        let mut experience = ecs.write_storage::<Experience>();
+        let mut faiths = ecs.write_storage::<Faith>();
+        let mut pending = ecs.write_storage::<PendingLevelUps>();
+        let mut log = ecs.write_resource::<GameLog>();
+
+        let foes : Vec<&Entity> = dead.iter()
+            .filter(|v| monsters.get(**v).is_some() && owned.get(**v).map_or(true, |o| o.owner != player_entity))
+            .collect();
+        let gained : i32 = foes.iter()
+            .filter_map(|v| combat_stats.get(**v))
+            .map(|s| s.max_hp)
+            .sum();
+        let gained = gained * ecs.fetch::<Difficulty>().xp_percent() / 100;
+
+        // Let whoever is listening know who fell; only foes count once the player is gone
+        {
+            let mut events = ecs.write_resource::<GameEvents>();
+            for victim in dead.iter() {
+                if let Some(name) = names.get(*victim) {
+                    events.single_write(GameEvent::Died{ entity: *victim, name: name.name.clone(), foe: !player_died && foes.contains(&victim) });
+                }
+            }
+        }
+
+        // Tyrus counts every fallen foe in the player's favour
+        if let Some(faith) = faiths.get_mut(player_entity) {
+            if faith.deity == Deity::Tyrus && !player_died {
+                faith.piety += religion::KILL_PIETY * foes.len() as i32;
+            }
+        }
+        if gained > 0 && !player_died {
+            for (entity, exp, stats) in (&entities, &mut experience, &mut combat_stats).join() {
+                if entity != player_entity && companions.get(entity).is_none() { continue; }
+                if dead.contains(&entity) { continue; }
+
+                let levels = gain_xp(exp, stats, gained);
+                if levels < 1 { continue; }
+                if entity == player_entity {
+                    log.push_colored(LogCategory::System, format!("Welcome to level {}!", exp.level), RGB::named(rltk::GOLD));
+                    ecs.write_resource::<GameEvents>().single_write(GameEvent::LevelledUp{ level: exp.level });
+                    match pending.get_mut(entity) {
+                        Some(owed) => owed.count += levels,
+                        None => { pending.insert(entity, PendingLevelUps{ count: levels }).expect("Unable to insert level up"); }
+                    }
+                } else {
+                    stats.power += levels;
+                    if let Some(name) = names.get(entity) {
+                        log.push(LogCategory::Combat, format!("The {} grows stronger.", name.name));
+                    }
+                }
+            }
+        }
+    }
+
+    if player_died {
+        events::publish(ecs, GameEvent::PlayerDied);
+        scores::record_score(ecs, false);
+    }
+
+    // Whatever the dead were carrying falls to the floor, next to their corpse
+    let mut corpses : Vec<(String, i32, i32)> = Vec::new();
+    let mut boss_loot : Vec<(String, i32, i32)> = Vec::new();
+    let mut explosions : Vec<(Entity, i32, i32, i32, i32)> = Vec::new();
+    let mut loot_drops : Vec<(String, i32, i32)> = Vec::new();
+    {
+        let entities = ecs.entities();
+        let names = ecs.read_storage::<Name>();
+        let loot_tables = ecs.read_storage::<LootTable>();
+        let abilities = ecs.read_storage::<SpecialAbilities>();
+        let bosses = ecs.read_storage::<Boss>();
+        let mut positions = ecs.write_storage::<Position>();
+        let mut backpack = ecs.write_storage::<InBackpack>();
+        let mut equipped = ecs.write_storage::<Equipped>();
+
+        for victim in dead.iter() {
+            let pos = match positions.get(*victim) {
+                Some(pos) => pos.clone(),
+                None => continue
+            };
+
+            let mut to_drop : Vec<Entity> = Vec::new();
+            for (item, pack) in (&entities, &backpack).join() {
+                if pack.owner == *victim {
+                    to_drop.push(item);
+                }
+            }
+            for (item, worn) in (&entities, &equipped).join() {
+                if worn.owner == *victim {
+                    to_drop.push(item);
+                }
+            }
+            for item in to_drop.iter() {
+                backpack.remove(*item);
+                equipped.remove(*item);
+                positions.insert(*item, Position{ x: pos.x, y: pos.y }).expect("Unable to insert position");
+            }
+
+            if let Some(name) = names.get(*victim) {
+                corpses.push((name.name.clone(), pos.x, pos.y));
+            }
+            if let Some(table) = loot_tables.get(*victim) {
+                loot_drops.push((table.table.clone(), pos.x, pos.y));
+            }
+            if let Some(loot) = bosses.get(*victim).and_then(|b| b.loot.clone()) {
+                boss_loot.push((loot, pos.x, pos.y));
+            }
+            if let Some(abilities) = abilities.get(*victim) {
+                for ability in abilities.abilities.iter().filter(|a| a.kind == AbilityKind::SelfDestruct) {
+                    explosions.push((*victim, pos.x, pos.y, ability.power, ability.range as i32));
+                }
+            }
+        }
+    }
+    // Some creatures go out with a bang, hurting everything nearby
+    for (victim, x, y, damage, radius) in explosions.iter() {
+        let tiles = {
+            let map = ecs.fetch::<Map>();
+            let mut tiles = Vec::new();
+            for ty in y - radius ..= y + radius {
+                for tx in x - radius ..= x + radius {
+                    if tx > 0 && tx < map.width - 1 && ty > 0 && ty < map.height - 1 {
+                        tiles.push(map.xy_idx(tx, ty));
+                    }
+                }
+            }
+            tiles
+        };
+        if let Some(name) = ecs.read_storage::<Name>().get(*victim) {
+            ecs.write_resource::<GameLog>().push(LogCategory::Combat, format!("{} explodes!", name.name));
+            events::publish(ecs, GameEvent::AbilityUsed{ by: *victim, name: name.name.clone(), ability: AbilityKind::SelfDestruct });
+        }
+        ecs.write_resource::<VisualEffects>().shake();
+        ecs.write_resource::<EffectQueue>().add_effect(Some(*victim), EffectType::Damage{ amount: *damage, kind: DamageType::Fire }, Targets::Tiles{ tiles });
+    }
+
+    for (name, x, y) in corpses.iter() {
+        spawner::corpse(ecs, name, *x, *y);
+    }
+    for (table, x, y) in loot_drops.iter() {
+        let drop = {
+            let depth = ecs.fetch::<Map>().depth;
+            let mut rng = ecs.write_resource::<rltk::RandomNumberGenerator>();
+            raws::get_item_drop(&raws::raws(), &mut rng, table, depth)
+        };
+        if let Some(item) = drop {
+            let depth = ecs.fetch::<Map>().depth;
+            let raws = raws::raws();
+            if let Some(entity) = raws::spawn_named_entity(&raws, ecs, &item, *x, *y) {
+                raws::roll_affixes(&raws, ecs, entity, depth);
+            }
+        }
+    }
+
+    // Bosses always leave their unique treasure behind
+    for (item, x, y) in boss_loot.iter() {
+        raws::spawn_named_entity(&raws::raws(), ecs, item, *x, *y);
+    }
+
     for victim in dead {
         ecs.delete_entity(victim).expect("Unable to delete");
     }
-}
\ No newline at end of file
+    for minion in dismissed {
+        ecs.delete_entity(minion).expect("Unable to delete");
+    }
+}
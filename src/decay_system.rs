@@ -0,0 +1,21 @@
+use specs::prelude::*;
+use super::{Decay, turn_counter::TurnCounter};
+
+/// Removes corpses and other perishables once their time is up.
+pub struct DecaySystem {}
+
+impl<'a> System<'a> for DecaySystem {
+    type SystemData = ( Entities<'a>,
+                        ReadStorage<'a, Decay>,
+                        ReadExpect<'a, TurnCounter> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, decay, turns) = data;
+
+        for (entity, decay) in (&entities, &decay).join() {
+            if turns.has_passed(decay.expires_at) {
+                entities.delete(entity).expect("Unable to delete");
+            }
+        }
+    }
+}
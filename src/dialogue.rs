@@ -0,0 +1,40 @@
+use specs::prelude::*;
+use super::{Talks, RunState, quests, vendor, raws};
+
+/// Which conversation the townsperson holds, if any.
+pub fn dialogue_of(ecs : &World, npc : Entity) -> Option<String> {
+    ecs.read_storage::<Talks>().get(npc).map(|t| t.dialogue.clone())
+}
+
+/// Opens a conversation with the townsperson at its first node.
+pub fn start(npc : Entity) -> RunState {
+    RunState::Dialogue{ npc, node: 0 }
+}
+
+/// Follows the player's reply: on to another node, or into whatever the reply does.
+pub fn respond(ecs : &mut World, npc : Entity, node : usize, choice : usize) -> RunState {
+    let dialogue = match dialogue_of(ecs, npc) {
+        Some(dialogue) => dialogue,
+        None => return RunState::AwaitingInput
+    };
+    let (next, action) = {
+        let raws = raws::raws();
+        let response = raws::dialogue_node(&raws, &dialogue, node).and_then(|n| n.responses.get(choice));
+        match response {
+            Some(response) => {
+                let next = response.next.as_ref().and_then(|id| raws::dialogue_node_index(&raws, &dialogue, id));
+                (next, response.action.clone())
+            }
+            None => return RunState::AwaitingInput
+        }
+    };
+
+    if let Some(next) = next {
+        return RunState::Dialogue{ npc, node: next };
+    }
+    match action.as_deref() {
+        Some("quests") => quests::talk(ecs, npc),
+        Some("shop") => vendor::open_shop(ecs, npc),
+        _ => RunState::AwaitingInput
+    }
+}
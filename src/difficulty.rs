@@ -0,0 +1,51 @@
+use serde::{Serialize, Deserialize};
+
+/// How hard the run is, picked when creating the character. Each setting scales a handful of
+/// numbers by a percentage: Normal leaves everything as it is.
+#[derive(PartialEq, Eq, Copy, Clone, Debug, Serialize, Deserialize, Default)]
+pub enum Difficulty { Easy, #[default] Normal, Hard }
+
+impl Difficulty {
+    pub const ALL : [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard"
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Fewer, weaker monsters; meals last longer and you learn faster.",
+            Difficulty::Normal => "The dungeon as it was meant to be.",
+            Difficulty::Hard => "More, tougher monsters; you go hungry sooner and learn slower."
+        }
+    }
+
+    /// Scales how many things are placed in each part of a new level.
+    pub fn spawn_percent(&self) -> i32 {
+        match self { Difficulty::Easy => 75, Difficulty::Normal => 100, Difficulty::Hard => 130 }
+    }
+
+    /// Scales the hit points and power of every monster as it is spawned.
+    pub fn monster_percent(&self) -> i32 {
+        match self { Difficulty::Easy => 80, Difficulty::Normal => 100, Difficulty::Hard => 125 }
+    }
+
+    /// Scales how long a meal keeps the player well fed.
+    pub fn meal_percent(&self) -> i32 {
+        match self { Difficulty::Easy => 150, Difficulty::Normal => 100, Difficulty::Hard => 60 }
+    }
+
+    /// Scales all the experience the player earns.
+    pub fn xp_percent(&self) -> i32 {
+        match self { Difficulty::Easy => 125, Difficulty::Normal => 100, Difficulty::Hard => 80 }
+    }
+}
+
+/// Applies one of the difficulty percentages to a number, never scaling a positive value below one.
+pub fn scale(value : i32, percent : i32) -> i32 {
+    if value > 0 { i32::max(1, value * percent / 100) } else { value }
+}
@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use specs::prelude::*;
+use super::{Door, BlocksVisibility, Position, Viewshed, Renderable, Name, Map, events::{GameEvents, GameEvent}};
+
+/// Swings closed doors open when anyone steps into the doorway, and keeps the map's note of which
+/// tiles they hide what lies beyond up to date. There are only ever a handful of doors, so it is
+/// simply rebuilt every tick.
+pub struct DoorSystem {}
+
+impl<'a> System<'a> for DoorSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        WriteExpect<'a, Map>,
+                        WriteExpect<'a, GameEvents>,
+                        ReadStorage<'a, Door>,
+                        WriteStorage<'a, BlocksVisibility>,
+                        ReadStorage<'a, Position>,
+                        WriteStorage<'a, Viewshed>,
+                        WriteStorage<'a, Renderable>,
+                        WriteStorage<'a, Name> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, mut map, mut events, doors, mut blocks_visibility, positions, mut viewsheds, mut renderables, mut names) = data;
+
+        let occupants : HashMap<usize, Entity> = (&entities, &positions, &viewsheds).join()
+            .map(|(entity, pos, _viewshed)| (map.xy_idx(pos.x, pos.y), entity))
+            .collect();
+
+        map.view_blocked.clear();
+        let mut opened : Vec<(Entity, Entity)> = Vec::new();
+        for (door, _door, _blocks, pos) in (&entities, &doors, &blocks_visibility, &positions).join() {
+            let idx = map.xy_idx(pos.x, pos.y);
+            match occupants.get(&idx) {
+                Some(by) => opened.push((door, *by)),
+                None => { map.view_blocked.insert(idx); }
+            }
+        }
+        if opened.is_empty() { return; }
+
+        for (door, by) in opened {
+            blocks_visibility.remove(door);
+            if let Some(render) = renderables.get_mut(door) {
+                render.glyph = rltk::to_cp437('\'');
+            }
+            let name = names.get(door).map_or("door".to_string(), |n| n.name.clone());
+            events.single_write(GameEvent::DoorOpened{ by, door, name });
+            names.insert(door, Name{ name: "Open Door".to_string() }).expect("Unable to insert name");
+        }
+        // The view through the doorway has opened up for everyone nearby
+        for viewshed in (&mut viewsheds).join() {
+            viewshed.dirty = true;
+        }
+    }
+}
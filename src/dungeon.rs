@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use specs::prelude::*;
+use rltk::Point;
+use super::{Map, Position, OtherLevelPosition, Branch, Companion, CompanionCommand};
+
+/// Keeps every level the player has visited, so backtracking finds them unchanged.
+#[derive(Default, Serialize, Deserialize, Clone)]
+pub struct MasterDungeonMap {
+    maps : HashMap<i32, Map>
+}
+
+impl MasterDungeonMap {
+    pub fn new() -> MasterDungeonMap {
+        MasterDungeonMap{ maps: HashMap::new() }
+    }
+
+    pub fn store_map(&mut self, map : &Map) {
+        self.maps.insert(map.branch.level_key(map.depth), map.clone());
+    }
+
+    pub fn get_map(&self, branch : Branch, depth : i32) -> Option<Map> {
+        if let Some(map) = self.maps.get(&branch.level_key(depth)) {
+            let mut result = map.clone();
+            result.reset_index();
+            Some(result)
+        } else {
+            None
+        }
+    }
+}
+
+/// A jump to another level that bypasses the stairs, such as a town portal, waiting for the game loop to carry it out.
+#[derive(Default)]
+pub struct LevelTeleport {
+    pub destination : Option<(Branch, i32, Point)>
+}
+
+/// Takes everything but the player and any companions following along off the current level,
+/// remembering where it was.
+pub fn freeze_level_entities(ecs: &mut World) {
+    let entities = ecs.entities();
+    let companions = ecs.read_storage::<Companion>();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut other_level_positions = ecs.write_storage::<OtherLevelPosition>();
+    let player_entity = ecs.fetch::<Entity>();
+    let (map_depth, map_branch) = {
+        let map = ecs.fetch::<Map>();
+        (map.depth, map.branch)
+    };
+
+    let mut pos_to_delete : Vec<Entity> = Vec::new();
+    for (entity, pos) in (&entities, &positions).join() {
+        let travelling = companions.get(entity).map_or(false, |c| c.command != CompanionCommand::Stay);
+        if entity != *player_entity && !travelling {
+            other_level_positions.insert(entity, OtherLevelPosition{ x: pos.x, y: pos.y, depth: map_depth, branch: map_branch }).expect("Insert fail");
+            pos_to_delete.push(entity);
+        }
+    }
+
+    for p in pos_to_delete.iter() {
+        positions.remove(*p);
+    }
+}
+
+/// Puts the entities frozen on the current depth back onto the map.
+pub fn thaw_level_entities(ecs: &mut World) {
+    let entities = ecs.entities();
+    let mut positions = ecs.write_storage::<Position>();
+    let mut other_level_positions = ecs.write_storage::<OtherLevelPosition>();
+    let player_entity = ecs.fetch::<Entity>();
+    let (map_depth, map_branch) = {
+        let map = ecs.fetch::<Map>();
+        (map.depth, map.branch)
+    };
+
+    let mut pos_to_delete : Vec<Entity> = Vec::new();
+    for (entity, pos) in (&entities, &other_level_positions).join() {
+        if entity != *player_entity && pos.depth == map_depth && pos.branch == map_branch {
+            positions.insert(entity, Position{ x: pos.x, y: pos.y }).expect("Insert fail");
+            pos_to_delete.push(entity);
+        }
+    }
+
+    for p in pos_to_delete.iter() {
+        other_level_positions.remove(*p);
+    }
+}
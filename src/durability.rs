@@ -0,0 +1,88 @@
+use specs::prelude::*;
+use specs::storage::GenericReadStorage;
+use super::{Durability, Equipped, Name, Purse, gamelog::{GameLog, LogCategory}};
+
+/// Durability of weapons, shields and armor whose raws don't say otherwise.
+pub const DEFAULT_DURABILITY : i32 = 40;
+/// Gold the blacksmith charges for every point of durability restored.
+pub const REPAIR_GOLD_PER_POINT : i32 = 2;
+
+/// Equipment below a quarter of its durability only gives half its bonus.
+pub fn worn_bonus(bonus : i32, durability : Option<&Durability>) -> i32 {
+    match durability {
+        Some(d) if d.current * 4 < d.max => bonus / 2,
+        _ => bonus
+    }
+}
+
+/// Wears each piece down by a point, however many blows it saw this turn. Anything worn out breaks.
+pub fn wear_down<E, N>(items : &mut Vec<Entity>, entities : &Entities, durability : &mut WriteStorage<Durability>, equipped : &E, names : &N, log : &mut GameLog)
+    where E : GenericReadStorage<Component = Equipped>, N : GenericReadStorage<Component = Name>
+{
+    items.sort();
+    items.dedup();
+    for item in items.iter() {
+        if let Some(d) = durability.get_mut(*item) {
+            d.current -= 1;
+            if d.current < 1 {
+                if let (Some(owner), Some(item_name)) = (equipped.get(*item).and_then(|e| names.get(e.owner)), names.get(*item)) {
+                    log.push(LogCategory::Items, format!("{}'s {} breaks!", owner.name, item_name.name));
+                }
+                entities.delete(*item).expect("Unable to delete broken item");
+            }
+        }
+    }
+}
+
+pub fn condition(durability : &Durability) -> &'static str {
+    match durability.current * 4 {
+        c if c >= durability.max * 4 => "pristine",
+        c if c >= durability.max * 2 => "worn",
+        c if c >= durability.max => "damaged",
+        _ => "badly damaged"
+    }
+}
+
+pub fn repair_cost(durability : &Durability) -> i32 {
+    (durability.max - durability.current) * REPAIR_GOLD_PER_POINT
+}
+
+/// The blacksmith fully repairs an item if the customer can pay for it.
+pub fn blacksmith_repair(ecs : &mut World, customer : Entity, item : Entity) {
+    let name = ecs.read_storage::<Name>().get(item).map_or("item".to_string(), |n| n.name.clone());
+    let cost = match ecs.read_storage::<Durability>().get(item) {
+        Some(d) => repair_cost(d),
+        None => return
+    };
+    let mut purses = ecs.write_storage::<Purse>();
+    match purses.get_mut(customer) {
+        Some(purse) if purse.gold >= cost => {
+            purse.gold -= cost;
+            if let Some(d) = ecs.write_storage::<Durability>().get_mut(item) { d.current = d.max; }
+            ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("The blacksmith mends your {} for {} gold.", name, cost));
+        }
+        _ => ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("Mending the {} costs {} gold, which you don't have.", name, cost))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn condition_follows_the_quarters() {
+        assert_eq!(condition(&Durability{ current: 40, max: 40 }), "pristine");
+        assert_eq!(condition(&Durability{ current: 39, max: 40 }), "worn");
+        assert_eq!(condition(&Durability{ current: 20, max: 40 }), "worn");
+        assert_eq!(condition(&Durability{ current: 19, max: 40 }), "damaged");
+        assert_eq!(condition(&Durability{ current: 10, max: 40 }), "damaged");
+        assert_eq!(condition(&Durability{ current: 9, max: 40 }), "badly damaged");
+    }
+
+    #[test]
+    fn repairs_cost_per_missing_point() {
+        assert_eq!(repair_cost(&Durability{ current: 40, max: 40 }), 0);
+        assert_eq!(repair_cost(&Durability{ current: 25, max: 40 }), 15 * REPAIR_GOLD_PER_POINT);
+        assert_eq!(repair_cost(&Durability{ current: 0, max: 40 }), 40 * REPAIR_GOLD_PER_POINT);
+    }
+}
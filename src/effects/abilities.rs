@@ -0,0 +1,185 @@
+use specs::prelude::*;
+use crate::{Map, Name, BlocksTile, Webbed, OwnedBy, Charmed, Monster, Boss, WellFed, Pools, Equippable, EquipmentSlot, MeleePowerBonus, DefenseBonus, Faith, Ammo, Faction, FactionOverride,
+    InBackpack, Fleeing, Unidentified, raws, religion, identified_items, gamelog::{GameLog, LogCategory}};
+use super::EffectSpawner;
+
+pub fn web(ecs : &mut World, target : Entity, turns : i32) {
+    ecs.write_storage::<Webbed>().insert(target, Webbed{ turns }).expect("Unable to insert status");
+    if let Some(name) = ecs.read_storage::<Name>().get(target) {
+        ecs.write_resource::<GameLog>().push(LogCategory::Combat, format!("{} is caught in a sticky web!", name.name));
+    }
+}
+
+/// A thief grabs something at random from the target's backpack and makes off with it; it stays in
+/// the thief's own pack, to fall with everything else it carries when it dies.
+pub fn steal(ecs : &mut World, effect : &EffectSpawner, target : Entity, flee_turns : i32) {
+    let thief = match effect.creator {
+        Some(thief) => thief,
+        None => return
+    };
+    let loose : Vec<Entity> = (&ecs.entities(), &ecs.read_storage::<InBackpack>()).join()
+        .filter(|(_item, pack)| pack.owner == target)
+        .map(|(item, _pack)| item)
+        .collect();
+    if loose.is_empty() { return; }
+    let item = loose[(ecs.write_resource::<rltk::RandomNumberGenerator>().roll_dice(1, loose.len() as i32) - 1) as usize];
+
+    ecs.write_storage::<InBackpack>().insert(item, InBackpack{ owner: thief }).expect("Unable to insert backpack entry");
+    ecs.write_storage::<Fleeing>().insert(thief, Fleeing{ turns: flee_turns }).expect("Unable to insert fleeing");
+
+    let names = ecs.read_storage::<Name>();
+    if let (Some(thief_name), Some(item_name)) = (names.get(thief), names.get(item)) {
+        let message = if target == *ecs.fetch::<Entity>() {
+            format!("{} snatches your {} and runs!", thief_name.name, item_name.name)
+        } else {
+            format!("{} snatches a {} and runs!", thief_name.name, item_name.name)
+        };
+        ecs.write_resource::<GameLog>().push(LogCategory::Combat, message);
+    }
+}
+
+/// Brings a monster from the raws into being on the given tile, if it is free.
+/// Anything summoned by someone serves them, and may only stay for a limited number of turns.
+pub fn summon(ecs : &mut World, name : &str, tile : usize, owner : Option<Entity>, turns : Option<i32>) {
+    let (x, y, free) = {
+        let map = ecs.fetch::<Map>();
+        (tile as i32 % map.width, tile as i32 / map.width, !map.is_blocked(tile))
+    };
+    if !free { return; }
+
+    let minion = raws::spawn_named_entity(&raws::raws(), ecs, name, x, y);
+    if let Some(minion) = minion {
+        let blocks = ecs.read_storage::<BlocksTile>().get(minion).is_some();
+        ecs.write_resource::<Map>().index_entity(minion, tile, blocks);
+        if let Some(owner) = owner {
+            ecs.write_storage::<OwnedBy>().insert(minion, OwnedBy{ owner, turns }).expect("Unable to insert owner");
+            // A faction member's minions join its faction, and treat the player as it does
+            let faction = ecs.read_storage::<Faction>().get(owner).cloned();
+            if let Some(faction) = faction {
+                if ecs.read_storage::<Monster>().get(owner).is_none() {
+                    ecs.write_storage::<Monster>().remove(minion);
+                }
+                ecs.write_storage::<Faction>().insert(minion, faction).expect("Unable to insert faction");
+            }
+        }
+        if owner == Some(*ecs.fetch::<Entity>()) {
+            ecs.write_resource::<GameLog>().push(LogCategory::Combat, format!("A {} answers your call!", name));
+        } else {
+            ecs.write_resource::<GameLog>().push(LogCategory::Combat, format!("A {} appears!", name));
+        }
+    }
+}
+
+/// Leaves a single recoverable arrow or bolt lying where a shot landed.
+pub fn spent_ammo(ecs : &mut World, name : &str, tile : usize) {
+    let (x, y) = {
+        let map = ecs.fetch::<Map>();
+        (tile as i32 % map.width, tile as i32 / map.width)
+    };
+    if let Some(item) = raws::spawn_named_entity(&raws::raws(), ecs, name, x, y) {
+        if let Some(ammo) = ecs.write_storage::<Ammo>().get_mut(item) {
+            ammo.count = 1;
+        }
+    }
+}
+
+/// Turns a monster against its own side for a while. Bosses are too strong-willed to be charmed.
+pub fn charm(ecs : &mut World, effect : &EffectSpawner, target : Entity, turns : i32) {
+    let owner = match effect.creator {
+        Some(owner) => owner,
+        None => return
+    };
+    if ecs.read_storage::<Monster>().get(target).is_none() { return; }
+
+    let name = ecs.read_storage::<Name>().get(target).map_or("creature".to_string(), |n| n.name.clone());
+    if ecs.read_storage::<Boss>().get(target).is_some() {
+        ecs.write_resource::<GameLog>().push(LogCategory::Combat, format!("The {} resists your control!", name));
+        return;
+    }
+
+    let mut owned = ecs.write_storage::<OwnedBy>();
+    let mut charmed = ecs.write_storage::<Charmed>();
+    // Recharming only extends the duration; the original owner is kept from the first time
+    let (previous_owner, previous_turns) = match charmed.get(target) {
+        Some(existing) => (existing.previous_owner, existing.previous_turns),
+        None => owned.get(target).map_or((None, None), |o| (Some(o.owner), o.turns))
+    };
+    charmed.insert(target, Charmed{ turns, previous_owner, previous_turns }).expect("Unable to insert status");
+    owned.insert(target, OwnedBy{ owner, turns: None }).expect("Unable to insert owner");
+
+    // For as long as the charm lasts it fights on the player's side, and its old faction no longer counts it as one of theirs
+    let mut factions = ecs.write_storage::<Faction>();
+    let mut overrides = ecs.write_storage::<FactionOverride>();
+    let previous = match overrides.get(target) {
+        Some(existing) => existing.previous.clone(),
+        None => factions.get(target).map(|f| f.name.clone())
+    };
+    overrides.insert(target, FactionOverride{ turns, previous }).expect("Unable to insert faction override");
+    factions.insert(target, Faction{ name: crate::reputation::PLAYER_FACTION.to_string() }).expect("Unable to insert faction");
+    ecs.write_resource::<GameLog>().push(LogCategory::Combat, format!("The {} now fights for you!", name));
+}
+
+pub fn well_fed(ecs : &mut World, target : Entity, turns : i32) {
+    let turns = crate::difficulty::scale(turns, ecs.fetch::<crate::difficulty::Difficulty>().meal_percent());
+    let turns = ecs.read_storage::<crate::Mutations>().get(target).map_or(turns, |m| m.scale_meal(turns));
+    ecs.write_storage::<WellFed>().insert(target, WellFed{ turns }).expect("Unable to insert status");
+    if let Some(name) = ecs.read_storage::<Name>().get(target) {
+        ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("{} is well fed.", name.name));
+    }
+}
+
+/// A proper rest brings stamina and mana straight back to full.
+pub fn rest(ecs : &mut World, target : Entity) {
+    if let Some(pools) = ecs.write_storage::<Pools>().get_mut(target) {
+        pools.stamina.current = pools.stamina.max;
+        pools.mana.current = pools.mana.max;
+    }
+}
+
+/// Blesses a piece of equipment, improving it by one point. Each item can only be blessed once.
+/// Anything not yet identified is shown for what it really is instead.
+pub fn bless(ecs : &mut World, effect : &EffectSpawner, item : Entity) {
+    let name = ecs.read_storage::<Name>().get(item).map_or("item".to_string(), |n| n.name.clone());
+    let hidden = ecs.read_storage::<Unidentified>().get(item).map(|u| u.name.clone());
+    if let Some(real_name) = hidden {
+        identified_items::learn(ecs, &real_name);
+        ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("The light reveals the {} to be a {}.", name, real_name));
+        return;
+    }
+    let slot = ecs.read_storage::<Equippable>().get(item).map(|e| e.slot);
+    let slot = match slot {
+        Some(slot) if !name.starts_with("Blessed ") => slot,
+        None if effect.creator.map_or(false, |c| ecs.read_storage::<Faith>().get(c).is_some()) => {
+            // Anything that can't be blessed is offered up instead
+            let follower = effect.creator.expect("Sacrifice without a follower");
+            let deity = ecs.read_storage::<Faith>().get(follower).expect("Follower without faith").deity;
+            religion::add_piety(ecs, follower, religion::sacrifice_value(deity));
+            ecs.delete_entity(item).expect("Unable to delete sacrifice");
+            ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("You sacrifice the {} to {}.", name, deity.name()));
+            return;
+        }
+        _ => {
+            ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("The light passes over the {} and fades.", name));
+            return;
+        }
+    };
+
+    match slot {
+        EquipmentSlot::Melee => {
+            let mut bonuses = ecs.write_storage::<MeleePowerBonus>();
+            match bonuses.get_mut(item) {
+                Some(bonus) => bonus.power += 1,
+                None => { bonuses.insert(item, MeleePowerBonus{ power: 1 }).expect("Unable to insert bonus"); }
+            }
+        }
+        _ => {
+            let mut bonuses = ecs.write_storage::<DefenseBonus>();
+            match bonuses.get_mut(item) {
+                Some(bonus) => bonus.defense += 1,
+                None => { bonuses.insert(item, DefenseBonus{ defense: 1 }).expect("Unable to insert bonus"); }
+            }
+        }
+    }
+    ecs.write_storage::<Name>().insert(item, Name{ name: format!("Blessed {}", name) }).expect("Unable to insert name");
+    ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("The {} glows with a holy light.", name));
+}
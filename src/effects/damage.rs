@@ -0,0 +1,97 @@
+use specs::prelude::*;
+use super::EffectSpawner;
+use crate::{CombatStats, Equipped, MeleePowerBonus, DefenseBonus, Name, SufferDamage, DamageType, DamageOverTime, DotEffect, Durability, durability::{worn_bonus, wear_down}, gamelog::{GameLog, LogCategory}};
+
+fn equipment_bonus<T : Component, F : Fn(&T) -> i32>(ecs : &World, owner : Entity, value : F) -> i32 {
+    let equipped = ecs.read_storage::<Equipped>();
+    let bonuses = ecs.read_storage::<T>();
+    let durability = ecs.read_storage::<Durability>();
+    (&equipped, &bonuses, durability.maybe()).join().filter(|(e, _, _)| e.owner == owner).map(|(_, b, d)| worn_bonus(value(b), d)).sum()
+}
+
+/// A special attack hit: the attacker's melee power against the target's defense, no roll needed.
+pub fn weapon_hit(ecs : &mut World, effect : &EffectSpawner, target : Entity) {
+    let attacker = match effect.creator {
+        Some(attacker) => attacker,
+        None => return
+    };
+
+    let damage = {
+        let combat_stats = ecs.read_storage::<CombatStats>();
+        let (attacker_stats, target_stats) = match (combat_stats.get(attacker), combat_stats.get(target)) {
+            (Some(a), Some(t)) => (a, t),
+            _ => return
+        };
+        if target_stats.hp < 1 { return; }
+        let power = attacker_stats.power + equipment_bonus::<MeleePowerBonus, _>(ecs, attacker, |b| b.power);
+        let defense = target_stats.defense + equipment_bonus::<DefenseBonus, _>(ecs, target, |b| b.defense);
+        i32::max(1, power - defense)
+    };
+
+    let from_player = attacker == *ecs.fetch::<Entity>();
+    SufferDamage::new_damage(&mut ecs.write_storage::<SufferDamage>(), target, damage, DamageType::Physical, from_player, Some(attacker));
+
+    let names = ecs.read_storage::<Name>();
+    let mut log = ecs.write_resource::<GameLog>();
+    if let (Some(attacker_name), Some(target_name)) = (names.get(attacker), names.get(target)) {
+        log.push(LogCategory::Combat, format!("{} hits {}, for {} hp.", attacker_name.name, target_name.name, damage));
+    }
+
+    // The blow wears on the weapon that dealt it and the armor that took it, just like an ordinary hit
+    let entities = ecs.entities();
+    let equipped = ecs.read_storage::<Equipped>();
+    let power_bonuses = ecs.read_storage::<MeleePowerBonus>();
+    let defense_bonuses = ecs.read_storage::<DefenseBonus>();
+    let mut wear : Vec<Entity> = (&entities, &equipped).join()
+        .filter(|(item, worn)| (worn.owner == attacker && power_bonuses.get(*item).is_some())
+            || (worn.owner == target && defense_bonuses.get(*item).is_some()))
+        .map(|(item, _worn)| item)
+        .collect();
+    wear_down(&mut wear, &entities, &mut ecs.write_storage::<Durability>(), &equipped, &names, &mut log);
+}
+
+pub fn inflict_damage(ecs : &mut World, effect : &EffectSpawner, target : Entity, amount : i32, kind : DamageType) {
+    let from_player = effect.creator == Some(*ecs.fetch::<Entity>());
+    SufferDamage::new_damage(&mut ecs.write_storage::<SufferDamage>(), target, amount, kind, from_player, effect.creator);
+}
+
+/// Damages the target and heals the creator by the same amount.
+pub fn life_drain(ecs : &mut World, effect : &EffectSpawner, target : Entity, amount : i32) {
+    let caster = match effect.creator {
+        Some(caster) => caster,
+        None => return
+    };
+    inflict_damage(ecs, effect, target, amount, DamageType::Magic);
+    if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(caster) {
+        stats.hp = i32::min(stats.max_hp, stats.hp + amount);
+    }
+
+    let names = ecs.read_storage::<Name>();
+    if let (Some(caster_name), Some(target_name)) = (names.get(caster), names.get(target)) {
+        ecs.write_resource::<GameLog>().push(LogCategory::Combat, format!("{} drains {} hp from {}.", caster_name.name, amount, target_name.name));
+    }
+}
+
+pub fn heal(ecs : &mut World, target : Entity, amount : i32) {
+    if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(target) {
+        stats.hp = i32::min(stats.max_hp, stats.hp + amount);
+    }
+    if let Some(name) = ecs.read_storage::<Name>().get(target) {
+        ecs.write_resource::<GameLog>().push(LogCategory::Combat, format!("{} recovers {} hp.", name.name, amount));
+    }
+}
+
+/// Starts a poison or bleed, replacing any of the same kind already running.
+pub fn apply_dot(ecs : &mut World, target : Entity, effect : DotEffect) {
+    let mut damage_over_time = ecs.write_storage::<DamageOverTime>();
+    if let Some(dots) = damage_over_time.get_mut(target) {
+        dots.effects.retain(|e| e.kind != effect.kind);
+        dots.effects.push(effect);
+    } else {
+        damage_over_time.insert(target, DamageOverTime{ effects: vec![effect] }).expect("Unable to insert status");
+    }
+    if let Some(name) = ecs.read_storage::<Name>().get(target) {
+        let verb = if effect.kind == DamageType::Poison { "poisoned" } else { "bleeding" };
+        ecs.write_resource::<GameLog>().push(LogCategory::Combat, format!("{} is {}!", name.name, verb));
+    }
+}
@@ -0,0 +1,29 @@
+use specs::prelude::*;
+use crate::{Map, Position, Name, Renderable, BlocksTile, EntryTrigger, Portcullis, SecretPassage, TileType,
+    gamelog::{GameLog, LogCategory}, visibility_system::invalidate_viewsheds};
+
+/// Sets off a wired mechanism: raises a portcullis, opens a secret passage or disarms a trap.
+pub fn activate(ecs : &mut World, target : Entity) {
+    if ecs.read_storage::<Portcullis>().get(target).is_some() {
+        ecs.write_storage::<BlocksTile>().remove(target);
+        ecs.write_storage::<Portcullis>().remove(target);
+        if let Some(render) = ecs.write_storage::<Renderable>().get_mut(target) {
+            render.glyph = rltk::to_cp437('.');
+        }
+        ecs.write_storage::<Name>().insert(target, Name{ name: "Raised Portcullis".to_string() }).expect("Unable to insert name");
+        ecs.write_resource::<GameLog>().push(LogCategory::System, "With a grinding of chains, a portcullis rises.".to_string());
+    } else if ecs.read_storage::<SecretPassage>().get(target).is_some() {
+        if let Some(pos) = ecs.read_storage::<Position>().get(target) {
+            let mut map = ecs.write_resource::<Map>();
+            let idx = map.xy_idx(pos.x, pos.y);
+            map.tiles[idx] = TileType::Floor;
+            map.populate_blocked();
+        }
+        invalidate_viewsheds(ecs);
+        ecs.delete_entity(target).expect("Unable to delete passage");
+        ecs.write_resource::<GameLog>().push(LogCategory::System, "A section of wall slides away!".to_string());
+    } else if ecs.read_storage::<EntryTrigger>().get(target).is_some() {
+        ecs.delete_entity(target).expect("Unable to delete trap");
+        ecs.write_resource::<GameLog>().push(LogCategory::System, "Somewhere nearby, a trap clicks harmlessly.".to_string());
+    }
+}
@@ -0,0 +1,144 @@
+use std::collections::VecDeque;
+use specs::prelude::*;
+use super::{Map, CombatStats, Position, DamageType, DotEffect, AfflictionKind, affliction_system, mutation_system};
+
+mod damage;
+mod movement;
+mod abilities;
+mod mechanisms;
+mod scripts;
+pub use scripts::ScriptEngine;
+
+#[derive(Clone)]
+pub enum EffectType {
+    WeaponHit,
+    Knockback { dx : i32, dy : i32 },
+    Damage { amount : i32, kind : DamageType },
+    LifeDrain { amount : i32 },
+    /// Takes something from the target's backpack and runs off with it for `flee_turns` turns.
+    Steal { flee_turns : i32 },
+    Afflict { kind : AfflictionKind },
+    /// Lifts afflictions of one kind, or all of them, for `price` gold.
+    Cure { kind : Option<AfflictionKind>, price : i32 },
+    /// A new mutation, good or bad as `beneficial` says, or either if it's None.
+    Mutate { beneficial : Option<bool> },
+    Web { turns : i32 },
+    Summon { name : String, turns : Option<i32> },
+    Charm { turns : i32 },
+    TeleportTo { x : i32, y : i32 },
+    TeleportRandom,
+    TownPortal,
+    Activate,
+    Healing { amount : i32 },
+    Dot { effect : DotEffect },
+    WellFed { turns : i32 },
+    Rest,
+    Bless,
+    SpentAmmo { name : String },
+    /// Runs a script from the raws, once per target.
+    Script { source : String }
+}
+
+#[derive(Clone)]
+pub enum Targets {
+    Single { target : Entity },
+    Tiles { tiles : Vec<usize> },
+    Tile { tile : usize }
+}
+
+pub struct EffectSpawner {
+    pub creator : Option<Entity>,
+    pub effect_type : EffectType,
+    pub targets : Targets
+}
+
+/// Effects waiting to be applied; drained once per run of the systems.
+#[derive(Default)]
+pub struct EffectQueue {
+    queue : VecDeque<EffectSpawner>
+}
+
+impl EffectQueue {
+    pub fn add_effect(&mut self, creator : Option<Entity>, effect_type : EffectType, targets : Targets) {
+        self.queue.push_back(EffectSpawner{ creator, effect_type, targets });
+    }
+}
+
+pub fn run_effects_queue(ecs : &mut World) {
+    loop {
+        let effect = ecs.write_resource::<EffectQueue>().queue.pop_front();
+        match effect {
+            Some(effect) => target_applicator(ecs, &effect),
+            None => break
+        }
+    }
+}
+
+fn target_applicator(ecs : &mut World, effect : &EffectSpawner) {
+    match &effect.targets {
+        Targets::Single { target } => affect_entity(ecs, effect, *target),
+        Targets::Tile { tile } => affect_tile(ecs, effect, *tile),
+        Targets::Tiles { tiles } => {
+            let mut victims : Vec<Entity> = Vec::new();
+            {
+                let map = ecs.fetch::<Map>();
+                let combat_stats = ecs.read_storage::<CombatStats>();
+                for idx in tiles.iter() {
+                    for entity in map.entities_at(*idx).iter() {
+                        if combat_stats.get(*entity).is_some() && Some(*entity) != effect.creator {
+                            victims.push(*entity);
+                        }
+                    }
+                }
+            }
+            for victim in victims {
+                affect_entity(ecs, effect, victim);
+            }
+        }
+    }
+}
+
+fn affect_tile(ecs : &mut World, effect : &EffectSpawner, tile : usize) {
+    if let EffectType::Summon { name, turns } = &effect.effect_type {
+        abilities::summon(ecs, name, tile, effect.creator, *turns);
+    } else if let EffectType::SpentAmmo { name } = &effect.effect_type {
+        abilities::spent_ammo(ecs, name, tile);
+    } else if let EffectType::Script { source } = &effect.effect_type {
+        let width = ecs.fetch::<Map>().width;
+        scripts::run_script(ecs, effect.creator, rltk::Point::new(tile as i32 % width, tile as i32 / width), source);
+    } else {
+        let targets = Targets::Tiles { tiles : vec![tile] };
+        target_applicator(ecs, &EffectSpawner{ creator: effect.creator, effect_type: effect.effect_type.clone(), targets });
+    }
+}
+
+fn affect_entity(ecs : &mut World, effect : &EffectSpawner, target : Entity) {
+    match &effect.effect_type {
+        EffectType::WeaponHit => damage::weapon_hit(ecs, effect, target),
+        EffectType::Knockback { dx, dy } => movement::knockback(ecs, target, *dx, *dy),
+        EffectType::Damage { amount, kind } => damage::inflict_damage(ecs, effect, target, *amount, *kind),
+        EffectType::LifeDrain { amount } => damage::life_drain(ecs, effect, target, *amount),
+        EffectType::Steal { flee_turns } => abilities::steal(ecs, effect, target, *flee_turns),
+        EffectType::Afflict { kind } => affliction_system::afflict(ecs, target, *kind),
+        EffectType::Cure { kind, price } => affliction_system::cure(ecs, target, *kind, *price),
+        EffectType::Mutate { beneficial } => mutation_system::mutate(ecs, target, *beneficial),
+        EffectType::Web { turns } => abilities::web(ecs, target, *turns),
+        EffectType::Charm { turns } => abilities::charm(ecs, effect, target, *turns),
+        EffectType::TeleportTo { x, y } => movement::teleport_to(ecs, target, *x, *y),
+        EffectType::TeleportRandom => movement::teleport_random(ecs, target),
+        EffectType::TownPortal => movement::town_portal(ecs, target),
+        EffectType::Activate => mechanisms::activate(ecs, target),
+        EffectType::Healing { amount } => damage::heal(ecs, target, *amount),
+        EffectType::Dot { effect } => damage::apply_dot(ecs, target, *effect),
+        EffectType::WellFed { turns } => abilities::well_fed(ecs, target, *turns),
+        EffectType::Rest => abilities::rest(ecs, target),
+        EffectType::Bless => abilities::bless(ecs, effect, target),
+        EffectType::Script { source } => {
+            let pos = ecs.read_storage::<Position>().get(target).map(|p| rltk::Point::new(p.x, p.y));
+            if let Some(pos) = pos {
+                scripts::run_script(ecs, effect.creator, pos, source);
+            }
+        }
+        EffectType::Summon { .. } | EffectType::SpentAmmo { .. } => {}
+    }
+}
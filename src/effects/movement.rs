@@ -0,0 +1,124 @@
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+use rltk::RandomNumberGenerator;
+use crate::{Map, Position, OtherLevelPosition, Name, Renderable, Viewshed, Staggered, Player, EntryTrigger, PortalLink,
+    SerializeMe, Branch, tile_walkable, gamelog::{GameLog, LogCategory}, dungeon::{MasterDungeonMap, LevelTeleport}};
+
+/// Shoves the target one tile away; slamming into a wall or another creature stuns it instead.
+pub fn knockback(ecs : &mut World, target : Entity, dx : i32, dy : i32) {
+    let pos = match ecs.read_storage::<Position>().get(target) {
+        Some(pos) => pos.clone(),
+        None => return
+    };
+    let (x, y) = (pos.x + dx, pos.y + dy);
+
+    let can_move = {
+        let map = ecs.fetch::<Map>();
+        if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 {
+            false
+        } else {
+            let idx = map.xy_idx(x, y);
+            tile_walkable(map.tiles[idx]) && !map.is_blocked(idx)
+        }
+    };
+
+    let name = ecs.read_storage::<Name>().get(target).map_or("Something".to_string(), |n| n.name.clone());
+    if can_move {
+        {
+            let mut map = ecs.write_resource::<Map>();
+            let new_idx = map.xy_idx(x, y);
+            map.move_entity(target, new_idx);
+        }
+        ecs.write_storage::<Position>().insert(target, Position{ x, y }).expect("Unable to insert position");
+        if let Some(viewshed) = ecs.write_storage::<Viewshed>().get_mut(target) {
+            viewshed.dirty = true;
+        }
+        if ecs.read_storage::<Player>().get(target).is_some() {
+            *ecs.write_resource::<rltk::Point>() = rltk::Point::new(x, y);
+        }
+        ecs.write_resource::<GameLog>().push(LogCategory::Combat, format!("{} is knocked back.", name));
+    } else {
+        ecs.write_storage::<Staggered>().insert(target, Staggered{}).expect("Unable to insert status");
+        ecs.write_resource::<GameLog>().push(LogCategory::Combat, format!("{} slams into an obstacle and is stunned!", name));
+    }
+}
+
+/// Moves the target straight to the given tile, if nothing is standing there.
+pub fn teleport_to(ecs : &mut World, target : Entity, x : i32, y : i32) {
+    if ecs.read_storage::<Position>().get(target).is_none() { return; }
+    {
+        let mut map = ecs.write_resource::<Map>();
+        let new_idx = map.xy_idx(x, y);
+        if !tile_walkable(map.tiles[new_idx]) || map.is_blocked(new_idx) { return; }
+        map.move_entity(target, new_idx);
+    }
+    ecs.write_storage::<Position>().insert(target, Position{ x, y }).expect("Unable to insert position");
+    if let Some(viewshed) = ecs.write_storage::<Viewshed>().get_mut(target) {
+        viewshed.dirty = true;
+    }
+    if ecs.read_storage::<Player>().get(target).is_some() {
+        *ecs.write_resource::<rltk::Point>() = rltk::Point::new(x, y);
+    }
+}
+
+/// Flings the target to a random open tile somewhere on the level.
+pub fn teleport_random(ecs : &mut World, target : Entity) {
+    let destination = {
+        let map = ecs.fetch::<Map>();
+        let open_tiles : Vec<usize> = (0..map.tiles.len())
+            .filter(|idx| tile_walkable(map.tiles[*idx]) && !map.is_blocked(*idx))
+            .collect();
+        if open_tiles.is_empty() { return; }
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let idx = open_tiles[(rng.roll_dice(1, open_tiles.len() as i32) - 1) as usize];
+        (idx as i32 % map.width, idx as i32 / map.width)
+    };
+
+    let name = ecs.read_storage::<Name>().get(target).map_or("Something".to_string(), |n| n.name.clone());
+    ecs.write_resource::<GameLog>().push(LogCategory::System, format!("{} vanishes in a flash of light!", name));
+    teleport_to(ecs, target, destination.0, destination.1);
+}
+
+/// Opens a portal in town that leads back here, and sends the player through to it.
+pub fn town_portal(ecs : &mut World, target : Entity) {
+    if ecs.read_storage::<Player>().get(target).is_none() { return; }
+    let pos = match ecs.read_storage::<Position>().get(target) {
+        Some(pos) => pos.clone(),
+        None => return
+    };
+    let (branch, depth) = {
+        let map = ecs.fetch::<Map>();
+        (map.branch, map.depth)
+    };
+    let town = ecs.fetch::<MasterDungeonMap>().get_map(Branch::Overworld, 0);
+    let (town_x, town_y) = match town {
+        Some(town) => town.arrival_point(),
+        None => {
+            ecs.write_resource::<GameLog>().push(LogCategory::System, "The portal flickers and collapses.".to_string());
+            return;
+        }
+    };
+
+    // Only one portal stays open at a time
+    let old_portals : Vec<Entity> = (&ecs.entities(), &ecs.read_storage::<PortalLink>()).join().map(|(e, _)| e).collect();
+    for portal in old_portals {
+        ecs.delete_entity(portal).expect("Unable to delete portal");
+    }
+
+    ecs.create_entity()
+        .with(OtherLevelPosition{ x: town_x + 2, y: town_y, depth: 0, branch: Branch::Overworld })
+        .with(Renderable{
+            glyph: rltk::to_cp437('♥'),
+            fg: rltk::RGB::named(rltk::CYAN),
+            bg: rltk::RGB::named(rltk::BLACK),
+            render_order: 2
+        })
+        .with(Name{ name: "Town Portal".to_string() })
+        .with(EntryTrigger{})
+        .with(PortalLink{ branch, depth, x: pos.x, y: pos.y })
+        .marked::<SimpleMarker<SerializeMe>>()
+        .build();
+
+    ecs.write_resource::<GameLog>().push(LogCategory::System, "A shimmering portal opens and pulls you through.".to_string());
+    ecs.write_resource::<LevelTeleport>().destination = Some((Branch::Overworld, 0, rltk::Point::new(town_x, town_y)));
+}
@@ -0,0 +1,108 @@
+use std::sync::{Arc, Mutex};
+use specs::prelude::*;
+use rltk::Point;
+use crate::{Map, Position, DamageType, gamelog::{GameLog, LogCategory}};
+use super::{EffectQueue, EffectType, Targets};
+
+/// How much work a script may do before it's stopped, so a runaway loop can't hang the game.
+const MAX_OPERATIONS : u64 = 10_000;
+const MAX_CALL_LEVELS : usize = 8;
+const MAX_STRING_SIZE : usize = 500;
+
+/// What a script asked for, carried out once it has finished.
+enum ScriptCommand {
+    Damage { x : i64, y : i64, amount : i32 },
+    Heal { x : i64, y : i64, amount : i32 },
+    Spawn { name : String, x : i64, y : i64 },
+    Log { message : String }
+}
+
+/// A damage or healing amount from a script, which may have worked it out as a fraction or gone below zero.
+fn amount(value : f64) -> i32 {
+    value.round().clamp(0.0, i32::MAX as f64) as i32
+}
+
+/// The script interpreter, set up once with the functions scripts can call. What the scripts ask
+/// for piles up in `commands` until `run_script` carries it out.
+pub struct ScriptEngine {
+    engine : rhai::Engine,
+    commands : Arc<Mutex<Vec<ScriptCommand>>>
+}
+
+impl Default for ScriptEngine {
+    fn default() -> ScriptEngine {
+        let commands : Arc<Mutex<Vec<ScriptCommand>>> = Arc::new(Mutex::new(Vec::new()));
+        let mut engine = rhai::Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+        engine.set_max_call_levels(MAX_CALL_LEVELS);
+        engine.set_max_string_size(MAX_STRING_SIZE);
+        let queue = commands.clone();
+        engine.register_fn("damage", move |x : i64, y : i64, value : i64| queue.lock().unwrap().push(ScriptCommand::Damage{ x, y, amount: amount(value as f64) }));
+        let queue = commands.clone();
+        engine.register_fn("damage", move |x : i64, y : i64, value : f64| queue.lock().unwrap().push(ScriptCommand::Damage{ x, y, amount: amount(value) }));
+        let queue = commands.clone();
+        engine.register_fn("heal", move |x : i64, y : i64, value : i64| queue.lock().unwrap().push(ScriptCommand::Heal{ x, y, amount: amount(value as f64) }));
+        let queue = commands.clone();
+        engine.register_fn("heal", move |x : i64, y : i64, value : f64| queue.lock().unwrap().push(ScriptCommand::Heal{ x, y, amount: amount(value) }));
+        let queue = commands.clone();
+        engine.register_fn("spawn", move |name : &str, x : i64, y : i64| queue.lock().unwrap().push(ScriptCommand::Spawn{ name: name.to_string(), x, y }));
+        let queue = commands.clone();
+        engine.register_fn("log", move |message : &str| queue.lock().unwrap().push(ScriptCommand::Log{ message: message.to_string() }));
+        ScriptEngine{ engine, commands }
+    }
+}
+
+/// Runs a script from the raws on behalf of `creator`, aimed at `target`.
+///
+/// Scripts see `caster_x`, `caster_y`, `target_x`, `target_y`, `player_x`, `player_y` and `depth`,
+/// and can call `damage(x, y, amount)`, `heal(x, y, amount)`, `spawn(name, x, y)` and `log(message)`.
+/// Amounts are rounded to whole numbers and never go below zero.
+/// They can't touch the world directly: what they ask for goes on the effects queue like any other effect.
+pub fn run_script(ecs : &mut World, creator : Option<Entity>, target : Point, source : &str) {
+    let caster = creator.and_then(|c| ecs.read_storage::<Position>().get(c).map(|p| Point::new(p.x, p.y))).unwrap_or(target);
+    let player = *ecs.fetch::<Point>();
+    let depth = ecs.fetch::<Map>().depth;
+    let mut scope = rhai::Scope::new();
+    scope.push_constant("caster_x", caster.x as i64);
+    scope.push_constant("caster_y", caster.y as i64);
+    scope.push_constant("target_x", target.x as i64);
+    scope.push_constant("target_y", target.y as i64);
+    scope.push_constant("player_x", player.x as i64);
+    scope.push_constant("player_y", player.y as i64);
+    scope.push_constant("depth", depth as i64);
+    let commands = {
+        let scripts = ecs.fetch::<ScriptEngine>();
+        if let Err(err) = scripts.engine.run_with_scope(&mut scope, source) {
+            rltk::console::log(format!("Warning: script failed: {}", err));
+        }
+        let mut pending = scripts.commands.lock().unwrap();
+        std::mem::take(&mut *pending)
+    };
+    for command in commands {
+        match command {
+            ScriptCommand::Log{ message } => ecs.write_resource::<GameLog>().push(LogCategory::System, message),
+            ScriptCommand::Damage{ x, y, amount } => {
+                if let Some(tile) = tile_at(ecs, x, y) {
+                    ecs.write_resource::<EffectQueue>().add_effect(creator, EffectType::Damage{ amount, kind: DamageType::Magic }, Targets::Tile{ tile });
+                }
+            }
+            ScriptCommand::Heal{ x, y, amount } => {
+                if let Some(tile) = tile_at(ecs, x, y) {
+                    ecs.write_resource::<EffectQueue>().add_effect(None, EffectType::Healing{ amount }, Targets::Tile{ tile });
+                }
+            }
+            ScriptCommand::Spawn{ name, x, y } => {
+                if let Some(tile) = tile_at(ecs, x, y) {
+                    ecs.write_resource::<EffectQueue>().add_effect(None, EffectType::Summon{ name, turns: None }, Targets::Tile{ tile });
+                }
+            }
+        }
+    }
+}
+
+/// The map index of a tile a script named, if it's inside the walls.
+fn tile_at(ecs : &World, x : i64, y : i64) -> Option<usize> {
+    let map = ecs.fetch::<Map>();
+    if x < 1 || y < 1 || x >= map.width as i64 - 1 || y >= map.height as i64 - 1 { return None; }
+    Some(map.xy_idx(x as i32, y as i32))
+}
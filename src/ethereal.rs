@@ -0,0 +1,59 @@
+use rltk::{Algorithm2D, BaseMap, Point, SmallVec};
+use specs::prelude::*;
+use super::{Map, MovesThroughWalls, map::tile_opaque};
+
+/// Whether an entity is out of sight inside solid rock. Only things that move through walls ever
+/// end up there; they can be sensed but not seen or aimed at until they step out.
+pub fn in_wall(map : &Map, walkers : &ReadStorage<MovesThroughWalls>, entity : Entity, x : i32, y : i32) -> bool {
+    walkers.get(entity).is_some() && tile_opaque(map.tiles[map.xy_idx(x, y)])
+}
+
+/// An open tile next to `from` that is also next to `target`, for something in a wall to step out onto.
+pub fn way_out(map : &Map, from : Point, target : Point) -> Option<usize> {
+    (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| Point::new(from.x + dx, from.y + dy)))
+        .filter(|pt| *pt != from && map.in_bounds(*pt))
+        .filter(|pt| rltk::DistanceAlg::Pythagoras.distance2d(*pt, target) < 1.5)
+        .map(|pt| map.xy_idx(pt.x, pt.y))
+        .find(|idx| !map.is_blocked(*idx))
+}
+
+/// The map as seen by something that drifts through walls: only the edge of the map and whatever
+/// stands in the way stop it.
+pub struct ThroughWalls<'a> {
+    pub map : &'a Map
+}
+
+impl<'a> ThroughWalls<'a> {
+    fn is_exit_valid(&self, x : i32, y : i32) -> bool {
+        if x < 1 || x > self.map.width - 2 || y < 1 || y > self.map.height - 2 { return false; }
+        !self.map.is_occupied(self.map.xy_idx(x, y))
+    }
+}
+
+impl<'a> Algorithm2D for ThroughWalls<'a> {
+    fn dimensions(&self) -> Point {
+        self.map.dimensions()
+    }
+}
+
+impl<'a> BaseMap for ThroughWalls<'a> {
+    fn is_opaque(&self, idx : usize) -> bool {
+        self.map.is_opaque(idx)
+    }
+
+    fn get_available_exits(&self, idx : usize) -> SmallVec<[(usize, f32); 10]> {
+        let mut exits = SmallVec::new();
+        let x = idx as i32 % self.map.width;
+        let y = idx as i32 / self.map.width;
+        for (dx, dy, cost) in [(-1, 0, 1.0), (1, 0, 1.0), (0, -1, 1.0), (0, 1, 1.0), (-1, -1, 1.45), (1, -1, 1.45), (-1, 1, 1.45), (1, 1, 1.45)] {
+            if self.is_exit_valid(x + dx, y + dy) {
+                exits.push((self.map.xy_idx(x + dx, y + dy), cost));
+            }
+        }
+        exits
+    }
+
+    fn get_pathing_distance(&self, idx1 : usize, idx2 : usize) -> f32 {
+        self.map.get_pathing_distance(idx1, idx2)
+    }
+}
@@ -0,0 +1,81 @@
+use specs::prelude::*;
+use specs::shrev::{EventChannel, ReaderId};
+use rltk::RGB;
+use super::{AbilityKind, Skill, gamelog::{GameLog, LogCategory}, quests::QuestLog};
+
+/// Something that happened in the game. Systems publish these instead of each telling the log,
+/// the quests and whatever else cares; anything that wants to know registers a reader.
+#[derive(Clone, Debug)]
+pub enum GameEvent {
+    /// `amount` is after resistances; `by` is whoever dealt it, if anyone did.
+    Damaged { target : Entity, amount : i32, from_player : bool, by : Option<Entity> },
+    /// `foe` is set for the player's enemies, whose deaths count towards quests and the like.
+    Died { entity : Entity, name : String, foe : bool },
+    ItemPickedUp { by : Entity, item : Entity, name : String, by_player : bool },
+    /// A potion, scroll or the like used up; `name` is its real name, even if it wasn't identified.
+    ItemConsumed { by : Entity, item : Entity, name : String, by_player : bool },
+    DoorOpened { by : Entity, door : Entity, name : String },
+    /// The player arrived on a level, new or visited before.
+    LevelEntered { depth : i32 },
+    /// The player escaped the dungeon with the Amulet.
+    Victory,
+    /// The player gained a level from experience.
+    LevelledUp { level : i32 },
+    /// The player's run ended in death.
+    PlayerDied,
+    /// The player learned what a kind of item is; `name` is its real name.
+    ItemIdentified { name : String },
+    /// A creature used one of its special abilities on the player.
+    AbilityUsed { by : Entity, name : String, ability : AbilityKind },
+    /// Someone put a skill to use outside a fight, earning `xp` towards its next rank.
+    SkillUsed { by : Entity, skill : Skill, xp : i32 }
+}
+
+/// The resource events are published on.
+pub type GameEvents = EventChannel<GameEvent>;
+
+/// Publishes an event from outside a system.
+pub fn publish(ecs : &World, event : GameEvent) {
+    ecs.write_resource::<GameEvents>().single_write(event);
+}
+
+/// Writes up events in the game log, and counts them towards the player's quests.
+#[derive(Default)]
+pub struct EventLogSystem {
+    reader : Option<ReaderId<GameEvent>>
+}
+
+impl<'a> System<'a> for EventLogSystem {
+    type SystemData = ( ReadExpect<'a, GameEvents>,
+                        WriteExpect<'a, GameLog>,
+                        WriteExpect<'a, QuestLog> );
+
+    fn setup(&mut self, world : &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(world.write_resource::<GameEvents>().register_reader());
+    }
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (events, mut log, mut quests) = data;
+
+        for event in events.read(self.reader.as_mut().expect("EventLogSystem was not set up")) {
+            match event {
+                GameEvent::Died{ name, foe, .. } => {
+                    log.push(LogCategory::Combat, format!("{} is dead", name));
+                    if *foe {
+                        for message in quests.record_kill(name) {
+                            log.push_colored(LogCategory::System, message, RGB::named(rltk::GOLD));
+                        }
+                    }
+                }
+                GameEvent::ItemPickedUp{ name, by_player: true, .. } => {
+                    log.push(LogCategory::Items, format!("You pick up the {}.", name));
+                    for message in quests.record_pickup(name) {
+                        log.push_colored(LogCategory::System, message, RGB::named(rltk::GOLD));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
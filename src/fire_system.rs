@@ -0,0 +1,117 @@
+use specs::prelude::*;
+use rltk::{Point, RandomNumberGenerator};
+use super::{Burning, Flammable, CombatStats, SufferDamage, DamageType, Name, Position, Map, TileType, RunState, tile_walkable, spawner,
+            gamelog::{GameLog, LogCategory}};
+
+const FIRE_DAMAGE : i32 = 2;
+const SPREAD_CHANCE : i32 = 25;
+const SPREAD_TURNS : i32 = 4;
+
+/// Tiles waiting to catch fire, filled by fireballs, oil flasks and spreading flames.
+#[derive(Default)]
+pub struct Ignitions {
+    pub tiles : Vec<(Point, i32)>
+}
+
+impl Ignitions {
+    pub fn ignite(&mut self, pos : Point, turns : i32) {
+        self.tiles.push((pos, turns));
+    }
+}
+
+fn is_flammable_terrain(tt : TileType) -> bool {
+    matches!(tt, TileType::Grass | TileType::Forest)
+}
+
+pub struct FireSystem {}
+
+impl<'a> System<'a> for FireSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        WriteExpect<'a, Map>,
+                        ReadExpect<'a, RunState>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteExpect<'a, Ignitions>,
+                        WriteExpect<'a, GameLog>,
+                        ReadExpect<'a, Entity>,
+                        WriteStorage<'a, Burning>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Flammable>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, SufferDamage>
+    );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, mut map, runstate, mut rng, mut ignitions, mut log, player_entity, mut burning,
+            positions, flammable, combat_stats, names, mut suffer_damage) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        for (fire_entity, fire, pos) in (&entities, &mut burning, &positions).join() {
+            let idx = map.xy_idx(pos.x, pos.y);
+
+            // Water puts fires out straight away
+            if map.tiles[idx] == TileType::Water {
+                entities.delete(fire_entity).expect("Delete failed");
+                continue;
+            }
+
+            for occupant in map.entities_at(idx).iter() {
+                if combat_stats.get(*occupant).is_some() {
+                    SufferDamage::new_damage(&mut suffer_damage, *occupant, FIRE_DAMAGE, DamageType::Fire, false, None);
+                    if *occupant == *player_entity {
+                        log.push(LogCategory::Combat, format!("The flames burn you for {} fire damage.", FIRE_DAMAGE));
+                    }
+                } else if flammable.get(*occupant).is_some() {
+                    if let Some(name) = names.get(*occupant) {
+                        log.push(LogCategory::Combat, format!("The {} burns up.", name.name));
+                    }
+                    entities.delete(*occupant).expect("Delete failed");
+                }
+            }
+
+            for (dx, dy) in [(-1, 0), (1, 0), (0, -1), (0, 1)].iter() {
+                let (x, y) = (pos.x + dx, pos.y + dy);
+                if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 { continue; }
+                let neighbour = map.xy_idx(x, y);
+                let fuel = is_flammable_terrain(map.tiles[neighbour])
+                    || map.entities_at(neighbour).iter().any(|e| flammable.get(*e).is_some());
+                if fuel && rng.roll_dice(1, 100) <= SPREAD_CHANCE {
+                    ignitions.ignite(Point::new(x, y), SPREAD_TURNS);
+                }
+            }
+
+            fire.turns -= 1;
+            if fire.turns < 1 {
+                // Burnt out vegetation can't catch fire again
+                if is_flammable_terrain(map.tiles[idx]) {
+                    map.tiles[idx] = TileType::Floor;
+                }
+                entities.delete(fire_entity).expect("Delete failed");
+            }
+        }
+    }
+}
+
+/// Turns queued ignitions into fire entities, skipping water, walls and tiles that are already alight.
+pub fn spawn_fires(ecs : &mut World) {
+    let pending : Vec<(Point, i32)> = ecs.write_resource::<Ignitions>().tiles.drain(..).collect();
+    for (pos, turns) in pending {
+        let can_burn = {
+            let map = ecs.fetch::<Map>();
+            let idx = map.xy_idx(pos.x, pos.y);
+            if map.tiles[idx] == TileType::Water || !tile_walkable(map.tiles[idx]) {
+                false
+            } else {
+                let burning = ecs.read_storage::<Burning>();
+                let positions = ecs.read_storage::<Position>();
+                let entities = ecs.entities();
+                !(&entities, &burning, &positions).join().any(|(e, _, p)| p.x == pos.x && p.y == pos.y && entities.is_alive(e))
+            }
+        };
+        if can_burn {
+            spawner::fire(ecs, pos.x, pos.y, turns);
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashSet;
+use rltk::{Point, BaseMap, DistanceAlg};
+use super::Map;
+
+/// One of the four quarters of the view around the origin, scanned outwards row by row.
+#[derive(Clone, Copy)]
+enum Quadrant { North, East, South, West }
+
+impl Quadrant {
+    /// Turns a row (distance out) and column (distance across) into map coordinates.
+    fn transform(self, origin : Point, row : i32, col : i32) -> Point {
+        match self {
+            Quadrant::North => Point::new(origin.x + col, origin.y - row),
+            Quadrant::South => Point::new(origin.x + col, origin.y + row),
+            Quadrant::East => Point::new(origin.x + row, origin.y + col),
+            Quadrant::West => Point::new(origin.x - row, origin.y + col)
+        }
+    }
+}
+
+/// A row of a quadrant still in view, between two slopes.
+struct Row {
+    depth : i32,
+    start_slope : f64,
+    end_slope : f64
+}
+
+impl Row {
+    fn columns(&self) -> std::ops::RangeInclusive<i32> {
+        let min_col = (self.depth as f64 * self.start_slope + 0.5).floor() as i32;
+        let max_col = (self.depth as f64 * self.end_slope - 0.5).ceil() as i32;
+        min_col ..= max_col
+    }
+
+    fn next(&self) -> Row {
+        Row{ depth: self.depth + 1, start_slope: self.start_slope, end_slope: self.end_slope }
+    }
+
+    /// Whether a floor tile's centre is inside the row's slopes; only then can it see back.
+    fn is_symmetric(&self, col : i32) -> bool {
+        col as f64 >= self.depth as f64 * self.start_slope && col as f64 <= self.depth as f64 * self.end_slope
+    }
+}
+
+/// The slope to the left edge of a tile.
+fn slope(depth : i32, col : i32) -> f64 {
+    (2 * col - 1) as f64 / (2 * depth) as f64
+}
+
+/// Symmetric shadowcasting: if one tile can see another, that one can always see it back. Walls
+/// and anything else opaque are seen if any part of them is; floors only if their centre is.
+/// Returns the visible tiles within `range`, like `rltk::field_of_view`.
+pub fn symmetric_field_of_view(origin : Point, range : i32, map : &Map) -> Vec<Point> {
+    let in_bounds = |p : Point| p.x >= 0 && p.x < map.width && p.y >= 0 && p.y < map.height;
+    let in_range = |p : Point| DistanceAlg::Pythagoras.distance2d(origin, p) <= range as f32;
+    let mut visible : HashSet<Point> = HashSet::new();
+    if in_bounds(origin) {
+        visible.insert(origin);
+    }
+
+    for quadrant in [Quadrant::North, Quadrant::East, Quadrant::South, Quadrant::West] {
+        let mut rows = vec![Row{ depth: 1, start_slope: -1.0, end_slope: 1.0 }];
+        while let Some(mut row) = rows.pop() {
+            if row.depth > range { continue; }
+            let mut previous_wall : Option<bool> = None;
+            for col in row.columns() {
+                let tile = quadrant.transform(origin, row.depth, col);
+                let is_wall = !in_bounds(tile) || map.is_opaque(map.xy_idx(tile.x, tile.y));
+                if (is_wall || row.is_symmetric(col)) && in_bounds(tile) && in_range(tile) {
+                    visible.insert(tile);
+                }
+                match previous_wall {
+                    Some(true) if !is_wall => row.start_slope = slope(row.depth, col),
+                    Some(false) if is_wall => {
+                        let mut next = row.next();
+                        next.end_slope = slope(row.depth, col);
+                        rows.push(next);
+                    }
+                    _ => {}
+                }
+                previous_wall = Some(is_wall);
+            }
+            if previous_wall == Some(false) {
+                rows.push(row.next());
+            }
+        }
+    }
+
+    visible.into_iter().collect()
+}
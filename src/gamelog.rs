@@ -1,3 +1,71 @@
+use rltk::RGB;
+
+/// What a message is about, so the log viewer can show only some of them.
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum LogCategory { Combat, Items, System }
+
+/// One line of the log. The same message said again straight after is counted instead of repeated.
+pub struct LogEntry {
+    pub text : String,
+    pub color : RGB,
+    pub category : LogCategory,
+    pub count : u32
+}
+
+impl LogEntry {
+    /// The entry as it's shown, with how many times it happened if more than once.
+    pub fn line(&self) -> String {
+        if self.count > 1 { format!("{} x{}", self.text, self.count) } else { self.text.clone() }
+    }
+}
+
+/// Which categories the log viewer shows.
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub struct LogFilter {
+    pub combat : bool,
+    pub items : bool,
+    pub system : bool
+}
+
+impl Default for LogFilter {
+    fn default() -> LogFilter {
+        LogFilter{ combat: true, items: true, system: true }
+    }
+}
+
+impl LogFilter {
+    pub fn shows(&self, category : LogCategory) -> bool {
+        match category {
+            LogCategory::Combat => self.combat,
+            LogCategory::Items => self.items,
+            LogCategory::System => self.system
+        }
+    }
+}
+
 pub struct GameLog {
-    pub entries : Vec<String>
-}
\ No newline at end of file
+    pub entries : Vec<LogEntry>
+}
+
+impl GameLog {
+    pub fn new(first_entry : &str) -> GameLog {
+        let mut log = GameLog{ entries : Vec::new() };
+        log.push(LogCategory::System, first_entry.to_string());
+        log
+    }
+
+    pub fn push(&mut self, category : LogCategory, entry : String) {
+        self.push_colored(category, entry, RGB::named(rltk::WHITE));
+    }
+
+    /// Adds an entry drawn in `color` instead of the default white.
+    pub fn push_colored(&mut self, category : LogCategory, entry : String, color : RGB) {
+        if let Some(last) = self.entries.last_mut() {
+            if last.text == entry && last.color == color && last.category == category {
+                last.count += 1;
+                return;
+            }
+        }
+        self.entries.push(LogEntry{ text: entry, color, category, count: 1 });
+    }
+}
@@ -0,0 +1,39 @@
+use super::{Experience, CombatStats};
+
+/// Modifier granted by an attribute score: 10 is average, every two points above or below shift it by one.
+pub fn attr_bonus(value : i32) -> i32 {
+    (value - 10) / 2
+}
+
+/// Maximum stamina for an entity with the given Fitness score.
+pub fn stamina_at_fitness(fitness : i32) -> i32 {
+    i32::max(4, 10 + attr_bonus(fitness) * 2)
+}
+
+/// Maximum mana for an entity with the given Intelligence score.
+pub fn mana_at_intelligence(intelligence : i32) -> i32 {
+    i32::max(4, 10 + attr_bonus(intelligence) * 2)
+}
+
+/// Experience needed to advance past the given level.
+pub fn xp_for_level(level : i32) -> i32 {
+    level * 50
+}
+
+/// Hit points gained with every new level.
+pub const HP_PER_LEVEL : i32 = 5;
+
+/// Adds experience, raising the level (and restoring hit points) as often as it allows.
+/// Returns the number of levels gained.
+pub fn gain_xp(exp : &mut Experience, stats : &mut CombatStats, amount : i32) -> i32 {
+    let mut levels = 0;
+    exp.xp += amount;
+    while exp.xp >= xp_for_level(exp.level) {
+        exp.xp -= xp_for_level(exp.level);
+        exp.level += 1;
+        stats.max_hp += HP_PER_LEVEL;
+        stats.hp = stats.max_hp;
+        levels += 1;
+    }
+    levels
+}
@@ -1,123 +1,637 @@
-use rltk::{ RGB, Rltk, Point, VirtualKeyCode };
+use rltk::{ RGB, Point, VirtualKeyCode, DrawBatch, ColorPair };
+use std::sync::atomic::{AtomicUsize, Ordering};
 use specs::prelude::*;
 use crate::map::Map;
-use super::{CombatStats, Player, gamelog::GameLog, Name, Position, State, InBackpack, Viewshed, RunState};
+use super::{CombatStats, Player, Hotbar, gamelog::{GameLog, LogFilter, LogCategory}, Name, Monster, Position, State, InBackpack, Viewshed, RunState, Rarity, RarityTier,
+            DamageOverTime, DamageType, Pools, Boss, Companion, CompanionCommand, Experience, Invisible, CanSeeInvisible, Hidden, InContainer, MovesThroughWalls, ethereal, gamesystem::xp_for_level,
+            Equipped, Equippable, EquipmentSlot, Consumable, Category, ItemCategory, Weight, Value, Artifact, Durability, durability, HungerClock, HungerState, Charges, Unidentified, Ammo, Reagent, Purse, Attributes, Skills, Skill, SkillTraining, skill_training, KeenEye, FastHealing, FarSight, Faith, Deity, Afflictions, AfflictionKind, Mutations, level_up::{self, LevelUpChoice}, religion::piety_rating, raws, turn_counter::TurnCounter, vendor,
+            quests::{self, QuestLog}, dialogue, difficulty::Difficulty, run_stats::RunStats, camera, options::{Options, OptionChoice},
+            achievements::{Profile, ACHIEVEMENTS}, bestiary::{self, KnownBeasts}, identified_items::{self, IdentifiedItems}, scores::{ScoreTable, ScoreView}, challenge::Challenge, replay::Replay, input::Input, palette, undo::RunMode};
+
+/// The consoles stacked up to make the screen: the map and everything on it at the bottom, with the
+/// tileset's sprites over it, then anything caught moving between tiles, then the interface, and
+/// tooltips and particles on top. All but the bottom one are sparse, so whatever they leave empty
+/// shows through from below.
+pub const MAP_CONSOLE : usize = 0;
+pub const TILE_CONSOLE : usize = 1;
+pub const MOTION_CONSOLE : usize = 2;
+pub const UI_CONSOLE : usize = 3;
+pub const OVERLAY_CONSOLE : usize = 4;
+/// The consoles wiped every frame. The map and its sprites stay put until the camera redraws them.
+pub const REDRAWN_CONSOLES : [usize; 3] = [MOTION_CONSOLE, UI_CONSOLE, OVERLAY_CONSOLE];
+
+/// Draw batches are layered by these z orders. Every command in a batch takes the next slot up from
+/// its layer, so they are spaced widely enough for a whole screen of map tiles.
+pub const MAP_LAYER : usize = 0;
+pub const ENTITY_LAYER : usize = 10000;
+pub const UI_LAYER : usize = 20000;
+pub const TOOLTIP_LAYER : usize = 30000;
+pub const MENU_LAYER : usize = 40000;
+
+/// A draw batch aimed at the given console.
+pub fn batch_for(console : usize) -> DrawBatch {
+    let mut draw_batch = DrawBatch::new();
+    draw_batch.target(console);
+    draw_batch
+}
 
 #[derive(PartialEq, Copy, Clone)]
-pub enum MainMenuSelection { NewGame, LoadGame, Quit }
+pub enum MainMenuSelection { NewGame, DailyChallenge, LoadGame, WatchReplay, Options, Achievements, HighScores, Quit }
 
 pub enum MainMenuResult {
     NoSelection { selected : MainMenuSelection},
     Selected{ selected: MainMenuSelection}
 }
 
-pub fn draw_ui(ecs: &World, ctx : &mut Rltk) {
-    ctx.draw_box(0, 43, 79, 6, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
+/// Colour used to print an item's name, reflecting how rare it is.
+pub fn item_name_color(ecs: &World, item : Entity) -> RGB {
+    let rarities = ecs.read_storage::<Rarity>();
+    match rarities.get(item).map(|r| r.tier) {
+        Some(RarityTier::Rare) => palette::named(rltk::CYAN),
+        Some(RarityTier::Legendary) => palette::named(rltk::GOLD),
+        Some(RarityTier::Artifact) => palette::named(rltk::ORANGE),
+        _ => palette::named(rltk::WHITE)
+    }
+}
+
+/// An item's name, followed by its condition once it has seen some wear,
+/// its remaining charges once the player knows what it is, or how many are in a stack.
+fn item_label(ecs : &World, item : Entity, name : &str) -> String {
+    if let Some(a) = ecs.read_storage::<Ammo>().get(item) {
+        return format!("{} ({})", name, a.count);
+    }
+    if let Some(c) = ecs.read_storage::<Charges>().get(item) {
+        if ecs.read_storage::<Unidentified>().get(item).is_none() {
+            return format!("{} ({}/{})", name, c.current, c.max);
+        }
+    }
+    match ecs.read_storage::<Durability>().get(item) {
+        Some(d) if d.current < d.max => format!("{} ({})", name, durability::condition(d)),
+        _ => name.to_string()
+    }
+}
+
+/// Says a replay is playing, and how to control it, across the top of the screen.
+pub fn draw_replay_status(ecs : &World) {
+    if let Some(status) = ecs.fetch::<Replay>().status() {
+        let mut draw_batch = batch_for(OVERLAY_CONSOLE);
+        draw_batch.print_color(Point::new(1, 0), &status, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+        draw_batch.submit(MENU_LAYER + 100).expect("Unable to submit draw batch");
+    }
+}
+
+/// The log and status panel along the bottom of the screen, and the boss bar across the top. The
+/// panel spans the whole width, with the mana, stamina and companion readouts kept to its right edge and
+/// the health bar stretching to fill the space between.
+pub fn draw_ui(ecs: &World, ctx : &mut Input) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let screen = *ecs.fetch::<camera::Screen>();
+    let (width, top, bottom) = (screen.width, screen.view_height(), screen.height - 1);
+    draw_batch.draw_box(rltk::Rect::with_size(0, top, width - 1, camera::PANEL_HEIGHT - 1), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+
+    let map = ecs.fetch::<Map>();
+    let depth = map.branch.level_name(map.depth);
+    draw_batch.print_color(Point::new(2, top), &depth, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
 
     let combat_stats = ecs.read_storage::<CombatStats>();
+    let pools = ecs.read_storage::<Pools>();
     let players = ecs.read_storage::<Player>();
-    for (_player, stats) in (&players, &combat_stats).join() {
+    for (_player, stats, pools) in (&players, &combat_stats, pools.maybe()).join() {
+        // Without mana and stamina to show, health gets the whole top row
+        let health_width = if pools.is_some() { width - 70 } else { width - 30 };
         let health = format!(" HP: {} / {} ", stats.hp, stats.max_hp);
-        ctx.print_color(12, 43, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), &health);
+        draw_batch.print_color(Point::new(12, top), &health, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+        draw_batch.bar_horizontal(Point::new(28, top), health_width, stats.hp, stats.max_hp, ColorPair::new(palette::named(rltk::RED), palette::named(rltk::BLACK)));
+
+        let pools = match pools {
+            Some(pools) => pools,
+            None => continue
+        };
+        let mana = format!(" MP: {}/{} ", pools.mana.current, pools.mana.max);
+        draw_batch.print_color(Point::new(width - 41, top), &mana, ColorPair::new(palette::named(rltk::CYAN), palette::named(rltk::BLACK)));
+
+        let stamina = format!(" SP: {} / {} ", pools.stamina.current, pools.stamina.max);
+        draw_batch.print_color(Point::new(width - 29, top), &stamina, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+        draw_batch.bar_horizontal(Point::new(width - 15, top), 13, pools.stamina.current, pools.stamina.max, ColorPair::new(palette::named(rltk::GREEN), palette::named(rltk::BLACK)));
+    }
+
+    let turns = ecs.fetch::<TurnCounter>();
+    draw_batch.print_color(Point::new(2, bottom), &format!(" Turn: {} ", turns.turn), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    let player_entity = ecs.fetch::<Entity>();
+    if let Some(exp) = ecs.read_storage::<Experience>().get(*player_entity) {
+        let level = format!(" Lvl {} ({}/{} XP) ", exp.level, exp.xp, xp_for_level(exp.level));
+        draw_batch.print_color(Point::new(30, bottom), &level, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    }
+
+    // The first companion's health gets its own slot
+    {
+        let names = ecs.read_storage::<Name>();
+        let companions = ecs.read_storage::<Companion>();
+        if let Some((_companion, stats, name)) = (&companions, &combat_stats, &names).join().next() {
+            let health = format!(" {} {}/{} ", name.name, stats.hp, stats.max_hp);
+            draw_batch.print_color(Point::new(width - 28, bottom), &health, ColorPair::new(palette::named(rltk::CYAN), palette::named(rltk::BLACK)));
+            draw_batch.bar_horizontal(Point::new(width - 14, bottom), 12, stats.hp, stats.max_hp, ColorPair::new(palette::named(rltk::CYAN), palette::named(rltk::BLACK)));
+        }
+    }
+
+    // Hunger, once it starts to bite, then active damage over time effects on the player
+    let mut x = 16;
+    if let Some(hunger) = ecs.read_storage::<HungerClock>().get(*player_entity) {
+        let status = match hunger.state {
+            HungerState::Hungry => Some(("Hungry", palette::named(rltk::ORANGE))),
+            HungerState::Starving => Some(("Starving", palette::named(rltk::RED))),
+            _ => None
+        };
+        if let Some((label, color)) = status {
+            draw_batch.print_color(Point::new(x, bottom), label, ColorPair::new(color, palette::named(rltk::BLACK)));
+            x += label.len() as i32 + 1;
+        }
+    }
+    if let Some(dots) = ecs.read_storage::<DamageOverTime>().get(*player_entity) {
+        for effect in dots.effects.iter() {
+            let (glyph, color) = match effect.kind {
+                DamageType::Poison => ('\u{2663}', palette::named(rltk::GREEN)),
+                _ => ('\u{2665}', palette::named(rltk::RED))
+            };
+            draw_batch.set(Point::new(x, bottom), ColorPair::new(color, palette::named(rltk::BLACK)), rltk::to_cp437(glyph));
+            draw_batch.print_color(Point::new(x + 1, bottom), &format!("{} ", effect.turns), ColorPair::new(color, palette::named(rltk::BLACK)));
+            x += 4;
+        }
+    }
 
-        ctx.draw_bar_horizontal(28, 43, 51, stats.hp, stats.max_hp, RGB::named(rltk::RED), RGB::named(rltk::BLACK));
+    // A boss in sight gets its health bar across the top of the screen
+    if let Some(viewshed) = ecs.read_storage::<Viewshed>().get(*player_entity) {
+        let names = ecs.read_storage::<Name>();
+        let positions = ecs.read_storage::<Position>();
+        let bosses = ecs.read_storage::<Boss>();
+        for (_boss, stats, pos, name) in (&bosses, &combat_stats, &positions, &names).join() {
+            if !viewshed.visible_tiles.contains(&Point::new(pos.x, pos.y)) { continue; }
+            draw_batch.print_color(Point::new(1, 0), &name.name, ColorPair::new(palette::named(rltk::MAGENTA), palette::named(rltk::BLACK)));
+            draw_batch.bar_horizontal(Point::new(2 + name.name.len() as i32, 0), width - 4 - name.name.len() as i32, stats.hp, stats.max_hp, ColorPair::new(palette::named(rltk::MAGENTA), palette::named(rltk::BLACK)));
+            break;
+        }
+    }
+
+    // Bound hotbar slots take the last line of the panel, with how many of each are left
+    let mut log_bottom = bottom;
+    if let Some(hotbar) = ecs.read_storage::<Hotbar>().get(*player_entity) {
+        let names = ecs.read_storage::<Name>();
+        let backpack = ecs.read_storage::<InBackpack>();
+        let mut x = 2;
+        for (slot, bound) in hotbar.slots.iter().enumerate() {
+            let bound = match bound { Some(bound) => bound, None => continue };
+            let carried = (&backpack, &names).join().filter(|(pack, name)| pack.owner == *player_entity && name.name == *bound).count();
+            let short : String = bound.chars().take(12).collect();
+            let label = format!("{}:{}({})", slot + 1, short, carried);
+            if x + label.len() as i32 >= width - 1 { break; }
+            let color = if carried > 0 { palette::named(rltk::WHITE) } else { palette::named(rltk::GREY) };
+            draw_batch.print_color(Point::new(x, bottom - 1), &label, ColorPair::new(color, palette::named(rltk::BLACK)));
+            x += label.len() as i32 + 2;
+            log_bottom = bottom - 1;
+        }
     }
 
     let log = ecs.fetch::<GameLog>();
-    let mut y = 44;
-    for s in log.entries.iter().rev() {
-        if y < 49 { ctx.print(2, y, s); }
+    let mut y = top + 1;
+    for entry in log.entries.iter().rev() {
+        if y >= log_bottom { break; }
+        draw_batch.print_color(Point::new(2, y), &entry.line(), ColorPair::new(palette::color(entry.color), palette::named(rltk::BLACK)));
         y += 1;
     }
 
-    // Draw mouse cursor
+    draw_batch.submit(UI_LAYER).expect("Unable to submit draw batch");
+
+    // Draw mouse cursor, highlighting the map cell underneath
     let mouse_pos = ctx.mouse_pos();
-    ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::MAGENTA));
+    camera::highlight(ecs, &[(Point::new(mouse_pos.0, mouse_pos.1), palette::named(rltk::MAGENTA))], UI_LAYER);
     draw_tooltips(ecs, ctx);
 }
 
-fn draw_tooltips(ecs: &World, ctx : &mut Rltk) {
+fn draw_tooltips(ecs: &World, ctx : &mut Input) {
+    let mut draw_batch = batch_for(OVERLAY_CONSOLE);
     let map = ecs.fetch::<Map>();
     let names = ecs.read_storage::<Name>();
     let positions = ecs.read_storage::<Position>();
 
     let mouse_pos = ctx.mouse_pos();
-    if mouse_pos.0 >= map.width || mouse_pos.1 >= map.height { return; }
-    let mut tooltip : Vec<String> = Vec::new();
-    for (name, position) in (&names, &positions).join() {
+    if !camera::on_screen(ecs, mouse_pos.0, mouse_pos.1) { return; }
+    let mouse_map_pos = camera::screen_to_map(ecs, mouse_pos.0, mouse_pos.1);
+    if mouse_map_pos.x >= map.width || mouse_map_pos.y >= map.height { return; }
+    let entities = ecs.entities();
+    let invisible = ecs.read_storage::<Invisible>();
+    let sees_invisible = ecs.read_storage::<CanSeeInvisible>().get(*ecs.fetch::<Entity>()).is_some();
+    let hidden = ecs.read_storage::<Hidden>();
+    let walkers = ecs.read_storage::<MovesThroughWalls>();
+    let mut tooltip : Vec<(String, RGB)> = Vec::new();
+    for (entity, name, position) in (&entities, &names, &positions).join() {
+        if (!sees_invisible && invisible.get(entity).is_some()) || hidden.get(entity).is_some() { continue; }
+        if ethereal::in_wall(&map, &walkers, entity, position.x, position.y) { continue; }
         let idx = map.xy_idx(position.x, position.y);
-        if position.x == mouse_pos.0 && position.y == mouse_pos.1 && map.visible_tiles[idx] {
-            tooltip.push(name.name.to_string());
+        if position.x == mouse_map_pos.x && position.y == mouse_map_pos.y && map.visible_tiles[idx] {
+            tooltip.push((name.name.to_string(), item_name_color(ecs, entity)));
         }
     }
 
     if !tooltip.is_empty() {
         let mut width :i32 = 0;
-        for s in tooltip.iter() {
+        for (s, _) in tooltip.iter() {
             if width < s.len() as i32 { width = s.len() as i32; }
         }
         width += 3;
 
-        if mouse_pos.0 > 40 {
+        if mouse_pos.0 > ecs.fetch::<camera::Screen>().width / 2 {
             let arrow_pos = Point::new(mouse_pos.0 - 2, mouse_pos.1);
             let left_x = mouse_pos.0 - width;
             let mut y = mouse_pos.1;
-            for s in tooltip.iter() {
-                ctx.print_color(left_x, y, RGB::named(rltk::WHITE), RGB::named(rltk::GREY), s);
+            for (s, fg) in tooltip.iter() {
+                draw_batch.print_color(Point::new(left_x, y), s, ColorPair::new(*fg, palette::named(rltk::GREY)));
                 let padding = (width - s.len() as i32)-1;
                 for i in 0..padding {
-                    ctx.print_color(arrow_pos.x - i, y, RGB::named(rltk::WHITE), RGB::named(rltk::GREY), &" ".to_string());
+                    draw_batch.print_color(Point::new(arrow_pos.x - i, y), &" ".to_string(), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::GREY)));
                 }
                 y += 1;
             }
-            ctx.print_color(arrow_pos.x, arrow_pos.y, RGB::named(rltk::WHITE), RGB::named(rltk::GREY), &"->".to_string());
+            draw_batch.print_color(Point::new(arrow_pos.x, arrow_pos.y), &"->".to_string(), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::GREY)));
         } else {
             let arrow_pos = Point::new(mouse_pos.0 + 1, mouse_pos.1);
             let left_x = mouse_pos.0 +3;
             let mut y = mouse_pos.1;
-            for s in tooltip.iter() {
-                ctx.print_color(left_x + 1, y, RGB::named(rltk::WHITE), RGB::named(rltk::GREY), s);
+            for (s, fg) in tooltip.iter() {
+                draw_batch.print_color(Point::new(left_x + 1, y), s, ColorPair::new(*fg, palette::named(rltk::GREY)));
                 let padding = (width - s.len() as i32)-1;
                 for i in 0..padding {
-                    ctx.print_color(arrow_pos.x + 1 + i, y, RGB::named(rltk::WHITE), RGB::named(rltk::GREY), &" ".to_string());
+                    draw_batch.print_color(Point::new(arrow_pos.x + 1 + i, y), &" ".to_string(), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::GREY)));
                 }
                 y += 1;
             }
-            ctx.print_color(arrow_pos.x, arrow_pos.y, RGB::named(rltk::WHITE), RGB::named(rltk::GREY), &"<-".to_string());
+            draw_batch.print_color(Point::new(arrow_pos.x, arrow_pos.y), &"<-".to_string(), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::GREY)));
         }
     }
+    draw_batch.submit(TOOLTIP_LAYER).expect("Unable to submit draw batch");
 }
 
 #[derive(PartialEq, Copy, Clone)]
 pub enum ItemMenuResult { Cancel, NoResponse, Selected }
 
-pub fn show_inventory(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
-    let player_entity = gs.ecs.fetch::<Entity>();
+/// What the mouse did to a menu this tick.
+#[derive(PartialEq, Copy, Clone)]
+enum MenuClick { Nothing, Row(usize), Outside }
+
+/// Lets a boxed list be worked with the mouse as well as by letter: the row under the pointer is lit
+/// up, clicking it picks it, and clicking anywhere outside the box backs out. The rows are one line
+/// each, starting two lines below the top of `frame`. Call it after drawing the rows, so the
+/// highlight goes over them.
+fn menu_mouse(draw_batch : &mut DrawBatch, ctx : &Input, frame : rltk::Rect, count : usize) -> MenuClick {
+    let (x, y) = ctx.mouse_pos();
+    let inside = x >= frame.x1 && x <= frame.x2 && y >= frame.y1 && y <= frame.y2;
+    let row = y - (frame.y1 + 2);
+    if inside && row >= 0 && row < count as i32 && x > frame.x1 && x < frame.x2 {
+        for hx in frame.x1 + 1 .. frame.x2 {
+            draw_batch.set_bg(Point::new(hx, y), palette::named(rltk::DARK_BLUE));
+        }
+        if ctx.left_click { return MenuClick::Row(row as usize); }
+    } else if !inside && ctx.left_click {
+        return MenuClick::Outside;
+    }
+    MenuClick::Nothing
+}
+
+/// The row the d-pad cursor is on in whichever lettered menu is open, plus one, so that nothing is lit
+/// up until Up or Down is first pressed. Menus are drawn from the run state alone, so it's kept here
+/// and cleared whenever the run state changes.
+static MENU_CURSOR : AtomicUsize = AtomicUsize::new(0);
+
+/// Takes the d-pad cursor away, so the next menu starts without one.
+pub fn reset_menu_cursor() {
+    MENU_CURSOR.store(0, Ordering::Relaxed);
+}
+
+/// Lets a lettered list be worked without letters, as with a controller: Up and Down move a
+/// highlight through the `count` rows, which start at `first_row` and are `spacing` lines apart,
+/// and the highlighted row is returned for the menu to pick when Return is pressed. Call it after
+/// drawing the rows, so the highlight goes over them.
+fn menu_cursor(draw_batch : &mut DrawBatch, ctx : &Input, frame : rltk::Rect, first_row : i32, spacing : i32, count : usize) -> Option<usize> {
+    if count == 0 { return None; }
+    let cursor = match (MENU_CURSOR.load(Ordering::Relaxed).min(count), ctx.key) {
+        (0, Some(VirtualKeyCode::Down)) => Some(0),
+        (0, Some(VirtualKeyCode::Up)) => Some(count - 1),
+        (0, _) => None,
+        (shown, Some(VirtualKeyCode::Down)) => Some(shown % count),
+        (shown, Some(VirtualKeyCode::Up)) => Some((shown + count - 2) % count),
+        (shown, _) => Some(shown - 1)
+    };
+    MENU_CURSOR.store(cursor.map_or(0, |row| row + 1), Ordering::Relaxed);
+    if let Some(row) = cursor {
+        let y = first_row + row as i32 * spacing;
+        for hx in frame.x1 + 1 .. frame.x2 {
+            draw_batch.set_bg(Point::new(hx, y), palette::named(rltk::DARK_BLUE));
+        }
+    }
+    cursor
+}
+
+/// Works a boxed list of one-line rows with the mouse or the d-pad cursor as well as by letter:
+/// clicking a row or pressing Return on the highlighted one picks it.
+fn menu_select(draw_batch : &mut DrawBatch, ctx : &Input, frame : rltk::Rect, count : usize) -> MenuClick {
+    let click = menu_mouse(draw_batch, ctx, frame, count);
+    let cursor = menu_cursor(draw_batch, ctx, frame, frame.y1 + 2, 1, count);
+    match (cursor, ctx.key) {
+        (Some(row), Some(VirtualKeyCode::Return)) if click == MenuClick::Nothing => MenuClick::Row(row),
+        _ => click
+    }
+}
+
+/// The headings the inventory is split under, in the order they're shown.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+enum InventoryGroup { Weapons, Armor, Potions, Scrolls, Misc }
+
+impl InventoryGroup {
+    fn name(self) -> &'static str {
+        match self {
+            InventoryGroup::Weapons => "Weapons",
+            InventoryGroup::Armor => "Armor",
+            InventoryGroup::Potions => "Potions",
+            InventoryGroup::Scrolls => "Scrolls",
+            InventoryGroup::Misc => "Miscellaneous"
+        }
+    }
+
+    fn of(ecs : &World, item : Entity) -> InventoryGroup {
+        if let Some(category) = ecs.read_storage::<Category>().get(item) {
+            match category.category {
+                ItemCategory::Potion => return InventoryGroup::Potions,
+                ItemCategory::Scroll => return InventoryGroup::Scrolls,
+                _ => {}
+            }
+        }
+        match ecs.read_storage::<Equippable>().get(item).map(|e| e.slot) {
+            Some(EquipmentSlot::Melee) | Some(EquipmentSlot::Quiver) => InventoryGroup::Weapons,
+            Some(_) => InventoryGroup::Armor,
+            None => InventoryGroup::Misc
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum InventorySort { Name, Weight, Value }
+
+impl InventorySort {
+    pub fn name(self) -> &'static str {
+        match self {
+            InventorySort::Name => "name",
+            InventorySort::Weight => "weight",
+            InventorySort::Value => "value"
+        }
+    }
+
+    pub fn next(self) -> InventorySort {
+        match self {
+            InventorySort::Name => InventorySort::Weight,
+            InventorySort::Weight => InventorySort::Value,
+            InventorySort::Value => InventorySort::Name
+        }
+    }
+}
+
+/// Which items the inventory offers: everything, or only what the quaff, read and apply commands
+/// can use.
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum InventoryFilter { All, Quaff, Read, Apply }
+
+impl InventoryFilter {
+    fn title(self) -> &'static str {
+        match self {
+            InventoryFilter::All => "Inventory",
+            InventoryFilter::Quaff => "Quaff what?",
+            InventoryFilter::Read => "Read what?",
+            InventoryFilter::Apply => "Apply what?"
+        }
+    }
+
+    /// What the player is told when they have nothing the filter lets through.
+    pub fn nothing(self) -> &'static str {
+        match self {
+            InventoryFilter::All => "You are carrying nothing.",
+            InventoryFilter::Quaff => "You have nothing to drink.",
+            InventoryFilter::Read => "You have nothing to read.",
+            InventoryFilter::Apply => "You have nothing to apply."
+        }
+    }
+
+    fn admits(self, stack : &InventoryStack) -> bool {
+        match self {
+            InventoryFilter::All => true,
+            InventoryFilter::Quaff => stack.group == InventoryGroup::Potions,
+            InventoryFilter::Read => stack.group == InventoryGroup::Scrolls,
+            InventoryFilter::Apply => stack.group == InventoryGroup::Misc && stack.usable
+        }
+    }
+}
+
+/// How the inventory is being looked at: what it's sorted by, which page is showing and what it
+/// lets through.
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub struct InventoryView {
+    pub sort : InventorySort,
+    pub page : usize,
+    pub filter : InventoryFilter
+}
+
+impl Default for InventoryView {
+    fn default() -> InventoryView {
+        InventoryView{ sort: InventorySort::Name, page: 0, filter: InventoryFilter::All }
+    }
+}
+
+impl InventoryView {
+    pub fn filtered(filter : InventoryFilter) -> InventoryView {
+        InventoryView{ filter, ..InventoryView::default() }
+    }
+}
+
+/// Whether the player has anything `filter` lets through.
+pub fn has_items(ecs : &World, filter : InventoryFilter) -> bool {
+    inventory_stacks(ecs, InventorySort::Name).iter().any(|stack| filter.admits(stack))
+}
+
+/// How many entries fit on a page of the inventory, each with its own letter.
+const INVENTORY_PAGE_SIZE : usize = 20;
+
+/// A line of the inventory screen, so clicks can be matched back to what was drawn there.
+enum InventoryLine { Heading(InventoryGroup), Entry(usize) }
+
+/// One entry in the inventory: identical items carried loose share an entry and a count.
+struct InventoryStack {
+    item : Entity,
+    label : String,
+    group : InventoryGroup,
+    equipped : bool,
+    usable : bool,
+    count : usize,
+    weight : i32,
+    value : i32
+}
+
+/// Everything the player carries or wears, stacked, grouped and sorted.
+fn inventory_stacks(ecs : &World, sort : InventorySort) -> Vec<InventoryStack> {
+    let player_entity = *ecs.fetch::<Entity>();
+    let names = ecs.read_storage::<Name>();
+    let backpack = ecs.read_storage::<InBackpack>();
+    let equipped = ecs.read_storage::<Equipped>();
+    let weights = ecs.read_storage::<Weight>();
+    let values = ecs.read_storage::<Value>();
+    let consumables = ecs.read_storage::<Consumable>();
+    let charges = ecs.read_storage::<Charges>();
+    let entities = ecs.entities();
+
+    let mut stacks : Vec<InventoryStack> = Vec::new();
+    for (entity, name) in (&entities, &names).join() {
+        let worn = equipped.get(entity).map_or(false, |e| e.owner == player_entity);
+        if !worn && backpack.get(entity).map_or(true, |b| b.owner != player_entity) { continue; }
+        let label = item_label(ecs, entity, &name.name);
+        if !worn {
+            if let Some(stack) = stacks.iter_mut().find(|s| !s.equipped && s.label == label) {
+                stack.count += 1;
+                continue;
+            }
+        }
+        stacks.push(InventoryStack{
+            item: entity,
+            label,
+            group: InventoryGroup::of(ecs, entity),
+            equipped: worn,
+            usable: !worn && (consumables.get(entity).is_some() || charges.get(entity).is_some()),
+            count: 1,
+            weight: weights.get(entity).map_or(0, |w| w.weight),
+            value: values.get(entity).map_or(0, |v| v.gold)
+        });
+    }
+
+    // Heaviest and dearest first; ties, and the name sort, go alphabetically
+    stacks.sort_by(|a, b| {
+        let by_sort = match sort {
+            InventorySort::Name => std::cmp::Ordering::Equal,
+            InventorySort::Weight => (b.weight * b.count as i32).cmp(&(a.weight * a.count as i32)),
+            InventorySort::Value => (b.value * b.count as i32).cmp(&(a.value * a.count as i32))
+        };
+        a.group.cmp(&b.group).then(by_sort).then(a.label.cmp(&b.label))
+    });
+    stacks
+}
+
+/// The player's belongings under a heading for each kind, a page at a time. TAB changes the order,
+/// the arrow keys turn the page. Whatever's worn is listed too, so picking it takes it off.
+pub fn show_inventory(gs : &mut State, ctx : &mut Input, view : InventoryView) -> (ItemMenuResult, Option<Entity>, InventoryView) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let stacks : Vec<InventoryStack> = inventory_stacks(&gs.ecs, view.sort).into_iter().filter(|stack| view.filter.admits(stack)).collect();
+    let pages = ((stacks.len() + INVENTORY_PAGE_SIZE - 1) / INVENTORY_PAGE_SIZE).max(1);
+    let view = InventoryView{ page: view.page.min(pages - 1), ..view };
+    let shown : Vec<&InventoryStack> = stacks.iter().skip(view.page * INVENTORY_PAGE_SIZE).take(INVENTORY_PAGE_SIZE).collect();
+
+    let mut lines : Vec<InventoryLine> = Vec::new();
+    for (j, stack) in shown.iter().enumerate() {
+        if j == 0 || shown[j-1].group != stack.group {
+            lines.push(InventoryLine::Heading(stack.group));
+        }
+        lines.push(InventoryLine::Entry(j));
+    }
+    let count = lines.len();
+
+    let mut y = (25 - (count / 2)) as i32;
+    let frame = rltk::Rect::with_size(15, y-2, 45, (count+3) as i32);
+    let title = if pages > 1 { format!("{} ({}/{})", view.filter.title(), view.page + 1, pages) } else { view.filter.title().to_string() };
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y-2), &title, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y+count as i32+1), &format!("TAB: by {}, ESCAPE to cancel", view.sort.name()), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    for line in lines.iter() {
+        match line {
+            InventoryLine::Heading(group) => {
+                draw_batch.print_color(Point::new(17, y), group.name(), ColorPair::new(palette::named(rltk::CYAN), palette::named(rltk::BLACK)));
+            }
+            InventoryLine::Entry(j) => {
+                let stack = shown[*j];
+                draw_batch.set(Point::new(17, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+                draw_batch.set(Point::new(18, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+*j as rltk::FontCharType);
+                draw_batch.set(Point::new(19, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+                let mut label = stack.label.clone();
+                if stack.count > 1 { label = format!("{} x{}", label, stack.count); }
+                if stack.equipped { label = format!("{} (equipped)", label); }
+                draw_batch.print_color(Point::new(21, y), &label, ColorPair::new(item_name_color(&gs.ecs, stack.item), palette::named(rltk::BLACK)));
+            }
+        }
+        y += 1;
+    }
+    let click = menu_select(&mut draw_batch, ctx, frame, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match click {
+        MenuClick::Row(row) => {
+            if let InventoryLine::Entry(j) = lines[row] { return (ItemMenuResult::Selected, Some(shown[j].item), view); }
+        }
+        MenuClick::Outside => return (ItemMenuResult::Cancel, None, view),
+        MenuClick::Nothing => {}
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None, view),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None, view) }
+                VirtualKeyCode::Tab => (ItemMenuResult::NoResponse, None, InventoryView{ sort: view.sort.next(), page: 0, ..view }),
+                VirtualKeyCode::Left | VirtualKeyCode::PageUp => (ItemMenuResult::NoResponse, None, InventoryView{ page: view.page.saturating_sub(1), ..view }),
+                VirtualKeyCode::Right | VirtualKeyCode::PageDown => (ItemMenuResult::NoResponse, None, InventoryView{ page: (view.page + 1).min(pages - 1), ..view }),
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < shown.len() as i32 {
+                        return (ItemMenuResult::Selected, Some(shown[selection as usize].item), view);
+                    }
+                    (ItemMenuResult::NoResponse, None, view)
+                }
+            }
+        }
+    }
+}
+
+/// Lists what a container holds; picking an item moves it into the player's backpack.
+pub fn loot_menu(gs : &mut State, ctx : &mut Input, container : Entity) -> (ItemMenuResult, Option<Entity>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
     let names = gs.ecs.read_storage::<Name>();
-    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let contents = gs.ecs.read_storage::<InContainer>();
     let entities = gs.ecs.entities();
 
-    let inventory = (&backpack, &names).join().filter(|item| item.0.owner == *player_entity );
-    let count = inventory.count();
+    let count = (&contents, &names).join().filter(|item| item.0.container == container).count();
+    let title = names.get(container).map_or("Container".to_string(), |n| n.name.clone());
 
     let mut y = (25 - (count / 2)) as i32;
-    ctx.draw_box(15, y-2, 31, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
-    ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Inventory");
-    ctx.print_color(18, y+count as i32+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "ESCAPE to cancel");
+    let frame = rltk::Rect::with_size(15, y-2, 31, (count+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y-2), &title, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y+count as i32+1), "ESCAPE to close", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    if count == 0 {
+        draw_batch.print_color(Point::new(21, y), "It is empty.", ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+    }
 
-    let mut equippable : Vec<Entity> = Vec::new();
+    let mut items : Vec<Entity> = Vec::new();
     let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names).join().filter(|item| item.1.owner == *player_entity ) {
-        ctx.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
-        ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
-        ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
+    for (entity, _content, name) in (&entities, &contents, &names).join().filter(|item| item.1.container == container ) {
+        draw_batch.set(Point::new(17, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(18, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(19, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
 
-        ctx.print(21, y, &name.name.to_string());
-        equippable.push(entity);
+        draw_batch.print_color(Point::new(21, y), &name.name.to_string(), ColorPair::new(item_name_color(&gs.ecs, entity), palette::named(rltk::BLACK)));
+        items.push(entity);
         y += 1;
         j += 1;
     }
+    let click = menu_select(&mut draw_batch, ctx, frame, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
 
+    match click {
+        MenuClick::Row(row) => return (ItemMenuResult::Selected, Some(items[row])),
+        MenuClick::Outside => return (ItemMenuResult::Cancel, None),
+        MenuClick::Nothing => {}
+    }
     match ctx.key {
         None => (ItemMenuResult::NoResponse, None),
         Some(key) => {
@@ -126,7 +640,7 @@ pub fn show_inventory(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Opti
                 _ => {
                     let selection = rltk::letter_to_option(key);
                     if selection > -1 && selection < count as i32 {
-                        return (ItemMenuResult::Selected, Some(equippable[selection as usize]));
+                        return (ItemMenuResult::Selected, Some(items[selection as usize]));
                     }
                     (ItemMenuResult::NoResponse, None)
                 }
@@ -135,33 +649,170 @@ pub fn show_inventory(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Opti
     }
 }
 
-pub fn drop_item_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Option<Entity>) {
-    let player_entity = gs.ecs.fetch::<Entity>();
+/// Lists what the player can do from where they stand.
+pub fn action_menu(ctx : &mut Input, labels : &[String]) -> (ItemMenuResult, Option<usize>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let count = labels.len();
+
+    let mut y = (25 - (count / 2)) as i32;
+    let frame = rltk::Rect::with_size(15, y-2, 40, (count+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y-2), "Actions", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y+count as i32+1), "ESCAPE to cancel", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    for (j, label) in labels.iter().enumerate() {
+        draw_batch.set(Point::new(17, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(18, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(19, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print_color(Point::new(21, y), label, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+        y += 1;
+    }
+    let click = menu_select(&mut draw_batch, ctx, frame, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match click {
+        MenuClick::Row(row) => return (ItemMenuResult::Selected, Some(row)),
+        MenuClick::Outside => return (ItemMenuResult::Cancel, None),
+        MenuClick::Nothing => {}
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(VirtualKeyCode::Escape) => (ItemMenuResult::Cancel, None),
+        Some(key) => {
+            let selection = rltk::letter_to_option(key);
+            if selection > -1 && selection < count as i32 {
+                return (ItemMenuResult::Selected, Some(selection as usize));
+            }
+            (ItemMenuResult::NoResponse, None)
+        }
+    }
+}
+
+/// The items ticked on the pickup menu.
+#[derive(Default)]
+pub struct PickupSelection {
+    pub chosen : Vec<Entity>
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum PickupResult { NoResponse, Cancel, Confirm, ToggleNearby }
+
+/// Lists what's lying at the player's feet, and next to them too if `nearby`, to tick what to take.
+/// A letter, a click or Left and Right on the d-pad cursor ticks an item, comma ticks or clears the
+/// lot, and TAB reaches further.
+pub fn pickup_menu(ecs : &World, ctx : &mut Input, items : &[Entity], nearby : bool, chosen : &mut Vec<Entity>) -> PickupResult {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let names = ecs.read_storage::<Name>();
+    chosen.retain(|item| items.contains(item));
+    let count = items.len();
+
+    let mut y = (25 - (count / 2)) as i32;
+    let frame = rltk::Rect::with_size(15, y-2, 42, (count+3) as i32);
+    let title = if nearby { "Pick up (here and nearby)" } else { "Pick up" };
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y-2), title, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y+count as i32+1), "ENTER take, ',' all, TAB nearby", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    if count == 0 {
+        draw_batch.print_color(Point::new(21, y), "There is nothing to pick up.", ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+    }
+
+    for (j, item) in items.iter().enumerate() {
+        draw_batch.set(Point::new(17, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(18, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(19, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        let tick = if chosen.contains(item) { "[x]" } else { "[ ]" };
+        draw_batch.print_color(Point::new(21, y), tick, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+        let name = names.get(*item).map_or("Something".to_string(), |n| n.name.clone());
+        draw_batch.print_color(Point::new(25, y), &name, ColorPair::new(item_name_color(ecs, *item), palette::named(rltk::BLACK)));
+        y += 1;
+    }
+    let click = menu_mouse(&mut draw_batch, ctx, frame, count);
+    let cursor = menu_cursor(&mut draw_batch, ctx, frame, frame.y1 + 2, 1, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    let toggle = |chosen : &mut Vec<Entity>, item : Entity| {
+        if chosen.contains(&item) { chosen.retain(|c| *c != item); } else { chosen.push(item); }
+    };
+    match click {
+        MenuClick::Row(row) => { toggle(chosen, items[row]); return PickupResult::NoResponse; }
+        MenuClick::Outside => return PickupResult::Cancel,
+        MenuClick::Nothing => {}
+    }
+    // Return takes what's ticked, so the d-pad ticks sideways
+    if let (Some(row), Some(VirtualKeyCode::Left | VirtualKeyCode::Right)) = (cursor, ctx.key) {
+        toggle(chosen, items[row]);
+        return PickupResult::NoResponse;
+    }
+    match ctx.key {
+        None => PickupResult::NoResponse,
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => PickupResult::Cancel,
+                VirtualKeyCode::Return => PickupResult::Confirm,
+                VirtualKeyCode::Tab => PickupResult::ToggleNearby,
+                VirtualKeyCode::Comma => {
+                    if chosen.len() == count { chosen.clear(); } else { *chosen = items.to_vec(); }
+                    PickupResult::NoResponse
+                }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < count as i32 {
+                        toggle(chosen, items[selection as usize]);
+                    }
+                    PickupResult::NoResponse
+                }
+            }
+        }
+    }
+}
+
+pub fn bless_item_menu(gs : &mut State, ctx : &mut Input) -> (ItemMenuResult, Option<Entity>) {
+    backpack_menu(gs, ctx, "Place What On The Altar?")
+}
+
+pub fn enchant_item_menu(gs : &mut State, ctx : &mut Input) -> (ItemMenuResult, Option<Entity>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let player_entity = *gs.ecs.fetch::<Entity>();
     let names = gs.ecs.read_storage::<Name>();
     let backpack = gs.ecs.read_storage::<InBackpack>();
+    let equipped = gs.ecs.read_storage::<Equipped>();
+    let equippable = gs.ecs.read_storage::<Equippable>();
+    let artifacts = gs.ecs.read_storage::<Artifact>();
+    let reagents = gs.ecs.read_storage::<Reagent>();
     let entities = gs.ecs.entities();
 
-    let inventory = (&backpack, &names).join().filter(|item| item.0.owner == *player_entity );
-    let count = inventory.count();
+    let items : Vec<Entity> = (&entities, &equippable, &names).join()
+        .filter(|(e, _eq, _n)| artifacts.get(*e).is_none())
+        .filter(|(e, _eq, _n)| backpack.get(*e).map_or(false, |b| b.owner == player_entity) || equipped.get(*e).map_or(false, |w| w.owner == player_entity))
+        .map(|(e, _eq, _n)| e)
+        .collect();
+    let count = items.len();
+    let gold = gs.ecs.read_storage::<Purse>().get(player_entity).map_or(0, |p| p.gold);
+    let dust = (&backpack, &reagents).join().filter(|(b, _r)| b.owner == player_entity).count();
 
     let mut y = (25 - (count / 2)) as i32;
-    ctx.draw_box(15, y-2, 31, (count+3) as i32, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK));
-    ctx.print_color(18, y-2, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Drop Which Item?");
-    ctx.print_color(18, y+count as i32+1, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "ESCAPE to cancel");
-
-    let mut equippable : Vec<Entity> = Vec::new();
-    let mut j = 0;
-    for (entity, _pack, name) in (&entities, &backpack, &names).join().filter(|item| item.1.owner == *player_entity ) {
-        ctx.set(17, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437('('));
-        ctx.set(18, y, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), 97+j as rltk::FontCharType);
-        ctx.set(19, y, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), rltk::to_cp437(')'));
+    let frame = rltk::Rect::with_size(10, y-2, 60, (count+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y-2), &format!("Enchant What? ({} gold, {} reagents)", gold, dust), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y+count as i32+1), "ESCAPE to cancel", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
 
-        ctx.print(21, y, &name.name.to_string());
-        equippable.push(entity);
+    for (j, item) in items.iter().enumerate() {
+        let (cost, reagent_cost, fail_chance) = raws::enchant_cost(&gs.ecs, player_entity, *item);
+        draw_batch.set(Point::new(12, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(13, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(14, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print_color(Point::new(16, y), &names.get(*item).unwrap().name, ColorPair::new(item_name_color(&gs.ecs, *item), palette::named(rltk::BLACK)));
+        draw_batch.print_color(Point::new(48, y), &format!("{}g {}r {}% fail", cost, reagent_cost, fail_chance), ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
         y += 1;
-        j += 1;
     }
+    let click = menu_select(&mut draw_batch, ctx, frame, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
 
+    match click {
+        MenuClick::Row(row) => return (ItemMenuResult::Selected, Some(items[row])),
+        MenuClick::Outside => return (ItemMenuResult::Cancel, None),
+        MenuClick::Nothing => {}
+    }
     match ctx.key {
         None => (ItemMenuResult::NoResponse, None),
         Some(key) => {
@@ -170,7 +821,7 @@ pub fn drop_item_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Opti
                 _ => {
                     let selection = rltk::letter_to_option(key);
                     if selection > -1 && selection < count as i32 {
-                        return (ItemMenuResult::Selected, Some(equippable[selection as usize]));
+                        return (ItemMenuResult::Selected, Some(items[selection as usize]));
                     }
                     (ItemMenuResult::NoResponse, None)
                 }
@@ -179,44 +830,1415 @@ pub fn drop_item_menu(gs : &mut State, ctx : &mut Rltk) -> (ItemMenuResult, Opti
     }
 }
 
-pub fn ranged_target(gs : &mut State, ctx : &mut Rltk, range : i32) -> (ItemMenuResult, Option<Point>) {
-    let player_entity = gs.ecs.fetch::<Entity>();
-    let player_pos = gs.ecs.fetch::<Point>();
-    let viewsheds = gs.ecs.read_storage::<Viewshed>();
+/// Lists the player's damaged gear, carried or equipped, with the blacksmith's price.
+pub fn repair_item_menu(gs : &mut State, ctx : &mut Input) -> (ItemMenuResult, Option<Entity>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let player_entity = *gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let equipped = gs.ecs.read_storage::<Equipped>();
+    let durability = gs.ecs.read_storage::<Durability>();
+    let entities = gs.ecs.entities();
+
+    let items : Vec<Entity> = (&entities, &durability, &names).join()
+        .filter(|(_e, d, _n)| d.current < d.max)
+        .filter(|(e, _d, _n)| backpack.get(*e).map_or(false, |b| b.owner == player_entity) || equipped.get(*e).map_or(false, |w| w.owner == player_entity))
+        .map(|(e, _d, _n)| e)
+        .collect();
+    let count = items.len();
+    let gold = gs.ecs.read_storage::<Purse>().get(player_entity).map_or(0, |p| p.gold);
+
+    let mut y = (25 - (count / 2)) as i32;
+    let frame = rltk::Rect::with_size(10, y-2, 60, (count+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y-2), &format!("Repair What? ({} gold)", gold), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y+count as i32+1), "ESCAPE to cancel", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
 
-    ctx.print_color(5, 0, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Select Target:");
+    for (j, item) in items.iter().enumerate() {
+        let d = durability.get(*item).unwrap();
+        draw_batch.set(Point::new(12, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(13, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(14, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print_color(Point::new(16, y), &item_label(&gs.ecs, *item, &names.get(*item).unwrap().name), ColorPair::new(item_name_color(&gs.ecs, *item), palette::named(rltk::BLACK)));
+        draw_batch.print_color(Point::new(56, y), &format!("{}g", durability::repair_cost(d)), ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+        y += 1;
+    }
+    let click = menu_select(&mut draw_batch, ctx, frame, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
 
-    // Highlight available target cells
-    let mut available_cells = Vec::new();
-    let visible = viewsheds.get(*player_entity);
-    if let Some(visible) = visible {
-        // We have a viewshed
-        for idx in visible.visible_tiles.iter() {
-            let distance = rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, *idx);
-            if distance <= range as f32 {
-                ctx.set_bg(idx.x, idx.y, RGB::named(rltk::BLUE));
-                available_cells.push(idx);
+    match click {
+        MenuClick::Row(row) => return (ItemMenuResult::Selected, Some(items[row])),
+        MenuClick::Outside => return (ItemMenuResult::Cancel, None),
+        MenuClick::Nothing => {}
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < count as i32 {
+                        return (ItemMenuResult::Selected, Some(items[selection as usize]));
+                    }
+                    (ItemMenuResult::NoResponse, None)
+                }
             }
         }
-    } else {
-        return (ItemMenuResult::Cancel, None);
     }
+}
 
-    // Draw mouse cursor
-    let mouse_pos = ctx.mouse_pos();
-    let mut valid_target = false;
-    for idx in available_cells.iter() { if idx.x == mouse_pos.0 && idx.y == mouse_pos.1 { valid_target = true; } }
-    if valid_target {
-        ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::CYAN));
-        if ctx.left_click {
-            return (ItemMenuResult::Selected, Some(Point::new(mouse_pos.0, mouse_pos.1)));
-        }
-    } else {
-        ctx.set_bg(mouse_pos.0, mouse_pos.1, RGB::named(rltk::RED));
-        if ctx.left_click {
-            return (ItemMenuResult::Cancel, None);
+pub fn zap_item_menu(gs : &mut State, ctx : &mut Input) -> (ItemMenuResult, Option<Entity>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let player_entity = *gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let charges = gs.ecs.read_storage::<Charges>();
+    let entities = gs.ecs.entities();
+
+    let wands : Vec<Entity> = (&entities, &backpack, &charges).join()
+        .filter(|(_e, b, _c)| b.owner == player_entity)
+        .map(|(e, _b, _c)| e)
+        .collect();
+    let count = wands.len();
+
+    let mut y = (25 - (count / 2)) as i32;
+    let frame = rltk::Rect::with_size(15, y-2, 31, (count+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y-2), "Zap Which Wand?", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y+count as i32+1), "ESCAPE to cancel", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    for (j, wand) in wands.iter().enumerate() {
+        draw_batch.set(Point::new(17, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(18, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(19, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print_color(Point::new(21, y), &item_label(&gs.ecs, *wand, &names.get(*wand).unwrap().name), ColorPair::new(item_name_color(&gs.ecs, *wand), palette::named(rltk::BLACK)));
+        y += 1;
+    }
+    let click = menu_select(&mut draw_batch, ctx, frame, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match click {
+        MenuClick::Row(row) => return (ItemMenuResult::Selected, Some(wands[row])),
+        MenuClick::Outside => return (ItemMenuResult::Cancel, None),
+        MenuClick::Nothing => {}
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < count as i32 {
+                        return (ItemMenuResult::Selected, Some(wands[selection as usize]));
+                    }
+                    (ItemMenuResult::NoResponse, None)
+                }
+            }
         }
     }
+}
 
-    (ItemMenuResult::NoResponse, None)
+#[derive(PartialEq, Copy, Clone)]
+pub enum VendorMode { Buy, Sell }
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum VendorResult { NoResponse, Cancel, Selected, ToggleMode }
+
+/// Lists the shopkeeper's wares, or what the player could sell them, with prices. Space switches between the two.
+pub fn vendor_menu(gs : &mut State, ctx : &mut Input, vendor : Entity, mode : VendorMode) -> (VendorResult, Option<Entity>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let player_entity = *gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let owner = if mode == VendorMode::Buy { vendor } else { player_entity };
+
+    let items : Vec<Entity> = (&gs.ecs.entities(), &backpack, &names).join()
+        .filter(|(_e, pack, _n)| pack.owner == owner)
+        .map(|(e, _pack, _n)| e)
+        .collect();
+    let count = items.len();
+    let gold = gs.ecs.read_storage::<Purse>().get(player_entity).map_or(0, |p| p.gold);
+    let vendor_name = names.get(vendor).map_or("Shopkeeper".to_string(), |n| n.name.clone());
+    let title = match mode {
+        VendorMode::Buy => format!("Buy from the {} ({} gold)", vendor_name, gold),
+        VendorMode::Sell => format!("Sell to the {} ({} gold)", vendor_name, gold)
+    };
+
+    let mut y = (25 - (count / 2)) as i32;
+    let frame = rltk::Rect::with_size(10, y-2, 60, (count+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y-2), &title, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y+count as i32+1), "SPACE to switch buy/sell, ESCAPE to leave", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    for (j, item) in items.iter().enumerate() {
+        let price = match mode {
+            VendorMode::Buy => vendor::buy_price(&gs.ecs, player_entity, *item),
+            VendorMode::Sell => vendor::sell_price(&gs.ecs, player_entity, *item)
+        };
+        draw_batch.set(Point::new(12, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(13, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(14, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print_color(Point::new(16, y), &item_label(&gs.ecs, *item, &names.get(*item).unwrap().name), ColorPair::new(item_name_color(&gs.ecs, *item), palette::named(rltk::BLACK)));
+        draw_batch.print_color(Point::new(60, y), &format!("{}g", price), ColorPair::new(palette::named(rltk::GOLD), palette::named(rltk::BLACK)));
+        y += 1;
+    }
+    let click = menu_select(&mut draw_batch, ctx, frame, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match click {
+        MenuClick::Row(row) => return (VendorResult::Selected, Some(items[row])),
+        MenuClick::Outside => return (VendorResult::Cancel, None),
+        MenuClick::Nothing => {}
+    }
+    match ctx.key {
+        None => (VendorResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (VendorResult::Cancel, None) }
+                VirtualKeyCode::Space => { (VendorResult::ToggleMode, None) }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < count as i32 {
+                        return (VendorResult::Selected, Some(items[selection as usize]));
+                    }
+                    (VendorResult::NoResponse, None)
+                }
+            }
+        }
+    }
+}
+
+/// Breaks text into lines no wider than `width`, at word boundaries.
+fn wrap_text(text : &str, width : usize) -> Vec<String> {
+    let mut lines : Vec<String> = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        if !line.is_empty() && line.len() + word.len() + 1 > width {
+            lines.push(line);
+            line = String::new();
+        }
+        if !line.is_empty() { line.push(' '); }
+        line.push_str(word);
+    }
+    if !line.is_empty() { lines.push(line); }
+    lines
+}
+
+/// Shows what the townsperson says and the replies the player can give; returns the chosen reply.
+pub fn dialogue_menu(gs : &mut State, ctx : &mut Input, npc : Entity, node : usize) -> (ItemMenuResult, Option<usize>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let (text, responses) = {
+        let dialogue = dialogue::dialogue_of(&gs.ecs, npc).unwrap_or_default();
+        let raws = raws::raws();
+        match raws::dialogue_node(&raws, &dialogue, node) {
+            Some(n) => (n.text.clone(), n.responses.iter().map(|r| r.text.clone()).collect::<Vec<String>>()),
+            None => return (ItemMenuResult::Cancel, None)
+        }
+    };
+    let npc_name = gs.ecs.read_storage::<Name>().get(npc).map_or("Stranger".to_string(), |n| n.name.clone());
+    let lines = wrap_text(&text, 54);
+    let count = responses.len();
+    let height = lines.len() + count + 1;
+
+    let mut y = (25 - (height / 2)) as i32;
+    let frame = rltk::Rect::with_size(10, y-2, 60, (height+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y-2), &npc_name, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y+height as i32+1), "ESCAPE to walk away", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    for line in lines.iter() {
+        draw_batch.print_color(Point::new(12, y), line, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+        y += 1;
+    }
+    y += 1;
+    let first_row = y;
+    for (j, response) in responses.iter().enumerate() {
+        draw_batch.set(Point::new(12, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(13, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(14, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print_color(Point::new(16, y), response, ColorPair::new(palette::named(rltk::CYAN), palette::named(rltk::BLACK)));
+        y += 1;
+    }
+    let cursor = menu_cursor(&mut draw_batch, ctx, frame, first_row, 1, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    if let (Some(row), Some(VirtualKeyCode::Return)) = (cursor, ctx.key) {
+        return (ItemMenuResult::Selected, Some(row));
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < count as i32 {
+                        return (ItemMenuResult::Selected, Some(selection as usize));
+                    }
+                    (ItemMenuResult::NoResponse, None)
+                }
+            }
+        }
+    }
+}
+
+/// Lists the work a townsperson has on offer; picking a quest takes it on.
+pub fn quest_offer_menu(gs : &mut State, ctx : &mut Input, giver : Entity) -> (ItemMenuResult, Option<String>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let offers = quests::offers(&gs.ecs, giver);
+    let count = offers.len();
+    let giver_name = gs.ecs.read_storage::<Name>().get(giver).map_or("Stranger".to_string(), |n| n.name.clone());
+
+    let mut y = (25 - count) as i32;
+    let frame = rltk::Rect::with_size(10, y-2, 60, (count*2+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y-2), &format!("The {} has work for you", giver_name), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y+(count*2) as i32+1), "ESCAPE to decline", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    for (j, (name, description)) in offers.iter().enumerate() {
+        draw_batch.set(Point::new(12, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(13, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(14, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print_color(Point::new(16, y), name, ColorPair::new(palette::named(rltk::CYAN), palette::named(rltk::BLACK)));
+        draw_batch.print_color(Point::new(16, y+1), description, ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+        y += 2;
+    }
+    let cursor = menu_cursor(&mut draw_batch, ctx, frame, frame.y1 + 2, 2, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    if let (Some(row), Some(VirtualKeyCode::Return)) = (cursor, ctx.key) {
+        return (ItemMenuResult::Selected, Some(offers[row].0.clone()));
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < count as i32 {
+                        return (ItemMenuResult::Selected, Some(offers[selection as usize].0.clone()));
+                    }
+                    (ItemMenuResult::NoResponse, None)
+                }
+            }
+        }
+    }
+}
+
+/// Lists the kinds of item in the backpack, to bind one to hotbar `slot`. BACKSPACE empties the
+/// slot, which comes back as selecting nothing.
+pub fn hotkey_menu(gs : &mut State, ctx : &mut Input, slot : usize) -> (ItemMenuResult, Option<Entity>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let entities = gs.ecs.entities();
+
+    let mut items : Vec<(Entity, String)> = Vec::new();
+    for (entity, _pack, name) in (&entities, &backpack, &names).join().filter(|item| item.1.owner == *player_entity) {
+        if !items.iter().any(|(_e, n)| *n == name.name) { items.push((entity, name.name.clone())); }
+    }
+    items.sort_by(|a, b| a.1.cmp(&b.1));
+    let count = items.len();
+
+    let mut y = (25 - (count / 2)) as i32;
+    let frame = rltk::Rect::with_size(15, y-2, 36, (count+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y-2), &format!("Bind to key {}", slot + 1), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y+count as i32+1), "BACKSPACE to clear, ESCAPE", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    for (j, (entity, name)) in items.iter().enumerate() {
+        draw_batch.set(Point::new(17, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(18, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(19, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print_color(Point::new(21, y), name, ColorPair::new(item_name_color(&gs.ecs, *entity), palette::named(rltk::BLACK)));
+        y += 1;
+    }
+    let click = menu_select(&mut draw_batch, ctx, frame, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match click {
+        MenuClick::Row(row) => return (ItemMenuResult::Selected, Some(items[row].0)),
+        MenuClick::Outside => return (ItemMenuResult::Cancel, None),
+        MenuClick::Nothing => {}
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
+                VirtualKeyCode::Back => { (ItemMenuResult::Selected, None) }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < count as i32 {
+                        return (ItemMenuResult::Selected, Some(items[selection as usize].0));
+                    }
+                    (ItemMenuResult::NoResponse, None)
+                }
+            }
+        }
+    }
+}
+
+pub fn remove_item_menu(gs : &mut State, ctx : &mut Input) -> (ItemMenuResult, Option<Entity>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let player_entity = *gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let equipped = gs.ecs.read_storage::<Equipped>();
+
+    let mut items : Vec<(Entity, EquipmentSlot)> = (&gs.ecs.entities(), &equipped).join()
+        .filter(|(_e, w)| w.owner == player_entity)
+        .map(|(e, w)| (e, w.slot))
+        .collect();
+    items.sort_by_key(|(_e, slot)| EquipmentSlot::ALL.iter().position(|s| s == slot));
+    let count = items.len();
+
+    let mut y = (25 - (count / 2)) as i32;
+    let frame = rltk::Rect::with_size(15, y-2, 40, (count+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y-2), "Remove Which Item?", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y+count as i32+1), "ESCAPE to cancel", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    for (j, (item, slot)) in items.iter().enumerate() {
+        draw_batch.set(Point::new(17, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(18, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(19, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print_color(Point::new(21, y), slot.name(), ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+        draw_batch.print_color(Point::new(28, y), &item_label(&gs.ecs, *item, &names.get(*item).unwrap().name), ColorPair::new(item_name_color(&gs.ecs, *item), palette::named(rltk::BLACK)));
+        y += 1;
+    }
+    let click = menu_select(&mut draw_batch, ctx, frame, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match click {
+        MenuClick::Row(row) => return (ItemMenuResult::Selected, Some(items[row].0)),
+        MenuClick::Outside => return (ItemMenuResult::Cancel, None),
+        MenuClick::Nothing => {}
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < count as i32 {
+                        return (ItemMenuResult::Selected, Some(items[selection as usize].0));
+                    }
+                    (ItemMenuResult::NoResponse, None)
+                }
+            }
+        }
+    }
+}
+
+/// Lets the player pick any item from their backpack, under `title`.
+fn backpack_menu(gs : &mut State, ctx : &mut Input, title : &str) -> (ItemMenuResult, Option<Entity>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let player_entity = gs.ecs.fetch::<Entity>();
+    let names = gs.ecs.read_storage::<Name>();
+    let backpack = gs.ecs.read_storage::<InBackpack>();
+    let entities = gs.ecs.entities();
+
+    let inventory = (&backpack, &names).join().filter(|item| item.0.owner == *player_entity );
+    let count = inventory.count();
+
+    let mut y = (25 - (count / 2)) as i32;
+    let frame = rltk::Rect::with_size(15, y-2, 31, (count+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y-2), title, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y+count as i32+1), "ESCAPE to cancel", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    let mut items : Vec<Entity> = Vec::new();
+    let mut j = 0;
+    for (entity, _pack, name) in (&entities, &backpack, &names).join().filter(|item| item.1.owner == *player_entity ) {
+        draw_batch.set(Point::new(17, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(18, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(19, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+
+        draw_batch.print_color(Point::new(21, y), &name.name.to_string(), ColorPair::new(item_name_color(&gs.ecs, entity), palette::named(rltk::BLACK)));
+        items.push(entity);
+        y += 1;
+        j += 1;
+    }
+    let click = menu_select(&mut draw_batch, ctx, frame, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match click {
+        MenuClick::Row(row) => return (ItemMenuResult::Selected, Some(items[row])),
+        MenuClick::Outside => return (ItemMenuResult::Cancel, None),
+        MenuClick::Nothing => {}
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < count as i32 {
+                        return (ItemMenuResult::Selected, Some(items[selection as usize]));
+                    }
+                    (ItemMenuResult::NoResponse, None)
+                }
+            }
+        }
+    }
+}
+
+pub fn drop_item_menu(gs : &mut State, ctx : &mut Input) -> (ItemMenuResult, Option<Entity>) {
+    backpack_menu(gs, ctx, "Drop Which Item?")
+}
+
+pub fn throw_item_menu(gs : &mut State, ctx : &mut Input) -> (ItemMenuResult, Option<Entity>) {
+    backpack_menu(gs, ctx, "Throw Which Item?")
+}
+
+/// Where the targeting cursor sits while aiming, and who was aimed at last time.
+#[derive(Default)]
+pub struct Targeting {
+    pub cursor : Option<Point>,
+    pub last_target : Option<Entity>,
+    pub last_mouse : (i32, i32)
+}
+
+/// Visible monsters within `range`, nearest first.
+fn targets_in_range(ecs : &World, range : i32) -> Vec<(Entity, Point)> {
+    let player_entity = ecs.fetch::<Entity>();
+    let player_pos = ecs.fetch::<Point>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    let entities = ecs.entities();
+    let monsters = ecs.read_storage::<Monster>();
+    let positions = ecs.read_storage::<Position>();
+    let walkers = ecs.read_storage::<MovesThroughWalls>();
+    let map = ecs.fetch::<Map>();
+
+    let visible = match viewsheds.get(*player_entity) { Some(v) => v, None => return Vec::new() };
+    let mut targets : Vec<(Entity, Point, f32)> = (&entities, &monsters, &positions).join()
+        .filter(|(entity, _monster, pos)| !ethereal::in_wall(&map, &walkers, *entity, pos.x, pos.y))
+        .map(|(entity, _monster, pos)| (entity, Point::new(pos.x, pos.y)))
+        .filter(|(_entity, pt)| visible.visible_tiles.contains(pt))
+        .map(|(entity, pt)| (entity, pt, rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, pt)))
+        .filter(|(_entity, _pt, distance)| *distance <= range as f32)
+        .collect();
+    targets.sort_by(|a, b| a.2.partial_cmp(&b.2).expect("Distances are never NaN"));
+    targets.into_iter().map(|(entity, pt, _distance)| (entity, pt)).collect()
+}
+
+/// Picks a tile within `range` of the player. The cursor starts on the last creature aimed at, or
+/// the nearest one, and Tab steps through the rest; the mouse moves it too. Tiles that can't be
+/// reached are refused with a reason rather than accepted or cancelled.
+pub fn ranged_target(gs : &mut State, ctx : &mut Input, range : i32) -> (ItemMenuResult, Option<Point>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let player_entity = *gs.ecs.fetch::<Entity>();
+    let player_pos = *gs.ecs.fetch::<Point>();
+    let targets = targets_in_range(&gs.ecs, range);
+    let visible_tiles = match gs.ecs.read_storage::<Viewshed>().get(player_entity) {
+        Some(visible) => visible.visible_tiles.clone(),
+        None => return (ItemMenuResult::Cancel, None)
+    };
+
+    draw_batch.print_color(Point::new(5, 0), "Select Target: TAB to cycle, ENTER to fire, ESCAPE to cancel", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    let mouse_pos = ctx.mouse_pos();
+    let mut cursor = {
+        let mut targeting = gs.ecs.write_resource::<Targeting>();
+        let cursor = match targeting.cursor {
+            Some(_cursor) if targeting.last_mouse != mouse_pos => camera::screen_to_map(&gs.ecs, mouse_pos.0, mouse_pos.1),
+            Some(cursor) => cursor,
+            None => {
+                let remembered = targeting.last_target.and_then(|last| targets.iter().find(|(entity, _pt)| *entity == last));
+                remembered.or_else(|| targets.first()).map_or(player_pos, |(_entity, pt)| *pt)
+            }
+        };
+        targeting.last_mouse = mouse_pos;
+        cursor
+    };
+
+    if ctx.key == Some(VirtualKeyCode::Tab) && !targets.is_empty() {
+        let next = match targets.iter().position(|(_entity, pt)| *pt == cursor) {
+            Some(current) => (current + 1) % targets.len(),
+            None => 0
+        };
+        cursor = targets[next].1;
+    }
+    gs.ecs.write_resource::<Targeting>().cursor = Some(cursor);
+
+    // Highlight available target cells, then the path a projectile would take
+    let in_range = |pt : &Point| rltk::DistanceAlg::Pythagoras.distance2d(player_pos, *pt) <= range as f32;
+    let mut highlights = Vec::new();
+    for idx in visible_tiles.iter().filter(|pt| in_range(pt)) {
+        highlights.push((camera::map_to_screen(&gs.ecs, idx.x, idx.y), palette::named(rltk::BLUE)));
+    }
+    let valid_target = visible_tiles.contains(&cursor) && in_range(&cursor);
+    for step in rltk::line2d(rltk::LineAlg::Bresenham, player_pos, cursor).iter().skip(1) {
+        highlights.push((camera::map_to_screen(&gs.ecs, step.x, step.y), palette::named(rltk::DARK_CYAN)));
+    }
+    let cursor_color = if valid_target { palette::named(rltk::CYAN) } else { palette::named(rltk::RED) };
+    highlights.push((camera::map_to_screen(&gs.ecs, cursor.x, cursor.y), cursor_color));
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+    camera::highlight(&gs.ecs, &highlights, MENU_LAYER);
+
+    let fire = ctx.left_click || matches!(ctx.key, Some(VirtualKeyCode::Return) | Some(VirtualKeyCode::Space));
+    if ctx.key == Some(VirtualKeyCode::Escape) {
+        gs.ecs.write_resource::<Targeting>().cursor = None;
+        return (ItemMenuResult::Cancel, None);
+    }
+    if fire {
+        if !visible_tiles.contains(&cursor) {
+            gs.ecs.write_resource::<GameLog>().push(LogCategory::System, "You can't see that spot.".to_string());
+            return (ItemMenuResult::NoResponse, None);
+        }
+        if !in_range(&cursor) {
+            gs.ecs.write_resource::<GameLog>().push(LogCategory::System, "That is out of range.".to_string());
+            return (ItemMenuResult::NoResponse, None);
+        }
+        let mut targeting = gs.ecs.write_resource::<Targeting>();
+        targeting.cursor = None;
+        if let Some((entity, _pt)) = targets.iter().find(|(_entity, pt)| *pt == cursor) {
+            targeting.last_target = Some(*entity);
+        }
+        return (ItemMenuResult::Selected, Some(cursor));
+    }
+    (ItemMenuResult::NoResponse, None)
+}
+
+/// Lets the player tell their companions what to do.
+pub fn companion_menu(ctx : &mut Input) -> (ItemMenuResult, Option<CompanionCommand>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let commands = [(CompanionCommand::Follow, "Follow me"), (CompanionCommand::Stay, "Stay here"), (CompanionCommand::Attack, "Attack a target")];
+
+    let mut y = 25 - (commands.len() / 2) as i32;
+    let frame = rltk::Rect::with_size(15, y-2, 31, (commands.len()+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y-2), "Command Companions", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y+commands.len() as i32+1), "ESCAPE to cancel", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    for (j, (_command, label)) in commands.iter().enumerate() {
+        draw_batch.set(Point::new(17, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(18, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(19, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print(Point::new(21, y), label);
+        y += 1;
+    }
+    let click = menu_select(&mut draw_batch, ctx, frame, commands.len());
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match click {
+        MenuClick::Row(row) => return (ItemMenuResult::Selected, Some(commands[row].0)),
+        MenuClick::Outside => return (ItemMenuResult::Cancel, None),
+        MenuClick::Nothing => {}
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < commands.len() as i32 {
+                        return (ItemMenuResult::Selected, Some(commands[selection as usize].0));
+                    }
+                    (ItemMenuResult::NoResponse, None)
+                }
+            }
+        }
+    }
+}
+
+/// Has to be answered; there's no backing out of a level-up.
+pub fn level_up_menu(ecs : &World, ctx : &mut Input) -> (ItemMenuResult, Option<LevelUpChoice>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let choices = level_up::available_choices(ecs);
+
+    let mut y = 25 - (choices.len() / 2) as i32;
+    let frame = rltk::Rect::with_size(15, y-2, 50, (choices.len()+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y-2), "Level Up! Choose an Improvement", ColorPair::new(palette::named(rltk::GOLD), palette::named(rltk::BLACK)));
+
+    for (j, choice) in choices.iter().enumerate() {
+        draw_batch.set(Point::new(17, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(18, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(19, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print(Point::new(21, y), choice.name());
+        draw_batch.print_color(Point::new(38, y), choice.description(), ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+        y += 1;
+    }
+    let click = menu_select(&mut draw_batch, ctx, frame, choices.len());
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    if let MenuClick::Row(row) = click {
+        return (ItemMenuResult::Selected, Some(choices[row]));
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            let selection = rltk::letter_to_option(key);
+            if selection > -1 && selection < choices.len() as i32 {
+                return (ItemMenuResult::Selected, Some(choices[selection as usize]));
+            }
+            (ItemMenuResult::NoResponse, None)
+        }
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum CreationStep { Name, Class, Deity, Difficulty, Mode }
+
+/// The character being put together on the creation screens.
+#[derive(Default)]
+pub struct CharacterDraft {
+    pub name : String,
+    pub class : String,
+    pub deity : Option<Deity>,
+    pub difficulty : Difficulty,
+    /// Names and blurbs of the classes on offer, read from the raws when the class screen opens.
+    pub classes : Vec<(String, String)>
+}
+
+const MAX_NAME_LENGTH : usize = 20;
+
+pub fn name_entry(name : &mut String, ctx : &mut Input) -> ItemMenuResult {
+    text_entry("What is your name?", name, ctx)
+}
+
+/// The player's name for the tile they're standing on, when marking it.
+#[derive(Default)]
+pub struct MarkerDraft {
+    pub name : String
+}
+
+pub fn marker_entry(name : &mut String, ctx : &mut Input) -> ItemMenuResult {
+    text_entry("Name this spot:", name, ctx)
+}
+
+/// Asks for a short capitalised name of letters and spaces.
+fn text_entry(question : &str, name : &mut String, ctx : &mut Input) -> ItemMenuResult {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    draw_batch.draw_box(rltk::Rect::with_size(15, 20, 40, 5), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, 20), question, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(17, 22), &format!("{}_", name), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, 25), "ENTER to accept, ESCAPE to cancel", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match ctx.key {
+        None => ItemMenuResult::NoResponse,
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => ItemMenuResult::Cancel,
+                VirtualKeyCode::Return if !name.trim().is_empty() => {
+                    *name = name.trim().to_string();
+                    ItemMenuResult::Selected
+                }
+                VirtualKeyCode::Back => {
+                    name.pop();
+                    ItemMenuResult::NoResponse
+                }
+                VirtualKeyCode::Space if !name.is_empty() && name.len() < MAX_NAME_LENGTH => {
+                    name.push(' ');
+                    ItemMenuResult::NoResponse
+                }
+                _ => {
+                    let letter = rltk::letter_to_option(key);
+                    if letter > -1 && name.len() < MAX_NAME_LENGTH {
+                        let c = (b'a' + letter as u8) as char;
+                        name.push(if ctx.shift || name.is_empty() { c.to_ascii_uppercase() } else { c });
+                    }
+                    ItemMenuResult::NoResponse
+                }
+            }
+        }
+    }
+}
+
+/// The marked tiles on this level, to pick one to travel back to.
+pub fn marker_menu(ecs : &World, ctx : &mut Input) -> (ItemMenuResult, Option<usize>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let map = ecs.fetch::<Map>();
+    let mut markers : Vec<(&usize, &String)> = map.markers.iter().collect();
+    markers.sort_by(|a, b| a.1.cmp(b.1));
+    let count = markers.len();
+
+    let mut y = (25 - (count / 2)) as i32;
+    let frame = rltk::Rect::with_size(15, y-2, 31, (count+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y-2), "Travel to", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y+count as i32+1), "ESCAPE to cancel", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    for (j, (_idx, name)) in markers.iter().enumerate() {
+        draw_batch.set(Point::new(17, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(18, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(19, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print(Point::new(21, y), name);
+        y += 1;
+    }
+    let click = menu_select(&mut draw_batch, ctx, frame, count);
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match click {
+        MenuClick::Row(row) => return (ItemMenuResult::Selected, Some(*markers[row].0)),
+        MenuClick::Outside => return (ItemMenuResult::Cancel, None),
+        MenuClick::Nothing => {}
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(VirtualKeyCode::Escape) => (ItemMenuResult::Cancel, None),
+        Some(key) => {
+            let selection = rltk::letter_to_option(key);
+            if selection > -1 && selection < count as i32 {
+                return (ItemMenuResult::Selected, Some(*markers[selection as usize].0));
+            }
+            (ItemMenuResult::NoResponse, None)
+        }
+    }
+}
+
+/// The wizard mode command line. Takes lower case words, numbers and spaces.
+pub fn wizard_console(input : &mut String, ctx : &mut Input) -> ItemMenuResult {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    draw_batch.draw_box(rltk::Rect::with_size(5, 1, 70, 4), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(8, 1), "Wizard Console", ColorPair::new(palette::named(rltk::MAGENTA), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(7, 3), &format!("> {}_", input), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(8, 5), "ENTER to run, ESCAPE to close", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match ctx.key {
+        None => ItemMenuResult::NoResponse,
+        Some(key) => {
+            let digit = match key {
+                VirtualKeyCode::Key0 | VirtualKeyCode::Numpad0 => Some('0'),
+                VirtualKeyCode::Key1 | VirtualKeyCode::Numpad1 => Some('1'),
+                VirtualKeyCode::Key2 | VirtualKeyCode::Numpad2 => Some('2'),
+                VirtualKeyCode::Key3 | VirtualKeyCode::Numpad3 => Some('3'),
+                VirtualKeyCode::Key4 | VirtualKeyCode::Numpad4 => Some('4'),
+                VirtualKeyCode::Key5 | VirtualKeyCode::Numpad5 => Some('5'),
+                VirtualKeyCode::Key6 | VirtualKeyCode::Numpad6 => Some('6'),
+                VirtualKeyCode::Key7 | VirtualKeyCode::Numpad7 => Some('7'),
+                VirtualKeyCode::Key8 | VirtualKeyCode::Numpad8 => Some('8'),
+                VirtualKeyCode::Key9 | VirtualKeyCode::Numpad9 => Some('9'),
+                VirtualKeyCode::Minus => Some('-'),
+                _ => None
+            };
+            match key {
+                VirtualKeyCode::Escape => ItemMenuResult::Cancel,
+                VirtualKeyCode::Return if !input.trim().is_empty() => ItemMenuResult::Selected,
+                VirtualKeyCode::Back => {
+                    input.pop();
+                    ItemMenuResult::NoResponse
+                }
+                VirtualKeyCode::Space => {
+                    input.push(' ');
+                    ItemMenuResult::NoResponse
+                }
+                _ => {
+                    if let Some(digit) = digit {
+                        input.push(digit);
+                    } else {
+                        let letter = rltk::letter_to_option(key);
+                        if letter > -1 {
+                            input.push((b'a' + letter as u8) as char);
+                        }
+                    }
+                    ItemMenuResult::NoResponse
+                }
+            }
+        }
+    }
+}
+
+/// Settings that outlast a single run. Each choice flips between its values.
+pub fn options_menu(ecs : &World, ctx : &mut Input) -> (ItemMenuResult, Option<OptionChoice>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let options = ecs.fetch::<Options>();
+    let profile = ecs.fetch::<Profile>();
+    let choices = OptionChoice::all();
+
+    let mut y = 22;
+    let frame = rltk::Rect::with_size(15, y-2, 31, (choices.len()+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y-2), "Options", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(18, y+choices.len() as i32+1), "ESCAPE to go back", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    for (j, choice) in choices.iter().enumerate() {
+        draw_batch.set(Point::new(17, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(18, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(19, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print(Point::new(21, y), &choice.label(&options, &profile));
+        y += 1;
+    }
+    let click = menu_select(&mut draw_batch, ctx, frame, choices.len());
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match click {
+        MenuClick::Row(row) => return (ItemMenuResult::Selected, Some(choices[row])),
+        MenuClick::Outside => return (ItemMenuResult::Cancel, None),
+        MenuClick::Nothing => {}
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(VirtualKeyCode::Escape) => (ItemMenuResult::Cancel, None),
+        Some(key) => {
+            let selection = rltk::letter_to_option(key);
+            if selection > -1 && selection < choices.len() as i32 {
+                return (ItemMenuResult::Selected, Some(choices[selection as usize]));
+            }
+            (ItemMenuResult::NoResponse, None)
+        }
+    }
+}
+
+/// Lists every achievement, lit up if the player has unlocked it on any run.
+pub fn achievements_screen(ecs : &World, ctx : &mut Input) -> ItemMenuResult {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let profile = ecs.fetch::<Profile>();
+
+    let mut y = 20;
+    draw_batch.draw_box(rltk::Rect::with_size(10, y-2, 60, (ACHIEVEMENTS.len()*2+3) as i32), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y-2), &format!("Achievements ({}/{})", profile.unlocked.len(), ACHIEVEMENTS.len()), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y+(ACHIEVEMENTS.len()*2) as i32+1), "ESCAPE to go back", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    for achievement in ACHIEVEMENTS.iter() {
+        let (mark, color) = if profile.is_unlocked(achievement.name) { ('*', palette::named(rltk::GOLD)) } else { ('-', palette::named(rltk::GREY)) };
+        draw_batch.set(Point::new(12, y), ColorPair::new(color, palette::named(rltk::BLACK)), rltk::to_cp437(mark));
+        draw_batch.print_color(Point::new(14, y), achievement.name, ColorPair::new(color, palette::named(rltk::BLACK)));
+        draw_batch.print_color(Point::new(16, y+1), achievement.description, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+        y += 2;
+    }
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match ctx.key {
+        Some(VirtualKeyCode::Escape) => ItemMenuResult::Cancel,
+        _ => ItemMenuResult::NoResponse
+    }
+}
+
+/// The best runs so far, ordered by whichever column the player picks with TAB; D switches between
+/// ordinary runs and today's daily challenge. Returns what to show next time.
+pub fn high_scores(ecs : &World, ctx : &mut Input, view : ScoreView) -> (ItemMenuResult, ScoreView) {
+    const SHOWN : usize = 20;
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let table = ecs.fetch::<ScoreTable>();
+    let today = Challenge::today().and_then(|c| c.date);
+    let daily = if view.daily { today.as_deref() } else { None };
+    let scores = table.sorted(view.sort, daily);
+
+    let mut y = 12;
+    let title = match daily {
+        Some(date) => format!("Daily Challenge {}, by {}", date, view.sort.name()),
+        None => format!("High Scores, by {}", view.sort.name())
+    };
+    let help = if today.is_some() { "TAB to change the order, D for the daily challenge, ESCAPE to go back" } else { "TAB to change the order, ESCAPE to go back" };
+    draw_batch.draw_box(rltk::Rect::with_size(4, y-2, 72, (SHOWN+4) as i32), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(7, y-2), &title, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(7, y+SHOWN as i32+2), help, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(6, y), &format!("{:<3} {:<20} {:>6} {:>5} {:>5} {:>6} {:>6}", "#", "Name", "Score", "Depth", "Kills", "Gold", "Turns"), ColorPair::new(palette::named(rltk::CYAN), palette::named(rltk::BLACK)));
+    y += 1;
+    if scores.is_empty() {
+        draw_batch.print_color(Point::new(6, y), "No runs have been recorded yet.", ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+    }
+    if scores.iter().take(SHOWN).any(|score| score.forgiving) {
+        draw_batch.print_color(Point::new(6, y+SHOWN as i32), "* played in forgiving mode", ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+    }
+    for (rank, score) in scores.iter().take(SHOWN).enumerate() {
+        let color = if score.victory { palette::named(rltk::GOLD) } else { palette::named(rltk::WHITE) };
+        let mut name : String = score.name.chars().take(if score.forgiving { 19 } else { 20 }).collect();
+        if score.forgiving { name.push('*'); }
+        draw_batch.print_color(Point::new(6, y), &format!("{:<3} {:<20} {:>6} {:>5} {:>5} {:>6} {:>6}", rank + 1, name, score.points(), score.depth, score.kills, score.gold, score.turns), ColorPair::new(color, palette::named(rltk::BLACK)));
+        if score.victory {
+            draw_batch.print_color(Point::new(66, y), "Escaped", ColorPair::new(color, palette::named(rltk::BLACK)));
+        }
+        y += 1;
+    }
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match ctx.key {
+        Some(VirtualKeyCode::Escape) => (ItemMenuResult::Cancel, view),
+        Some(VirtualKeyCode::Tab) => (ItemMenuResult::Selected, ScoreView{ sort: view.sort.next(), ..view }),
+        Some(VirtualKeyCode::D) if today.is_some() => (ItemMenuResult::Selected, ScoreView{ daily: !view.daily, ..view }),
+        _ => (ItemMenuResult::NoResponse, view)
+    }
+}
+
+pub fn class_menu(classes : &[(String, String)], ctx : &mut Input) -> (ItemMenuResult, Option<String>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+
+    let mut y = 20;
+    let frame = rltk::Rect::with_size(10, y-2, 60, (classes.len()*2+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y-2), "Choose Your Class", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y+(classes.len()*2) as i32+1), "ESCAPE to go back", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    for (j, (name, description)) in classes.iter().enumerate() {
+        draw_batch.set(Point::new(12, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(13, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(14, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print(Point::new(16, y), name);
+        draw_batch.print_color(Point::new(18, y+1), description, ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+        y += 2;
+    }
+    let cursor = menu_cursor(&mut draw_batch, ctx, frame, frame.y1 + 2, 2, classes.len());
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    if let (Some(row), Some(VirtualKeyCode::Return)) = (cursor, ctx.key) {
+        return (ItemMenuResult::Selected, Some(classes[row].0.clone()));
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < classes.len() as i32 {
+                        return (ItemMenuResult::Selected, Some(classes[selection as usize].0.clone()));
+                    }
+                    (ItemMenuResult::NoResponse, None)
+                }
+            }
+        }
+    }
+}
+
+/// Picks a patron god at the start of a new game; `None` with `Selected` means no god at all.
+pub fn deity_menu(ctx : &mut Input) -> (ItemMenuResult, Option<Deity>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let choices = [Some(Deity::Tyrus), Some(Deity::Mirel), None];
+
+    let mut y = 20;
+    let frame = rltk::Rect::with_size(10, y-2, 60, (choices.len()*2+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y-2), "Choose Your God", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y+(choices.len()*2) as i32+1), "ESCAPE to go back", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    for (j, deity) in choices.iter().enumerate() {
+        draw_batch.set(Point::new(12, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(13, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(14, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        match deity {
+            Some(deity) => {
+                draw_batch.print(Point::new(16, y), deity.title());
+                draw_batch.print_color(Point::new(18, y+1), deity.description(), ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+            }
+            None => {
+                draw_batch.print(Point::new(16, y), "No god");
+                draw_batch.print_color(Point::new(18, y+1), "Walk the dungeon alone.", ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+            }
+        }
+        y += 2;
+    }
+    let cursor = menu_cursor(&mut draw_batch, ctx, frame, frame.y1 + 2, 2, choices.len());
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    if let (Some(row), Some(VirtualKeyCode::Return)) = (cursor, ctx.key) {
+        return (ItemMenuResult::Selected, choices[row]);
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < choices.len() as i32 {
+                        return (ItemMenuResult::Selected, choices[selection as usize]);
+                    }
+                    (ItemMenuResult::NoResponse, None)
+                }
+            }
+        }
+    }
+}
+
+pub fn difficulty_menu(ctx : &mut Input) -> (ItemMenuResult, Option<Difficulty>) {
+    let choices : Vec<(&str, &str)> = Difficulty::ALL.iter().map(|d| (d.title(), d.description())).collect();
+    let (result, selection) = described_menu("Choose Your Difficulty", &choices, ctx);
+    (result, selection.map(|i| Difficulty::ALL[i]))
+}
+
+pub fn mode_menu(ctx : &mut Input) -> (ItemMenuResult, Option<RunMode>) {
+    let choices : Vec<(&str, &str)> = RunMode::ALL.iter().map(|m| (m.title(), m.description())).collect();
+    let (result, selection) = described_menu("Choose Your Mode", &choices, ctx);
+    (result, selection.map(|i| RunMode::ALL[i]))
+}
+
+/// A lettered list of choices, each with a line describing it underneath.
+fn described_menu(title : &str, choices : &[(&str, &str)], ctx : &mut Input) -> (ItemMenuResult, Option<usize>) {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+
+    let mut y = 20;
+    let frame = rltk::Rect::with_size(10, y-2, 70, (choices.len()*2+3) as i32);
+    draw_batch.draw_box(frame, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y-2), title, ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(13, y+(choices.len()*2) as i32+1), "ESCAPE to go back", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+
+    for (j, (name, description)) in choices.iter().enumerate() {
+        draw_batch.set(Point::new(12, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437('('));
+        draw_batch.set(Point::new(13, y), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), 97+j as rltk::FontCharType);
+        draw_batch.set(Point::new(14, y), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)), rltk::to_cp437(')'));
+        draw_batch.print(Point::new(16, y), name);
+        draw_batch.print_color(Point::new(18, y+1), description, ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+        y += 2;
+    }
+    let cursor = menu_cursor(&mut draw_batch, ctx, frame, frame.y1 + 2, 2, choices.len());
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    if let (Some(row), Some(VirtualKeyCode::Return)) = (cursor, ctx.key) {
+        return (ItemMenuResult::Selected, Some(row));
+    }
+    match ctx.key {
+        None => (ItemMenuResult::NoResponse, None),
+        Some(key) => {
+            match key {
+                VirtualKeyCode::Escape => { (ItemMenuResult::Cancel, None) }
+                _ => {
+                    let selection = rltk::letter_to_option(key);
+                    if selection > -1 && selection < choices.len() as i32 {
+                        return (ItemMenuResult::Selected, Some(selection as usize));
+                    }
+                    (ItemMenuResult::NoResponse, None)
+                }
+            }
+        }
+    }
+}
+
+pub fn character_sheet(ecs : &World, ctx : &mut Input) -> ItemMenuResult {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let player_entity = ecs.fetch::<Entity>();
+    let white = palette::named(rltk::WHITE);
+    let black = palette::named(rltk::BLACK);
+
+    draw_batch.draw_box(rltk::Rect::with_size(15, 6, 48, 36), ColorPair::new(white, black));
+    let name = ecs.read_storage::<Name>().get(*player_entity).map_or("Character".to_string(), |n| n.name.clone());
+    draw_batch.print_color(Point::new(18, 6), &name, ColorPair::new(palette::named(rltk::YELLOW), black));
+    draw_batch.print_color(Point::new(18, 42), "ESCAPE to close", ColorPair::new(palette::named(rltk::YELLOW), black));
+
+    let mut y = 8;
+    if let Some(exp) = ecs.read_storage::<Experience>().get(*player_entity) {
+        draw_batch.print_color(Point::new(17, y), &format!("Level {}  (XP {}/{})", exp.level, exp.xp, xp_for_level(exp.level)), ColorPair::new(white, black));
+        y += 1;
+    }
+    if let Some(stats) = ecs.read_storage::<CombatStats>().get(*player_entity) {
+        draw_batch.print_color(Point::new(17, y), &format!("HP {}/{}  Power {}  Defense {}", stats.hp, stats.max_hp, stats.power, stats.defense), ColorPair::new(white, black));
+        y += 1;
+    }
+    if let Some(purse) = ecs.read_storage::<Purse>().get(*player_entity) {
+        draw_batch.print_color(Point::new(17, y), &format!("Gold {}", purse.gold), ColorPair::new(palette::named(rltk::GOLD), black));
+        y += 1;
+    }
+    if let Some(attr) = ecs.read_storage::<Attributes>().get(*player_entity) {
+        y += 1;
+        draw_batch.print_color(Point::new(17, y), &format!("Might        {}", attr.might), ColorPair::new(white, black));
+        draw_batch.print_color(Point::new(17, y+1), &format!("Fitness      {}", attr.fitness), ColorPair::new(white, black));
+        draw_batch.print_color(Point::new(17, y+2), &format!("Quickness    {}", attr.quickness), ColorPair::new(white, black));
+        draw_batch.print_color(Point::new(17, y+3), &format!("Intelligence {}", attr.intelligence), ColorPair::new(white, black));
+        y += 4;
+    }
+    if let Some(skills) = ecs.read_storage::<Skills>().get(*player_entity) {
+        // Skills that improve with use show how far along they are towards the next rank
+        let training = ecs.read_storage::<SkillTraining>();
+        let trained : Vec<String> = Skill::ALL.iter().map(|skill| {
+            let rank = skills.rank(*skill);
+            let xp = training.get(*player_entity).map_or(0, |t| t.xp(*skill));
+            format!("{} {} ({}/{})", skill.name(), rank, xp, skill_training::xp_to_next(rank))
+        }).collect();
+        draw_batch.print_color(Point::new(17, y), &format!("Skills: Melee {}  {}", skills.melee, trained[..2].join("  ")), ColorPair::new(white, black));
+        draw_batch.print_color(Point::new(25, y+1), &trained[2..].join("  "), ColorPair::new(white, black));
+        y += 2;
+    }
+    let perks : Vec<&str> = [
+        (ecs.read_storage::<KeenEye>().get(*player_entity).is_some(), "Keen Eye"),
+        (ecs.read_storage::<FastHealing>().get(*player_entity).is_some(), "Fast Healing"),
+        (ecs.read_storage::<FarSight>().get(*player_entity).is_some(), "Far Sight")
+    ].iter().filter(|p| p.0).map(|p| p.1).collect();
+    if !perks.is_empty() {
+        draw_batch.print_color(Point::new(17, y), &format!("Perks: {}", perks.join(", ")), ColorPair::new(white, black));
+        y += 1;
+    }
+    let names = ecs.read_storage::<Name>();
+    let equipped = ecs.read_storage::<Equipped>();
+    y += 1;
+    for slot in EquipmentSlot::ALL.iter() {
+        draw_batch.print_color(Point::new(17, y), slot.name(), ColorPair::new(palette::named(rltk::GREY), black));
+        let worn = (&ecs.entities(), &equipped).join().find(|(_e, w)| w.owner == *player_entity && w.slot == *slot);
+        match worn.and_then(|(item, _w)| names.get(item).map(|n| (item, n))) {
+            Some((item, name)) => draw_batch.print_color(Point::new(25, y), &item_label(ecs, item, &name.name), ColorPair::new(item_name_color(ecs, item), black)),
+            None => draw_batch.print_color(Point::new(25, y), "-", ColorPair::new(palette::named(rltk::GREY), black))
+        };
+        y += 1;
+    }
+
+    y += 1;
+    match ecs.read_storage::<Faith>().get(*player_entity) {
+        Some(faith) => {
+            draw_batch.print_color(Point::new(17, y), &format!("Follower of {}", faith.deity.title()), ColorPair::new(palette::named(rltk::GOLD), black));
+            draw_batch.print_color(Point::new(17, y+1), &format!("Piety: {} ({})", faith.piety, piety_rating(faith.piety)), ColorPair::new(white, black));
+        }
+        None => { draw_batch.print_color(Point::new(17, y), "You follow no god.", ColorPair::new(palette::named(rltk::GREY), black)); }
+    }
+    y += 1;
+
+    if let Some(afflicted) = ecs.read_storage::<Afflictions>().get(*player_entity) {
+        y += 2;
+        draw_batch.print_color(Point::new(17, y), "Afflictions:", ColorPair::new(palette::named(rltk::GREY), black));
+        for affliction in afflicted.list.iter() {
+            y += 1;
+            let drained = match (affliction.kind, affliction.drained) {
+                (_, 0) => String::new(),
+                (AfflictionKind::Disease, amount) => format!(" (-{} Fitness)", amount),
+                (AfflictionKind::Curse, amount) => format!(" (-{} Might)", amount)
+            };
+            draw_batch.print_color(Point::new(19, y), &format!("{}{}", affliction.kind.name(), drained), ColorPair::new(palette::named(rltk::RED), black));
+        }
+    }
+    if let Some(mutated) = ecs.read_storage::<Mutations>().get(*player_entity) {
+        y += 2;
+        draw_batch.print_color(Point::new(17, y), "Mutations:", ColorPair::new(palette::named(rltk::GREY), black));
+        for mutation in mutated.list.iter() {
+            y += 1;
+            let color = if mutation.beneficial() { rltk::GREEN } else { rltk::RED };
+            draw_batch.print_color(Point::new(19, y), &format!("{} ({})", mutation.name(), mutation.description()), ColorPair::new(palette::named(color), black));
+        }
+    }
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match ctx.key {
+        Some(VirtualKeyCode::Escape) => ItemMenuResult::Cancel,
+        _ => ItemMenuResult::NoResponse
+    }
+}
+
+/// The quest journal: what the player is working on, and what they have finished.
+pub fn journal(ecs : &World, ctx : &mut Input) -> ItemMenuResult {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let white = palette::named(rltk::WHITE);
+    let black = palette::named(rltk::BLACK);
+    let log = ecs.fetch::<QuestLog>();
+
+    draw_batch.draw_box(rltk::Rect::with_size(15, 6, 48, 36), ColorPair::new(white, black));
+    draw_batch.print_color(Point::new(18, 6), "Journal", ColorPair::new(palette::named(rltk::YELLOW), black));
+    draw_batch.print_color(Point::new(18, 42), "ESCAPE to close", ColorPair::new(palette::named(rltk::YELLOW), black));
+
+    if log.quests.is_empty() {
+        draw_batch.print_color(Point::new(17, 8), "You have taken on no quests.", ColorPair::new(palette::named(rltk::GREY), black));
+    }
+    let mut y = 8;
+    for quest in log.quests.iter().filter(|q| !q.turned_in).chain(log.quests.iter().filter(|q| q.turned_in)) {
+        if y > 40 { break; }
+        let (status, color) = if quest.turned_in {
+            ("done".to_string(), palette::named(rltk::GREY))
+        } else if quest.is_complete() {
+            (format!("return to the {}", quest.giver), palette::named(rltk::GOLD))
+        } else {
+            (format!("{}/{}", quest.progress, quest.needed), white)
+        };
+        draw_batch.print_color(Point::new(17, y), &format!("{} ({})", quest.name, status), ColorPair::new(color, black));
+        if !quest.turned_in {
+            let verb = if quest.kind == quests::QuestKind::Kill { "Slay" } else { "Bring" };
+            draw_batch.print_color(Point::new(19, y+1), &format!("{} {} {} for the {}", verb, quest.needed, quest.target, quest.giver), ColorPair::new(palette::named(rltk::GREY), black));
+            y += 1;
+        }
+        y += 1;
+    }
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match ctx.key {
+        Some(VirtualKeyCode::Escape) => ItemMenuResult::Cancel,
+        _ => ItemMenuResult::NoResponse
+    }
+}
+
+/// The whole message log, newest at the bottom, showing only the categories the filter lets through.
+pub fn log_viewer(ecs : &World, ctx : &mut Input, filter : LogFilter) -> (ItemMenuResult, LogFilter) {
+    const SHOWN : usize = 34;
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let white = palette::named(rltk::WHITE);
+    let black = palette::named(rltk::BLACK);
+    let log = ecs.fetch::<GameLog>();
+
+    draw_batch.draw_box(rltk::Rect::with_size(2, 4, 76, (SHOWN+3) as i32), ColorPair::new(white, black));
+    draw_batch.print_color(Point::new(5, 4), "Message Log", ColorPair::new(palette::named(rltk::YELLOW), black));
+    let mut x = 19;
+    for (key, name, shown) in [("C", "combat", filter.combat), ("I", "items", filter.items), ("S", "system", filter.system)] {
+        let color = if shown { palette::named(rltk::GREEN) } else { palette::named(rltk::GREY) };
+        let label = format!("({}) {}", key, name);
+        draw_batch.print_color(Point::new(x, 4), &label, ColorPair::new(color, black));
+        x += label.len() as i32 + 2;
+    }
+    draw_batch.print_color(Point::new(5, 7 + SHOWN as i32), "C, I, S to show or hide, ESCAPE to close", ColorPair::new(palette::named(rltk::YELLOW), black));
+
+    let entries : Vec<_> = log.entries.iter().filter(|entry| filter.shows(entry.category)).collect();
+    if entries.is_empty() {
+        draw_batch.print_color(Point::new(4, 6), "Nothing to show.", ColorPair::new(palette::named(rltk::GREY), black));
+    }
+    let first = entries.len().saturating_sub(SHOWN);
+    for (y, entry) in entries[first..].iter().enumerate() {
+        let line : String = entry.line().chars().take(74).collect();
+        draw_batch.print_color(Point::new(4, 6 + y as i32), &line, ColorPair::new(palette::color(entry.color), black));
+    }
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match ctx.key {
+        Some(VirtualKeyCode::Escape) => (ItemMenuResult::Cancel, filter),
+        Some(VirtualKeyCode::C) => (ItemMenuResult::Selected, LogFilter{ combat: !filter.combat, ..filter }),
+        Some(VirtualKeyCode::I) => (ItemMenuResult::Selected, LogFilter{ items: !filter.items, ..filter }),
+        Some(VirtualKeyCode::S) => (ItemMenuResult::Selected, LogFilter{ system: !filter.system, ..filter }),
+        _ => (ItemMenuResult::NoResponse, filter)
+    }
+}
+
+/// Lists every item that hides behind an appearance, with what it really is once identified.
+pub fn discoveries(ecs : &World, ctx : &mut Input) -> ItemMenuResult {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let white = palette::named(rltk::WHITE);
+    let black = palette::named(rltk::BLACK);
+    let identified = ecs.fetch::<IdentifiedItems>();
+    let disguises = identified_items::disguises();
+    let found = disguises.iter().filter(|(_appearance, name)| identified.is_known(name)).count();
+
+    let mut y = 25 - (disguises.len() / 2) as i32;
+    draw_batch.draw_box(rltk::Rect::with_size(15, y-2, 50, (disguises.len()+3) as i32), ColorPair::new(white, black));
+    draw_batch.print_color(Point::new(18, y-2), &format!("Discoveries ({}/{})", found, disguises.len()), ColorPair::new(palette::named(rltk::YELLOW), black));
+    draw_batch.print_color(Point::new(18, y+disguises.len() as i32+1), "ESCAPE to close", ColorPair::new(palette::named(rltk::YELLOW), black));
+    for (appearance, name) in disguises.iter() {
+        draw_batch.print_color(Point::new(17, y), appearance, ColorPair::new(white, black));
+        if identified.is_known(name) {
+            draw_batch.print_color(Point::new(36, y), name, ColorPair::new(palette::named(rltk::GREEN), black));
+        } else {
+            draw_batch.print_color(Point::new(36, y), "unknown", ColorPair::new(palette::named(rltk::GREY), black));
+        }
+        y += 1;
+    }
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match ctx.key {
+        Some(VirtualKeyCode::Escape) => ItemMenuResult::Cancel,
+        _ => ItemMenuResult::NoResponse
+    }
+}
+
+/// Lists every kind of creature met, with what's known about the highlighted one alongside.
+/// Returns the highlighted row, moved by the arrow keys.
+pub fn bestiary(ecs : &World, ctx : &mut Input, selected : usize) -> (ItemMenuResult, usize) {
+    const SHOWN : usize = 34;
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let white = palette::named(rltk::WHITE);
+    let black = palette::named(rltk::BLACK);
+    let known = ecs.fetch::<KnownBeasts>();
+    let beasts = known.sorted();
+    let selected = usize::min(selected, beasts.len().saturating_sub(1));
+
+    draw_batch.draw_box(rltk::Rect::with_size(2, 4, 76, (SHOWN+3) as i32), ColorPair::new(white, black));
+    draw_batch.print_color(Point::new(5, 4), &format!("Bestiary ({} known)", beasts.len()), ColorPair::new(palette::named(rltk::YELLOW), black));
+    draw_batch.print_color(Point::new(5, 7 + SHOWN as i32), "UP/DOWN to choose, ESCAPE to close", ColorPair::new(palette::named(rltk::YELLOW), black));
+
+    if beasts.is_empty() {
+        draw_batch.print_color(Point::new(4, 6), "You haven't met anything yet.", ColorPair::new(palette::named(rltk::GREY), black));
+    }
+    let first = (selected + 1).saturating_sub(SHOWN);
+    for (y, (name, _record)) in beasts.iter().skip(first).take(SHOWN).enumerate() {
+        let color = if first + y == selected { palette::named(rltk::MAGENTA) } else { white };
+        let line : String = name.chars().take(26).collect();
+        draw_batch.print_color(Point::new(4, 6 + y as i32), &line, ColorPair::new(color, black));
+    }
+    if let Some((name, record)) = beasts.get(selected) {
+        draw_batch.print_color(Point::new(32, 6), name, ColorPair::new(palette::named(rltk::CYAN), black));
+        for (y, line) in bestiary::describe(name, record).iter().enumerate() {
+            draw_batch.print_color(Point::new(32, 8 + y as i32), line, ColorPair::new(white, black));
+        }
+    }
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match ctx.key {
+        Some(VirtualKeyCode::Escape) => (ItemMenuResult::Cancel, selected),
+        Some(VirtualKeyCode::Up) if selected > 0 => (ItemMenuResult::Selected, selected - 1),
+        Some(VirtualKeyCode::Down) if selected + 1 < beasts.len() => (ItemMenuResult::Selected, selected + 1),
+        _ => (ItemMenuResult::NoResponse, selected)
+    }
+}
+
+/// Something that can't be taken back, held until the player says whether they meant it.
+#[derive(PartialEq, Copy, Clone)]
+pub enum ConfirmAction { QuitRun, Descend, UseUnidentified { item: Entity }, OverwriteSave { selection: MainMenuSelection } }
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum ConfirmResult { NoResponse, Yes, No }
+
+/// Asks a yes or no question about `action`. Y or RETURN goes ahead; N or ESCAPE backs out.
+pub fn confirm_dialog(ecs : &World, ctx : &mut Input, action : ConfirmAction) -> ConfirmResult {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let question = match action {
+        ConfirmAction::QuitRun => "Save and quit to the main menu?".to_string(),
+        ConfirmAction::Descend => "There are enemies next to you. Go down anyway?".to_string(),
+        ConfirmAction::UseUnidentified{ item } => {
+            let name = ecs.read_storage::<Name>().get(item).map_or("it".to_string(), |n| n.name.clone());
+            format!("You don't know what the {} does. Use it anyway?", name)
+        }
+        ConfirmAction::OverwriteSave{ .. } => "Starting a new game will delete your saved one. Go ahead?".to_string()
+    };
+
+    let centre = ecs.fetch::<camera::Screen>().width / 2;
+    let width = question.len() as i32 + 4;
+    let x = centre - width / 2;
+    draw_batch.draw_box(rltk::Rect::with_size(x, 22, width, 4), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(x + 2, 23), &question, ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(centre - 6, 25), "(Y)es / (N)o", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match ctx.key {
+        Some(VirtualKeyCode::Y) | Some(VirtualKeyCode::Return) => ConfirmResult::Yes,
+        Some(VirtualKeyCode::N) | Some(VirtualKeyCode::Escape) => ConfirmResult::No,
+        _ => ConfirmResult::NoResponse
+    }
+}
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum GameOverResult { NoSelection, QuitToMenu, ShowScores }
+
+pub fn game_over(ecs: &World, ctx : &mut Input) -> GameOverResult {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let turns = ecs.fetch::<TurnCounter>();
+
+    draw_batch.print_color_centered(15, "Your journey has ended!", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    draw_batch.print_color_centered(17, &format!("You survived for {} turns.", turns.turn), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color_centered(18, "A morgue file has been written to morgue.txt", ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+
+    let mut y = 20;
+    for line in ecs.fetch::<RunStats>().summary() {
+        draw_batch.print_color_centered(y, &line, ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+        y += 1;
+    }
+
+    draw_batch.print_color_centered(y+1, "Press H to see the high scores, or any other key to return to the menu.", ColorPair::new(palette::named(rltk::MAGENTA), palette::named(rltk::BLACK)));
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match ctx.key {
+        None => GameOverResult::NoSelection,
+        Some(VirtualKeyCode::H) => GameOverResult::ShowScores,
+        Some(_) => GameOverResult::QuitToMenu
+    }
+}
+
+pub fn victory(ecs: &World, ctx : &mut Input) -> GameOverResult {
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    let turns = ecs.fetch::<TurnCounter>();
+
+    draw_batch.print_color_centered(15, "You have escaped with the Amulet!", ColorPair::new(palette::named(rltk::GOLD), palette::named(rltk::BLACK)));
+    draw_batch.print_color_centered(17, &format!("Your quest took {} turns.", turns.turn), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color_centered(18, "A morgue file has been written to morgue.txt", ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+
+    let mut y = 20;
+    for line in ecs.fetch::<RunStats>().summary() {
+        draw_batch.print_color_centered(y, &line, ColorPair::new(palette::named(rltk::GREY), palette::named(rltk::BLACK)));
+        y += 1;
+    }
+
+    draw_batch.print_color_centered(y+1, "Press H to see the high scores, or any other key to return to the menu.", ColorPair::new(palette::named(rltk::MAGENTA), palette::named(rltk::BLACK)));
+    draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+    match ctx.key {
+        None => GameOverResult::NoSelection,
+        Some(VirtualKeyCode::H) => GameOverResult::ShowScores,
+        Some(_) => GameOverResult::QuitToMenu
+    }
 }
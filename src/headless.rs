@@ -0,0 +1,154 @@
+use specs::prelude::*;
+use super::{State, Map, RunState, CombatStats, Name, InBackpack, Unidentified, wizard, gamelog::GameLog, input::{Input, key_named}};
+use rltk::VirtualKeyCode;
+
+/// How long each tick pretends to take, as a window running at 60 frames a second would report.
+const FRAME_MS : f32 = 1000.0 / 60.0;
+
+/// How many ticks a turn may take to play out before the script moves on regardless.
+const MAX_SETTLE_TICKS : usize = 1000;
+
+/// Plays the game without a window, pressing the keys a script asks for and checking the world
+/// afterwards, so combat, item use and level changes can be tested where there's no display.
+///
+/// Each line of the script is one command; blank lines and anything after a # are ignored:
+///
+/// * `seed N` - seeds the random numbers, so the next new game is always the same one
+/// * `key NAME [shift] [ctrl] [TIMES]` - presses a key, by the name rltk gives it ("A", "Return",
+///   "Numpad4"), then lets the turn play out; repeated presses stop early if the run ends
+/// * `type TEXT` - types letters and spaces, as when naming a character
+/// * `wait TICKS` - lets ticks pass with nothing pressed
+/// * `wizard COMMAND` - runs a wizard console command, such as `spawn Orc`, `give Health Potion`
+///   or `stairs`, whether or not the game was started with `--wizard`
+/// * `expect depth N`, `expect hp = N` (or `<`, `>`), `expect log TEXT`, `expect carrying ITEM`,
+///   `expect alive`, `expect dead`, `expect victory` - checks how things stand
+///
+/// Returns how many lines failed, so it can be used as the exit code.
+pub fn run(gs : &mut State, script : &str) -> i32 {
+    let mut failures = 0;
+    for (number, line) in script.lines().enumerate() {
+        let line = line.split('#').next().unwrap_or("").trim();
+        if line.is_empty() { continue; }
+        if let Err(message) = command(gs, line) {
+            eprintln!("line {}: {}: {}", number + 1, line, message);
+            failures += 1;
+        }
+    }
+    println!("{}", if failures == 0 { "All checks passed".to_string() } else { format!("{} line(s) failed", failures) });
+    failures
+}
+
+fn command(gs : &mut State, line : &str) -> Result<(), String> {
+    let (verb, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let rest = rest.trim();
+    match verb {
+        "seed" => {
+            let seed = rest.parse::<u64>().map_err(|_| format!("'{}' is not a seed", rest))?;
+            gs.ecs.insert(rltk::RandomNumberGenerator::seeded(seed));
+        }
+        "key" => {
+            let mut words = rest.split_whitespace();
+            let name = words.next().ok_or("Which key?")?;
+            let key = key_named(name).ok_or(format!("There is no key called '{}'", name))?;
+            let mut input = Input{ key: Some(key), ..Default::default() };
+            let mut times = 1;
+            for word in words {
+                match word {
+                    "shift" => input.shift = true,
+                    "ctrl" => input.control = true,
+                    _ => times = word.parse::<usize>().map_err(|_| format!("'{}' is not shift, ctrl or a count", word))?
+                }
+            }
+            for n in 0..times {
+                if n > 0 && matches!(*gs.ecs.fetch::<RunState>(), RunState::GameOver | RunState::Victory) { break; }
+                press(gs, input);
+            }
+        }
+        "type" => {
+            for c in rest.chars() {
+                let key = if c == ' ' { Some(VirtualKeyCode::Space) } else { key_named(&c.to_ascii_uppercase().to_string()) };
+                let key = key.ok_or(format!("Can't type '{}'", c))?;
+                press(gs, Input{ key: Some(key), shift: c.is_ascii_uppercase(), ..Default::default() });
+            }
+        }
+        "wait" => {
+            let ticks = rest.parse::<usize>().map_err(|_| format!("'{}' is not a number of ticks", rest))?;
+            for _ in 0..ticks {
+                step(gs, Input::default());
+            }
+        }
+        "wizard" => wizard::run_command(&mut gs.ecs, rest),
+        "expect" => expect(&gs.ecs, rest)?,
+        _ => return Err(format!("Unknown command '{}'", verb))
+    }
+    Ok(())
+}
+
+fn expect(ecs : &World, check : &str) -> Result<(), String> {
+    let (what, value) = check.split_once(' ').unwrap_or((check, ""));
+    let value = value.trim();
+    let player_entity = *ecs.fetch::<Entity>();
+    let runstate = *ecs.fetch::<RunState>();
+    match what {
+        "depth" => {
+            let wanted = value.parse::<i32>().map_err(|_| format!("'{}' is not a depth", value))?;
+            let depth = ecs.fetch::<Map>().depth;
+            if depth != wanted { return Err(format!("depth is {}", depth)); }
+        }
+        "hp" => {
+            let (op, number) = value.split_once(' ').ok_or("Expected hp =, < or > and a number")?;
+            let wanted = number.trim().parse::<i32>().map_err(|_| format!("'{}' is not a number", number))?;
+            let hp = ecs.read_storage::<CombatStats>().get(player_entity).map(|stats| stats.hp).ok_or("The player has no hit points")?;
+            let holds = match op {
+                "=" => hp == wanted,
+                "<" => hp < wanted,
+                ">" => hp > wanted,
+                _ => return Err(format!("Unknown comparison '{}'", op))
+            };
+            if !holds { return Err(format!("hp is {}", hp)); }
+        }
+        "log" => {
+            let log = ecs.fetch::<GameLog>();
+            if !log.entries.iter().any(|entry| entry.text.contains(value)) {
+                return Err(format!("the log says: {}", log.entries.iter().rev().take(3).map(|entry| entry.line()).collect::<Vec<String>>().join(" / ")));
+            }
+        }
+        "carrying" => {
+            let names = ecs.read_storage::<Name>();
+            let backpack = ecs.read_storage::<InBackpack>();
+            let unidentified = ecs.read_storage::<Unidentified>();
+            let carried : Vec<(String, Option<String>)> = (&backpack, &names, unidentified.maybe()).join()
+                .filter(|(pack, _, _)| pack.owner == player_entity)
+                .map(|(_, name, hidden)| (name.name.clone(), hidden.map(|h| h.name.clone())))
+                .collect();
+            // An unidentified item answers to its real name as well as its appearance
+            if !carried.iter().any(|(name, real)| name == value || real.as_deref() == Some(value)) {
+                let carried : Vec<String> = carried.into_iter().map(|(name, _)| name).collect();
+                return Err(format!("carrying only: {}", carried.join(", ")));
+            }
+        }
+        "alive" => if runstate == RunState::GameOver { return Err("the player is dead".to_string()); },
+        "dead" => if runstate != RunState::GameOver { return Err("the player is still alive".to_string()); },
+        "victory" => if runstate != RunState::Victory { return Err("the game hasn't been won".to_string()); },
+        _ => return Err(format!("Don't know how to check '{}'", what))
+    }
+    Ok(())
+}
+
+/// Holds a key down for one tick, then lets whatever it started play out.
+fn press(gs : &mut State, input : Input) {
+    step(gs, input);
+    for _ in 0..MAX_SETTLE_TICKS {
+        let busy = matches!(*gs.ecs.fetch::<RunState>(),
+            RunState::PreRun | RunState::PlayerTurn | RunState::MonsterTurn | RunState::SaveGame | RunState::NextLevel | RunState::PreviousLevel);
+        if !busy { break; }
+        step(gs, Input::default());
+    }
+}
+
+/// One tick of the game. What it drew is thrown away, as there's nowhere to show it.
+fn step(gs : &mut State, mut input : Input) {
+    input.frame_time_ms = FRAME_MS;
+    gs.run_tick(&mut input);
+    rltk::clear_command_buffer().expect("Unable to clear the draw buffer");
+}
@@ -0,0 +1,53 @@
+use specs::prelude::*;
+use super::{HungerClock, HungerState, WellFed, RunState, turn_counter::TurnCounter, gamelog::{GameLog, LogCategory}};
+
+/// Turns from the end of a good meal until the player grows hungry.
+pub const NORMAL_TURNS : i32 = 400;
+/// Turns of going hungry before the player is starving.
+const HUNGRY_TURNS : i32 = 200;
+
+/// Winds down the player's hunger clock once they are no longer well fed. Going hungry slows their
+/// natural healing, and starving stops it altogether.
+pub struct HungerSystem {}
+
+impl<'a> System<'a> for HungerSystem {
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, TurnCounter>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, HungerClock>,
+                        ReadStorage<'a, WellFed> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, runstate, turns, mut log, mut hunger, well_fed) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+        let clock = match hunger.get_mut(*player_entity) {
+            Some(clock) => clock,
+            None => return
+        };
+
+        if well_fed.get(*player_entity).is_some() {
+            clock.state = HungerState::WellFed;
+            clock.duration = NORMAL_TURNS;
+            return;
+        }
+
+        match clock.state {
+            HungerState::WellFed => clock.state = HungerState::Normal,
+            HungerState::Normal | HungerState::Hungry => {
+                clock.duration -= turns.elapsed();
+                if clock.duration > 0 { return; }
+                if clock.state == HungerState::Normal {
+                    clock.state = HungerState::Hungry;
+                    clock.duration = HUNGRY_TURNS;
+                    log.push(LogCategory::System, "You are hungry.".to_string());
+                } else {
+                    clock.state = HungerState::Starving;
+                    log.push(LogCategory::System, "You are starving!".to_string());
+                }
+            }
+            HungerState::Starving => {}
+        }
+    }
+}
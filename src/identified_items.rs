@@ -0,0 +1,85 @@
+use std::collections::HashSet;
+use serde::{Serialize, Deserialize};
+use specs::prelude::*;
+use specs::shrev::ReaderId;
+use super::{Name, Unidentified, achievements::Profile, events::{self, GameEvents, GameEvent}, gamelog::{GameLog, LogCategory}, replay::Replay, raws};
+
+/// Item kinds the player has learned to recognise this run.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct IdentifiedItems {
+    pub known : HashSet<String>
+}
+
+impl IdentifiedItems {
+    pub fn is_known(&self, name : &str) -> bool {
+        self.known.contains(name)
+    }
+
+    /// What a new run starts out knowing: nothing, unless the profile carries identifications over.
+    pub fn for_new_run(profile : &Profile) -> IdentifiedItems {
+        let known = if profile.remember_identifications { profile.identified.clone() } else { HashSet::new() };
+        IdentifiedItems{ known }
+    }
+}
+
+/// Teaches the player a kind of item without them using one, as an altar does: every item of that
+/// kind goes by its real name from now on.
+pub fn learn(ecs : &mut World, real_name : &str) {
+    let matching : Vec<Entity> = {
+        let entities = ecs.entities();
+        let unidentified = ecs.read_storage::<Unidentified>();
+        (&entities, &unidentified).join().filter(|(_e, u)| u.name == real_name).map(|(e, _u)| e).collect()
+    };
+    for item in matching.iter() {
+        ecs.write_storage::<Unidentified>().remove(*item);
+        if let Some(name) = ecs.write_storage::<Name>().get_mut(*item) {
+            name.name = real_name.to_string();
+        }
+    }
+    ecs.write_resource::<IdentifiedItems>().known.insert(real_name.to_string());
+    events::publish(ecs, GameEvent::ItemIdentified{ name: real_name.to_string() });
+}
+
+/// Every item that goes by an appearance until identified, as (appearance, real name) pairs
+/// ordered by appearance.
+pub fn disguises() -> Vec<(String, String)> {
+    let raws = raws::raws();
+    let mut disguises = raws::disguised_items(&raws);
+    disguises.sort();
+    disguises
+}
+
+/// Copies identifications into the profile, so they are there to carry over whenever the player
+/// turns remembering on.
+#[derive(Default)]
+pub struct IdentificationMemorySystem {
+    reader : Option<ReaderId<GameEvent>>
+}
+
+impl<'a> System<'a> for IdentificationMemorySystem {
+    type SystemData = ( ReadExpect<'a, GameEvents>,
+                        WriteExpect<'a, Profile>,
+                        ReadExpect<'a, Replay>,
+                        WriteExpect<'a, GameLog> );
+
+    fn setup(&mut self, world : &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(world.write_resource::<GameEvents>().register_reader());
+    }
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (events, mut profile, replay, mut log) = data;
+
+        let mut changed = false;
+        for event in events.read(self.reader.as_mut().expect("IdentificationMemorySystem was not set up")) {
+            if let GameEvent::ItemIdentified{ name } = event {
+                if !replay.is_playing() {
+                    changed |= profile.identified.insert(name.clone());
+                }
+            }
+        }
+        if changed {
+            if let Err(err) = profile.save() { log.push(LogCategory::System, err); }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use rltk::{Rltk, VirtualKeyCode};
+
+/// Every key the game listens for, so keys can be stored by their code or written by name (as in
+/// replays and headless scripts) and turned back into keys.
+const KEYS : [VirtualKeyCode; 59] = [
+    VirtualKeyCode::A, VirtualKeyCode::B, VirtualKeyCode::C, VirtualKeyCode::D, VirtualKeyCode::E, VirtualKeyCode::F,
+    VirtualKeyCode::G, VirtualKeyCode::H, VirtualKeyCode::I, VirtualKeyCode::J, VirtualKeyCode::K, VirtualKeyCode::L,
+    VirtualKeyCode::M, VirtualKeyCode::N, VirtualKeyCode::O, VirtualKeyCode::P, VirtualKeyCode::Q, VirtualKeyCode::R,
+    VirtualKeyCode::S, VirtualKeyCode::T, VirtualKeyCode::U, VirtualKeyCode::V, VirtualKeyCode::W, VirtualKeyCode::X,
+    VirtualKeyCode::Y, VirtualKeyCode::Z,
+    VirtualKeyCode::Key0, VirtualKeyCode::Key1, VirtualKeyCode::Key2, VirtualKeyCode::Key3, VirtualKeyCode::Key4,
+    VirtualKeyCode::Key5, VirtualKeyCode::Key6, VirtualKeyCode::Key7, VirtualKeyCode::Key8, VirtualKeyCode::Key9,
+    VirtualKeyCode::Numpad0, VirtualKeyCode::Numpad1, VirtualKeyCode::Numpad2, VirtualKeyCode::Numpad3, VirtualKeyCode::Numpad4,
+    VirtualKeyCode::Numpad5, VirtualKeyCode::Numpad6, VirtualKeyCode::Numpad7, VirtualKeyCode::Numpad8, VirtualKeyCode::Numpad9,
+    VirtualKeyCode::Up, VirtualKeyCode::Down, VirtualKeyCode::Left, VirtualKeyCode::Right,
+    VirtualKeyCode::Escape, VirtualKeyCode::Return, VirtualKeyCode::Back, VirtualKeyCode::Space, VirtualKeyCode::Tab,
+    VirtualKeyCode::Period, VirtualKeyCode::Comma, VirtualKeyCode::Minus, VirtualKeyCode::Grave
+];
+
+pub fn key_from_code(code : u32) -> Option<VirtualKeyCode> {
+    KEYS.iter().copied().find(|key| *key as u32 == code)
+}
+
+/// Looks a key up by the name rltk gives it, e.g. "A", "Return" or "Numpad4".
+#[cfg(not(target_arch = "wasm32"))]
+pub fn key_named(name : &str) -> Option<VirtualKeyCode> {
+    KEYS.iter().copied().find(|key| format!("{:?}", key) == name)
+}
+
+/// What the player did this tick: the parts of rltk's context the game acts on. It's copied from the
+/// window every frame, or made up by a script when running without one.
+#[derive(Clone, Copy, Default)]
+pub struct Input {
+    pub key : Option<VirtualKeyCode>,
+    pub shift : bool,
+    pub control : bool,
+    pub left_click : bool,
+    pub mouse_pos : (i32, i32),
+    pub frame_time_ms : f32
+}
+
+impl Input {
+    pub fn from_context(ctx : &Rltk) -> Input {
+        Input{
+            key : ctx.key,
+            shift : ctx.shift,
+            control : ctx.control,
+            left_click : ctx.left_click,
+            mouse_pos : ctx.mouse_pos(),
+            frame_time_ms : ctx.frame_time_ms
+        }
+    }
+
+    pub fn mouse_pos(&self) -> (i32, i32) {
+        self.mouse_pos
+    }
+}
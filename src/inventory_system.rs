@@ -1,31 +1,71 @@
 use specs::prelude::*;
 use crate::map::Map;
-use super::{WantsToPickupItem, Name, InBackpack, Position, gamelog::GameLog, WantsToUseItem,
-            Consumable, ProvidesHealing, CombatStats, WantsToDropItem, InflictsDamage, SufferDamage,
-            AreaOfEffect, Confusion};
+use super::{WantsToPickupItem, Name, InBackpack, Position, gamelog::{GameLog, LogCategory}, WantsToUseItem,
+            Consumable, ProvidesHealing, CombatStats, WantsToDropItem, WantsToRemoveItem, InflictsDamage, SufferDamage,
+            AreaOfEffect, Confusion, Equippable, Equipped, StartsFire, SummonsMinion, Charms, GrantsInvisibility, GrantsSeeInvisible, Invisible,
+            CanSeeInvisible, GrantsTelepathy, Telepathy, Cures, Mutates, ProvidesFood, Blink, TownPortal, InContainer, Gold, Purse, Skills, Skill, Repairs, Durability, Charges, Recharges, Unidentified, Scripted, TwoHanded, OffHand, EquipmentSlot, Ammo, identified_items::IdentifiedItems, skill_training::REPAIR_XP, events::{GameEvents, GameEvent}, Attributes, gamesystem::attr_bonus, Branch, tile_walkable, fire_system::Ignitions,
+            effects::{EffectQueue, EffectType, Targets}};
 
 pub struct ItemCollectionSystem {}
 
 impl<'a> System<'a> for ItemCollectionSystem {
     #[allow(clippy::type_complexity)]
     type SystemData = ( ReadExpect<'a, Entity>,
+                        Entities<'a>,
                         WriteExpect<'a, GameLog>,
                         WriteStorage<'a, WantsToPickupItem>,
                         WriteStorage<'a, Position>,
                         ReadStorage<'a, Name>,
-                        WriteStorage<'a, InBackpack>
+                        WriteStorage<'a, InBackpack>,
+                        WriteStorage<'a, InContainer>,
+                        ReadStorage<'a, Gold>,
+                        WriteStorage<'a, Purse>,
+                        ReadStorage<'a, Equipped>,
+                        WriteStorage<'a, Ammo>,
+                        WriteExpect<'a, GameEvents>
     );
 
     fn run(&mut self, data : Self::SystemData) {
-        let (player_entity, mut gamelog, mut wants_pickup, mut positions, names, mut backpack) = data;
+        let (player_entity, entities, mut gamelog, mut wants_pickup, mut positions, names, mut backpack, mut in_container,
+            gold, mut purses, equipped, mut ammo, mut events) = data;
 
         for pickup in wants_pickup.join() {
             positions.remove(pickup.item);
-            backpack.insert(pickup.item, InBackpack{ owner: pickup.collected_by }).expect("Unable to insert backpack entry");
+            in_container.remove(pickup.item);
 
-            if pickup.collected_by == *player_entity {
-                gamelog.entries.push(format!("You pick up the {}.", names.get(pickup.item).unwrap().name));
+            // Coins go straight into the purse
+            if let (Some(coins), Some(purse)) = (gold.get(pickup.item), purses.get_mut(pickup.collected_by)) {
+                purse.gold += coins.amount;
+                entities.delete(pickup.item).expect("Unable to delete coins");
+                if pickup.collected_by == *player_entity {
+                    gamelog.push(LogCategory::Items, format!("You pick up {} gold.", coins.amount));
+                }
+                continue;
+            }
+
+            // Arrows and bolts join a stack of the same kind the collector already has
+            let picked_name = &names.get(pickup.item).unwrap().name;
+            let stack = (&entities, &names, &ammo).join()
+                .filter(|(e, n, _a)| *e != pickup.item && n.name == *picked_name)
+                .find(|(e, _n, _a)| backpack.get(*e).map_or(false, |b| b.owner == pickup.collected_by)
+                    || equipped.get(*e).map_or(false, |w| w.owner == pickup.collected_by))
+                .map(|(e, _n, _a)| e);
+            if let (Some(stack), Some(count)) = (stack, ammo.get(pickup.item).map(|a| a.count)) {
+                ammo.get_mut(stack).unwrap().count += count;
+                entities.delete(pickup.item).expect("Unable to delete merged ammo");
+                if pickup.collected_by == *player_entity {
+                    gamelog.push(LogCategory::Items, format!("You add {} to your {}.", count, picked_name));
+                }
+                continue;
             }
+            backpack.insert(pickup.item, InBackpack{ owner: pickup.collected_by }).expect("Unable to insert backpack entry");
+
+            events.single_write(GameEvent::ItemPickedUp{
+                by: pickup.collected_by,
+                item: pickup.item,
+                name: names.get(pickup.item).unwrap().name.clone(),
+                by_player: pickup.collected_by == *player_entity
+            });
         }
 
         wants_pickup.clear();
@@ -41,35 +81,105 @@ impl<'a> System<'a> for ItemUseSystem {
                         ReadExpect<'a, Map>,
                         Entities<'a>,
                         WriteStorage<'a, WantsToUseItem>,
-                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, Name>,
                         ReadStorage<'a, Consumable>,
                         ReadStorage<'a, ProvidesHealing>,
                         ReadStorage<'a, InflictsDamage>,
                         WriteStorage<'a, CombatStats>,
                         WriteStorage<'a, SufferDamage>,
                         ReadStorage<'a, AreaOfEffect>,
-                        WriteStorage<'a, Confusion>
+                        WriteStorage<'a, Confusion>,
+                        ReadStorage<'a, Equippable>,
+                        WriteStorage<'a, Equipped>,
+                        WriteStorage<'a, InBackpack>,
+                        ReadStorage<'a, StartsFire>,
+                        WriteExpect<'a, Ignitions>,
+                        ReadStorage<'a, SummonsMinion>,
+                        ReadStorage<'a, Position>,
+                        WriteExpect<'a, EffectQueue>,
+                        ReadStorage<'a, Charms>,
+                        (ReadStorage<'a, GrantsInvisibility>, ReadStorage<'a, GrantsSeeInvisible>,
+                         WriteStorage<'a, Invisible>, WriteStorage<'a, CanSeeInvisible>),
+                        (ReadStorage<'a, GrantsTelepathy>, WriteStorage<'a, Telepathy>, ReadStorage<'a, Scripted>, ReadStorage<'a, Cures>,
+                         ReadStorage<'a, Mutates>, ReadStorage<'a, ProvidesFood>),
+                        (ReadStorage<'a, Blink>, ReadStorage<'a, TownPortal>, ReadStorage<'a, Skills>, ReadStorage<'a, Attributes>),
+                        (ReadStorage<'a, Repairs>, WriteStorage<'a, Durability>, WriteStorage<'a, Charges>, ReadStorage<'a, Recharges>,
+                         WriteStorage<'a, Unidentified>, WriteExpect<'a, IdentifiedItems>, ReadStorage<'a, TwoHanded>, ReadStorage<'a, OffHand>,
+                         WriteExpect<'a, GameEvents>)
     );
 
     fn run(&mut self, data : Self::SystemData) {
-        let (player_entity, mut gamelog, map, entities, mut wants_use, names,
+        let (player_entity, mut gamelog, map, entities, mut wants_use, mut names,
             consumables, healing, inflict_damage, mut combat_stats, mut suffer_damage,
-            aoe, mut confused) = data;
+            aoe, mut confused, equippable, mut equipped, mut backpack, starts_fire, mut ignitions, summons, positions,
+            mut effects, charms, (grants_invisibility, grants_see_invisible, mut invisible, mut see_invisible),
+            (grants_telepathy, mut telepathy, scripted, cures, mutates, food), (blinks, town_portals, skills, attributes), (repairs, mut durability, mut charges, recharges, mut unidentified, mut identified, two_handed, off_hands, mut events)) = data;
 
+        let mut learned : Vec<String> = Vec::new();
         for (entity, useitem) in (&entities, &wants_use).join() {
             let mut used_item = true;
 
+            // An empty wand does nothing at all
+            if charges.get(useitem.item).map_or(false, |c| c.current < 1) {
+                if entity == *player_entity {
+                    gamelog.push(LogCategory::Items, format!("The {} fizzles; it has no charges left.", names.get(useitem.item).unwrap().name));
+                }
+                continue;
+            }
+
+            // If it is equippable, swap it with whatever currently fills that slot
+            if let Some(can_equip) = equippable.get(useitem.item) {
+                let mut target_slot = can_equip.slot;
+
+                // A light weapon goes in the off hand if the main hand already holds a one-handed weapon
+                if target_slot == EquipmentSlot::Melee && off_hands.get(useitem.item).is_some() {
+                    let main_hand = (&entities, &equipped).join()
+                        .find(|(_e, w)| w.owner == entity && w.slot == EquipmentSlot::Melee)
+                        .map(|(e, _w)| e);
+                    if let Some(main_hand) = main_hand {
+                        if two_handed.get(main_hand).is_none() { target_slot = EquipmentSlot::Shield; }
+                    }
+                }
+
+                // Two-handed weapons and whatever is held in the off hand can't go together
+                let needs_both_hands = two_handed.get(useitem.item).is_some();
+                let mut to_unequip : Vec<Entity> = Vec::new();
+                for (item_entity, already_equipped) in (&entities, &equipped).join() {
+                    if already_equipped.owner != entity { continue; }
+                    let hands_clash = (needs_both_hands && already_equipped.slot == EquipmentSlot::Shield)
+                        || (target_slot == EquipmentSlot::Shield && two_handed.get(item_entity).is_some());
+                    if already_equipped.slot == target_slot || hands_clash {
+                        to_unequip.push(item_entity);
+                    }
+                }
+                for item in to_unequip.iter() {
+                    equipped.remove(*item);
+                    backpack.insert(*item, InBackpack{ owner: entity }).expect("Unable to insert backpack entry");
+                    if entity == *player_entity {
+                        gamelog.push(LogCategory::Items, format!("You unequip {}.", names.get(*item).unwrap().name));
+                    }
+                }
+
+                equipped.insert(useitem.item, Equipped{ owner: entity, slot: target_slot }).expect("Unable to insert equipped component");
+                backpack.remove(useitem.item);
+                if entity == *player_entity {
+                    gamelog.push(LogCategory::Items, format!("You equip {}.", names.get(useitem.item).unwrap().name));
+                }
+            }
+
             // Targeting
             let mut targets : Vec<Entity> = Vec::new();
+            let mut target_tiles : Vec<rltk::Point> = Vec::new();
             match useitem.target {
-                None => { targets.push( *player_entity ); }
+                None => { targets.push( entity ); }
                 Some(target) => {
                     let area_effect = aoe.get(useitem.item);
                     match area_effect {
                         None => {
                             // Single target in tile
+                            target_tiles.push(target);
                             let idx = map.xy_idx(target.x, target.y);
-                            for mob in map.tile_content[idx].iter() {
+                            for mob in map.entities_at(idx).iter() {
                                 targets.push(*mob);
                             }
                         }
@@ -78,8 +188,9 @@ impl<'a> System<'a> for ItemUseSystem {
                             let mut blast_tiles = rltk::field_of_view(target, area_effect.radius, &*map);
                             blast_tiles.retain(|p| p.x > 0 && p.x < map.width-1 && p.y > 0 && p.y < map.height-1 );
                             for tile_idx in blast_tiles.iter() {
+                                target_tiles.push(*tile_idx);
                                 let idx = map.xy_idx(tile_idx.x, tile_idx.y);
-                                for mob in map.tile_content[idx].iter() {
+                                for mob in map.entities_at(idx).iter() {
                                     targets.push(*mob);
                                 }
                             }
@@ -88,6 +199,15 @@ impl<'a> System<'a> for ItemUseSystem {
                 }
             }
 
+            // Scripted items hand their script to the effects queue, aimed wherever the item was
+            if let Some(script) = scripted.get(useitem.item) {
+                let source = script.script.clone();
+                match useitem.target {
+                    None => effects.add_effect(Some(entity), EffectType::Script{ source }, Targets::Single{ target: entity }),
+                    Some(target) => effects.add_effect(Some(entity), EffectType::Script{ source }, Targets::Tile{ tile: map.xy_idx(target.x, target.y) })
+                }
+            }
+
             // If it heals, apply the healing
             let item_heals = healing.get(useitem.item);
             match item_heals {
@@ -99,7 +219,7 @@ impl<'a> System<'a> for ItemUseSystem {
                         if let Some(stats) = stats {
                             stats.hp = i32::min(stats.max_hp, stats.hp + healer.heal_amount);
                             if entity == *player_entity {
-                                gamelog.entries.push(format!("You use the {}, healing {} hp.", names.get(useitem.item).unwrap().name, healer.heal_amount));
+                                gamelog.push(LogCategory::Items, format!("You use the {}, healing {} hp.", names.get(useitem.item).unwrap().name, healer.heal_amount));
                             }
                             used_item = true;
                         }
@@ -107,18 +227,45 @@ impl<'a> System<'a> for ItemUseSystem {
                 }
             }
 
+            // Recharging tops up every wand the user carries
+            if recharges.get(useitem.item).is_some() {
+                let mut recharged = false;
+                for (_pack, wand) in (&backpack, &mut charges).join().filter(|(b, _c)| b.owner == entity) {
+                    wand.current = wand.max;
+                    recharged = true;
+                }
+                if entity == *player_entity {
+                    let message = if recharged { "Your wands hum with renewed power." } else { "You feel a brief tingle in your fingers." };
+                    gamelog.push(LogCategory::Items, message.to_string());
+                }
+            }
+
+            // Repair kits patch up everything the user has equipped, and a skilled hand gets more out of them
+            if let Some(kit) = repairs.get(useitem.item) {
+                let amount = kit.amount + skills.get(entity).map_or(0, |s| s.crafting);
+                for (_worn, d) in (&equipped, &mut durability).join().filter(|(w, _d)| w.owner == entity) {
+                    d.current = i32::min(d.max, d.current + amount);
+                }
+                if entity == *player_entity {
+                    gamelog.push(LogCategory::Items, "You patch up your equipment.".to_string());
+                }
+                events.single_write(GameEvent::SkillUsed{ by: entity, skill: Skill::Crafting, xp: REPAIR_XP });
+            }
+
             // If it inflicts damage, apply it to the target cell
             let item_damages = inflict_damage.get(useitem.item);
             match item_damages {
                 None => {}
                 Some(damage) => {
                     used_item = false;
+                    let amount = damage.damage + skills.get(entity).map_or(0, |s| s.magic)
+                        + i32::max(0, attr_bonus(attributes.get(entity).map_or(10, |a| a.intelligence)));
                     for mob in targets.iter() {
-                        SufferDamage::new_damage(&mut suffer_damage, *mob, damage.damage);
+                        SufferDamage::new_damage(&mut suffer_damage, *mob, amount, damage.damage_type, entity == *player_entity, Some(entity));
                         if entity == *player_entity {
                             let mob_name = names.get(*mob).unwrap();
                             let item_name = names.get(useitem.item).unwrap();
-                            gamelog.entries.push(format!("You use {} on {}, inflicting {} hp.", item_name.name, mob_name.name, damage.damage));
+                            gamelog.push(LogCategory::Combat, format!("You use {} on {}, inflicting {} {} damage.", item_name.name, mob_name.name, amount, damage.damage_type.name()));
                         }
 
                         used_item = true;
@@ -139,7 +286,7 @@ impl<'a> System<'a> for ItemUseSystem {
                             if entity == *player_entity {
                                 let mob_name = names.get(*mob).unwrap();
                                 let item_name = names.get(useitem.item).unwrap();
-                                gamelog.entries.push(format!("You use {} on {}, confusing them.", item_name.name, mob_name.name));
+                                gamelog.push(LogCategory::Combat, format!("You use {} on {}, confusing them.", item_name.name, mob_name.name));
                             }
                         }
                     }
@@ -149,12 +296,137 @@ impl<'a> System<'a> for ItemUseSystem {
                 confused.insert(mob.0, Confusion{ turns: mob.1 }).expect("Unable to insert status");
             }
 
+            // Fire magic leaves the targeted tiles burning
+            if let Some(fire) = starts_fire.get(useitem.item) {
+                for tile in target_tiles.iter() {
+                    ignitions.ignite(*tile, fire.turns);
+                }
+            }
+
+            // Potions of invisibility and true sight
+            if let Some(potion) = grants_invisibility.get(useitem.item) {
+                for target in targets.iter() {
+                    invisible.insert(*target, Invisible{ turns: Some(potion.turns) }).expect("Unable to insert status");
+                    if *target == *player_entity {
+                        gamelog.push(LogCategory::Items, "You fade from sight!".to_string());
+                    }
+                }
+            }
+            if let Some(potion) = grants_see_invisible.get(useitem.item) {
+                for target in targets.iter() {
+                    see_invisible.insert(*target, CanSeeInvisible{ turns: Some(potion.turns) }).expect("Unable to insert status");
+                    if *target == *player_entity {
+                        gamelog.push(LogCategory::Items, "Your eyes tingle as hidden things come into focus.".to_string());
+                    }
+                }
+            }
+
+            // Telepathy lets the user sense monsters beyond line of sight
+            if let Some(detection) = grants_telepathy.get(useitem.item) {
+                for target in targets.iter() {
+                    telepathy.insert(*target, Telepathy{ turns: detection.turns }).expect("Unable to insert status");
+                    if *target == *player_entity {
+                        gamelog.push(LogCategory::Items, "You sense the minds of the creatures around you.".to_string());
+                    }
+                }
+            }
+
+            // A proper meal keeps hunger at bay for a while
+            if let Some(meal) = food.get(useitem.item) {
+                for target in targets.iter() {
+                    effects.add_effect(Some(entity), EffectType::WellFed{ turns: meal.turns }, Targets::Single{ target: *target });
+                }
+            }
+
+            // Cures lift a disease or curse from whoever they're used on
+            if let Some(cure) = cures.get(useitem.item) {
+                for target in targets.iter() {
+                    effects.add_effect(Some(entity), EffectType::Cure{ kind: Some(cure.kind), price: 0 }, Targets::Single{ target: *target });
+                }
+            }
+
+            // Chaos in a bottle changes whoever drinks it for good
+            if let Some(mutates) = mutates.get(useitem.item) {
+                for target in targets.iter() {
+                    effects.add_effect(Some(entity), EffectType::Mutate{ beneficial: mutates.beneficial }, Targets::Single{ target: *target });
+                }
+            }
+
+            // Mind control turns the targets against their friends
+            if let Some(charm) = charms.get(useitem.item) {
+                for mob in targets.iter() {
+                    effects.add_effect(Some(entity), EffectType::Charm{ turns: charm.turns }, Targets::Single{ target: *mob });
+                }
+            }
+
+            // Blinking jumps the user to the targeted tile
+            if blinks.get(useitem.item).is_some() {
+                if let Some(target) = useitem.target {
+                    let idx = map.xy_idx(target.x, target.y);
+                    if tile_walkable(map.tiles[idx]) && !map.is_blocked(idx) {
+                        effects.add_effect(Some(entity), EffectType::TeleportTo{ x: target.x, y: target.y }, Targets::Single{ target: entity });
+                    } else {
+                        used_item = false;
+                        if entity == *player_entity {
+                            gamelog.push(LogCategory::Items, "Something is in the way.".to_string());
+                        }
+                    }
+                }
+            }
+
+            // Town portals only make sense away from town
+            if town_portals.get(useitem.item).is_some() {
+                if map.branch == Branch::Overworld {
+                    used_item = false;
+                    if entity == *player_entity {
+                        gamelog.push(LogCategory::Items, "You are already above ground.".to_string());
+                    }
+                } else {
+                    effects.add_effect(Some(entity), EffectType::TownPortal, Targets::Single{ target: entity });
+                }
+            }
+
+            // Summoning calls a minion to the first free tile next to the user
+            if let Some(summon) = summons.get(useitem.item) {
+                used_item = false;
+                if let Some(pos) = positions.get(entity) {
+                    let free_tile = (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                        .map(|(dx, dy)| (pos.x + dx, pos.y + dy))
+                        .filter(|(x, y)| *x > 0 && *x < map.width - 1 && *y > 0 && *y < map.height - 1)
+                        .map(|(x, y)| map.xy_idx(x, y))
+                        .find(|idx| !map.is_blocked(*idx));
+                    if let Some(tile) = free_tile {
+                        effects.add_effect(Some(entity), EffectType::Summon{ name: summon.name.clone(), turns: Some(summon.turns) }, Targets::Tile{ tile });
+                        used_item = true;
+                    } else if entity == *player_entity {
+                        gamelog.push(LogCategory::Items, "There is no room for anything to answer your call.".to_string());
+                    }
+                }
+            }
+
+            if used_item {
+                if let Some(wand) = charges.get_mut(useitem.item) {
+                    wand.current -= 1;
+                }
+                // Using an unknown item is how the player finds out what it does
+                if entity == *player_entity {
+                    if let Some(hidden) = unidentified.get(useitem.item) {
+                        learned.push(hidden.name.clone());
+                    }
+                }
+            }
+
             // If it's a consumable, we delete it on use
             if used_item {
                 let consumable = consumables.get(useitem.item);
                 match consumable {
                     None => {}
                     Some(_) => {
+                        let name = match unidentified.get(useitem.item) {
+                            Some(hidden) => hidden.name.clone(),
+                            None => names.get(useitem.item).map_or(String::new(), |n| n.name.clone())
+                        };
+                        events.single_write(GameEvent::ItemConsumed{ by: entity, item: useitem.item, name, by_player: entity == *player_entity });
                         entities.delete(useitem.item).expect("Delete failed");
                     }
                 }
@@ -162,6 +434,20 @@ impl<'a> System<'a> for ItemUseSystem {
         }
 
         wants_use.clear();
+
+        // Every item of a newly learned kind is recognised from now on
+        for real_name in learned.iter() {
+            let matching : Vec<Entity> = (&entities, &unidentified).join().filter(|(_e, u)| u.name == *real_name).map(|(e, _u)| e).collect();
+            for item in matching.iter() {
+                unidentified.remove(*item);
+                if let Some(name) = names.get_mut(*item) {
+                    name.name = real_name.clone();
+                }
+            }
+            identified.known.insert(real_name.clone());
+            gamelog.push(LogCategory::Items, format!("It's a {}!", real_name));
+            events.single_write(GameEvent::ItemIdentified{ name: real_name.clone() });
+        }
     }
 }
 
@@ -192,10 +478,39 @@ impl<'a> System<'a> for ItemDropSystem {
             backpack.remove(to_drop.item);
 
             if entity == *player_entity {
-                gamelog.entries.push(format!("You drop the {}.", names.get(to_drop.item).unwrap().name));
+                gamelog.push(LogCategory::Items, format!("You drop the {}.", names.get(to_drop.item).unwrap().name));
             }
         }
 
         wants_drop.clear();
     }
-}
\ No newline at end of file
+}
+
+pub struct ItemRemoveSystem {}
+
+impl<'a> System<'a> for ItemRemoveSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToRemoveItem>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, Equipped>,
+                        WriteStorage<'a, InBackpack>
+    );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut gamelog, entities, mut wants_remove, names, mut equipped, mut backpack) = data;
+
+        for (entity, to_remove) in (&entities, &wants_remove).join() {
+            equipped.remove(to_remove.item);
+            backpack.insert(to_remove.item, InBackpack{ owner: entity }).expect("Unable to insert backpack");
+
+            if entity == *player_entity {
+                gamelog.push(LogCategory::Items, format!("You take off the {}.", names.get(to_remove.item).unwrap().name));
+            }
+        }
+
+        wants_remove.clear();
+    }
+}
@@ -0,0 +1,67 @@
+use specs::prelude::*;
+use super::{Invisible, CanSeeInvisible, Telepathy, RunState, turn_counter::TurnCounter, gamelog::{GameLog, LogCategory}};
+
+/// Counts down temporary invisibility, true sight and telepathy.
+pub struct InvisibilitySystem {}
+
+impl<'a> System<'a> for InvisibilitySystem {
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, TurnCounter>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, Invisible>,
+                        WriteStorage<'a, CanSeeInvisible>,
+                        WriteStorage<'a, Telepathy> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, runstate, player_entity, turn_counter, mut log, mut invisible, mut see_invisible, mut telepathy) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        let mut revealed : Vec<Entity> = Vec::new();
+        for (entity, status) in (&entities, &mut invisible).join() {
+            if let Some(turns) = status.turns.as_mut() {
+                *turns -= turn_counter.elapsed();
+                if *turns < 1 { revealed.push(entity); }
+            }
+        }
+        for entity in revealed {
+            invisible.remove(entity);
+            if entity == *player_entity {
+                log.push(LogCategory::System, "You fade back into view.".to_string());
+            }
+        }
+
+        let mut blinded : Vec<Entity> = Vec::new();
+        for (entity, status) in (&entities, &mut see_invisible).join() {
+            if let Some(turns) = status.turns.as_mut() {
+                *turns -= turn_counter.elapsed();
+                if *turns < 1 { blinded.push(entity); }
+            }
+        }
+        for entity in blinded {
+            see_invisible.remove(entity);
+            if entity == *player_entity {
+                log.push(LogCategory::System, "Your sight returns to normal.".to_string());
+            }
+        }
+
+        let mut closed : Vec<Entity> = Vec::new();
+        for (entity, status) in (&entities, &mut telepathy).join() {
+            status.turns -= turn_counter.elapsed();
+            if status.turns < 1 { closed.push(entity); }
+        }
+        for entity in closed {
+            telepathy.remove(entity);
+            if entity == *player_entity {
+                log.push(LogCategory::System, "The distant minds fade from your awareness.".to_string());
+            }
+        }
+    }
+}
+
+/// Whether the observer can make out the target at all, as far as invisibility goes.
+pub fn can_perceive(observer : Entity, target : Entity, invisible : &ReadStorage<Invisible>, see_invisible : &ReadStorage<CanSeeInvisible>) -> bool {
+    invisible.get(target).is_none() || see_invisible.get(observer).is_some()
+}
@@ -0,0 +1,93 @@
+use specs::prelude::*;
+use super::{Attributes, Pools, Viewshed, KeenEye, FastHealing, FarSight, PendingLevelUps, gamesystem::{stamina_at_fitness, mana_at_intelligence},
+            gamelog::{GameLog, LogCategory}};
+
+/// Extra tiles of sight granted by Far Sight.
+pub const FAR_SIGHT_BONUS : i32 = 3;
+
+#[derive(PartialEq, Copy, Clone)]
+pub enum LevelUpChoice { Might, Fitness, Quickness, Intelligence, KeenEye, FastHealing, FarSight }
+
+impl LevelUpChoice {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LevelUpChoice::Might => "+1 Might",
+            LevelUpChoice::Fitness => "+1 Fitness",
+            LevelUpChoice::Quickness => "+1 Quickness",
+            LevelUpChoice::Intelligence => "+1 Intelligence",
+            LevelUpChoice::KeenEye => "Keen Eye",
+            LevelUpChoice::FastHealing => "Fast Healing",
+            LevelUpChoice::FarSight => "Far Sight"
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            LevelUpChoice::Might => "Hit harder in melee.",
+            LevelUpChoice::Fitness => "More stamina.",
+            LevelUpChoice::Quickness => "Hit, dodge and pick locks more easily.",
+            LevelUpChoice::Intelligence => "More mana, and offensive scrolls deal more damage.",
+            LevelUpChoice::KeenEye => "Score critical hits more often.",
+            LevelUpChoice::FastHealing => "Recover faster between fights.",
+            LevelUpChoice::FarSight => "See further."
+        }
+    }
+}
+
+pub fn has_pending(ecs : &World) -> bool {
+    ecs.read_storage::<PendingLevelUps>().get(*ecs.fetch::<Entity>()).is_some()
+}
+
+/// Attribute increases are always on offer; each perk can only be taken once.
+pub fn available_choices(ecs : &World) -> Vec<LevelUpChoice> {
+    let player_entity = *ecs.fetch::<Entity>();
+    let mut choices = vec![LevelUpChoice::Might, LevelUpChoice::Fitness, LevelUpChoice::Quickness, LevelUpChoice::Intelligence];
+    if ecs.read_storage::<KeenEye>().get(player_entity).is_none() { choices.push(LevelUpChoice::KeenEye); }
+    if ecs.read_storage::<FastHealing>().get(player_entity).is_none() { choices.push(LevelUpChoice::FastHealing); }
+    if ecs.read_storage::<FarSight>().get(player_entity).is_none() { choices.push(LevelUpChoice::FarSight); }
+    choices
+}
+
+/// Spends one pending level on the chosen improvement.
+pub fn apply(ecs : &mut World, choice : LevelUpChoice) {
+    let player_entity = *ecs.fetch::<Entity>();
+    match choice {
+        LevelUpChoice::KeenEye => { ecs.write_storage::<KeenEye>().insert(player_entity, KeenEye{}).expect("Unable to insert perk"); }
+        LevelUpChoice::FastHealing => { ecs.write_storage::<FastHealing>().insert(player_entity, FastHealing{}).expect("Unable to insert perk"); }
+        LevelUpChoice::FarSight => {
+            ecs.write_storage::<FarSight>().insert(player_entity, FarSight{}).expect("Unable to insert perk");
+            if let Some(viewshed) = ecs.write_storage::<Viewshed>().get_mut(player_entity) {
+                viewshed.dirty = true;
+            }
+        }
+        _ => {
+            let mut attributes = ecs.write_storage::<Attributes>();
+            if let Some(attr) = attributes.get_mut(player_entity) {
+                match choice {
+                    LevelUpChoice::Might => attr.might += 1,
+                    LevelUpChoice::Fitness => {
+                        attr.fitness += 1;
+                        if let Some(pools) = ecs.write_storage::<Pools>().get_mut(player_entity) {
+                            pools.stamina.max = stamina_at_fitness(attr.fitness);
+                        }
+                    }
+                    LevelUpChoice::Quickness => attr.quickness += 1,
+                    _ => {
+                        attr.intelligence += 1;
+                        if let Some(pools) = ecs.write_storage::<Pools>().get_mut(player_entity) {
+                            pools.mana.max = mana_at_intelligence(attr.intelligence);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    ecs.write_resource::<GameLog>().push(LogCategory::System, format!("You gain {}.", choice.name()));
+
+    let mut pending = ecs.write_storage::<PendingLevelUps>();
+    let done = match pending.get_mut(player_entity) {
+        Some(levels) => { levels.count -= 1; levels.count < 1 }
+        None => false
+    };
+    if done { pending.remove(player_entity); }
+}
@@ -7,6 +7,7 @@ mod components;
 pub use components::*;
 
 mod map;
+mod spatial;
 
 pub use map::*;
 
@@ -26,6 +27,14 @@ mod monster_ai_system;
 
 use monster_ai_system::MonsterAI;
 
+mod pack_ai_system;
+
+use pack_ai_system::PackAI;
+
+mod monster_item_system;
+
+use monster_item_system::MonsterItemAI;
+
 mod map_indexing_system;
 
 use map_indexing_system::MapIndexingSystem;
@@ -44,94 +53,668 @@ mod spawner;
 mod inventory_system;
 mod menu;
 mod saveload_system;
+mod turn_counter;
+mod morgue;
+mod dungeon;
+mod branch;
+mod random_table;
+mod map_builders;
+mod decay_system;
+mod raws;
+mod unique_items;
+mod artifact_system;
+mod throw_system;
+mod ranged_combat_system;
+mod fire_system;
+mod damage_over_time_system;
+mod particle_system;
+mod gamesystem;
+mod cooldown_system;
+mod stamina_system;
+mod regen_system;
+mod durability;
+mod identified_items;
+mod effects;
+mod special_ability_system;
+mod boss_system;
+mod minion_system;
+mod invisibility_system;
+mod trigger_system;
+mod religion;
+mod level_up;
+mod vendor;
+mod quests;
+mod dialogue;
+mod reputation;
+mod difficulty;
+mod wizard;
+mod systems;
+mod camera;
+mod options;
+mod animation;
+mod visual_effects;
+mod minimap;
+mod fov;
+mod tile_set;
+mod events;
+mod achievements;
+mod bestiary;
+mod scores;
+mod challenge;
+mod replay;
+mod input;
+mod storage;
+mod controller;
+mod audio;
+mod undo;
+mod run_stats;
+mod ethereal;
+mod palette;
+mod reproduction_system;
+mod affliction_system;
+mod mutation_system;
+mod skill_training;
+mod door_system;
+mod hunger_system;
+#[cfg(not(target_arch = "wasm32"))]
+mod headless;
+
+pub use branch::Branch;
 
-use inventory_system::{ItemCollectionSystem, ItemUseSystem, ItemDropSystem};
+use inventory_system::{ItemCollectionSystem, ItemUseSystem, ItemDropSystem, ItemRemoveSystem};
+use decay_system::DecaySystem;
 use crate::gui::MainMenuSelection;
+use input::Input;
+use gamelog::LogCategory;
+use turn_counter::TurnCounter;
+use options::OptionChoice;
+use dungeon::{MasterDungeonMap, LevelTeleport};
+use unique_items::UniqueItems;
+use identified_items::IdentifiedItems;
+use artifact_system::ArtifactSystem;
+use throw_system::ThrowSystem;
+use ranged_combat_system::RangedCombatSystem;
+use fire_system::{FireSystem, Ignitions};
+use damage_over_time_system::DamageOverTimeSystem;
+use particle_system::{ParticleSpawnSystem, ParticleBuilder};
+use cooldown_system::CooldownSystem;
+use stamina_system::StaminaSystem;
+use regen_system::RegenerationSystem;
+use special_ability_system::SpecialAbilitySystem;
+use boss_system::BossSystem;
+use minion_system::{MinionSystem, AllyAI};
+use invisibility_system::InvisibilitySystem;
+use trigger_system::TriggerSystem;
+use reproduction_system::{ReproductionSystem, Births};
+use affliction_system::AfflictionSystem;
+use mutation_system::MutationSystem;
+use skill_training::SneakSystem;
+use door_system::DoorSystem;
+use hunger_system::HungerSystem;
 
 
+const OVERWORLD_TRAVEL_TURNS: i32 = 10;
+const THROW_RANGE: i32 = 6;
+
 #[derive(PartialEq, Copy, Clone)]
 pub enum RunState {
     AwaitingInput,
     PreRun,
     PlayerTurn,
     MonsterTurn,
-    ShowInventory,
+    ShowInventory { view: gui::InventoryView },
+    ShowZapItem,
     ShowDropItem,
+    ShowRemoveItem,
+    AssignHotkey { slot: usize },
     ShowTargeting { range: i32, item: Entity },
+    ShowThrowItem,
+    ShowThrowTargeting { item: Entity },
+    ShowShootTargeting { range: i32 },
+    ShowCompanionMenu,
+    ShowCompanionTargeting,
+    ShowContainer { container: Entity },
+    ShowActions,
+    ShowPickup { nearby: bool },
+    ShowBlessItem { altar: Entity },
+    ShowEnchantItem,
+    ShowRepairItem,
+    ShowVendor { vendor: Entity, mode: gui::VendorMode },
+    ShowQuestOffer { giver: Entity },
+    Dialogue { npc: Entity, node: usize },
+    ShowCharacter,
+    ShowJournal,
+    ShowLog { filter: gamelog::LogFilter },
+    ShowBestiary { selected: usize },
+    ShowDiscoveries,
+    Confirm { action: gui::ConfirmAction },
+    WizardConsole,
+    NameMarker,
+    ShowMarkers,
+    LevelUp,
+    CharacterCreation { step: gui::CreationStep },
     MainMenu { menu_selection: gui::MainMenuSelection },
-    SaveGame
+    OptionsMenu,
+    AchievementsMenu,
+    HighScores { view: scores::ScoreView },
+    SaveGame,
+    NextLevel,
+    PreviousLevel,
+    GameOver,
+    Victory
 }
 
 
 pub struct State {
     pub ecs: World,
+    systems: systems::Systems,
+    controller: controller::Controller,
+    audio: audio::Audio,
+    raw_watcher: raws::RawWatcher
 }
 
 impl State {
-    fn run_systems(&mut self) {
-        let mut vis = VisibilitySystem {};
-        vis.run_now(&self.ecs);
-        let mut mob = MonsterAI {};
-        mob.run_now(&self.ecs);
-        let mut mapindex = MapIndexingSystem {};
-        mapindex.run_now(&self.ecs);
-        let mut melee = MeleeCombatSystem {};
-        melee.run_now(&self.ecs);
-        let mut damage = DamageSystem {};
-        damage.run_now(&self.ecs);
-        let mut pickup = ItemCollectionSystem {};
-        pickup.run_now(&self.ecs);
-        let mut itemuse = ItemUseSystem {};
-        itemuse.run_now(&self.ecs);
-        let mut drop_items = ItemDropSystem {};
-        drop_items.run_now(&self.ecs);
+    /// A world with every component registered and every resource the systems expect, but nothing in it.
+    fn new() -> State {
+        let mut gs = State {
+            ecs: World::new(),
+            systems: systems::Systems::new(),
+            controller: controller::Controller::default(),
+            audio: audio::Audio::default(),
+            raw_watcher: raws::RawWatcher::default()
+        };
+        gs.ecs.register::<Position>();
+        gs.ecs.register::<OtherLevelPosition>();
+        gs.ecs.register::<Renderable>();
+        gs.ecs.register::<Player>();
+        gs.ecs.register::<Viewshed>();
+        gs.ecs.register::<Monster>();
+        gs.ecs.register::<Name>();
+        gs.ecs.register::<BlocksTile>();
+        gs.ecs.register::<CombatStats>();
+        gs.ecs.register::<WantsToMelee>();
+        gs.ecs.register::<SufferDamage>();
+        gs.ecs.register::<Item>();
+        gs.ecs.register::<ProvidesHealing>();
+        gs.ecs.register::<InflictsDamage>();
+        gs.ecs.register::<AreaOfEffect>();
+        gs.ecs.register::<Consumable>();
+        gs.ecs.register::<MacGuffin>();
+        gs.ecs.register::<Ranged>();
+        gs.ecs.register::<InBackpack>();
+        gs.ecs.register::<WantsToPickupItem>();
+        gs.ecs.register::<WantsToUseItem>();
+        gs.ecs.register::<WantsToDropItem>();
+        gs.ecs.register::<WantsToRemoveItem>();
+        gs.ecs.register::<TwoHanded>();
+        gs.ecs.register::<OffHand>();
+        gs.ecs.register::<Launcher>();
+        gs.ecs.register::<Ammo>();
+        gs.ecs.register::<WantsToShoot>();
+        gs.ecs.register::<Vendor>();
+        gs.ecs.register::<Value>();
+        gs.ecs.register::<QuestGiver>();
+        gs.ecs.register::<Talks>();
+        gs.ecs.register::<Faction>();
+        gs.ecs.register::<FactionOverride>();
+        gs.ecs.register::<Category>();
+        gs.ecs.register::<Hotbar>();
+        gs.ecs.register::<Scripted>();
+        gs.ecs.register::<MovesThroughWalls>();
+        gs.ecs.register::<PackMember>();
+        gs.ecs.register::<Fleeing>();
+        gs.ecs.register::<Territory>();
+        gs.ecs.register::<Patrol>();
+        gs.ecs.register::<UsesItems>();
+        gs.ecs.register::<Afflictions>();
+        gs.ecs.register::<Afflicts>();
+        gs.ecs.register::<Cures>();
+        gs.ecs.register::<Mutations>();
+        gs.ecs.register::<Mutates>();
+        gs.ecs.register::<SkillTraining>();
+        gs.ecs.register::<Confusion>();
+        gs.ecs.register::<Decay>();
+        gs.ecs.register::<Artifact>();
+        gs.ecs.register::<WantsToThrowItem>();
+        gs.ecs.register::<Thrown>();
+        gs.ecs.register::<Weight>();
+        gs.ecs.register::<Burning>();
+        gs.ecs.register::<Flammable>();
+        gs.ecs.register::<Butcherable>();
+        gs.ecs.register::<StartsFire>();
+        gs.ecs.register::<Resistances>();
+        gs.ecs.register::<DamageOverTime>();
+        gs.ecs.register::<InflictsDot>();
+        gs.ecs.register::<Attributes>();
+        gs.ecs.register::<CriticalHit>();
+        gs.ecs.register::<Staggered>();
+        gs.ecs.register::<UsedAbility>();
+        gs.ecs.register::<BlockChance>();
+        gs.ecs.register::<ParryChance>();
+        gs.ecs.register::<ReactionCooldown>();
+        gs.ecs.register::<Pools>();
+        gs.ecs.register::<PowerAttack>();
+        gs.ecs.register::<WeaponSpecial>();
+        gs.ecs.register::<SpecialAbilities>();
+        gs.ecs.register::<Webbed>();
+        gs.ecs.register::<Boss>();
+        gs.ecs.register::<OwnedBy>();
+        gs.ecs.register::<SummonsMinion>();
+        gs.ecs.register::<Companion>();
+        gs.ecs.register::<Recruitable>();
+        gs.ecs.register::<Experience>();
+        gs.ecs.register::<Charmed>();
+        gs.ecs.register::<Charms>();
+        gs.ecs.register::<Invisible>();
+        gs.ecs.register::<CanSeeInvisible>();
+        gs.ecs.register::<GrantsInvisibility>();
+        gs.ecs.register::<GrantsSeeInvisible>();
+        gs.ecs.register::<Telepathy>();
+        gs.ecs.register::<GrantsTelepathy>();
+        gs.ecs.register::<Hidden>();
+        gs.ecs.register::<EntryTrigger>();
+        gs.ecs.register::<TeleportsRandomly>();
+        gs.ecs.register::<Blink>();
+        gs.ecs.register::<TownPortal>();
+        gs.ecs.register::<PortalLink>();
+        gs.ecs.register::<Container>();
+        gs.ecs.register::<InContainer>();
+        gs.ecs.register::<Locked>();
+        gs.ecs.register::<Key>();
+        gs.ecs.register::<Door>();
+        gs.ecs.register::<BlocksVisibility>();
+        gs.ecs.register::<HeardNoise>();
+        gs.ecs.register::<Lever>();
+        gs.ecs.register::<Wired>();
+        gs.ecs.register::<Portcullis>();
+        gs.ecs.register::<SecretPassage>();
+        gs.ecs.register::<Interactive>();
+        gs.ecs.register::<WellFed>();
+        gs.ecs.register::<HungerClock>();
+        gs.ecs.register::<ProvidesFood>();
+        gs.ecs.register::<Faith>();
+        gs.ecs.register::<Skills>();
+        gs.ecs.register::<PendingLevelUps>();
+        gs.ecs.register::<Resting>();
+        gs.ecs.register::<Travelling>();
+        gs.ecs.register::<KeenEye>();
+        gs.ecs.register::<FastHealing>();
+        gs.ecs.register::<FarSight>();
+        gs.ecs.register::<ParticleLifetime>();
+        gs.ecs.register::<Rarity>();
+        gs.ecs.register::<LootTable>();
+        gs.ecs.register::<Equippable>();
+        gs.ecs.register::<Equipped>();
+        gs.ecs.register::<MeleePowerBonus>();
+        gs.ecs.register::<DefenseBonus>();
+        gs.ecs.register::<BonusDamage>();
+        gs.ecs.register::<Regeneration>();
+        gs.ecs.register::<Affixed>();
+        gs.ecs.register::<Purse>();
+        gs.ecs.register::<Gold>();
+        gs.ecs.register::<Reagent>();
+        gs.ecs.register::<Durability>();
+        gs.ecs.register::<Repairs>();
+        gs.ecs.register::<Charges>();
+        gs.ecs.register::<Recharges>();
+        gs.ecs.register::<Unidentified>();
+        gs.ecs.register::<ConfuseOnHit>();
+        gs.ecs.register::<SimpleMarker<SerializeMe>>();
+        gs.ecs.register::<SerializationHelper>();
+
+        gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
+        gs.ecs.insert(events::GameEvents::new());
+        gs.systems.setup(&mut gs.ecs);
+        gs.audio.listen(&mut gs.ecs);
+        gs.ecs.insert(rltk::RandomNumberGenerator::new());
+        gs.ecs.insert(Ignitions::default());
+        gs.ecs.insert(Births::default());
+        gs.ecs.insert(ParticleBuilder::new());
+        gs.ecs.insert(effects::EffectQueue::default());
+        gs.ecs.insert(effects::ScriptEngine::default());
+        gs.ecs.insert(LevelTeleport::default());
+        gs.ecs.insert(camera::View::default());
+        gs.ecs.insert(animation::Animations::default());
+        gs.ecs.insert(visual_effects::VisualEffects::default());
+        gs.ecs.insert(gui::MarkerDraft::default());
+        gs.ecs.insert(gui::PickupSelection::default());
+        gs.ecs.insert(gui::Targeting::default());
+        gs.ecs.insert(undo::Snapshots::default());
+        gs.ecs.insert(camera::Screen::default());
+        gs
+    }
+
+    /// Starts over with a brand new world, so the next run can't be affected by what happened in the
+    /// last one; even the order entities are numbered in has to match for a replay to come out the same.
+    /// Only what belongs to the player rather than the run carries over.
+    fn reset_world(&mut self) {
+        let mut fresh = State::new();
+        fresh.ecs.insert(self.ecs.remove::<options::Options>().expect("No options"));
+        fresh.ecs.insert(self.ecs.remove::<achievements::Profile>().expect("No profile"));
+        fresh.ecs.insert(self.ecs.remove::<bestiary::KnownBeasts>().expect("No bestiary"));
+        fresh.ecs.insert(self.ecs.remove::<scores::ScoreTable>().expect("No score table"));
+        fresh.ecs.insert(self.ecs.remove::<wizard::WizardMode>().expect("No wizard mode"));
+        fresh.ecs.insert(self.ecs.remove::<replay::Replay>().expect("No replay"));
+        fresh.ecs.insert(*self.ecs.fetch::<RunState>());
+        self.ecs = fresh.ecs;
+        self.systems = fresh.systems;
+        self.audio.listen(&mut self.ecs);
+    }
 
+    /// Sets up a run from a seed, so that it can be played again exactly from the same seed.
+    fn begin_run(&mut self, seed : u64, challenge : challenge::Challenge) {
+        self.reset_world();
+        self.ecs.insert(rltk::RandomNumberGenerator::seeded(seed));
+        self.new_game();
+        if challenge.is_daily() {
+            // Today's town is built from today's seed, like every level after it
+            self.ecs.insert(challenge);
+            self.build_overworld();
+        }
+    }
+
+    fn run_systems(&mut self) {
+        self.systems.run(&mut self.ecs);
         self.ecs.maintain();
     }
+
+    fn new_game(&mut self) {
+        // Every run starts in town, up on the overworld, knowing nothing of potions, scrolls or wands unless the profile remembers them
+        let identified = IdentifiedItems::for_new_run(&self.ecs.fetch::<achievements::Profile>());
+        self.ecs.insert(identified);
+        self.ecs.insert(reputation::Reputation::default());
+        self.ecs.insert(difficulty::Difficulty::default());
+        self.ecs.insert(challenge::Challenge::default());
+        self.ecs.insert(undo::RunMode::default());
+        self.build_overworld();
+        self.ecs.insert(TurnCounter::default());
+        self.ecs.insert(MasterDungeonMap::new());
+        self.ecs.insert(UniqueItems::default());
+        self.ecs.insert(quests::QuestLog::default());
+        self.ecs.insert(achievements::RunRecord::default());
+        self.ecs.insert(run_stats::RunStats::default());
+        self.ecs.insert(gui::CharacterDraft::default());
+        self.ecs.insert(gamelog::GameLog::new("Welcome to Rusty Roguelike"));
+    }
+
+    /// Clears out the world and lays out the overworld, with the town, afresh.
+    fn build_overworld(&mut self) {
+        let mut to_delete = Vec::new();
+        for e in self.ecs.entities().join() {
+            to_delete.push(e);
+        }
+        for del in to_delete.iter() {
+            self.ecs.delete_entity(*del).expect("Deletion failed");
+        }
+
+        let map = self.generate_level(Branch::Overworld, 0);
+        let (player_x, player_y) = map.arrival_point();
+        self.ecs.insert(map);
+        self.ecs.insert(Point::new(player_x, player_y));
+    }
+
+    /// Puts the character from the creation screens into the world, or explains why they can't be.
+    fn create_player(&mut self, difficulty : difficulty::Difficulty) -> Result<(), String> {
+        let (name, class, deity) = {
+            let draft = self.ecs.fetch::<gui::CharacterDraft>();
+            (draft.name.clone(), draft.class.clone(), draft.deity)
+        };
+        // The starting level was built before the difficulty was known, so it is rebuilt to match
+        self.ecs.insert(difficulty);
+        if difficulty != difficulty::Difficulty::Normal {
+            self.build_overworld();
+        }
+        let start = *self.ecs.fetch::<Point>();
+        let player_entity = spawner::player(&mut self.ecs, start.x, start.y, &name, &class)?;
+        self.ecs.insert(player_entity);
+
+        let mut log = self.ecs.write_resource::<gamelog::GameLog>();
+        log.push(LogCategory::System, format!("{} the {} sets out from town ({}).", name, class, difficulty.title()));
+        if let Some(deity) = deity {
+            self.ecs.write_storage::<Faith>().insert(player_entity, Faith{ deity, piety: 0, last_prayer: None }).expect("Unable to insert faith");
+            log.push(LogCategory::System, format!("You swear yourself to {}.", deity.title()));
+        }
+        Ok(())
+    }
+
+    /// Starts a fresh run, or today's daily challenge, from the main menu. Stays on the menu if there
+    /// is no challenge to play.
+    fn start_run(&mut self, selection : gui::MainMenuSelection) -> RunState {
+        let (seed, challenge) = match selection {
+            gui::MainMenuSelection::DailyChallenge => match challenge::Challenge::today() {
+                Some(challenge) => (challenge.seed, challenge),
+                None => return RunState::MainMenu{ menu_selection: selection }
+            },
+            _ => (self.ecs.write_resource::<rltk::RandomNumberGenerator>().next_u64(), challenge::Challenge::default())
+        };
+        self.begin_run(seed, challenge.clone());
+        self.ecs.write_resource::<replay::Replay>().record(seed, challenge);
+        RunState::CharacterCreation{ step: gui::CreationStep::Name }
+    }
+
+    /// Checks before using something the player hasn't identified yet.
+    fn try_use_item(&mut self, item : Entity) -> RunState {
+        if self.ecs.read_storage::<Unidentified>().get(item).is_some() {
+            return RunState::Confirm{ action: gui::ConfirmAction::UseUnidentified{ item } };
+        }
+        self.use_item(item)
+    }
+
+    /// Ranged items need a target first; everything else is used straight away.
+    fn use_item(&mut self, item : Entity) -> RunState {
+        if let Some(ranged) = self.ecs.read_storage::<Ranged>().get(item) {
+            return RunState::ShowTargeting { range: ranged.range, item };
+        }
+        let mut intent = self.ecs.write_storage::<WantsToUseItem>();
+        intent.insert(*self.ecs.fetch::<Entity>(), WantsToUseItem { item, target: None }).expect("Unable to insert intent");
+        RunState::PlayerTurn
+    }
+
+    /// Builds a brand new level and populates it.
+    fn generate_level(&mut self, branch : Branch, depth : i32) -> Map {
+        // A daily challenge builds and fills each level from its own seed, then carries on with the usual dice
+        let level_rng = self.ecs.fetch::<challenge::Challenge>().level_rng(branch, depth);
+        let run_rng = level_rng.map(|rng| std::mem::replace(&mut *self.ecs.write_resource::<rltk::RandomNumberGenerator>(), rng));
+
+        let builder = {
+            let mut rng = self.ecs.write_resource::<rltk::RandomNumberGenerator>();
+            map_builders::build_level(branch, depth, &mut rng)
+        };
+        let map = builder.map;
+
+        if builder.spawn_list.is_empty() {
+            spawner::spawn_level(&mut self.ecs, &map);
+        } else {
+            spawner::spawn_list(&mut self.ecs, &map, &builder.spawn_list);
+        }
+        spawner::spawn_list(&mut self.ecs, &map, &builder.extra_spawns);
+        spawner::wire_mechanisms(&mut self.ecs, &map, &builder.wires);
+        for (door_idx, key_idx) in builder.locked_doors.iter() {
+            spawner::locked_door(&mut self.ecs, &map, *door_idx, *key_idx);
+        }
+        if branch == Branch::Dungeon && depth == MAX_DEPTH {
+            let (amulet_x, amulet_y) = map.farthest_point();
+            spawner::amulet(&mut self.ecs, amulet_x, amulet_y);
+        }
+
+        if let Some(rng) = run_rng {
+            self.ecs.insert(rng);
+        }
+        map
+    }
+
+    /// Follows the stairs or branch entrance the player is standing on downwards.
+    fn next_level(&mut self) {
+        let (branch, depth) = {
+            let map = self.ecs.fetch::<Map>();
+            let player_pos = self.ecs.fetch::<Point>();
+            let idx = map.xy_idx(player_pos.x, player_pos.y);
+            match map.branch_exits.get(&idx) {
+                Some(branch) => (*branch, branch.entry_depth() + 1),
+                None => (map.branch, map.depth + 1)
+            }
+        };
+        self.goto_level(branch, depth, None);
+    }
+
+    /// Climbs up, leaving a branch through its entrance once on its top level.
+    fn previous_level(&mut self) {
+        let (branch, depth) = {
+            let map = self.ecs.fetch::<Map>();
+            (map.branch, map.depth)
+        };
+        match branch.parent() {
+            Some(parent) if depth == branch.entry_depth() + 1 => self.goto_level(parent, branch.entry_depth(), None),
+            _ => self.goto_level(branch, depth - 1, None)
+        }
+    }
+
+    /// Carries out a pending teleport to another level, returning whether there was one.
+    fn level_teleport(&mut self) -> bool {
+        let destination = self.ecs.write_resource::<LevelTeleport>().destination.take();
+        match destination {
+            Some((branch, depth, arrival)) => {
+                self.goto_level(branch, depth, Some(arrival));
+                true
+            }
+            None => false
+        }
+    }
+
+    /// Moves the player to another level, arriving at `arrival` or else on the way back to the level just left.
+    fn goto_level(&mut self, branch : Branch, new_depth : i32, arrival : Option<Point>) {
+        dungeon::freeze_level_entities(&mut self.ecs);
+
+        // Remember the level we are leaving
+        let (old_branch, old_depth) = {
+            let map = self.ecs.fetch::<Map>();
+            self.ecs.write_resource::<MasterDungeonMap>().store_map(&map);
+            (map.branch, map.depth)
+        };
+
+        let stored_map = self.ecs.fetch::<MasterDungeonMap>().get_map(branch, new_depth);
+        let first_visit = stored_map.is_none();
+        let map = match stored_map {
+            Some(map) => map,
+            None => self.generate_level(branch, new_depth)
+        };
+
+        // Arrive on the entrance or staircase leading back where we came from
+        let (player_x, player_y) = arrival.map(|p| (p.x, p.y))
+            .or_else(|| map.branch_exits.iter()
+                .find(|(_, exit)| **exit == old_branch)
+                .map(|(idx, _)| (*idx as i32 % map.width, *idx as i32 / map.width)))
+            .or_else(|| map.find_tile(if new_depth > old_depth { TileType::UpStairs } else { TileType::DownStairs }))
+            .unwrap_or_else(|| map.arrival_point());
+        self.ecs.insert(map);
+        self.ecs.insert(Point::new(player_x, player_y));
+        dungeon::thaw_level_entities(&mut self.ecs);
+
+        let player_entity = *self.ecs.fetch::<Entity>();
+        {
+            let mut positions = self.ecs.write_storage::<Position>();
+            if let Some(player_pos) = positions.get_mut(player_entity) {
+                player_pos.x = player_x;
+                player_pos.y = player_y;
+            }
+            let mut viewsheds = self.ecs.write_storage::<Viewshed>();
+            if let Some(vs) = viewsheds.get_mut(player_entity) {
+                vs.dirty = true;
+            }
+
+            // Companions who followed us arrive right next to the player
+            let map = self.ecs.fetch::<Map>();
+            let entities = self.ecs.entities();
+            let mut companions = self.ecs.write_storage::<Companion>();
+            let mut free_tiles = (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (player_x + dx, player_y + dy)))
+                .filter(|(x, y)| (*x != player_x || *y != player_y) && *x > 0 && *x < map.width - 1 && *y > 0 && *y < map.height - 1)
+                .filter(|(x, y)| { let idx = map.xy_idx(*x, *y); tile_walkable(map.tiles[idx]) && !map.is_blocked(idx) });
+            for (entity, companion, vs) in (&entities, &mut companions, &mut viewsheds).join() {
+                if let (Some(pos), Some((x, y))) = (positions.get_mut(entity), free_tiles.next()) {
+                    pos.x = x;
+                    pos.y = y;
+                    vs.dirty = true;
+                    companion.command = CompanionCommand::Follow;
+                    companion.target = None;
+                }
+            }
+        }
+
+        events::publish(&self.ecs, events::GameEvent::LevelEntered{ depth: new_depth });
+        let mut gamelog = self.ecs.fetch_mut::<gamelog::GameLog>();
+        if arrival.is_some() {
+            gamelog.push(LogCategory::System, format!("You step out of the portal into {}.", branch.level_name(new_depth)));
+        } else if new_depth > old_depth {
+            gamelog.push(LogCategory::System, format!("You descend to {}.", branch.level_name(new_depth)));
+        } else {
+            gamelog.push(LogCategory::System, format!("You climb up to {}.", branch.level_name(new_depth)));
+        }
+        if first_visit {
+            let feeling = spawner::level_feeling(&self.ecs);
+            gamelog.push(LogCategory::System, feeling);
+        }
+    }
 }
 
 impl GameState for State {
     fn tick(&mut self, ctx: &mut Rltk) {
+        let mut input = Input::from_context(ctx);
+        let (width, height) = ctx.get_char_size();
+        self.ecs.insert(camera::Screen::new(width as i32, height as i32));
+        let bindings = self.ecs.fetch::<options::Options>().controller;
+        let pad_key = self.controller.poll(&bindings, input.frame_time_ms);
+        input.key = input.key.or(pad_key);
+        if let Some(result) = self.raw_watcher.poll(input.frame_time_ms) {
+            let message = match result {
+                Ok(()) => "The raws have been reloaded.".to_string(),
+                Err(err) => format!("The raws could not be reloaded: {}", err)
+            };
+            self.ecs.write_resource::<gamelog::GameLog>().push(LogCategory::System, message);
+        }
+        let playback = self.ecs.write_resource::<replay::Replay>().controls(&input);
+        let ticks = match playback {
+            replay::Playback::Run(ticks) => ticks,
+            replay::Playback::Stop => {
+                self.ecs.write_resource::<replay::Replay>().stop();
+                self.new_game();
+                self.ecs.insert(RunState::MainMenu{ menu_selection: gui::MainMenuSelection::WatchReplay });
+                input.key = None;
+                1
+            }
+        };
+        for _ in 0..ticks {
+            for console in gui::REDRAWN_CONSOLES {
+                ctx.set_active_console(console);
+                ctx.cls();
+            }
+            ctx.set_active_console(gui::MAP_CONSOLE);
+            self.run_tick(&mut input);
+            rltk::render_draw_buffer(ctx).expect("Render error");
+        }
+    }
+}
+
+impl State {
+    /// One step of the game: acts on whatever the current state is waiting for and queues up the
+    /// screen to draw. It never touches the window, so it can be driven without one.
+    fn run_tick(&mut self, ctx: &mut Input) {
+        self.ecs.write_resource::<replay::Replay>().capture(ctx);
         let mut newrunstate;
         {
             let runstate = self.ecs.fetch::<RunState>();
             newrunstate = *runstate;
         }
 
-        ctx.cls();
+        particle_system::cull_dead_particles(&mut self.ecs);
+        animation::update(&self.ecs, ctx.frame_time_ms);
+        visual_effects::update(&self.ecs, ctx.frame_time_ms);
 
         match newrunstate {
-            RunState::MainMenu { .. } => {}
+            RunState::MainMenu { .. } | RunState::OptionsMenu | RunState::AchievementsMenu | RunState::HighScores { .. } | RunState::CharacterCreation { .. } | RunState::Confirm { action: gui::ConfirmAction::OverwriteSave { .. } } | RunState::GameOver | RunState::Victory => camera::clear(&self.ecs),
             _ => {
-                draw_map(&self.ecs, ctx);
-
-                {
-                    let positions = self.ecs.read_storage::<Position>();
-                    let renderables = self.ecs.read_storage::<Renderable>();
-                    let map = self.ecs.fetch::<Map>();
-
-                    let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
-                    data.sort_by(|&a, &b| b.1.render_order.cmp(&a.1.render_order));
-                    for (pos, render) in data.iter() {
-                        let idx = map.xy_idx(pos.x, pos.y);
-                        if map.visible_tiles[idx] { ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph) }
-                    }
-
-                    gui::draw_ui(&self.ecs, ctx);
-
-                    let positions = self.ecs.read_storage::<Position>();
-                    let renderables = self.ecs.read_storage::<Renderable>();
-                    let map = self.ecs.fetch::<Map>();
-
-                    let mut data = (&positions, &renderables).join().collect::<Vec<_>>();
-                    data.sort_by(|&a, &b| b.1.render_order.cmp(&a.1.render_order));
-                    for (pos, render) in data.iter() {
-                        let idx = map.xy_idx(pos.x, pos.y);
-                        if map.visible_tiles[idx] { ctx.set(pos.x, pos.y, render.fg, render.bg, render.glyph) }
-                    }
-
-                    gui::draw_ui(&self.ecs, ctx);
-                }
+                camera::render(&self.ecs);
+                gui::draw_ui(&self.ecs, ctx);
+                minimap::draw(&self.ecs);
             }
         }
 
@@ -139,40 +722,57 @@ impl GameState for State {
             RunState::PreRun => {
                 self.run_systems();
                 self.ecs.maintain();
+                undo::take_snapshot(&mut self.ecs);
                 newrunstate = RunState::AwaitingInput;
             }
             RunState::AwaitingInput => {
-                newrunstate = player_input(self, ctx);
+                newrunstate = if level_up::has_pending(&self.ecs) { RunState::LevelUp } else { player_input(self, ctx) };
             }
             RunState::PlayerTurn => {
                 self.run_systems();
                 self.ecs.maintain();
-                newrunstate = RunState::MonsterTurn;
+                newrunstate = if self.level_teleport() { RunState::PreRun } else { RunState::MonsterTurn };
             }
             RunState::MonsterTurn => {
+                // Travelling the overworld takes a lot longer than walking a dungeon corridor, and every
+                // timed status counts down by however long the turn took
+                let turns = if self.ecs.fetch::<Map>().branch == Branch::Overworld { OVERWORLD_TRAVEL_TURNS } else { 1 };
+                self.ecs.write_resource::<TurnCounter>().advance_by(turns);
                 self.run_systems();
                 self.ecs.maintain();
-                newrunstate = RunState::AwaitingInput;
+                spawner::wandering_monster(&mut self.ecs);
+                if self.level_teleport() {
+                    newrunstate = RunState::PreRun;
+                } else {
+                    undo::take_snapshot(&mut self.ecs);
+                    newrunstate = RunState::AwaitingInput;
+                }
             }
-            RunState::ShowInventory => {
-                let result = gui::show_inventory(self, ctx);
+            RunState::ShowInventory{ view } => {
+                let result = gui::show_inventory(self, ctx, view);
                 match result.0 {
                     gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
-                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::NoResponse => newrunstate = RunState::ShowInventory{ view: result.2 },
                     gui::ItemMenuResult::Selected => {
-                        let item_entity = result.1.unwrap();
-                        let is_ranged = self.ecs.read_storage::<Ranged>();
-                        let is_item_ranged = is_ranged.get(item_entity);
-                        if let Some(is_item_ranged) = is_item_ranged {
-                            newrunstate = RunState::ShowTargeting { range: is_item_ranged.range, item: item_entity };
-                        } else {
-                            let mut intent = self.ecs.write_storage::<WantsToUseItem>();
-                            intent.insert(*self.ecs.fetch::<Entity>(), WantsToUseItem { item: item_entity, target: None }).expect("Unable to insert intent");
+                        let item = result.1.unwrap();
+                        if self.ecs.read_storage::<Equipped>().get(item).is_some() {
+                            let mut intent = self.ecs.write_storage::<WantsToRemoveItem>();
+                            intent.insert(*self.ecs.fetch::<Entity>(), WantsToRemoveItem { item }).expect("Unable to insert intent");
                             newrunstate = RunState::PlayerTurn;
+                        } else {
+                            newrunstate = self.try_use_item(item);
                         }
                     }
                 }
             }
+            RunState::ShowZapItem => {
+                let result = gui::zap_item_menu(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => newrunstate = self.try_use_item(result.1.unwrap())
+                }
+            }
             RunState::ShowDropItem => {
                 let result = gui::drop_item_menu(self, ctx);
                 match result.0 {
@@ -186,6 +786,30 @@ impl GameState for State {
                     }
                 }
             }
+            RunState::ShowRemoveItem => {
+                let result = gui::remove_item_menu(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let item_entity = result.1.unwrap();
+                        let mut intent = self.ecs.write_storage::<WantsToRemoveItem>();
+                        intent.insert(*self.ecs.fetch::<Entity>(), WantsToRemoveItem { item: item_entity }).expect("Unable to insert intent");
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::AssignHotkey{ slot } => {
+                let result = gui::hotkey_menu(self, ctx, slot);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        bind_hotkey(&mut self.ecs, slot, result.1);
+                        newrunstate = RunState::AwaitingInput;
+                    }
+                }
+            }
             RunState::ShowTargeting { range, item } => {
                 let result = gui::ranged_target(self, ctx, range);
                 match result.0 {
@@ -198,87 +822,590 @@ impl GameState for State {
                     }
                 }
             }
+            RunState::ShowThrowItem => {
+                let result = gui::throw_item_menu(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => newrunstate = RunState::ShowThrowTargeting { item: result.1.unwrap() }
+                }
+            }
+            RunState::ShowThrowTargeting { item } => {
+                let result = gui::ranged_target(self, ctx, THROW_RANGE);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let mut intent = self.ecs.write_storage::<WantsToThrowItem>();
+                        intent.insert(*self.ecs.fetch::<Entity>(), WantsToThrowItem { item, target: result.1.unwrap() }).expect("Unable to insert intent");
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::ShowShootTargeting { range } => {
+                let result = gui::ranged_target(self, ctx, range);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let mut intent = self.ecs.write_storage::<WantsToShoot>();
+                        intent.insert(*self.ecs.fetch::<Entity>(), WantsToShoot { target: result.1.unwrap() }).expect("Unable to insert intent");
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::ShowContainer { container } => {
+                let result = gui::loot_menu(self, ctx, container);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::PlayerTurn,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let player_entity = *self.ecs.fetch::<Entity>();
+                        let mut pickup = self.ecs.write_storage::<WantsToPickupItem>();
+                        pickup.insert(player_entity, WantsToPickupItem{ collected_by: player_entity, item: result.1.unwrap() }).expect("Unable to insert want to pickup");
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::ShowEnchantItem => {
+                let result = gui::enchant_item_menu(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let player_entity = *self.ecs.fetch::<Entity>();
+                        raws::enchant(&raws::raws(), &mut self.ecs, player_entity, result.1.unwrap());
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::ShowRepairItem => {
+                let result = gui::repair_item_menu(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let player_entity = *self.ecs.fetch::<Entity>();
+                        durability::blacksmith_repair(&mut self.ecs, player_entity, result.1.unwrap());
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::ShowVendor { vendor, mode } => {
+                let result = gui::vendor_menu(self, ctx, vendor, mode);
+                match result.0 {
+                    gui::VendorResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::VendorResult::NoResponse => {}
+                    gui::VendorResult::ToggleMode => {
+                        let mode = if mode == gui::VendorMode::Buy { gui::VendorMode::Sell } else { gui::VendorMode::Buy };
+                        newrunstate = RunState::ShowVendor { vendor, mode };
+                    }
+                    gui::VendorResult::Selected => {
+                        let player_entity = *self.ecs.fetch::<Entity>();
+                        match mode {
+                            gui::VendorMode::Buy => vendor::buy(&mut self.ecs, player_entity, result.1.unwrap()),
+                            gui::VendorMode::Sell => vendor::sell(&mut self.ecs, player_entity, vendor, result.1.unwrap())
+                        }
+                    }
+                }
+            }
+            RunState::ShowActions => {
+                let actions = tile_actions(&self.ecs);
+                let labels : Vec<String> = actions.iter().map(|(label, _action)| label.clone()).collect();
+                let result = gui::action_menu(ctx, &labels);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => newrunstate = perform_action(&mut self.ecs, actions[result.1.unwrap()].1)
+                }
+            }
+            RunState::ShowPickup{ nearby } => {
+                let items = items_to_pick_up(&self.ecs, nearby);
+                let result = {
+                    let mut selection = self.ecs.write_resource::<gui::PickupSelection>();
+                    gui::pickup_menu(&self.ecs, ctx, &items, nearby, &mut selection.chosen)
+                };
+                match result {
+                    gui::PickupResult::NoResponse => {}
+                    gui::PickupResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::PickupResult::ToggleNearby => newrunstate = RunState::ShowPickup{ nearby: !nearby },
+                    gui::PickupResult::Confirm => {
+                        let chosen = std::mem::take(&mut self.ecs.write_resource::<gui::PickupSelection>().chosen);
+                        if chosen.is_empty() {
+                            newrunstate = RunState::AwaitingInput;
+                        } else {
+                            pick_up_items(&mut self.ecs, &chosen);
+                            newrunstate = RunState::PlayerTurn;
+                        }
+                    }
+                }
+            }
+            RunState::ShowBlessItem { altar } => {
+                let result = gui::bless_item_menu(self, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let player_entity = *self.ecs.fetch::<Entity>();
+                        spend_use(&mut self.ecs, altar);
+                        self.ecs.write_resource::<effects::EffectQueue>()
+                            .add_effect(Some(player_entity), effects::EffectType::Bless, effects::Targets::Single{ target: result.1.unwrap() });
+                        newrunstate = RunState::PlayerTurn;
+                    }
+                }
+            }
+            RunState::LevelUp => {
+                let result = gui::level_up_menu(&self.ecs, ctx);
+                if let (gui::ItemMenuResult::Selected, Some(choice)) = result {
+                    level_up::apply(&mut self.ecs, choice);
+                    newrunstate = RunState::AwaitingInput;
+                }
+            }
+            RunState::ShowQuestOffer { giver } => {
+                let result = gui::quest_offer_menu(self, ctx, giver);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        quests::accept(&mut self.ecs, &result.1.unwrap());
+                        newrunstate = RunState::AwaitingInput;
+                    }
+                }
+            }
+            RunState::Dialogue { npc, node } => {
+                let result = gui::dialogue_menu(self, ctx, npc, node);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => newrunstate = dialogue::respond(&mut self.ecs, npc, node, result.1.unwrap())
+                }
+            }
+            RunState::ShowCharacter => {
+                if gui::character_sheet(&self.ecs, ctx) == gui::ItemMenuResult::Cancel {
+                    newrunstate = RunState::AwaitingInput;
+                }
+            }
+            RunState::WizardConsole => {
+                let result = {
+                    let mut wizard = self.ecs.write_resource::<wizard::WizardMode>();
+                    gui::wizard_console(&mut wizard.input, ctx)
+                };
+                match result {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let command = std::mem::take(&mut self.ecs.write_resource::<wizard::WizardMode>().input);
+                        wizard::run_command(&mut self.ecs, &command);
+                        newrunstate = RunState::PreRun;
+                    }
+                }
+            }
+            RunState::ShowJournal => {
+                if gui::journal(&self.ecs, ctx) == gui::ItemMenuResult::Cancel {
+                    newrunstate = RunState::AwaitingInput;
+                }
+            }
+            RunState::ShowLog{ filter } => {
+                match gui::log_viewer(&self.ecs, ctx, filter) {
+                    (gui::ItemMenuResult::Cancel, _) => newrunstate = RunState::AwaitingInput,
+                    (_, filter) => newrunstate = RunState::ShowLog{ filter }
+                }
+            }
+            RunState::ShowDiscoveries => {
+                if gui::discoveries(&self.ecs, ctx) == gui::ItemMenuResult::Cancel {
+                    newrunstate = RunState::AwaitingInput;
+                }
+            }
+            RunState::ShowBestiary{ selected } => {
+                match gui::bestiary(&self.ecs, ctx, selected) {
+                    (gui::ItemMenuResult::Cancel, _) => newrunstate = RunState::AwaitingInput,
+                    (_, selected) => newrunstate = RunState::ShowBestiary{ selected }
+                }
+            }
+            RunState::Confirm{ action } => {
+                match (gui::confirm_dialog(&self.ecs, ctx, action), action) {
+                    (gui::ConfirmResult::NoResponse, _) => {}
+                    (gui::ConfirmResult::No, gui::ConfirmAction::OverwriteSave{ selection }) => newrunstate = RunState::MainMenu{ menu_selection: selection },
+                    (gui::ConfirmResult::No, _) => newrunstate = RunState::AwaitingInput,
+                    (gui::ConfirmResult::Yes, gui::ConfirmAction::QuitRun) => newrunstate = RunState::SaveGame,
+                    (gui::ConfirmResult::Yes, gui::ConfirmAction::Descend) => newrunstate = RunState::NextLevel,
+                    (gui::ConfirmResult::Yes, gui::ConfirmAction::UseUnidentified{ item }) => newrunstate = self.use_item(item),
+                    (gui::ConfirmResult::Yes, gui::ConfirmAction::OverwriteSave{ selection }) => {
+                        saveload_system::delete_save();
+                        newrunstate = self.start_run(selection);
+                    }
+                }
+            }
+            RunState::NameMarker => {
+                let result = {
+                    let mut draft = self.ecs.write_resource::<gui::MarkerDraft>();
+                    gui::marker_entry(&mut draft.name, ctx)
+                };
+                match result {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        place_marker(&mut self.ecs);
+                        newrunstate = RunState::AwaitingInput;
+                    }
+                }
+            }
+            RunState::ShowMarkers => {
+                let result = gui::marker_menu(&self.ecs, ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => newrunstate = start_travel(&mut self.ecs, result.1.unwrap())
+                }
+            }
+            RunState::CharacterCreation { step } => {
+                match step {
+                    gui::CreationStep::Name => {
+                        let result = {
+                            let mut draft = self.ecs.write_resource::<gui::CharacterDraft>();
+                            gui::name_entry(&mut draft.name, ctx)
+                        };
+                        let daily = self.ecs.fetch::<challenge::Challenge>().is_daily();
+                        match result {
+                            gui::ItemMenuResult::Cancel if daily => {
+                                // Put back the ordinary starting level the challenge replaced
+                                self.ecs.write_resource::<replay::Replay>().stop();
+                                self.new_game();
+                                newrunstate = RunState::MainMenu{ menu_selection: gui::MainMenuSelection::DailyChallenge };
+                            }
+                            gui::ItemMenuResult::Cancel => {
+                                self.ecs.write_resource::<replay::Replay>().stop();
+                                newrunstate = RunState::MainMenu{ menu_selection: gui::MainMenuSelection::NewGame };
+                            }
+                            gui::ItemMenuResult::NoResponse => {}
+                            gui::ItemMenuResult::Selected if daily => {
+                                // Everyone takes on the challenge as the same class, with no god and at Normal
+                                let class = self.ecs.fetch::<challenge::Challenge>().class(&raws::class_list(&raws::raws())).to_string();
+                                self.ecs.write_resource::<gui::CharacterDraft>().class = class;
+                                match self.create_player(difficulty::Difficulty::Normal) {
+                                    Ok(()) => {
+                                        let date = self.ecs.fetch::<challenge::Challenge>().date.clone().unwrap_or_default();
+                                        self.ecs.write_resource::<gamelog::GameLog>().push(LogCategory::System, format!("This is the daily challenge for {}.", date));
+                                        newrunstate = RunState::PreRun;
+                                    }
+                                    Err(err) => {
+                                        rltk::console::log(format!("Warning: {}", err));
+                                        self.ecs.write_resource::<replay::Replay>().stop();
+                                        self.new_game();
+                                        newrunstate = RunState::MainMenu{ menu_selection: gui::MainMenuSelection::DailyChallenge };
+                                    }
+                                }
+                            }
+                            gui::ItemMenuResult::Selected => {
+                                self.ecs.write_resource::<gui::CharacterDraft>().classes = raws::class_list(&raws::raws());
+                                newrunstate = RunState::CharacterCreation{ step: gui::CreationStep::Class };
+                            }
+                        }
+                    }
+                    gui::CreationStep::Class => {
+                        let result = gui::class_menu(&self.ecs.fetch::<gui::CharacterDraft>().classes, ctx);
+                        match result.0 {
+                            gui::ItemMenuResult::Cancel => newrunstate = RunState::CharacterCreation{ step: gui::CreationStep::Name },
+                            gui::ItemMenuResult::NoResponse => {}
+                            gui::ItemMenuResult::Selected => {
+                                self.ecs.write_resource::<gui::CharacterDraft>().class = result.1.unwrap();
+                                newrunstate = RunState::CharacterCreation{ step: gui::CreationStep::Deity };
+                            }
+                        }
+                    }
+                    gui::CreationStep::Deity => {
+                        let result = gui::deity_menu(ctx);
+                        match result.0 {
+                            gui::ItemMenuResult::Cancel => newrunstate = RunState::CharacterCreation{ step: gui::CreationStep::Class },
+                            gui::ItemMenuResult::NoResponse => {}
+                            gui::ItemMenuResult::Selected => {
+                                self.ecs.write_resource::<gui::CharacterDraft>().deity = result.1;
+                                newrunstate = RunState::CharacterCreation{ step: gui::CreationStep::Difficulty };
+                            }
+                        }
+                    }
+                    gui::CreationStep::Difficulty => {
+                        let result = gui::difficulty_menu(ctx);
+                        match result.0 {
+                            gui::ItemMenuResult::Cancel => newrunstate = RunState::CharacterCreation{ step: gui::CreationStep::Deity },
+                            gui::ItemMenuResult::NoResponse => {}
+                            gui::ItemMenuResult::Selected => {
+                                self.ecs.write_resource::<gui::CharacterDraft>().difficulty = result.1.unwrap();
+                                newrunstate = RunState::CharacterCreation{ step: gui::CreationStep::Mode };
+                            }
+                        }
+                    }
+                    gui::CreationStep::Mode => {
+                        let result = gui::mode_menu(ctx);
+                        match result.0 {
+                            gui::ItemMenuResult::Cancel => newrunstate = RunState::CharacterCreation{ step: gui::CreationStep::Difficulty },
+                            gui::ItemMenuResult::NoResponse => {}
+                            gui::ItemMenuResult::Selected => {
+                                self.ecs.insert(result.1.unwrap());
+                                let difficulty = self.ecs.fetch::<gui::CharacterDraft>().difficulty;
+                                newrunstate = match self.create_player(difficulty) {
+                                    Ok(()) => RunState::PreRun,
+                                    Err(err) => {
+                                        rltk::console::log(format!("Warning: {}", err));
+                                        self.ecs.write_resource::<gui::CharacterDraft>().classes = raws::class_list(&raws::raws());
+                                        RunState::CharacterCreation{ step: gui::CreationStep::Class }
+                                    }
+                                };
+                            }
+                        }
+                    }
+                }
+            }
+            RunState::ShowCompanionMenu => {
+                let result = gui::companion_menu(ctx);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        match result.1.unwrap() {
+                            CompanionCommand::Attack => newrunstate = RunState::ShowCompanionTargeting,
+                            command => {
+                                minion_system::order_companions(&mut self.ecs, command, None);
+                                newrunstate = RunState::PlayerTurn;
+                            }
+                        }
+                    }
+                }
+            }
+            RunState::ShowCompanionTargeting => {
+                let range = self.ecs.read_storage::<Viewshed>().get(*self.ecs.fetch::<Entity>()).map_or(8, |v| v.range);
+                let result = gui::ranged_target(self, ctx, range);
+                match result.0 {
+                    gui::ItemMenuResult::Cancel => newrunstate = RunState::AwaitingInput,
+                    gui::ItemMenuResult::NoResponse => {}
+                    gui::ItemMenuResult::Selected => {
+                        let target = result.1.unwrap();
+                        let victim = {
+                            let map = self.ecs.fetch::<Map>();
+                            let monsters = self.ecs.read_storage::<Monster>();
+                            map.entities_at(map.xy_idx(target.x, target.y)).iter().find(|e| monsters.get(**e).is_some()).copied()
+                        };
+                        match victim {
+                            Some(victim) => {
+                                minion_system::order_companions(&mut self.ecs, CompanionCommand::Attack, Some(victim));
+                                newrunstate = RunState::PlayerTurn;
+                            }
+                            None => {
+                                self.ecs.write_resource::<gamelog::GameLog>().push(LogCategory::Combat, "There is nothing there to attack.".to_string());
+                                newrunstate = RunState::AwaitingInput;
+                            }
+                        }
+                    }
+                }
+            }
             RunState::MainMenu{ .. } => {
                 let result = menu::main_menu(self, ctx);
                 match result {
                     gui::MainMenuResult::NoSelection{ selected } => newrunstate = RunState::MainMenu{ menu_selection: selected },
                     gui::MainMenuResult::Selected{ selected } => {
                         match selected {
-                            gui::MainMenuSelection::NewGame => newrunstate = RunState::PreRun,
+                            gui::MainMenuSelection::NewGame | gui::MainMenuSelection::DailyChallenge => {
+                                newrunstate = if saveload_system::does_save_exist() {
+                                    RunState::Confirm{ action: gui::ConfirmAction::OverwriteSave{ selection: selected } }
+                                } else {
+                                    self.start_run(selected)
+                                };
+                            }
+                            gui::MainMenuSelection::WatchReplay => {
+                                if let Some(recording) = replay::Recording::load() {
+                                    self.begin_run(recording.seed, recording.challenge.clone());
+                                    self.ecs.write_resource::<replay::Replay>().play(recording);
+                                    newrunstate = RunState::CharacterCreation{ step: gui::CreationStep::Name };
+                                }
+                            }
                             gui::MainMenuSelection::LoadGame => {
+                                // A run picked up from a save can't be replayed from its start
+                                self.ecs.write_resource::<replay::Replay>().stop();
                                 saveload_system::load_game(&mut self.ecs);
                                 newrunstate = RunState::AwaitingInput;
                                 saveload_system::delete_save();
                             }
+                            gui::MainMenuSelection::Options => newrunstate = RunState::OptionsMenu,
+                            gui::MainMenuSelection::Achievements => newrunstate = RunState::AchievementsMenu,
+                            gui::MainMenuSelection::HighScores => newrunstate = RunState::HighScores{ view: scores::ScoreView::default() },
                             gui::MainMenuSelection::Quit => { ::std::process::exit(0); }
                         }
                     }
                 }
             },
+            RunState::OptionsMenu => {
+                match gui::options_menu(&self.ecs, ctx) {
+                    (gui::ItemMenuResult::Cancel, _) => newrunstate = RunState::MainMenu{ menu_selection: gui::MainMenuSelection::Options },
+                    (gui::ItemMenuResult::Selected, Some(choice)) => {
+                        let mut options = self.ecs.write_resource::<options::Options>();
+                        match choice {
+                            OptionChoice::Graphics => options.tile_graphics = !options.tile_graphics,
+                            OptionChoice::Movement => options.smooth_movement = !options.smooth_movement,
+                            OptionChoice::ScreenShake => options.screen_shake = !options.screen_shake,
+                            OptionChoice::HitFlashes => options.hit_flashes = !options.hit_flashes,
+                            OptionChoice::FieldOfView => {
+                                options.symmetric_fov = !options.symmetric_fov;
+                                visibility_system::invalidate_viewsheds(&self.ecs);
+                            }
+                            OptionChoice::Colors => {
+                                options.palette = options.palette.next();
+                                palette::set(options.palette);
+                            }
+                            OptionChoice::PickupGold => options.auto_pickup.gold = !options.auto_pickup.gold,
+                            OptionChoice::PickupAmmo => options.auto_pickup.ammo = !options.auto_pickup.ammo,
+                            OptionChoice::PickupPotions => options.auto_pickup.potions = !options.auto_pickup.potions,
+                            OptionChoice::RememberIdentified => {
+                                let mut profile = self.ecs.write_resource::<achievements::Profile>();
+                                profile.remember_identifications = !profile.remember_identifications;
+                                if let Err(err) = profile.save() {
+                                    self.ecs.write_resource::<gamelog::GameLog>().push(LogCategory::System, err);
+                                }
+                            }
+                            OptionChoice::SoundVolume => options.sound_volume = options::Options::next_volume(options.sound_volume),
+                            OptionChoice::MusicVolume => options.music_volume = options::Options::next_volume(options.music_volume),
+                            OptionChoice::Pad(action) => options.controller.rebind(action)
+                        }
+                        if let Err(err) = options.save() {
+                            self.ecs.write_resource::<gamelog::GameLog>().push(LogCategory::System, err);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            RunState::AchievementsMenu => {
+                if gui::achievements_screen(&self.ecs, ctx) == gui::ItemMenuResult::Cancel {
+                    newrunstate = RunState::MainMenu{ menu_selection: gui::MainMenuSelection::Achievements };
+                }
+            }
+            RunState::HighScores{ view } => {
+                match gui::high_scores(&self.ecs, ctx, view) {
+                    (gui::ItemMenuResult::Cancel, _) => newrunstate = RunState::MainMenu{ menu_selection: gui::MainMenuSelection::HighScores },
+                    (_, view) => newrunstate = RunState::HighScores{ view }
+                }
+            }
             RunState::SaveGame => {
-                saveload_system::save_game(&mut self.ecs);
-                newrunstate = RunState::MainMenu { menu_selection: gui::MainMenuSelection::LoadGame }
+                // If the game couldn't be saved, the run carries on rather than being lost
+                newrunstate = match saveload_system::save_game(&mut self.ecs) {
+                    Ok(()) => RunState::MainMenu { menu_selection: gui::MainMenuSelection::LoadGame },
+                    Err(err) => {
+                        self.ecs.write_resource::<gamelog::GameLog>().push(LogCategory::System, err);
+                        RunState::AwaitingInput
+                    }
+                };
+            }
+            RunState::NextLevel => {
+                self.next_level();
+                newrunstate = RunState::PreRun;
+            }
+            RunState::PreviousLevel => {
+                self.previous_level();
+                newrunstate = RunState::PreRun;
+            }
+            RunState::Victory => {
+                let result = gui::victory(&self.ecs, ctx);
+                match result {
+                    gui::GameOverResult::NoSelection => {}
+                    gui::GameOverResult::QuitToMenu => {
+                        self.new_game();
+                        newrunstate = RunState::MainMenu { menu_selection: gui::MainMenuSelection::NewGame };
+                    }
+                    gui::GameOverResult::ShowScores => {
+                        self.new_game();
+                        newrunstate = RunState::HighScores{ view: scores::ScoreView::default() };
+                    }
+                }
+            }
+            RunState::GameOver => {
+                let result = gui::game_over(&self.ecs, ctx);
+                match result {
+                    gui::GameOverResult::NoSelection => {}
+                    gui::GameOverResult::QuitToMenu => {
+                        self.new_game();
+                        newrunstate = RunState::MainMenu { menu_selection: gui::MainMenuSelection::NewGame };
+                    }
+                    gui::GameOverResult::ShowScores => {
+                        self.new_game();
+                        newrunstate = RunState::HighScores{ view: scores::ScoreView::default() };
+                    }
+                }
             }
         }
 
         {
             let mut runwriter = self.ecs.write_resource::<RunState>();
+            if *runwriter != newrunstate { gui::reset_menu_cursor(); }
             *runwriter = newrunstate;
         }
-        damage_system::delete_the_dead(&mut self.ecs);
+        // There's nobody to bury until a character has been created
+        match newrunstate {
+            RunState::MainMenu { .. } | RunState::OptionsMenu | RunState::AchievementsMenu | RunState::HighScores { .. } | RunState::CharacterCreation { .. } | RunState::Confirm { action: gui::ConfirmAction::OverwriteSave { .. } } => {}
+            _ => {
+                damage_system::delete_the_dead(&mut self.ecs);
+                self.systems.react(&mut self.ecs);
+            }
+        }
+        // The menus keep the music going, and keep up with the events so none are left to pile up
+        self.audio.update(&self.ecs);
+
+        // A finished run is kept so it can be watched again
+        if matches!(*self.ecs.fetch::<RunState>(), RunState::GameOver | RunState::Victory) {
+            let saved = self.ecs.write_resource::<replay::Replay>().finish();
+            if let Err(err) = saved {
+                self.ecs.write_resource::<gamelog::GameLog>().push(LogCategory::System, err);
+            }
+        }
+        gui::draw_replay_status(&self.ecs);
     }
 }
 
+rltk::embedded_resource!(TILE_FONT, "../resources/example_tiles.jpg");
+
+/// A game sitting at the main menu, with the player's options, achievements, bestiary and scores loaded.
+fn init_state() -> State {
+    let mut gs = State::new();
+    let options = options::Options::load();
+    palette::set(options.palette);
+    gs.ecs.insert(options);
+    gs.ecs.insert(achievements::Profile::load());
+    gs.ecs.insert(bestiary::KnownBeasts::load());
+    gs.ecs.insert(scores::ScoreTable::load());
+    gs.ecs.insert(wizard::WizardMode{ enabled: std::env::args().any(|arg| arg == "--wizard"), ..Default::default() });
+    gs.ecs.insert(replay::Replay::default());
+
+    gs.new_game();
+    gs.ecs.insert(RunState::MainMenu {menu_selection: MainMenuSelection::NewGame});
+    gs
+}
+
+/// Also the web build's entry point: wasm-bindgen starts a binary by calling its main once the page
+/// has loaded the module, and rltk draws to the page's canvas from there.
 fn main() -> rltk::BError {
     use rltk::RltkBuilder;
+
+    // --headless <script> plays through a script without opening a window, for testing
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let args : Vec<String> = std::env::args().collect();
+        if let Some(i) = args.iter().position(|arg| arg == "--headless") {
+            let path = args.get(i + 1).expect("--headless needs a script to run");
+            let script = std::fs::read_to_string(path).expect("Unable to read headless script");
+            let mut gs = init_state();
+            std::process::exit(headless::run(&mut gs, &script));
+        }
+    }
+
+    rltk::link_resource!(TILE_FONT, "resources/example_tiles.jpg");
     let mut context = RltkBuilder::simple80x50()
+        .with_font("example_tiles.jpg", 16, 16)
+        .with_sparse_console(80, 50, "example_tiles.jpg")
+        .with_fancy_console(80, 50, "terminal8x8.png")
+        .with_sparse_console(80, 50, "terminal8x8.png")
+        .with_sparse_console(80, 50, "terminal8x8.png")
         .with_title("Roguelike Tutorial")
+        .with_automatic_console_resize(true)
         .build()?;
     //context.with_post_scanlines(true);
-    let mut gs = State {
-        ecs: World::new()
-    };
-    gs.ecs.register::<Position>();
-    gs.ecs.register::<Renderable>();
-    gs.ecs.register::<Player>();
-    gs.ecs.register::<Viewshed>();
-    gs.ecs.register::<Monster>();
-    gs.ecs.register::<Name>();
-    gs.ecs.register::<BlocksTile>();
-    gs.ecs.register::<CombatStats>();
-    gs.ecs.register::<WantsToMelee>();
-    gs.ecs.register::<SufferDamage>();
-    gs.ecs.register::<Item>();
-    gs.ecs.register::<ProvidesHealing>();
-    gs.ecs.register::<InflictsDamage>();
-    gs.ecs.register::<AreaOfEffect>();
-    gs.ecs.register::<Consumable>();
-    gs.ecs.register::<Ranged>();
-    gs.ecs.register::<InBackpack>();
-    gs.ecs.register::<WantsToPickupItem>();
-    gs.ecs.register::<WantsToUseItem>();
-    gs.ecs.register::<WantsToDropItem>();
-    gs.ecs.register::<Confusion>();
-    gs.ecs.register::<SimpleMarker<SerializeMe>>();
-    gs.ecs.register::<SerializationHelper>();
-
-    gs.ecs.insert(SimpleMarkerAllocator::<SerializeMe>::new());
-
-    let map: Map = Map::new_map_rooms_and_corridors();
-    let (player_x, player_y) = map.rooms[0].center();
-
-    let player_entity = spawner::player(&mut gs.ecs, player_x, player_y);
-
-    gs.ecs.insert(rltk::RandomNumberGenerator::new());
-    for room in map.rooms.iter().skip(1) {
-        spawner::spawn_room(&mut gs.ecs, room);
-    }
-
-    gs.ecs.insert(map);
-    gs.ecs.insert(Point::new(player_x, player_y));
-    gs.ecs.insert(player_entity);
-    gs.ecs.insert(RunState::MainMenu {menu_selection: MainMenuSelection::NewGame});
-    gs.ecs.insert(gamelog::GameLog { entries: vec!["Welcome to Rusty Roguelike".to_string()] });
+    let gs = init_state();
 
     rltk::main_loop(context, gs)
-}
\ No newline at end of file
+}
@@ -1,17 +1,39 @@
-use rltk::{Algorithm2D, BaseMap, Point, RandomNumberGenerator, RGB, Rltk, SmallVec};
-use crate::{Rect};
-use std::cmp::{max, min};
-use specs::{Entity, World, WorldExt};
+use rltk::{Algorithm2D, BaseMap, Point, RGB, SmallVec};
+use crate::{Rect, Branch, spatial::SpatialIndex};
+use specs::Entity;
 use serde::{Serialize, Deserialize};
+use std::collections::{HashMap, HashSet};
 
-pub const MAP_WIDTH: usize = 80;
-pub const MAP_HEIGHT: usize = 43;
-pub const MAP_COUNT: usize = MAP_WIDTH * MAP_WIDTH;
+pub const MAP_WIDTH: i32 = 80;
+pub const MAP_HEIGHT: i32 = 43;
+pub const MAX_DEPTH: i32 = 8;
 
 #[derive(PartialEq, Copy, Clone, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
-    Floor
+    Floor,
+    DownStairs,
+    UpStairs,
+    BranchEntrance,
+    Grass,
+    Forest,
+    Mountain,
+    Water
+}
+
+pub fn tile_walkable(tt : TileType) -> bool {
+    !matches!(tt, TileType::Wall | TileType::Mountain | TileType::Water)
+}
+
+pub fn tile_opaque(tt : TileType) -> bool {
+    matches!(tt, TileType::Wall | TileType::Mountain)
+}
+
+/// Something the player saw on a tile before it went out of view.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Remembered {
+    pub glyph : rltk::FontCharType,
+    pub fg : RGB
 }
 
 #[derive(Default, Serialize, Deserialize, Clone)]
@@ -22,11 +44,21 @@ pub struct Map {
     pub height: i32,
     pub revealed_tiles : Vec<bool>,
     pub visible_tiles : Vec<bool>,
-    pub blocked : Vec<bool>,
+    pub depth : i32,
+    pub branch : Branch,
+    pub branch_exits : HashMap<usize, Branch>,
+    /// Names the player has given to tiles, to find their way back to them.
+    pub markers : HashMap<usize, String>,
+    /// The top item or creature last seen on each tile, drawn in place while the tile is out of view.
+    pub remembered : HashMap<usize, Remembered>,
 
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
-    pub tile_content : Vec<Vec<Entity>>
+    pub spatial : SpatialIndex,
+    /// Tiles that can't be seen past because of what stands on them, such as a closed door.
+    #[serde(skip_serializing)]
+    #[serde(skip_deserializing)]
+    pub view_blocked : HashSet<usize>
 }
 
 impl Algorithm2D for Map {
@@ -36,8 +68,8 @@ impl Algorithm2D for Map {
 }
 
 impl BaseMap for Map {
-    fn is_opaque(&self, _idx: usize) -> bool {
-        self.tiles[_idx] == TileType::Wall
+    fn is_opaque(&self, idx: usize) -> bool {
+        tile_opaque(self.tiles[idx]) || self.view_blocked.contains(&idx)
     }
 
     fn get_available_exits(&self, idx: usize) -> SmallVec<[(usize, f32); 10]> {
@@ -74,131 +106,149 @@ impl Map {
         (y as usize * self.width as usize) + x as usize
     }
 
-    fn apply_room_to_map(&mut self, room: &Rect) {
-        for y in room.y1 + 1 ..= room.y2 {
-            for x in room.x1 + 1 ..= room.x2 {
-                let idx = self.xy_idx(x, y);
-                self.tiles[idx] = TileType::Floor;
-            }
-        }
+    fn is_exit_valid(&self, x :i32, y: i32) -> bool {
+        if x < 1 || x > self.width - 1 || y < 1 || y > self.height - 1 {return false; }
+        let idx = self.xy_idx(x, y);
+        !self.is_blocked(idx)
     }
 
-    fn apply_horizontal_tunnel(&mut self, x1: i32, x2: i32, y: i32) {
-        for x in min(x1, x2) ..= max(x1, x2) {
-            let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < self.width as usize * self.height as usize {
-                self.tiles[idx]= TileType::Floor;
-            }
-        }
+    /// Returns the coordinates of the first tile of the given type, if there is one.
+    pub fn find_tile(&self, tile : TileType) -> Option<(i32, i32)> {
+        self.tiles.iter()
+            .position(|t| *t == tile)
+            .map(|idx| (idx as i32 % self.width, idx as i32 / self.width))
     }
 
-    fn apply_vertical_tunnel(&mut self, y1:i32, y2:i32, x:i32) {
-        for y in min(y1,y2) ..= max(y1,y2) {
-            let idx = self.xy_idx(x, y);
-            if idx > 0 && idx < self.width as usize * self.height as usize {
-                self.tiles[idx] = TileType::Floor;
-            }
-        }
+    /// Where the player lands on a fresh level: the first room, or the up stairs on maps without rooms.
+    pub fn arrival_point(&self) -> (i32, i32) {
+        self.rooms.first().map(|room| room.center())
+            .or_else(|| self.find_tile(TileType::UpStairs))
+            .unwrap_or((self.width / 2, self.height / 2))
     }
 
-    fn is_exit_valid(&self, x :i32, y: i32) -> bool {
-        if x < 1 || x > self.width - 1 || y < 1 || y > self.height - 1 {return false; }
-        let idx = self.xy_idx(x, y);
-        !self.blocked[idx]
+    /// The far end of the level: the last room, or the down stairs on maps without rooms.
+    pub fn farthest_point(&self) -> (i32, i32) {
+        self.rooms.last().map(|room| room.center())
+            .or_else(|| self.find_tile(TileType::DownStairs))
+            .unwrap_or_else(|| self.arrival_point())
     }
 
+    /// Blocks the tiles that can't be walked on, and unblocks the rest, leaving whatever stands on
+    /// them alone. Needs calling whenever tiles change between walkable and not.
     pub fn populate_blocked(&mut self) {
-        for (i,tile) in self.tiles.iter_mut().enumerate() {
-            self.blocked[i] = *tile == TileType::Wall;
+        for (i,tile) in self.tiles.iter().enumerate() {
+            self.spatial.set_solid(i, !tile_walkable(*tile));
         }
     }
 
-    pub fn clear_content_index(&mut self) {
-        for content in self.tile_content.iter_mut() {
-            content.clear();
-        }
+    /// Treats a tile as solid until blocking is next populated; for builders testing what a wall there would cut off.
+    pub fn block_tile(&mut self, idx : usize) {
+        self.spatial.set_solid(idx, true);
+    }
+
+    /// Starts a fresh spatial index, for a map that has just been made or loaded. The entities on
+    /// it are added the next time the map is indexed.
+    pub fn reset_index(&mut self) {
+        self.spatial = SpatialIndex::new(self.tile_count());
+        self.populate_blocked();
     }
 
-    pub fn new_map_rooms_and_corridors() -> Map {
-        let mut map = Map {
-            tiles : vec![TileType::Wall; MAP_COUNT],
+    pub fn is_blocked(&self, idx : usize) -> bool {
+        self.spatial.is_blocked(idx)
+    }
+
+    pub fn is_occupied(&self, idx : usize) -> bool {
+        self.spatial.is_occupied(idx)
+    }
+
+    pub fn entities_at(&self, idx : usize) -> &[Entity] {
+        self.spatial.entities_at(idx)
+    }
+
+    /// Moves an entity to another tile in the index straight away, so that others moving in the
+    /// same turn see it there.
+    pub fn move_entity(&mut self, entity : Entity, idx : usize) {
+        self.spatial.move_entity(entity, idx);
+    }
+
+    /// Adds a newly spawned entity to the index straight away, rather than waiting for the next
+    /// indexing pass to find it.
+    pub fn index_entity(&mut self, entity : Entity, idx : usize, blocks : bool) {
+        self.spatial.insert(entity, idx, blocks);
+    }
+
+    /// Number of tiles on the map.
+    pub fn tile_count(&self) -> usize {
+        (self.width * self.height) as usize
+    }
+
+    /// Creates a solid block of wall, ready to be carved up by the map builders.
+    pub fn new(branch : Branch, new_depth : i32, width : i32, height : i32) -> Map {
+        let map_count = (width * height) as usize;
+        Map {
+            tiles : vec![TileType::Wall; map_count],
             rooms : Vec::new(),
-            width : MAP_WIDTH as i32,
-            height : MAP_HEIGHT as i32,
-            revealed_tiles : vec![false; MAP_COUNT],
-            visible_tiles : vec![false; MAP_COUNT],
-            blocked : vec![false; MAP_COUNT],
-            tile_content : vec![Vec::new(); MAP_COUNT]
-        };
-        const MAX_ROOMS: i32 = 30;
-        const MIN_SIZE : i32 = 6;
-        const MAX_SIZE : i32 = 10;
-
-        let mut rng = RandomNumberGenerator::new();
-
-        for _ in 0..MAX_ROOMS {
-            let w = rng.range(MIN_SIZE, MAX_SIZE);
-            let h = rng.range(MIN_SIZE, MAX_SIZE);
-            let x = rng.roll_dice(1, MAP_WIDTH as i32 - w - 1) - 1;
-            let y = rng.roll_dice(1, MAP_HEIGHT as i32 - h - 1) - 1;
-            let new_room = Rect::new(x, y, w, h);
-            let mut ok = true;
-
-            for other_room in map.rooms.iter() {
-                if new_room.intersect(other_room) { ok = false }
-            }
-
-            if ok {
-                map.apply_room_to_map(&new_room);
-
-                if !map.rooms.is_empty() {
-                    let (new_x, new_y) = new_room.center();
-                    let (prev_x, prev_y) = map.rooms[map.rooms.len() - 1].center();
-                    if rng.range(0,2) == 1 {
-                        map.apply_horizontal_tunnel(prev_x, new_x, prev_y);
-                        map.apply_vertical_tunnel(prev_y, new_y, new_x);
-                    } else {
-                        map.apply_vertical_tunnel(prev_y, new_y, prev_x);
-                        map.apply_horizontal_tunnel(prev_x, new_x, new_y);
-                    }
-                }
-
-                map.rooms.push(new_room);
-            }
+            width,
+            height,
+            revealed_tiles : vec![false; map_count],
+            visible_tiles : vec![false; map_count],
+            depth : new_depth,
+            branch,
+            branch_exits : HashMap::new(),
+            markers : HashMap::new(),
+            remembered : HashMap::new(),
+            spatial : SpatialIndex::new(map_count),
+            view_blocked : HashSet::new()
         }
-        map
     }
 }
 
-pub fn draw_map(ecs: &World, ctx : &mut Rltk) {
-    let map = ecs.fetch::<Map>();
-
-    let mut y = 0;
-    let mut x = 0;
-    for (idx,tile) in map.tiles.iter().enumerate() {
-        // Render a tile depending upon the tile type
-        if map.revealed_tiles[idx] {
-            let glyph;
-            let mut fg;
-            match tile {
-                TileType::Floor => {
-                    glyph = rltk::to_cp437('.');
-                    fg = RGB::from_f32(0.0, 0.5, 0.5);
-                }
-                TileType::Wall => {
-                    glyph = rltk::to_cp437('#');
-                    fg = RGB::from_f32(0., 1.0, 0.);
-                }
-            }
-            if !map.visible_tiles[idx] { fg = fg.to_greyscale() }
-            ctx.set(x, y, fg, RGB::from_f32(0., 0., 0.), glyph);
+/// How the player's markers are drawn, on the map and the minimap.
+pub const MARKER_GLYPH : char = '♦';
+pub const MARKER_COLOR : (u8, u8, u8) = rltk::MAGENTA;
+
+/// The glyph and colour a revealed tile is drawn with; greyed out when it isn't in view.
+pub fn tile_glyph(idx : usize, map : &Map) -> (rltk::FontCharType, RGB) {
+    let glyph;
+    let mut fg;
+    match map.tiles[idx] {
+        TileType::Floor => {
+            glyph = rltk::to_cp437('.');
+            fg = RGB::from_f32(0.0, 0.5, 0.5);
         }
-
-        // Move the coordinates
-        x += 1;
-        if x > MAP_WIDTH - 1 {
-            x = 0;
-            y += 1;
+        TileType::Wall => {
+            glyph = rltk::to_cp437('#');
+            fg = RGB::from_f32(0., 1.0, 0.);
+        }
+        TileType::DownStairs => {
+            glyph = rltk::to_cp437('>');
+            fg = RGB::from_f32(0., 1.0, 1.0);
+        }
+        TileType::UpStairs => {
+            glyph = rltk::to_cp437('<');
+            fg = RGB::from_f32(0., 1.0, 1.0);
+        }
+        TileType::BranchEntrance => {
+            glyph = rltk::to_cp437('>');
+            fg = RGB::named(rltk::ORANGE);
+        }
+        TileType::Grass => {
+            glyph = rltk::to_cp437('"');
+            fg = RGB::named(rltk::GREEN);
+        }
+        TileType::Forest => {
+            glyph = rltk::to_cp437('♣');
+            fg = RGB::named(rltk::DARK_GREEN);
+        }
+        TileType::Mountain => {
+            glyph = rltk::to_cp437('^');
+            fg = RGB::named(rltk::GREY);
+        }
+        TileType::Water => {
+            glyph = rltk::to_cp437('~');
+            fg = RGB::named(rltk::BLUE);
         }
     }
+    if !map.visible_tiles[idx] { fg = fg.to_greyscale() }
+    (glyph, fg)
 }
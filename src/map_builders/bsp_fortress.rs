@@ -0,0 +1,88 @@
+use rltk::RandomNumberGenerator;
+use super::{InitialMapBuilder, Map};
+use super::common::{apply_room_to_map, draw_corridor};
+use super::super::{Rect, TileType};
+
+const MAX_ATTEMPTS : i32 = 240;
+
+/// Packs rooms tightly by repeatedly splitting the map into smaller rectangles.
+pub struct BspFortressBuilder {
+    rects : Vec<Rect>
+}
+
+impl InitialMapBuilder for BspFortressBuilder {
+    fn build_map(&mut self, rng : &mut RandomNumberGenerator, map : &mut Map) {
+        self.rects.clear();
+        self.rects.push(Rect::new(2, 2, map.width - 5, map.height - 5));
+        let first_room = self.rects[0];
+        self.add_subrects(first_room);
+
+        for _ in 0..MAX_ATTEMPTS {
+            let rect = self.rects[rng.roll_dice(1, self.rects.len() as i32) as usize - 1];
+            let candidate = Self::random_sub_rect(rect, rng);
+
+            if Self::is_possible(candidate, map) {
+                apply_room_to_map(map, &candidate);
+                map.rooms.push(candidate);
+                self.add_subrects(rect);
+            }
+        }
+
+        // Link the rooms from west to east
+        map.rooms.sort_by(|a, b| a.x1.cmp(&b.x1));
+        for i in 0..map.rooms.len().saturating_sub(1) {
+            let start = map.rooms[i].center();
+            let end = map.rooms[i + 1].center();
+            draw_corridor(map, rng.range(0, 2) == 1, start, end);
+        }
+    }
+}
+
+impl BspFortressBuilder {
+    pub fn new() -> Box<BspFortressBuilder> {
+        Box::new(BspFortressBuilder{ rects: Vec::new() })
+    }
+
+    fn add_subrects(&mut self, rect : Rect) {
+        let width = i32::abs(rect.x1 - rect.x2);
+        let height = i32::abs(rect.y1 - rect.y2);
+        let half_width = i32::max(width / 2, 1);
+        let half_height = i32::max(height / 2, 1);
+
+        self.rects.push(Rect::new(rect.x1, rect.y1, half_width, half_height));
+        self.rects.push(Rect::new(rect.x1, rect.y1 + half_height, half_width, half_height));
+        self.rects.push(Rect::new(rect.x1 + half_width, rect.y1, half_width, half_height));
+        self.rects.push(Rect::new(rect.x1 + half_width, rect.y1 + half_height, half_width, half_height));
+    }
+
+    fn random_sub_rect(rect : Rect, rng : &mut RandomNumberGenerator) -> Rect {
+        let rect_width = i32::abs(rect.x1 - rect.x2);
+        let rect_height = i32::abs(rect.y1 - rect.y2);
+
+        let w = i32::max(3, rng.roll_dice(1, i32::min(rect_width, 10)) - 1) + 1;
+        let h = i32::max(3, rng.roll_dice(1, i32::min(rect_height, 10)) - 1) + 1;
+        let x = rect.x1 + rng.roll_dice(1, 6) - 1;
+        let y = rect.y1 + rng.roll_dice(1, 6) - 1;
+
+        Rect::new(x, y, w, h)
+    }
+
+    /// A room fits if it and a one tile border around it are solid wall inside the map.
+    fn is_possible(rect : Rect, map : &Map) -> bool {
+        let mut expanded = rect;
+        expanded.x1 -= 2;
+        expanded.x2 += 2;
+        expanded.y1 -= 2;
+        expanded.y2 += 2;
+
+        for y in expanded.y1 ..= expanded.y2 {
+            for x in expanded.x1 ..= expanded.x2 {
+                if x < 1 || x > map.width - 2 || y < 1 || y > map.height - 2 { return false; }
+                let idx = map.xy_idx(x, y);
+                if map.tiles[idx] != TileType::Wall { return false; }
+            }
+        }
+
+        true
+    }
+}
@@ -0,0 +1,45 @@
+use rltk::RandomNumberGenerator;
+use super::{InitialMapBuilder, Map};
+use super::super::TileType;
+
+const ITERATIONS : i32 = 15;
+
+pub struct CellularAutomataBuilder {}
+
+impl InitialMapBuilder for CellularAutomataBuilder {
+    fn build_map(&mut self, rng : &mut RandomNumberGenerator, map : &mut Map) {
+        // Start with random noise, a little more than half of it floor
+        for y in 1 .. map.height - 1 {
+            for x in 1 .. map.width - 1 {
+                let idx = map.xy_idx(x, y);
+                map.tiles[idx] = if rng.roll_dice(1, 100) > 55 { TileType::Floor } else { TileType::Wall };
+            }
+        }
+
+        // Smooth the noise out into caves
+        for _ in 0..ITERATIONS {
+            let mut newtiles = map.tiles.clone();
+
+            for y in 1 .. map.height - 1 {
+                for x in 1 .. map.width - 1 {
+                    let idx = map.xy_idx(x, y);
+                    let w = map.width as usize;
+                    let neighbors = [idx - 1, idx + 1, idx - w, idx + w, idx - w - 1, idx - w + 1, idx + w - 1, idx + w + 1]
+                        .iter()
+                        .filter(|i| map.tiles[**i] == TileType::Wall)
+                        .count();
+
+                    newtiles[idx] = if neighbors > 4 || neighbors == 0 { TileType::Wall } else { TileType::Floor };
+                }
+            }
+
+            map.tiles = newtiles;
+        }
+    }
+}
+
+impl CellularAutomataBuilder {
+    pub fn new() -> Box<CellularAutomataBuilder> {
+        Box::new(CellularAutomataBuilder{})
+    }
+}
@@ -0,0 +1,40 @@
+use std::cmp::{max, min};
+use super::super::{Map, Rect, TileType};
+
+pub fn apply_room_to_map(map : &mut Map, room : &Rect) {
+    for y in room.y1 + 1 ..= room.y2 {
+        for x in room.x1 + 1 ..= room.x2 {
+            let idx = map.xy_idx(x, y);
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+pub fn apply_horizontal_tunnel(map : &mut Map, x1: i32, x2: i32, y: i32) {
+    for x in min(x1, x2) ..= max(x1, x2) {
+        let idx = map.xy_idx(x, y);
+        if idx > 0 && idx < map.width as usize * map.height as usize {
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+pub fn apply_vertical_tunnel(map : &mut Map, y1: i32, y2: i32, x: i32) {
+    for y in min(y1, y2) ..= max(y1, y2) {
+        let idx = map.xy_idx(x, y);
+        if idx > 0 && idx < map.width as usize * map.height as usize {
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+}
+
+/// Joins two points with an L-shaped corridor, randomly picking which leg comes first.
+pub fn draw_corridor(map : &mut Map, horizontal_first : bool, (x1, y1) : (i32, i32), (x2, y2) : (i32, i32)) {
+    if horizontal_first {
+        apply_horizontal_tunnel(map, x1, x2, y1);
+        apply_vertical_tunnel(map, y1, y2, x2);
+    } else {
+        apply_vertical_tunnel(map, y1, y2, x1);
+        apply_horizontal_tunnel(map, x1, x2, y2);
+    }
+}
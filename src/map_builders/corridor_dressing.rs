@@ -0,0 +1,144 @@
+use rltk::RandomNumberGenerator;
+use super::{MetaMapBuilder, Map};
+use super::super::{TileType, tile_walkable};
+
+/// One corridor tile in this many is strewn with rubble.
+const RUBBLE_ODDS : i32 = 30;
+/// One tile in this many along a room's walls gets something to look at.
+const DECOR_ODDS : i32 = 25;
+const DECORATIONS : [&str; 3] = ["Rubble", "Bones", "Cobwebs"];
+
+/// Dresses up rooms and corridors: squeezes the wide junctions where corridors cross back down to
+/// a single tile, hangs doors in some of the doorways, and leaves rubble and the odd grim reminder
+/// lying about.
+pub struct CorridorDressing {
+    door_chance : i32,
+    spawns : Vec<(usize, String)>
+}
+
+impl MetaMapBuilder for CorridorDressing {
+    fn build_map(&mut self, rng : &mut RandomNumberGenerator, map : &mut Map) {
+        if map.rooms.is_empty() { return; }
+        let mut corridors : Vec<bool> = (0 .. map.tiles.len())
+            .map(|idx| map.tiles[idx] == TileType::Floor && !in_room(map, idx))
+            .collect();
+
+        narrow_junctions(map, rng, &mut corridors);
+        let doors = self.hang_doors(map, rng);
+        for idx in doors.iter() {
+            self.spawns.push((*idx, "Door".to_string()));
+        }
+
+        for (idx, tile) in map.tiles.iter().enumerate() {
+            if *tile != TileType::Floor || doors.contains(&idx) { continue; }
+            if corridors[idx] {
+                if rng.roll_dice(1, RUBBLE_ODDS) == 1 {
+                    self.spawns.push((idx, "Rubble".to_string()));
+                }
+            } else if beside_wall(map, idx) && rng.roll_dice(1, DECOR_ODDS) == 1 {
+                let decoration = DECORATIONS[(rng.roll_dice(1, DECORATIONS.len() as i32) - 1) as usize];
+                self.spawns.push((idx, decoration.to_string()));
+            }
+        }
+    }
+
+    fn spawn_list(&self) -> Vec<(usize, String)> {
+        self.spawns.clone()
+    }
+}
+
+impl CorridorDressing {
+    /// `door_chance` is the percentage of doorways that get a door.
+    pub fn new(door_chance : i32) -> Box<CorridorDressing> {
+        Box::new(CorridorDressing{ door_chance, spawns : Vec::new() })
+    }
+
+    /// Openings in a room's wall with wall either side, so a door fits; some of them get one.
+    /// Kept in the order they were found, so the doors spawn in the same order every time.
+    fn hang_doors(&self, map : &Map, rng : &mut RandomNumberGenerator) -> Vec<usize> {
+        let mut doorways : Vec<usize> = Vec::new();
+        for room in map.rooms.iter() {
+            for x in room.x1 + 1 ..= room.x2 {
+                for y in [room.y1, room.y2 + 1].iter() {
+                    if is_doorway(map, x, *y, (1, 0)) { doorways.push(map.xy_idx(x, *y)); }
+                }
+            }
+            for y in room.y1 + 1 ..= room.y2 {
+                for x in [room.x1, room.x2 + 1].iter() {
+                    if is_doorway(map, *x, y, (0, 1)) { doorways.push(map.xy_idx(*x, y)); }
+                }
+            }
+        }
+
+        let mut doors = Vec::new();
+        for idx in doorways {
+            if !doors.contains(&idx) && !in_room(map, idx) && rng.roll_dice(1, 100) <= self.door_chance {
+                doors.push(idx);
+            }
+        }
+        doors
+    }
+}
+
+/// Inside one of the rooms, rather than on its wall or out in a corridor.
+fn in_room(map : &Map, idx : usize) -> bool {
+    let (x, y) = (idx as i32 % map.width, idx as i32 / map.width);
+    map.rooms.iter().any(|room| x > room.x1 && x <= room.x2 && y > room.y1 && y <= room.y2)
+}
+
+/// A floor tile in a room's wall, with solid wall on both sides along it.
+fn is_doorway(map : &Map, x : i32, y : i32, (dx, dy) : (i32, i32)) -> bool {
+    if x < 1 || y < 1 || x >= map.width - 1 || y >= map.height - 1 { return false; }
+    map.tiles[map.xy_idx(x, y)] == TileType::Floor
+        && !tile_walkable(map.tiles[map.xy_idx(x - dx, y - dy)])
+        && !tile_walkable(map.tiles[map.xy_idx(x + dx, y + dy)])
+}
+
+fn beside_wall(map : &Map, idx : usize) -> bool {
+    let (x, y) = (idx as i32 % map.width, idx as i32 / map.width);
+    if x < 1 || y < 1 || x >= map.width - 1 || y >= map.height - 1 { return false; }
+    [(-1, 0), (1, 0), (0, -1), (0, 1)].iter()
+        .any(|(dx, dy)| map.tiles[map.xy_idx(x + dx, y + dy)] == TileType::Wall)
+}
+
+/// Where corridors cross or run alongside each other they leave open squares of floor. Each one
+/// has a tile walled back in, so long as everything around it stays connected without it.
+fn narrow_junctions(map : &mut Map, rng : &mut RandomNumberGenerator, corridors : &mut [bool]) {
+    for y in 1 .. map.height - 2 {
+        for x in 1 .. map.width - 2 {
+            let square = [map.xy_idx(x, y), map.xy_idx(x + 1, y), map.xy_idx(x, y + 1), map.xy_idx(x + 1, y + 1)];
+            if !square.iter().all(|idx| corridors[*idx]) { continue; }
+            let start = (rng.roll_dice(1, 4) - 1) as usize;
+            for i in 0 .. 4 {
+                let idx = square[(start + i) % 4];
+                if can_fill(map, idx) {
+                    map.tiles[idx] = TileType::Wall;
+                    corridors[idx] = false;
+                    break;
+                }
+            }
+        }
+    }
+}
+
+/// Whether walling in the tile leaves its walkable neighbours joined up, stepping only straight
+/// across from one to the next, so no way through is lost.
+fn can_fill(map : &Map, idx : usize) -> bool {
+    let (x, y) = (idx as i32 % map.width, idx as i32 / map.width);
+    let open : Vec<(i32, i32)> = (-1 ..= 1).flat_map(|dy| (-1 ..= 1).map(move |dx| (dx, dy)))
+        .filter(|(dx, dy)| (*dx, *dy) != (0, 0) && tile_walkable(map.tiles[map.xy_idx(x + dx, y + dy)]))
+        .collect();
+    if open.is_empty() { return false; }
+
+    let mut reached = vec![open[0]];
+    let mut frontier = vec![open[0]];
+    while let Some((cx, cy)) = frontier.pop() {
+        for next in open.iter() {
+            if !reached.contains(next) && (next.0 - cx).abs() + (next.1 - cy).abs() == 1 {
+                reached.push(*next);
+                frontier.push(*next);
+            }
+        }
+    }
+    reached.len() == open.len()
+}
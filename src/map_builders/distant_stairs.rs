@@ -0,0 +1,53 @@
+use rltk::RandomNumberGenerator;
+use super::{MetaMapBuilder, Map};
+use super::super::TileType;
+
+/// For room-less maps: enters from the west, walls off anything unreachable
+/// and puts the way down on the farthest reachable tile.
+pub struct DistantStairs {}
+
+impl MetaMapBuilder for DistantStairs {
+    fn build_map(&mut self, _rng : &mut RandomNumberGenerator, map : &mut Map) {
+        // Start as close to the middle of the west edge as possible
+        let mut start_x = 1;
+        let start_y = map.height / 2;
+        let mut start_idx = map.xy_idx(start_x, start_y);
+        while map.tiles[start_idx] != TileType::Floor {
+            start_x += 1;
+            if start_x > map.width - 2 {
+                // Nothing on this row; open up the starting point instead
+                start_x = map.width / 2;
+                start_idx = map.xy_idx(start_x, start_y);
+                map.tiles[start_idx] = TileType::Floor;
+                break;
+            }
+            start_idx = map.xy_idx(start_x, start_y);
+        }
+
+        map.populate_blocked();
+        let dijkstra_map = rltk::DijkstraMap::new(map.width as usize, map.height as usize, &[start_idx], &*map, 1000.0);
+
+        let mut exit_tile = (start_idx, 0.0f32);
+        for (idx, tile) in map.tiles.iter_mut().enumerate() {
+            if *tile == TileType::Floor {
+                let distance_to_start = dijkstra_map.map[idx];
+                if distance_to_start == f32::MAX {
+                    *tile = TileType::Wall;
+                } else if distance_to_start > exit_tile.1 {
+                    exit_tile = (idx, distance_to_start);
+                }
+            }
+        }
+
+        map.tiles[start_idx] = TileType::UpStairs;
+        if map.depth < map.branch.max_depth() {
+            map.tiles[exit_tile.0] = TileType::DownStairs;
+        }
+    }
+}
+
+impl DistantStairs {
+    pub fn new() -> Box<DistantStairs> {
+        Box::new(DistantStairs{})
+    }
+}
@@ -0,0 +1,69 @@
+use rltk::RandomNumberGenerator;
+use super::{MetaMapBuilder, Map};
+use super::super::TileType;
+
+/// Seals off a room that has a single way in behind a locked door, and hides
+/// the matching key somewhere still reachable from the up stairs.
+pub struct LockedDoors {
+    chance : i32,
+    locked_doors : Vec<(usize, usize)>
+}
+
+impl MetaMapBuilder for LockedDoors {
+    fn build_map(&mut self, rng : &mut RandomNumberGenerator, map : &mut Map) {
+        if rng.roll_dice(1, 100) > self.chance { return; }
+        let start_idx = match map.find_tile(TileType::UpStairs) {
+            Some((x, y)) => map.xy_idx(x, y),
+            None => return
+        };
+
+        // Rooms with exactly one opening in the surrounding wall
+        let mut candidates : Vec<(usize, usize)> = Vec::new();
+        for room in map.rooms.iter() {
+            if room.contains(start_idx as i32 % map.width, start_idx as i32 / map.width) { continue; }
+            let mut openings : Vec<usize> = Vec::new();
+            for x in room.x1 + 1 ..= room.x2 {
+                for y in [room.y1, room.y2 + 1].iter() {
+                    openings.push(map.xy_idx(x, *y));
+                }
+            }
+            for y in room.y1 + 1 ..= room.y2 {
+                for x in [room.x1, room.x2 + 1].iter() {
+                    openings.push(map.xy_idx(*x, y));
+                }
+            }
+            openings.retain(|idx| map.tiles[*idx] == TileType::Floor);
+            if openings.len() == 1 {
+                let (cx, cy) = room.center();
+                candidates.push((openings[0], map.xy_idx(cx, cy)));
+            }
+        }
+        if candidates.is_empty() { return; }
+        let (door_idx, inside_idx) = candidates[(rng.roll_dice(1, candidates.len() as i32) - 1) as usize];
+
+        // Make sure the door really cuts the room off, and find where the key can go
+        map.populate_blocked();
+        map.block_tile(door_idx);
+        let dijkstra_map = rltk::DijkstraMap::new(map.width as usize, map.height as usize, &[start_idx], &*map, 1000.0);
+        map.populate_blocked();
+        if dijkstra_map.map[inside_idx] != f32::MAX { return; }
+
+        let key_spots : Vec<usize> = (0..map.tiles.len())
+            .filter(|idx| map.tiles[*idx] == TileType::Floor && *idx != start_idx && dijkstra_map.map[*idx] != f32::MAX)
+            .collect();
+        if key_spots.is_empty() { return; }
+        let key_idx = key_spots[(rng.roll_dice(1, key_spots.len() as i32) - 1) as usize];
+        self.locked_doors.push((door_idx, key_idx));
+    }
+
+    fn locked_doors(&self) -> Vec<(usize, usize)> {
+        self.locked_doors.clone()
+    }
+}
+
+impl LockedDoors {
+    /// `chance` is the percentage of levels that get a locked room.
+    pub fn new(chance : i32) -> Box<LockedDoors> {
+        Box::new(LockedDoors{ chance, locked_doors : Vec::new() })
+    }
+}
@@ -0,0 +1,154 @@
+use rltk::RandomNumberGenerator;
+use super::{Map, Branch, MAP_WIDTH, MAP_HEIGHT};
+
+mod common;
+mod rooms_and_corridors;
+mod cellular_automata;
+mod bsp_fortress;
+mod sewer_tunnels;
+mod room_based_stairs;
+mod distant_stairs;
+mod overworld;
+mod prefab_vault;
+mod locked_doors;
+mod corridor_dressing;
+mod reachability;
+
+use rooms_and_corridors::RoomsAndCorridorsBuilder;
+use cellular_automata::CellularAutomataBuilder;
+use bsp_fortress::BspFortressBuilder;
+use sewer_tunnels::SewerTunnels;
+use room_based_stairs::RoomBasedStairs;
+use distant_stairs::DistantStairs;
+use overworld::OverworldBuilder;
+use prefab_vault::{PrefabVault, PuzzleVaults, SPIDER_LAIR, WARLORD_HALL};
+use locked_doors::LockedDoors;
+use corridor_dressing::CorridorDressing;
+
+/// Layouts tried for a level before settling for one that failed validation.
+const MAX_BUILD_ATTEMPTS : i32 = 5;
+
+/// Carves the basic layout into a blank map.
+pub trait InitialMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, map : &mut Map);
+
+    /// Entities the builder wants placed at specific tiles, instead of the random spawns.
+    fn spawn_list(&self) -> Vec<(usize, String)> { Vec::new() }
+}
+
+/// Modifies a map that has already been laid out.
+pub trait MetaMapBuilder {
+    fn build_map(&mut self, rng: &mut RandomNumberGenerator, map : &mut Map);
+
+    /// Locked doors the builder placed, each paired with the tile its key goes on.
+    fn locked_doors(&self) -> Vec<(usize, usize)> { Vec::new() }
+
+    /// Entities to place at specific tiles on top of the usual spawns.
+    fn spawn_list(&self) -> Vec<(usize, String)> { Vec::new() }
+
+    /// Links from a trigger's tile to the tile of a mechanism it works.
+    fn wires(&self) -> Vec<(usize, usize)> { Vec::new() }
+}
+
+pub struct BuilderChain {
+    starter : Box<dyn InitialMapBuilder>,
+    builders : Vec<Box<dyn MetaMapBuilder>>,
+    pub map : Map,
+    pub spawn_list : Vec<(usize, String)>,
+    pub locked_doors : Vec<(usize, usize)>,
+    pub extra_spawns : Vec<(usize, String)>,
+    pub wires : Vec<(usize, usize)>
+}
+
+impl BuilderChain {
+    pub fn new(branch : Branch, depth : i32, starter : Box<dyn InitialMapBuilder>) -> BuilderChain {
+        BuilderChain{
+            starter,
+            builders : Vec::new(),
+            map : Map::new(branch, depth, MAP_WIDTH, MAP_HEIGHT),
+            spawn_list : Vec::new(),
+            locked_doors : Vec::new(),
+            extra_spawns : Vec::new(),
+            wires : Vec::new()
+        }
+    }
+
+    /// Lays the level out over a map of a different size; the camera scrolls over anything bigger than the screen.
+    pub fn with_size(mut self, width : i32, height : i32) -> BuilderChain {
+        self.map = Map::new(self.map.branch, self.map.depth, width, height);
+        self
+    }
+
+    pub fn with(mut self, metabuilder : Box<dyn MetaMapBuilder>) -> BuilderChain {
+        self.builders.push(metabuilder);
+        self
+    }
+
+    /// Builds the level, then makes sure it can be won. False if it couldn't be, and should be
+    /// built again from scratch.
+    pub fn build_map(&mut self, rng : &mut RandomNumberGenerator) -> bool {
+        self.starter.build_map(rng, &mut self.map);
+        for metabuilder in self.builders.iter_mut() {
+            metabuilder.build_map(rng, &mut self.map);
+            self.locked_doors.extend(metabuilder.locked_doors());
+            self.extra_spawns.extend(metabuilder.spawn_list());
+            self.wires.extend(metabuilder.wires());
+        }
+        // A locked door takes the place of whatever else was meant for its doorway
+        let locked_doors = &self.locked_doors;
+        self.extra_spawns.retain(|(idx, _name)| !locked_doors.iter().any(|(door, _key)| door == idx));
+        self.spawn_list = self.starter.spawn_list();
+
+        // Whatever a wire works is left to the puzzle: a secret passage is meant to stay shut until found
+        let mechanisms : Vec<usize> = self.wires.iter().map(|(_trigger, mechanism)| *mechanism).collect();
+        let placed : Vec<usize> = self.spawn_list.iter().chain(self.extra_spawns.iter()).map(|(idx, _name)| *idx)
+            .filter(|idx| !mechanisms.contains(idx))
+            .collect();
+        reachability::ensure_reachable(&mut self.map, rng, &placed, &self.locked_doors, &mechanisms)
+    }
+}
+
+/// Builds a level for the branch, starting over with a fresh layout whenever one comes out that
+/// can't be won even after repairs. Should every attempt fail, the last one is used as it is.
+pub fn build_level(branch : Branch, depth : i32, rng : &mut RandomNumberGenerator) -> BuilderChain {
+    let mut builder = level_builder(branch, depth);
+    for _attempt in 1 .. MAX_BUILD_ATTEMPTS {
+        if builder.build_map(rng) { return builder; }
+        rltk::console::log(format!("Warning: rebuilding unwinnable level {} of {:?}.", depth, branch));
+        builder = level_builder(branch, depth);
+    }
+    builder.build_map(rng);
+    builder
+}
+
+/// Picks the builder chain matching the theme of a dungeon branch.
+fn level_builder(branch : Branch, depth : i32) -> BuilderChain {
+    // The bottom of some branches is a boss lair
+    if depth == branch.max_depth() {
+        match branch {
+            Branch::Caverns => return BuilderChain::new(branch, depth, PrefabVault::new(SPIDER_LAIR)),
+            Branch::Fortress => return BuilderChain::new(branch, depth, PrefabVault::new(WARLORD_HALL)),
+            _ => {}
+        }
+    }
+
+    match branch {
+        Branch::Dungeon => BuilderChain::new(branch, depth, RoomsAndCorridorsBuilder::new(30, 6, 10))
+            .with(RoomBasedStairs::new())
+            .with(PuzzleVaults::new(30))
+            .with(CorridorDressing::new(40))
+            .with(LockedDoors::new(40)),
+        Branch::Caverns => BuilderChain::new(branch, depth, CellularAutomataBuilder::new())
+            .with_size(100, 100)
+            .with(DistantStairs::new()),
+        Branch::Sewers => BuilderChain::new(branch, depth, RoomsAndCorridorsBuilder::new(20, 4, 6))
+            .with(SewerTunnels::new(6))
+            .with(RoomBasedStairs::new())
+            .with(CorridorDressing::new(15)),
+        Branch::Fortress => BuilderChain::new(branch, depth, BspFortressBuilder::new())
+            .with(RoomBasedStairs::new())
+            .with(PuzzleVaults::new(30))
+            .with(LockedDoors::new(60)),
+        Branch::Overworld => BuilderChain::new(branch, depth, OverworldBuilder::new())
+    }
+}
@@ -0,0 +1,60 @@
+use rltk::RandomNumberGenerator;
+use super::{InitialMapBuilder, Map};
+use super::common::{apply_room_to_map, draw_corridor};
+use super::super::{Branch, Rect, TileType};
+
+/// Wilderness around the town, shaped by fractal noise, with roads leading
+/// out to the entrances of the dungeons.
+pub struct OverworldBuilder {}
+
+impl InitialMapBuilder for OverworldBuilder {
+    fn build_map(&mut self, rng : &mut RandomNumberGenerator, map : &mut Map) {
+        let mut noise = rltk::FastNoise::seeded(rng.roll_dice(1, 65536) as u64);
+        noise.set_noise_type(rltk::NoiseType::PerlinFractal);
+        noise.set_fractal_type(rltk::FractalType::FBM);
+        noise.set_fractal_octaves(5);
+        noise.set_fractal_gain(0.6);
+        noise.set_fractal_lacunarity(2.0);
+        noise.set_frequency(2.0);
+
+        for y in 1 .. map.height - 1 {
+            for x in 1 .. map.width - 1 {
+                let n = noise.get_noise(x as f32 / 160.0, y as f32 / 100.0);
+                let idx = map.xy_idx(x, y);
+                map.tiles[idx] = if n < -0.25 {
+                    TileType::Water
+                } else if n < 0.15 {
+                    TileType::Grass
+                } else if n < 0.35 {
+                    TileType::Forest
+                } else {
+                    TileType::Mountain
+                };
+            }
+        }
+
+        // The town sits in the middle of the map
+        let town = Rect::new(map.width / 2 - 8, map.height / 2 - 5, 16, 10);
+        apply_room_to_map(map, &town);
+        map.rooms.push(town);
+
+        // Dungeon entrances, each at the end of a road out of town
+        let entrances = [
+            (Branch::Dungeon, map.width - 6, rng.range(4, map.height - 4)),
+            (Branch::Caverns, 5, rng.range(4, map.height / 2)),
+            (Branch::Sewers, rng.range(map.width / 2 - 6, map.width / 2 + 6), map.height - 4)
+        ];
+        for (branch, x, y) in entrances.iter() {
+            draw_corridor(map, rng.range(0, 2) == 1, town.center(), (*x, *y));
+            let idx = map.xy_idx(*x, *y);
+            map.tiles[idx] = TileType::BranchEntrance;
+            map.branch_exits.insert(idx, *branch);
+        }
+    }
+}
+
+impl OverworldBuilder {
+    pub fn new() -> Box<OverworldBuilder> {
+        Box::new(OverworldBuilder{})
+    }
+}
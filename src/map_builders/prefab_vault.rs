@@ -0,0 +1,221 @@
+use rltk::RandomNumberGenerator;
+use super::{InitialMapBuilder, MetaMapBuilder, Map};
+use super::super::{TileType, Rect};
+
+/// A hand-drawn level. `#` wall, `.` floor, `~` water, `<` up stairs;
+/// any other character is a floor tile with the named spawn from `spawns` on it.
+pub struct PrefabLevel {
+    pub template : &'static str,
+    pub spawns : &'static [(char, &'static str)]
+}
+
+pub const SPIDER_LAIR : PrefabLevel = PrefabLevel {
+    template : "
+##########################################
+#<.......#########.......#########.......#
+#........#.......#.......#.......#.......#
+#...............s...........s............#
+#........#.......#.......#.......#.......#
+####.#####.......####.####.......#####.###
+####.#############.......#############.###
+####.........~~~...........~~~.........###
+#######.......~~.....Q.....~~.......######
+####.........~~~...........~~~.........###
+####.#############.......#############.###
+####.#####.......####.####.......#####.###
+#........#.......#.......#.......#.......#
+#...............s...........s............#
+#........#.......#.......#.......#.......#
+##########################################
+",
+    spawns : &[('Q', "Spider Queen"), ('s', "Giant Spider")]
+};
+
+pub const WARLORD_HALL : PrefabLevel = PrefabLevel {
+    template : "
+##########################################
+#<.....#..........................#......#
+#......#..o....................o..#......#
+#......#..........................#......#
+#..........######........######..........#
+#......#..######..........######..#......#
+#......#..........W...............#......#
+#......#..######..........######..#......#
+#..........######........######..........#
+#......#..........................#......#
+#......#..o....................o..#......#
+#......#..........................#......#
+##########################################
+",
+    spawns : &[('W', "Orc Warlord"), ('o', "Orc Guard")]
+};
+
+/// Stamps a prefab into the middle of the map and remembers where its inhabitants go.
+pub struct PrefabVault {
+    level : PrefabLevel,
+    spawn_list : Vec<(usize, String)>
+}
+
+impl InitialMapBuilder for PrefabVault {
+    fn build_map(&mut self, _rng : &mut RandomNumberGenerator, map : &mut Map) {
+        let lines : Vec<&str> = self.level.template.lines().filter(|l| !l.is_empty()).collect();
+        let height = lines.len() as i32;
+        let width = lines.iter().map(|l| l.len()).max().unwrap_or(0) as i32;
+        let offset_x = (map.width - width) / 2;
+        let offset_y = (map.height - height) / 2;
+
+        for (y, line) in lines.iter().enumerate() {
+            for (x, glyph) in line.chars().enumerate() {
+                let idx = map.xy_idx(offset_x + x as i32, offset_y + y as i32);
+                map.tiles[idx] = match glyph {
+                    '#' => TileType::Wall,
+                    '~' => TileType::Water,
+                    '<' => TileType::UpStairs,
+                    _ => TileType::Floor
+                };
+                if let Some((_, name)) = self.level.spawns.iter().find(|(c, _)| *c == glyph) {
+                    self.spawn_list.push((idx, name.to_string()));
+                }
+            }
+        }
+        map.rooms.push(Rect::new(offset_x, offset_y, width, height));
+    }
+
+    fn spawn_list(&self) -> Vec<(usize, String)> {
+        self.spawn_list.clone()
+    }
+}
+
+impl PrefabVault {
+    pub fn new(level : PrefabLevel) -> Box<PrefabVault> {
+        Box::new(PrefabVault{ level, spawn_list : Vec::new() })
+    }
+}
+
+/// A small hand-drawn room stamped into part of an existing room. Uses the
+/// same legend as `PrefabLevel`, plus `%` for a secret passage: a wall until
+/// opened. `wires` links everything spawned on one glyph to everything on another.
+pub struct PrefabRoom {
+    pub template : &'static str,
+    pub spawns : &'static [(char, &'static str)],
+    pub wires : &'static [(char, char)]
+}
+
+pub const GATED_TREASURY : PrefabRoom = PrefabRoom {
+    template : "
+#######
+#c...c#
+###P###
+..t.t..
+.......
+L..p...
+",
+    spawns : &[('c', "Chest"), ('P', "Portcullis"), ('t', "Teleport Trap"), ('L', "Lever"), ('p', "Pressure Plate")],
+    wires : &[('L', 'P'), ('p', 't')]
+};
+
+pub const HIDDEN_CACHE : PrefabRoom = PrefabRoom {
+    template : "
+#####
+#.c.#
+##%##
+.....
+..p..
+",
+    spawns : &[('c', "Chest"), ('%', "Secret Passage"), ('p', "Pressure Plate")],
+    wires : &[('p', '%')]
+};
+
+const PUZZLE_ROOMS : [PrefabRoom; 2] = [GATED_TREASURY, HIDDEN_CACHE];
+
+/// Turns a quiet corner of one room into a puzzle vault, as long as doing so
+/// doesn't cut anything else off from the up stairs.
+pub struct PuzzleVaults {
+    chance : i32,
+    spawn_list : Vec<(usize, String)>,
+    wires : Vec<(usize, usize)>
+}
+
+impl MetaMapBuilder for PuzzleVaults {
+    fn build_map(&mut self, rng : &mut RandomNumberGenerator, map : &mut Map) {
+        if rng.roll_dice(1, 100) > self.chance { return; }
+        let start_idx = match map.find_tile(TileType::UpStairs) {
+            Some((x, y)) => map.xy_idx(x, y),
+            None => return
+        };
+        let room = &PUZZLE_ROOMS[(rng.roll_dice(1, PUZZLE_ROOMS.len() as i32) - 1) as usize];
+        let lines : Vec<&str> = room.template.lines().filter(|l| !l.is_empty()).collect();
+        let height = lines.len() as i32;
+        let width = lines.iter().map(|l| l.len()).max().unwrap_or(0) as i32;
+
+        let reachable_before = reachable_tiles(map, start_idx, &[]);
+        for target in map.rooms.clone().iter().skip(1) {
+            // The vault sits in the top left of the room's floor, clear of its centre
+            let (x0, y0) = (target.x1 + 1, target.y1 + 1);
+            let (_, cy) = target.center();
+            if target.x2 - target.x1 < width || target.y2 - target.y1 < height || cy < y0 + height { continue; }
+
+            let original = map.tiles.clone();
+            let mut spawn_list : Vec<(usize, String)> = Vec::new();
+            let mut glyphs : Vec<(usize, char)> = Vec::new();
+            for (y, line) in lines.iter().enumerate() {
+                for (x, glyph) in line.chars().enumerate() {
+                    let idx = map.xy_idx(x0 + x as i32, y0 + y as i32);
+                    map.tiles[idx] = match glyph {
+                        '#' | '%' => TileType::Wall,
+                        _ => TileType::Floor
+                    };
+                    glyphs.push((idx, glyph));
+                    if let Some((_, name)) = room.spawns.iter().find(|(c, _)| *c == glyph) {
+                        spawn_list.push((idx, name.to_string()));
+                    }
+                }
+            }
+
+            // Gates and levers block the way until they're dealt with
+            let blockers : Vec<usize> = glyphs.iter().filter(|(_, g)| *g == 'P' || *g == 'L').map(|(idx, _)| *idx).collect();
+            let reachable_after = reachable_tiles(map, start_idx, &blockers);
+            let cut_off = (0..map.tiles.len()).any(|idx| reachable_before[idx] && !reachable_after[idx] && !glyphs.iter().any(|(tile, _)| *tile == idx));
+            if cut_off {
+                map.tiles = original;
+                continue;
+            }
+
+            for (from, to) in room.wires.iter() {
+                for (from_idx, _) in glyphs.iter().filter(|(_, g)| g == from) {
+                    for (to_idx, _) in glyphs.iter().filter(|(_, g)| g == to) {
+                        self.wires.push((*from_idx, *to_idx));
+                    }
+                }
+            }
+            self.spawn_list = spawn_list;
+            return;
+        }
+    }
+
+    fn spawn_list(&self) -> Vec<(usize, String)> {
+        self.spawn_list.clone()
+    }
+
+    fn wires(&self) -> Vec<(usize, usize)> {
+        self.wires.clone()
+    }
+}
+
+impl PuzzleVaults {
+    /// `chance` is the percentage of levels that get a vault.
+    pub fn new(chance : i32) -> Box<PuzzleVaults> {
+        Box::new(PuzzleVaults{ chance, spawn_list : Vec::new(), wires : Vec::new() })
+    }
+}
+
+/// Which tiles can be walked to from `start`, treating `blockers` as solid.
+fn reachable_tiles(map : &mut Map, start : usize, blockers : &[usize]) -> Vec<bool> {
+    map.populate_blocked();
+    for idx in blockers.iter() {
+        map.block_tile(*idx);
+    }
+    let dijkstra_map = rltk::DijkstraMap::new(map.width as usize, map.height as usize, &[start], &*map, 1000.0);
+    map.populate_blocked();
+    dijkstra_map.map.iter().map(|d| *d != f32::MAX).collect()
+}
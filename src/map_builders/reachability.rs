@@ -0,0 +1,151 @@
+use std::collections::{HashSet, VecDeque};
+use rltk::{RandomNumberGenerator, Point};
+use super::Map;
+use super::super::{TileType, Branch, Rect, tile_walkable};
+
+/// Corridors the validator will carve to reach stranded targets before giving up on a level.
+const MAX_REPAIRS : i32 = 20;
+
+/// Checks that everything the player needs can be walked to from where they arrive: both
+/// stairs, every branch entrance, every room, every placed spawn and every locked door, and that each key can
+/// be reached without going through the door it opens. Wired mechanisms, such as portcullises and
+/// secret passages, count as open, since whatever works them is reachable too. Whatever is
+/// stranded gets a corridor carved to it from the nearest reachable tile. Once everything is
+/// reachable, any pockets of floor still cut off are filled in, so random spawns can't land there.
+/// False if the level couldn't be repaired and should be built again.
+pub fn ensure_reachable(map : &mut Map, rng : &mut RandomNumberGenerator, spawns : &[usize], locked_doors : &[(usize, usize)], mechanisms : &[usize]) -> bool {
+    let start = match start_tile(map) {
+        Some(start) => start,
+        None => return false
+    };
+    let doors : HashSet<usize> = locked_doors.iter().map(|(door, _key)| *door).collect();
+    let keys : Vec<usize> = locked_doors.iter().map(|(_door, key)| *key).collect();
+    let mechanisms : HashSet<usize> = mechanisms.iter().copied().collect();
+
+    for _repair in 0 .. MAX_REPAIRS {
+        // Corridors are dug towards what can be reached without unlocking anything, and kept
+        // clear of the rooms behind locked doors, so a repair never opens a back way in
+        let reachable = flood_fill(map, start, &HashSet::new(), &mechanisms);
+        let before_doors = flood_fill(map, start, &doors, &mechanisms);
+        let stranded = targets(map, spawns, locked_doors).into_iter().find(|idx| !reachable[*idx])
+            .or_else(|| keys.iter().copied().find(|idx| !before_doors[*idx]));
+        if let Some(target) = stranded {
+            let locked_away : Vec<bool> = reachable.iter().zip(before_doors.iter()).map(|(all, open)| *all && !*open).collect();
+            if !carve_to(map, rng, target, &before_doors, &locked_away) { return false; }
+            continue;
+        }
+
+        if map.branch != Branch::Overworld {
+            for (idx, tile) in map.tiles.iter_mut().enumerate() {
+                if tile_walkable(*tile) && !reachable[idx] { *tile = TileType::Wall; }
+            }
+        }
+        return true;
+    }
+    false
+}
+
+/// Where the player arrives on a new level: the up stairs, or else the first room.
+fn start_tile(map : &Map) -> Option<usize> {
+    map.find_tile(TileType::UpStairs)
+        .or_else(|| map.rooms.first().map(|room| room.center()))
+        .map(|(x, y)| map.xy_idx(x, y))
+}
+
+fn targets(map : &Map, spawns : &[usize], locked_doors : &[(usize, usize)]) -> Vec<usize> {
+    let mut targets : Vec<usize> = map.tiles.iter().enumerate()
+        .filter(|(_idx, tile)| matches!(tile, TileType::DownStairs | TileType::UpStairs | TileType::BranchEntrance))
+        .map(|(idx, _tile)| idx)
+        .collect();
+    targets.extend(map.branch_exits.keys());
+    targets.extend(map.rooms.iter().filter_map(|room| room_tile(map, room)));
+    targets.extend(spawns.iter());
+    targets.extend(locked_doors.iter().map(|(door, _key)| *door));
+    targets
+}
+
+/// The room's walkable tile nearest its centre; hand-drawn rooms may have a wall in the middle.
+fn room_tile(map : &Map, room : &Rect) -> Option<usize> {
+    let (cx, cy) = room.center();
+    let centre = Point::new(cx, cy);
+    (room.y1 + 1 ..= room.y2).flat_map(|y| (room.x1 + 1 ..= room.x2).map(move |x| Point::new(x, y)))
+        .filter(|p| p.x > 0 && p.y > 0 && p.x < map.width - 1 && p.y < map.height - 1 && tile_walkable(map.tiles[map.xy_idx(p.x, p.y)]))
+        .min_by_key(|p| (rltk::DistanceAlg::Pythagoras.distance2d(centre, *p) * 100.0) as i32)
+        .map(|p| map.xy_idx(p.x, p.y))
+}
+
+/// Every tile that can be walked to from `start`, going round the `closed` tiles and through the
+/// `open` ones whether they can be walked on yet or not.
+fn flood_fill(map : &Map, start : usize, closed : &HashSet<usize>, open_tiles : &HashSet<usize>) -> Vec<bool> {
+    let mut reached = vec![false; map.tiles.len()];
+    let mut open : VecDeque<usize> = VecDeque::new();
+    reached[start] = true;
+    open.push_back(start);
+    while let Some(idx) = open.pop_front() {
+        let (x, y) = (idx as i32 % map.width, idx as i32 / map.width);
+        for dy in -1 ..= 1 {
+            for dx in -1 ..= 1 {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= map.width || ny >= map.height { continue; }
+                let next = map.xy_idx(nx, ny);
+                if reached[next] || closed.contains(&next) || !(tile_walkable(map.tiles[next]) || open_tiles.contains(&next)) { continue; }
+                reached[next] = true;
+                open.push_back(next);
+            }
+        }
+    }
+    reached
+}
+
+/// Digs an L-shaped corridor from `target` to the nearest reachable tile, only breaking through
+/// what can't be walked on so stairs and the like on the way are left alone. Neither way round
+/// the L may come next to a `locked_away` tile; false if both would, and nothing is dug.
+fn carve_to(map : &mut Map, rng : &mut RandomNumberGenerator, target : usize, reachable : &[bool], locked_away : &[bool]) -> bool {
+    let from = Point::new(target as i32 % map.width, target as i32 / map.width);
+    let nearest = reachable.iter().enumerate()
+        .filter(|(_idx, reached)| **reached)
+        .map(|(idx, _reached)| Point::new(idx as i32 % map.width, idx as i32 / map.width))
+        .min_by_key(|p| (rltk::DistanceAlg::Pythagoras.distance2d(from, *p) * 100.0) as i32);
+    let to = match nearest {
+        Some(to) => to,
+        None => return false
+    };
+
+    let mut corners = [Point::new(to.x, from.y), Point::new(from.x, to.y)];
+    if rng.roll_dice(1, 2) == 2 { corners.reverse(); }
+    let path = corners.iter()
+        .map(|corner| l_path(from, *corner, to))
+        .find(|path| !path.iter().any(|p| next_to_locked_away(map, *p, locked_away)));
+    let path = match path {
+        Some(path) => path,
+        None => return false
+    };
+    for p in path {
+        let idx = map.xy_idx(p.x, p.y);
+        if !tile_walkable(map.tiles[idx]) && p.x > 0 && p.y > 0 && p.x < map.width - 1 && p.y < map.height - 1 {
+            map.tiles[idx] = TileType::Floor;
+        }
+    }
+    true
+}
+
+/// The tiles along an L from `from` to `to`, turning at `corner`.
+fn l_path(from : Point, corner : Point, to : Point) -> Vec<Point> {
+    let mut path = Vec::new();
+    for (a, b) in [(from, corner), (corner, to)].iter() {
+        let (mut x, mut y) = (a.x, a.y);
+        loop {
+            path.push(Point::new(x, y));
+            if x == b.x && y == b.y { break; }
+            x += (b.x - x).signum();
+            y += (b.y - y).signum();
+        }
+    }
+    path
+}
+
+/// Whether a tile is, or touches, one that can only be reached through a locked door.
+fn next_to_locked_away(map : &Map, p : Point, locked_away : &[bool]) -> bool {
+    (-1 ..= 1).flat_map(|dy| (-1 ..= 1).map(move |dx| (p.x + dx, p.y + dy)))
+        .any(|(x, y)| x >= 0 && y >= 0 && x < map.width && y < map.height && locked_away[map.xy_idx(x, y)])
+}
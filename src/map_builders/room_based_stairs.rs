@@ -0,0 +1,43 @@
+use rltk::RandomNumberGenerator;
+use super::{MetaMapBuilder, Map, DistantStairs};
+use super::super::TileType;
+
+/// Puts the up stairs in the first room, the down stairs in the last one and
+/// any branch entrances in the rooms in between. Falls back to placing them by
+/// distance if the layout came out without any rooms.
+pub struct RoomBasedStairs {}
+
+impl MetaMapBuilder for RoomBasedStairs {
+    fn build_map(&mut self, rng : &mut RandomNumberGenerator, map : &mut Map) {
+        if map.rooms.is_empty() {
+            DistantStairs{}.build_map(rng, map);
+            return;
+        }
+
+        let (up_x, up_y) = map.rooms[0].center();
+        let up_idx = map.xy_idx(up_x, up_y);
+        map.tiles[up_idx] = TileType::UpStairs;
+
+        if map.depth < map.branch.max_depth() {
+            let (down_x, down_y) = map.rooms[map.rooms.len() - 1].center();
+            let down_idx = map.xy_idx(down_x, down_y);
+            map.tiles[down_idx] = TileType::DownStairs;
+        }
+
+        let children = map.branch.children_at(map.depth);
+        for (i, child) in children.iter().enumerate() {
+            let room_idx = map.rooms.len() / 2 + i;
+            if room_idx == 0 || room_idx >= map.rooms.len() - 1 { break; }
+            let (exit_x, exit_y) = map.rooms[room_idx].center();
+            let exit_idx = map.xy_idx(exit_x, exit_y);
+            map.tiles[exit_idx] = TileType::BranchEntrance;
+            map.branch_exits.insert(exit_idx, *child);
+        }
+    }
+}
+
+impl RoomBasedStairs {
+    pub fn new() -> Box<RoomBasedStairs> {
+        Box::new(RoomBasedStairs{})
+    }
+}
@@ -0,0 +1,44 @@
+use rltk::RandomNumberGenerator;
+use super::{InitialMapBuilder, Map};
+use super::common::{apply_room_to_map, draw_corridor};
+use super::super::Rect;
+
+pub struct RoomsAndCorridorsBuilder {
+    max_rooms : i32,
+    min_size : i32,
+    max_size : i32
+}
+
+impl InitialMapBuilder for RoomsAndCorridorsBuilder {
+    fn build_map(&mut self, rng : &mut RandomNumberGenerator, map : &mut Map) {
+        for _ in 0..self.max_rooms {
+            let w = rng.range(self.min_size, self.max_size);
+            let h = rng.range(self.min_size, self.max_size);
+            let x = rng.roll_dice(1, map.width - w - 1) - 1;
+            let y = rng.roll_dice(1, map.height - h - 1) - 1;
+            let new_room = Rect::new(x, y, w, h);
+            let mut ok = true;
+
+            for other_room in map.rooms.iter() {
+                if new_room.intersect(other_room) { ok = false }
+            }
+
+            if ok {
+                apply_room_to_map(map, &new_room);
+
+                if !map.rooms.is_empty() {
+                    let prev = map.rooms[map.rooms.len() - 1].center();
+                    draw_corridor(map, rng.range(0, 2) == 1, prev, new_room.center());
+                }
+
+                map.rooms.push(new_room);
+            }
+        }
+    }
+}
+
+impl RoomsAndCorridorsBuilder {
+    pub fn new(max_rooms : i32, min_size : i32, max_size : i32) -> Box<RoomsAndCorridorsBuilder> {
+        Box::new(RoomsAndCorridorsBuilder{ max_rooms, min_size, max_size })
+    }
+}
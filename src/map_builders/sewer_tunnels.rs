@@ -0,0 +1,28 @@
+use rltk::RandomNumberGenerator;
+use super::{MetaMapBuilder, Map};
+use super::common::{apply_horizontal_tunnel, apply_vertical_tunnel};
+
+/// Cuts long straight drainage tunnels right across the map.
+pub struct SewerTunnels {
+    count : i32
+}
+
+impl MetaMapBuilder for SewerTunnels {
+    fn build_map(&mut self, rng : &mut RandomNumberGenerator, map : &mut Map) {
+        for _ in 0..self.count {
+            if rng.range(0, 2) == 0 {
+                let y = rng.range(2, map.height - 2);
+                apply_horizontal_tunnel(map, 1, map.width - 2, y);
+            } else {
+                let x = rng.range(2, map.width - 2);
+                apply_vertical_tunnel(map, 1, map.height - 2, x);
+            }
+        }
+    }
+}
+
+impl SewerTunnels {
+    pub fn new(count : i32) -> Box<SewerTunnels> {
+        Box::new(SewerTunnels{ count })
+    }
+}
@@ -1,32 +1,70 @@
 use specs::prelude::*;
+use specs::shrev::ReaderId;
 use crate::map::Map;
-use super::{Position, BlocksTile};
+use super::{Position, BlocksTile, MovesThroughWalls};
 
-pub struct MapIndexingSystem {}
+/// Keeps the map's spatial index in step with where entities are. Rather than rebuilding it every
+/// turn, it only looks at the entities whose position or blocking changed since last time; a map
+/// that was just made or loaded gets indexed from scratch.
+#[derive(Default)]
+pub struct MapIndexingSystem {
+    position_events : Option<ReaderId<ComponentEvent>>,
+    blocker_events : Option<ReaderId<ComponentEvent>>,
+    changed : BitSet
+}
 
 impl<'a> System<'a> for MapIndexingSystem {
     type SystemData = ( WriteExpect<'a, Map>,
                         ReadStorage<'a, Position>,
                         ReadStorage<'a, BlocksTile>,
+                        ReadStorage<'a, MovesThroughWalls>,
                         Entities<'a>,);
 
+    fn setup(&mut self, world : &mut World) {
+        Self::SystemData::setup(world);
+        self.position_events = Some(world.write_storage::<Position>().register_reader());
+        self.blocker_events = Some(world.write_storage::<BlocksTile>().register_reader());
+    }
+
     fn run(&mut self, data : Self::SystemData) {
-        let (mut map, position, blockers, entities) = data;
+        let (mut map, position, blockers, walkers, entities) = data;
+        // Whatever passes through walls passes through everything else too
+        let blocks = |entity : Entity| blockers.get(entity).is_some() && walkers.get(entity).is_none();
 
-        map.populate_blocked();
-        map.clear_content_index();
-        for (entity, position) in (&entities, &position).join() {
-            let idx = map.xy_idx(position.x, position.y);
+        // Whatever happened, note which entities it happened to
+        self.changed.clear();
+        let position_events = self.position_events.as_mut().expect("MapIndexingSystem was not set up");
+        for event in position.channel().read(position_events) {
+            match event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) | ComponentEvent::Removed(id) => { self.changed.add(*id); }
+            }
+        }
+        let blocker_events = self.blocker_events.as_mut().expect("MapIndexingSystem was not set up");
+        for event in blockers.channel().read(blocker_events) {
+            match event {
+                ComponentEvent::Inserted(id) | ComponentEvent::Modified(id) | ComponentEvent::Removed(id) => { self.changed.add(*id); }
+            }
+        }
 
-            // If they block, update the blocking list
-            let _p : Option<&BlocksTile> = blockers.get(entity);
-            if let Some(_p) = _p {
-                map.blocked[idx] = true;
+        if !map.spatial.is_populated() {
+            map.populate_blocked();
+            map.spatial.clear_entities();
+            for (entity, position) in (&entities, &position).join() {
+                let idx = map.xy_idx(position.x, position.y);
+                map.spatial.insert(entity, idx, blocks(entity));
             }
+            return;
+        }
 
-            // Push the entity to the appropriate index slot. It's a Copy
-            // type, so we don't need to clone it (we want to avoid moving it out of the ECS!)
-            map.tile_content[idx].push(entity);
+        for id in (&self.changed).join() {
+            let entity = entities.entity(id);
+            match position.get(entity) {
+                Some(position) if entities.is_alive(entity) => {
+                    let idx = map.xy_idx(position.x, position.y);
+                    map.spatial.insert(entity, idx, blocks(entity));
+                }
+                _ => map.spatial.remove(id)
+            }
         }
     }
-}
\ No newline at end of file
+}
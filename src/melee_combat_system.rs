@@ -1,39 +1,275 @@
-use rltk::console;
+use rltk::{RandomNumberGenerator, RGB};
 use specs::prelude::*;
-use super::{CombatStats, WantsToMelee, Name, SufferDamage, gamelog::GameLog};
+use super::{CombatStats, WantsToMelee, Name, SufferDamage, gamelog::{GameLog, LogCategory}, Equipped, MeleePowerBonus,
+            DefenseBonus, BonusDamage, ConfuseOnHit, Confusion, DamageType, InflictsDot, DamageOverTime,
+            Attributes, CriticalHit, Staggered, Position, EquipmentSlot, BlockChance, ParryChance, ReactionCooldown,
+            Pools, PowerAttack, Invisible, CanSeeInvisible, particle_system::ParticleBuilder, gamesystem::attr_bonus,
+            invisibility_system::can_perceive, Skills, KeenEye, Durability, durability::{worn_bonus, wear_down}, OffHand, Afflicts, Mutations, Mutation,
+            effects::{EffectQueue, EffectType, Targets}};
+
+/// A d20 roll plus the attacker's Quickness bonus must beat this plus the defender's.
+const BASE_TO_HIT : i32 = 6;
+const DEFAULT_CRIT_MULTIPLIER : i32 = 2;
+/// Turns a defender has to wait after a successful block, parry or dodge.
+const REACTION_COOLDOWN : i32 = 2;
+/// Each point of Quickness bonus adds this much (in 100) to the dodge chance.
+const DODGE_PER_QUICKNESS : i32 = 5;
+const BLOCK_STAMINA_COST : i32 = 1;
+/// To-hit bonus for striking at someone who can't see you coming.
+const UNSEEN_ATTACKER_BONUS : i32 = 5;
+/// To-hit penalty for the extra swing of an off-hand weapon.
+const DUAL_WIELD_PENALTY : i32 = 4;
 
 pub struct MeleeCombatSystem {}
 
 impl<'a> System<'a> for MeleeCombatSystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = ( Entities<'a>,
                         WriteStorage<'a, WantsToMelee>,
                         ReadStorage<'a, Name>,
                         ReadStorage<'a, CombatStats>,
                         WriteStorage<'a, SufferDamage>,
                         WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, Equipped>,
+                        ReadStorage<'a, MeleePowerBonus>,
+                        ReadStorage<'a, DefenseBonus>,
+                        ReadStorage<'a, BonusDamage>,
+                        ReadStorage<'a, ConfuseOnHit>,
+                        WriteStorage<'a, Confusion>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        ReadStorage<'a, InflictsDot>,
+                        WriteStorage<'a, DamageOverTime>,
+                        ReadStorage<'a, Attributes>,
+                        ReadStorage<'a, CriticalHit>,
+                        WriteStorage<'a, Staggered>,
+                        WriteStorage<'a, Position>,
+                        WriteExpect<'a, ParticleBuilder>,
+                        ReadStorage<'a, BlockChance>,
+                        ReadStorage<'a, ParryChance>,
+                        WriteStorage<'a, ReactionCooldown>,
+                        WriteStorage<'a, Pools>,
+                        WriteStorage<'a, PowerAttack>,
+                        (ReadStorage<'a, Invisible>, ReadStorage<'a, CanSeeInvisible>, ReadStorage<'a, Skills>, ReadStorage<'a, KeenEye>,
+                         WriteStorage<'a, Durability>, ReadStorage<'a, OffHand>, ReadExpect<'a, Entity>, ReadStorage<'a, Afflicts>,
+                         WriteExpect<'a, EffectQueue>, ReadStorage<'a, Mutations>)
     );
 
     fn run(&mut self, data : Self::SystemData) {
-        let (entities, mut wants_melee, names, combat_stats, mut inflict_damage, mut log) = data;
+        let (entities, mut wants_melee, names, combat_stats, mut inflict_damage, mut log, mut equipped,
+            power_bonuses, defense_bonuses, bonus_damage, confuse_on_hit, mut confused, mut rng, inflicts_dot,
+            mut damage_over_time, attributes, critical_hits, mut staggered, mut positions, mut particle_builder,
+            block_chances, parry_chances, mut cooldowns, mut pools, mut power_attacks, (invisible, see_invisible, skills, keen_eyes, mut durability, off_hands, player_entity, afflicts, mut effects, mutations)) = data;
+
+        // Weapons that landed a blow and the shields and armor that took one, to be worn down afterwards
+        let mut wear : Vec<Entity> = Vec::new();
 
-        for (_entity, wants_melee, name, stats) in (&entities, &wants_melee, &names, &combat_stats).join() {
+        for (entity, wants_melee, name, stats) in (&entities, &wants_melee, &names, &combat_stats).join() {
             if stats.hp > 0 {
                 let target_stats = combat_stats.get(wants_melee.target).unwrap();
                 if target_stats.hp > 0 {
                     let target_name = names.get(wants_melee.target).unwrap();
 
-                    let damage = i32::max(0, stats.power - target_stats.defense);
+                    let mut weapon : Option<Entity> = None;
+                    let mut off_hand : Option<Entity> = None;
+                    let mut gear : Vec<Entity> = Vec::new();
+                    for (item, worn) in (&entities, &equipped).join() {
+                        if worn.owner != entity { continue; }
+                        match worn.slot {
+                            EquipmentSlot::Melee => weapon = Some(item),
+                            EquipmentSlot::Shield if off_hands.get(item).is_some() => off_hand = Some(item),
+                            _ => gear.push(item)
+                        }
+                    }
+                    // A second weapon in the off hand earns an extra, clumsier swing
+                    let mut strikes = vec![(weapon, 0)];
+                    if off_hand.is_some() { strikes.push((off_hand, DUAL_WIELD_PENALTY)); }
 
-                    if damage == 0 {
-                        log.entries.push(format!("{} is unable to hurt {}", &name.name, &target_name.name));
-                    } else {
-                        log.entries.push(format!("{} hits {}, for {} hp.", &name.name, &target_name.name, damage));
-                        SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage);
+                    let mut defensive_bonus = mutations.get(wants_melee.target).map_or(0, |m| m.total(Mutation::defense));
+                    let mut block : Option<(Entity, i32)> = None;
+                    let mut parry : Option<(Entity, i32)> = None;
+                    let mut armor : Vec<Entity> = Vec::new();
+                    for (item, worn) in (&entities, &equipped).join() {
+                        if worn.owner != wants_melee.target { continue; }
+                        if let Some(bonus) = defense_bonuses.get(item) {
+                            defensive_bonus += worn_bonus(bonus.defense, durability.get(item));
+                            armor.push(item);
+                        }
+                        if let Some(b) = block_chances.get(item) { block = Some((item, b.chance)); }
+                        if let Some(p) = parry_chances.get(item) { parry = Some((item, p.chance)); }
+                    }
+
+                    // Swinging a weapon is no rest
+                    let power_attack = power_attacks.remove(entity).is_some();
+                    if let Some(attacker_pools) = pools.get_mut(entity) { attacker_pools.exerted = true; }
+
+                    // Nobody can dodge or parry a blow they never saw coming
+                    let unseen = !can_perceive(wants_melee.target, entity, &invisible, &see_invisible);
+                    let attacker_quickness = attr_bonus(attributes.get(entity).map_or(10, |a| a.quickness));
+                    let to_hit_bonus = attacker_quickness + skills.get(entity).map_or(0, |s| s.melee)
+                        + if unseen { UNSEEN_ATTACKER_BONUS } else { 0 };
+                    let target_quickness = attr_bonus(attributes.get(wants_melee.target).map_or(10, |a| a.quickness));
+                    let keen_eye = if keen_eyes.get(entity).is_some() { 1 } else { 0 };
+                    let target_pos = positions.get(wants_melee.target).map(|p| (p.x, p.y));
+
+                    for (strike, &(weapon, penalty)) in strikes.iter().enumerate() {
+                        // Power attacks only put weight behind the main hand
+                        let power_attack = power_attack && strike == 0;
+                        let mut offensive_bonus = mutations.get(entity).map_or(0, |m| m.total(Mutation::melee_power));
+                        let mut elemental : Vec<&BonusDamage> = Vec::new();
+                        let mut confusion_procs : Vec<&ConfuseOnHit> = Vec::new();
+                        let mut dot_procs : Vec<&InflictsDot> = Vec::new();
+                        let mut crit_range = 0;
+                        let mut crit_multiplier = DEFAULT_CRIT_MULTIPLIER;
+                        if let Some(natural) = inflicts_dot.get(entity) { dot_procs.push(natural); }
+                        for &item in gear.iter().chain(weapon.iter()) {
+                            if let Some(crit) = critical_hits.get(item) {
+                                crit_range += crit.range;
+                                crit_multiplier = i32::max(crit_multiplier, crit.multiplier);
+                            }
+                            if let Some(bonus) = power_bonuses.get(item) { offensive_bonus += worn_bonus(bonus.power, durability.get(item)); }
+                            if let Some(bonus) = bonus_damage.get(item) { elemental.push(bonus); }
+                            if let Some(on_hit) = confuse_on_hit.get(item) { confusion_procs.push(on_hit); }
+                            if let Some(on_hit) = inflicts_dot.get(item) { dot_procs.push(on_hit); }
+                        }
+
+                        let natural_roll = rng.roll_dice(1, 20);
+                        let crit_threshold = 20 - crit_range - keen_eye - i32::max(0, attacker_quickness);
+
+                        // A natural 1 always misses and may cost the attacker dearly
+                        if natural_roll == 1 {
+                            log.push(LogCategory::Combat, format!("{} fumbles the attack!", &name.name));
+                            match weapon {
+                                Some(weapon) if rng.roll_dice(1, 2) == 1 => {
+                                    if let Some(pos) = positions.get(entity).cloned() {
+                                        equipped.remove(weapon);
+                                        positions.insert(weapon, pos).expect("Unable to insert position");
+                                        log.push(LogCategory::Combat, format!("{} drops the {}!", &name.name, &names.get(weapon).unwrap().name));
+                                    }
+                                }
+                                _ => {
+                                    staggered.insert(entity, Staggered{}).expect("Unable to insert status");
+                                    log.push(LogCategory::Combat, format!("{} stumbles and loses the initiative.", &name.name));
+                                }
+                            }
+                            continue;
+                        }
+
+                        let critical = natural_roll >= crit_threshold;
+                        if !critical && natural_roll != 20 && natural_roll + to_hit_bonus - penalty < BASE_TO_HIT + target_quickness {
+                            log.push(LogCategory::Combat, format!("{} misses {}.", &name.name, &target_name.name));
+                            continue;
+                        }
+
+                        // The defender gets a chance to react, unless still recovering from the last one
+                        if natural_roll != 20 && !unseen && cooldowns.get(wants_melee.target).is_none() {
+                            let mut reaction : Option<String> = None;
+                            if let Some((shield, chance)) = block {
+                                // Raising a shield takes effort; exhausted defenders can't block
+                                let can_block = match pools.get_mut(wants_melee.target) {
+                                    Some(defender_pools) if defender_pools.stamina.current >= BLOCK_STAMINA_COST => {
+                                        defender_pools.stamina.current -= BLOCK_STAMINA_COST;
+                                        defender_pools.exerted = true;
+                                        true
+                                    }
+                                    Some(_) => false,
+                                    None => true
+                                };
+                                if can_block && rng.roll_dice(1, 100) <= chance {
+                                    wear.push(shield);
+                                    reaction = Some(format!("{} blocks {}'s attack with the {}.", &target_name.name, &name.name, &names.get(shield).unwrap().name));
+                                }
+                            }
+                            if reaction.is_none() {
+                                if let Some((parrying_weapon, chance)) = parry {
+                                    if rng.roll_dice(1, 100) <= chance {
+                                        reaction = Some(format!("{} parries {}'s attack with the {}.", &target_name.name, &name.name, &names.get(parrying_weapon).unwrap().name));
+                                    }
+                                }
+                            }
+                            if reaction.is_none() && target_quickness > 0 && rng.roll_dice(1, 100) <= target_quickness * DODGE_PER_QUICKNESS {
+                                reaction = Some(format!("{} dodges {}'s attack.", &target_name.name, &name.name));
+                            }
+                            if let Some(reaction) = reaction {
+                                log.push(LogCategory::Combat, reaction);
+                                cooldowns.insert(wants_melee.target, ReactionCooldown{ turns: REACTION_COOLDOWN }).expect("Unable to insert cooldown");
+                                continue;
+                            }
+                        }
+
+                        let might = attr_bonus(attributes.get(entity).map_or(10, |a| a.might));
+                        let mut damage = i32::max(0, (stats.power + offensive_bonus + might) - (target_stats.defense + defensive_bonus));
+                        if power_attack {
+                            damage = i32::max(1, damage) * 3 / 2;
+                        }
+                        if critical {
+                            damage = i32::max(1, damage) * crit_multiplier;
+                        }
+
+                        if damage == 0 && elemental.is_empty() {
+                            log.push(LogCategory::Combat, format!("{} is unable to hurt {}", &name.name, &target_name.name));
+                            continue;
+                        }
+
+                        if let Some((x, y)) = target_pos {
+                            if critical {
+                                particle_builder.request(x, y, RGB::named(rltk::GOLD), RGB::named(rltk::BLACK), rltk::to_cp437('*'), 300.0);
+                            } else {
+                                particle_builder.request(x, y, RGB::named(rltk::ORANGE), RGB::named(rltk::BLACK), rltk::to_cp437('\u{203C}'), 200.0);
+                            }
+                        }
+
+                        let verb = match (critical, power_attack) {
+                            (true, _) => "critically hits",
+                            (false, true) => "power attacks",
+                            (false, false) => "hits"
+                        };
+                        if let Some(weapon) = weapon { wear.push(weapon); }
+                        wear.extend(armor.iter());
+                        let mut message = format!("{} {} {}, for {} hp", &name.name, verb, &target_name.name, damage);
+                        SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, damage, DamageType::Physical, entity == *player_entity, Some(entity));
+                        for bonus in elemental.iter() {
+                            message.push_str(&format!(" (+{} {})", bonus.amount, bonus.element));
+                            SufferDamage::new_damage(&mut inflict_damage, wants_melee.target, bonus.amount, DamageType::from_name(&bonus.element), entity == *player_entity, Some(entity));
+                        }
+                        message.push('.');
+                        if critical {
+                            log.push_colored(LogCategory::Combat, message, RGB::named(rltk::GOLD));
+                        } else {
+                            log.push(LogCategory::Combat, message);
+                        }
+
+                        for on_hit in confusion_procs.iter() {
+                            if rng.roll_dice(1, 100) <= on_hit.chance {
+                                confused.insert(wants_melee.target, Confusion{ turns: on_hit.turns }).expect("Unable to insert status");
+                                log.push(LogCategory::Combat, format!("{} is confused!", &target_name.name));
+                            }
+                        }
+
+                        for on_hit in dot_procs.iter() {
+                            if rng.roll_dice(1, 100) <= on_hit.chance {
+                                if let Some(dots) = damage_over_time.get_mut(wants_melee.target) {
+                                    dots.effects.retain(|e| e.kind != on_hit.effect.kind);
+                                    dots.effects.push(on_hit.effect);
+                                } else {
+                                    damage_over_time.insert(wants_melee.target, DamageOverTime{ effects: vec![on_hit.effect] }).expect("Unable to insert status");
+                                }
+                                let verb = if on_hit.effect.kind == DamageType::Poison { "poisoned" } else { "bleeding" };
+                                log.push(LogCategory::Combat, format!("{} is {}!", &target_name.name, verb));
+                            }
+                        }
+
+                        if let Some(afflicts) = afflicts.get(entity) {
+                            if rng.roll_dice(1, 100) <= afflicts.chance {
+                                effects.add_effect(Some(entity), EffectType::Afflict{ kind: afflicts.kind }, Targets::Single{ target: wants_melee.target });
+                            }
+                        }
                     }
                 }
             }
         }
 
         wants_melee.clear();
+
+        wear_down(&mut wear, &entities, &mut durability, &equipped, &names, &mut log);
     }
-}
\ No newline at end of file
+}
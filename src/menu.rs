@@ -1,32 +1,89 @@
-use rltk::{RGB, Rltk, VirtualKeyCode};
-use crate::gui::{MainMenuResult, MainMenuSelection};
-use crate::{RunState, State};
+use rltk::{Point, VirtualKeyCode, ColorPair};
+use crate::gui::{MainMenuResult, MainMenuSelection, MENU_LAYER, UI_CONSOLE, batch_for};
+use crate::{RunState, State, challenge::Challenge, input::Input, palette};
 
-pub fn main_menu(gs : &mut State, ctx : &mut Rltk) -> MainMenuResult {
+pub fn main_menu(gs : &mut State, ctx : &mut Input) -> MainMenuResult {
+    let mut draw_batch = batch_for(UI_CONSOLE);
     let save_exists = super::saveload_system::does_save_exist();
+    let daily_available = Challenge::today().is_some();
+    let replay_exists = super::replay::does_replay_exist();
     let runstate = gs.ecs.fetch::<RunState>();
 
-    ctx.print_color_centered(15, RGB::named(rltk::YELLOW), RGB::named(rltk::BLACK), "Morbis Roguelike Game");
+    draw_batch.print_color_centered(15, "Morbis Roguelike Game", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
 
     if let RunState::MainMenu{ menu_selection : selection } = *runstate {
         if selection == MainMenuSelection::NewGame {
-            ctx.print_color_centered(24, RGB::named(rltk::MAGENTA), RGB::named(rltk::BLACK), "Begin New Game");
+            draw_batch.print_color_centered(24, "Begin New Game", ColorPair::new(palette::named(rltk::MAGENTA), palette::named(rltk::BLACK)));
         } else {
-            ctx.print_color_centered(24, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Begin New Game");
+            draw_batch.print_color_centered(24, "Begin New Game", ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+        }
+
+        if daily_available {
+            if selection == MainMenuSelection::DailyChallenge {
+                draw_batch.print_color_centered(25, "Daily Challenge", ColorPair::new(palette::named(rltk::MAGENTA), palette::named(rltk::BLACK)));
+            } else {
+                draw_batch.print_color_centered(25, "Daily Challenge", ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+            }
         }
 
         if save_exists {
             if selection == MainMenuSelection::LoadGame {
-                ctx.print_color_centered(25, RGB::named(rltk::MAGENTA), RGB::named(rltk::BLACK), "Load Game");
+                draw_batch.print_color_centered(26, "Load Game", ColorPair::new(palette::named(rltk::MAGENTA), palette::named(rltk::BLACK)));
             } else {
-                ctx.print_color_centered(25, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Load Game");
+                draw_batch.print_color_centered(26, "Load Game", ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
             }
         }
 
+        if replay_exists {
+            if selection == MainMenuSelection::WatchReplay {
+                draw_batch.print_color_centered(27, "Watch Last Run", ColorPair::new(palette::named(rltk::MAGENTA), palette::named(rltk::BLACK)));
+            } else {
+                draw_batch.print_color_centered(27, "Watch Last Run", ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+            }
+        }
+
+        if selection == MainMenuSelection::Options {
+            draw_batch.print_color_centered(28, "Options", ColorPair::new(palette::named(rltk::MAGENTA), palette::named(rltk::BLACK)));
+        } else {
+            draw_batch.print_color_centered(28, "Options", ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+        }
+
+        if selection == MainMenuSelection::Achievements {
+            draw_batch.print_color_centered(29, "Achievements", ColorPair::new(palette::named(rltk::MAGENTA), palette::named(rltk::BLACK)));
+        } else {
+            draw_batch.print_color_centered(29, "Achievements", ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+        }
+
+        if selection == MainMenuSelection::HighScores {
+            draw_batch.print_color_centered(30, "High Scores", ColorPair::new(palette::named(rltk::MAGENTA), palette::named(rltk::BLACK)));
+        } else {
+            draw_batch.print_color_centered(30, "High Scores", ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+        }
+
         if selection == MainMenuSelection::Quit {
-            ctx.print_color_centered(26, RGB::named(rltk::MAGENTA), RGB::named(rltk::BLACK), "Quit");
+            draw_batch.print_color_centered(31, "Quit", ColorPair::new(palette::named(rltk::MAGENTA), palette::named(rltk::BLACK)));
         } else {
-            ctx.print_color_centered(26, RGB::named(rltk::WHITE), RGB::named(rltk::BLACK), "Quit");
+            draw_batch.print_color_centered(31, "Quit", ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+        }
+
+        // The entry under the mouse lights up, and clicking it picks it
+        let entries = [(24, MainMenuSelection::NewGame, true), (25, MainMenuSelection::DailyChallenge, daily_available),
+            (26, MainMenuSelection::LoadGame, save_exists), (27, MainMenuSelection::WatchReplay, replay_exists),
+            (28, MainMenuSelection::Options, true), (29, MainMenuSelection::Achievements, true),
+            (30, MainMenuSelection::HighScores, true), (31, MainMenuSelection::Quit, true)];
+        let (mouse_x, mouse_y) = ctx.mouse_pos();
+        let hovered = entries.iter()
+            .find(|(row, _entry, shown)| *shown && *row == mouse_y && (30..50).contains(&mouse_x))
+            .map(|(_row, entry, _shown)| *entry);
+        if hovered.is_some() {
+            for x in 30..50 {
+                draw_batch.set_bg(Point::new(x, mouse_y), palette::named(rltk::DARK_BLUE));
+            }
+        }
+        draw_batch.submit(MENU_LAYER).expect("Unable to submit draw batch");
+
+        if let (Some(entry), true) = (hovered, ctx.left_click) {
+            return MainMenuResult::Selected{ selected: entry };
         }
 
         match ctx.key {
@@ -38,10 +95,21 @@ pub fn main_menu(gs : &mut State, ctx : &mut Rltk) -> MainMenuResult {
                         let mut newselection;
                         match selection {
                             MainMenuSelection::NewGame => newselection = MainMenuSelection::Quit,
-                            MainMenuSelection::LoadGame => newselection = MainMenuSelection::NewGame,
-                            MainMenuSelection::Quit => newselection = MainMenuSelection::LoadGame
+                            MainMenuSelection::DailyChallenge => newselection = MainMenuSelection::NewGame,
+                            MainMenuSelection::LoadGame => newselection = MainMenuSelection::DailyChallenge,
+                            MainMenuSelection::WatchReplay => newselection = MainMenuSelection::LoadGame,
+                            MainMenuSelection::Options => newselection = MainMenuSelection::WatchReplay,
+                            MainMenuSelection::Achievements => newselection = MainMenuSelection::Options,
+                            MainMenuSelection::HighScores => newselection = MainMenuSelection::Achievements,
+                            MainMenuSelection::Quit => newselection = MainMenuSelection::HighScores
+                        }
+                        if newselection == MainMenuSelection::WatchReplay && !replay_exists {
+                            newselection = MainMenuSelection::LoadGame;
                         }
                         if newselection == MainMenuSelection::LoadGame && !save_exists {
+                            newselection = MainMenuSelection::DailyChallenge;
+                        }
+                        if newselection == MainMenuSelection::DailyChallenge && !daily_available {
                             newselection = MainMenuSelection::NewGame;
                         }
                         return MainMenuResult::NoSelection{ selected: newselection }
@@ -49,12 +117,23 @@ pub fn main_menu(gs : &mut State, ctx : &mut Rltk) -> MainMenuResult {
                     VirtualKeyCode::Down => {
                         let mut newselection;
                         match selection {
-                            MainMenuSelection::NewGame => newselection = MainMenuSelection::LoadGame,
-                            MainMenuSelection::LoadGame => newselection = MainMenuSelection::Quit,
+                            MainMenuSelection::NewGame => newselection = MainMenuSelection::DailyChallenge,
+                            MainMenuSelection::DailyChallenge => newselection = MainMenuSelection::LoadGame,
+                            MainMenuSelection::LoadGame => newselection = MainMenuSelection::WatchReplay,
+                            MainMenuSelection::WatchReplay => newselection = MainMenuSelection::Options,
+                            MainMenuSelection::Options => newselection = MainMenuSelection::Achievements,
+                            MainMenuSelection::Achievements => newselection = MainMenuSelection::HighScores,
+                            MainMenuSelection::HighScores => newselection = MainMenuSelection::Quit,
                             MainMenuSelection::Quit => newselection = MainMenuSelection::NewGame
                         }
+                        if newselection == MainMenuSelection::DailyChallenge && !daily_available {
+                            newselection = MainMenuSelection::LoadGame;
+                        }
                         if newselection == MainMenuSelection::LoadGame && !save_exists {
-                            newselection = MainMenuSelection::Quit;
+                            newselection = MainMenuSelection::WatchReplay;
+                        }
+                        if newselection == MainMenuSelection::WatchReplay && !replay_exists {
+                            newselection = MainMenuSelection::Options;
                         }
                         return MainMenuResult::NoSelection{ selected: newselection }
                     }
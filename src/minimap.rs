@@ -0,0 +1,82 @@
+use specs::prelude::*;
+use rltk::{Point, RGB, ColorPair};
+use super::{Map, TileType, tile_walkable, MARKER_GLYPH, MARKER_COLOR, options::Options, camera::Screen, palette, gui::{batch_for, UI_CONSOLE, UI_LAYER}};
+
+/// The most cells the minimap may take up; bigger maps are shrunk to fit.
+const MAX_WIDTH : i32 = 26;
+const MAX_HEIGHT : i32 = 14;
+
+/// The most telling thing in a block of tiles, in rising order of importance.
+#[derive(PartialEq, PartialOrd, Clone, Copy)]
+enum Feature { Unknown, Water, Wall, Floor, Stairs }
+
+fn feature(tile : TileType) -> Feature {
+    match tile {
+        TileType::DownStairs | TileType::UpStairs | TileType::BranchEntrance => Feature::Stairs,
+        TileType::Water => Feature::Water,
+        _ if tile_walkable(tile) => Feature::Floor,
+        _ => Feature::Wall
+    }
+}
+
+/// How many tiles each minimap cell stands for along both axes, so the whole map fits.
+fn scale(map : &Map) -> i32 {
+    i32::max(1, i32::max((map.width + MAX_WIDTH - 1) / MAX_WIDTH, (map.height + MAX_HEIGHT - 1) / MAX_HEIGHT))
+}
+
+/// Boils a `scale` by `scale` block of the map down to one cell: the most telling revealed
+/// feature in it, and whether any of it is in view.
+fn downsample(map : &Map, left : i32, top : i32, scale : i32) -> (Feature, bool) {
+    let mut best = Feature::Unknown;
+    let mut visible = false;
+    for y in top .. i32::min(top + scale, map.height) {
+        for x in left .. i32::min(left + scale, map.width) {
+            let idx = map.xy_idx(x, y);
+            if !map.revealed_tiles[idx] { continue; }
+            let here = feature(map.tiles[idx]);
+            if here > best { best = here; }
+            visible |= map.visible_tiles[idx];
+        }
+    }
+    (best, visible)
+}
+
+fn feature_color(feature : Feature, visible : bool) -> RGB {
+    let color = match feature {
+        Feature::Unknown => return palette::named(rltk::BLACK),
+        Feature::Water => palette::named(rltk::BLUE),
+        Feature::Wall => palette::named(rltk::GREY),
+        Feature::Floor => palette::named(rltk::DARK_GREEN),
+        Feature::Stairs => palette::named(rltk::CYAN)
+    };
+    if visible { color } else { color * 0.5 }
+}
+
+/// Draws the whole level, shrunk down, in the top right corner of the view while it's switched on,
+/// with the player's markers and the player on top.
+pub fn draw(ecs : &World) {
+    if !ecs.fetch::<Options>().show_minimap { return; }
+    let map = ecs.fetch::<Map>();
+    let ppos = ecs.fetch::<Point>();
+    let scale = scale(&map);
+    let width = (map.width + scale - 1) / scale;
+    let height = (map.height + scale - 1) / scale;
+    let left = ecs.fetch::<Screen>().view_width() - width - 2;
+    let top = 1;
+
+    let mut draw_batch = batch_for(UI_CONSOLE);
+    draw_batch.draw_box(rltk::Rect::with_size(left - 1, top - 1, width + 1, height + 1), ColorPair::new(palette::named(rltk::WHITE), palette::named(rltk::BLACK)));
+    draw_batch.print_color(Point::new(left + 1, top - 1), "Map", ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)));
+    for y in 0..height {
+        for x in 0..width {
+            let (feature, visible) = downsample(&map, x * scale, y * scale, scale);
+            draw_batch.set(Point::new(left + x, top + y), ColorPair::new(feature_color(feature, visible), palette::named(rltk::BLACK)), 219);
+        }
+    }
+    for idx in map.markers.keys() {
+        let (x, y) = (*idx as i32 % map.width, *idx as i32 / map.width);
+        draw_batch.set(Point::new(left + x / scale, top + y / scale), ColorPair::new(palette::named(MARKER_COLOR), palette::named(rltk::BLACK)), rltk::to_cp437(MARKER_GLYPH));
+    }
+    draw_batch.set(Point::new(left + ppos.x / scale, top + ppos.y / scale), ColorPair::new(palette::named(rltk::YELLOW), palette::named(rltk::BLACK)), rltk::to_cp437('@'));
+    draw_batch.submit(UI_LAYER + 100).expect("Unable to submit draw batch");
+}
@@ -0,0 +1,205 @@
+use specs::prelude::*;
+use rltk::Point;
+use super::{OwnedBy, CombatStats, Monster, Position, Viewshed, WantsToMelee, Confusion, Staggered, Name, Map, RunState,
+            Companion, CompanionCommand, Charmed, Faction, FactionOverride, Webbed, Invisible, CanSeeInvisible, invisibility_system::can_perceive,
+            turn_counter::TurnCounter, gamelog::{GameLog, LogCategory}};
+
+/// How close an idle ally tries to stay to the player.
+const FOLLOW_DISTANCE : f32 = 2.5;
+/// How far out the follow map is calculated; allies further away than this stay put.
+const FOLLOW_MAP_DEPTH : f32 = 200.0;
+
+/// Sends summoned minions away once their time is up or their owner is gone,
+/// and hands charmed monsters back to their own side, and their own faction, when the charm wears off.
+pub struct MinionSystem {}
+
+impl<'a> System<'a> for MinionSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, TurnCounter>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, OwnedBy>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, Charmed>,
+                        WriteStorage<'a, Faction>,
+                        WriteStorage<'a, FactionOverride> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, runstate, turn_counter, mut log, mut owned, combat_stats, names, mut charmed, mut factions, mut overrides) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        let mut restored : Vec<(Entity, Option<String>)> = Vec::new();
+        for (entity, faction_override) in (&entities, &mut overrides).join() {
+            faction_override.turns -= turn_counter.elapsed();
+            if faction_override.turns < 1 {
+                restored.push((entity, faction_override.previous.clone()));
+            }
+        }
+        for (entity, previous) in restored {
+            overrides.remove(entity);
+            match previous {
+                Some(name) => { factions.insert(entity, Faction{ name }).expect("Unable to insert faction"); }
+                None => { factions.remove(entity); }
+            }
+        }
+
+        let mut recovered : Vec<(Entity, Option<OwnedBy>)> = Vec::new();
+        for (entity, charm) in (&entities, &mut charmed).join() {
+            charm.turns -= turn_counter.elapsed();
+            if charm.turns < 1 {
+                let previous = charm.previous_owner
+                    .filter(|owner| entities.is_alive(*owner))
+                    .map(|owner| OwnedBy{ owner, turns: charm.previous_turns });
+                recovered.push((entity, previous));
+            }
+        }
+        for (entity, previous) in recovered {
+            charmed.remove(entity);
+            match previous {
+                Some(previous) => { owned.insert(entity, previous).expect("Unable to insert owner"); }
+                None => { owned.remove(entity); }
+            }
+            if let Some(name) = names.get(entity) {
+                log.push(LogCategory::Combat, format!("The {} shakes off your control!", name.name));
+            }
+        }
+
+        let mut dismissed : Vec<Entity> = Vec::new();
+        for (entity, minion) in (&entities, &mut owned).join() {
+            let owner_alive = entities.is_alive(minion.owner) && combat_stats.get(minion.owner).map_or(false, |s| s.hp > 0);
+            if let Some(turns) = minion.turns.as_mut() {
+                *turns -= turn_counter.elapsed();
+            }
+            if !owner_alive || minion.turns.map_or(false, |t| t < 1) {
+                dismissed.push(entity);
+            }
+        }
+
+        for entity in dismissed {
+            if let Some(name) = names.get(entity) {
+                log.push(LogCategory::Combat, format!("The {} fades away.", name.name));
+            }
+            entities.delete(entity).expect("Unable to delete");
+        }
+    }
+}
+
+/// Minions and companions of the player fight the nearest visible monster, or stay close to the player.
+pub struct AllyAI {}
+
+impl<'a> System<'a> for AllyAI {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( WriteExpect<'a, Map>,
+                        ReadExpect<'a, Point>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, TurnCounter>,
+                        Entities<'a>,
+                        WriteStorage<'a, Viewshed>,
+                        ReadStorage<'a, OwnedBy>,
+                        ReadStorage<'a, Monster>,
+                        WriteStorage<'a, Position>,
+                        WriteStorage<'a, WantsToMelee>,
+                        WriteStorage<'a, Confusion>,
+                        WriteStorage<'a, Staggered>,
+                        WriteStorage<'a, Companion>,
+                        ReadStorage<'a, Invisible>,
+                        ReadStorage<'a, CanSeeInvisible>,
+                        ReadStorage<'a, Webbed> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (mut map, player_pos, player_entity, runstate, turns, entities, mut viewsheds, owned, monsters, mut positions,
+            mut wants_to_melee, mut confused, mut staggered, mut companions, invisible, see_invisible, webbed) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+        if !owned.join().any(|o| o.owner == *player_entity) { return; }
+
+        // Everything hostile to the player is fair game
+        let enemies : Vec<(Entity, Point)> = (&entities, &monsters, &positions).join()
+            .filter(|(e, _m, _p)| owned.get(*e).map_or(true, |o| o.owner != *player_entity))
+            .map(|(e, _m, p)| (e, Point::new(p.x, p.y)))
+            .collect();
+
+        // Followers walk down this map towards the player, so they flow around each other in corridors
+        let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+        let follow_map = rltk::DijkstraMap::new(map.width, map.height, &[player_idx], &*map, FOLLOW_MAP_DEPTH);
+
+        for (entity, minion, viewshed, pos) in (&entities, &owned, &mut viewsheds, &mut positions).join() {
+            if minion.owner != *player_entity { continue; }
+
+            if let Some(confusion) = confused.get_mut(entity) {
+                confusion.turns -= turns.elapsed();
+                if confusion.turns < 1 { confused.remove(entity); }
+                continue;
+            }
+            if staggered.remove(entity).is_some() { continue; }
+
+            let my_pos = Point::new(pos.x, pos.y);
+            let mut command = CompanionCommand::Follow;
+            if let Some(companion) = companions.get_mut(entity) {
+                // Once the ordered target is gone, go back to following
+                if companion.command == CompanionCommand::Attack && companion.target.map_or(true, |t| !entities.is_alive(t)) {
+                    companion.command = CompanionCommand::Follow;
+                    companion.target = None;
+                }
+                command = companion.command;
+            }
+
+            let target = match command {
+                CompanionCommand::Attack => companions.get(entity)
+                    .and_then(|c| c.target)
+                    .and_then(|t| enemies.iter().find(|(e, _p)| *e == t))
+                    .map(|(e, p)| (*e, *p, rltk::DistanceAlg::Pythagoras.distance2d(my_pos, *p))),
+                _ => enemies.iter()
+                    .filter(|(e, p)| viewshed.visible_tiles.contains(p) && can_perceive(entity, *e, &invisible, &see_invisible))
+                    .map(|(e, p)| (*e, *p, rltk::DistanceAlg::Pythagoras.distance2d(my_pos, *p)))
+                    .min_by(|a, b| a.2.partial_cmp(&b.2).expect("Distances are never NaN"))
+            };
+
+            let next_step = match target {
+                Some((enemy, _enemy_pos, distance)) if distance < 1.5 => {
+                    wants_to_melee.insert(entity, WantsToMelee{ target: enemy }).expect("Unable to insert attack");
+                    continue;
+                }
+                // Allies told to stay only fight what comes to them
+                _ if command == CompanionCommand::Stay => continue,
+                Some((_enemy, enemy_pos, _distance)) => {
+                    let path = rltk::a_star_search(map.xy_idx(pos.x, pos.y), map.xy_idx(enemy_pos.x, enemy_pos.y), &*map);
+                    if path.success && path.steps.len() > 1 { Some(path.steps[1]) } else { None }
+                }
+                None if rltk::DistanceAlg::Pythagoras.distance2d(my_pos, *player_pos) > FOLLOW_DISTANCE => {
+                    rltk::DijkstraMap::find_lowest_exit(&follow_map, map.xy_idx(pos.x, pos.y), &*map)
+                }
+                None => None
+            };
+
+            if let Some(step) = next_step {
+                // Caught in a web, it can still fight but can't go anywhere
+                if map.is_blocked(step) || webbed.get(entity).is_some() { continue; }
+                pos.x = step as i32 % map.width;
+                pos.y = step as i32 / map.width;
+                map.move_entity(entity, step);
+                viewshed.dirty = true;
+            }
+        }
+    }
+}
+
+/// Gives every companion of the player the same order.
+pub fn order_companions(ecs : &mut World, command : CompanionCommand, target : Option<Entity>) {
+    let mut companions = ecs.write_storage::<Companion>();
+    for companion in (&mut companions).join() {
+        companion.command = command;
+        companion.target = target;
+    }
+
+    let message = match command {
+        CompanionCommand::Follow => "You call your companions to your side.",
+        CompanionCommand::Stay => "You tell your companions to stay.",
+        CompanionCommand::Attack => "You point out a target to your companions."
+    };
+    ecs.write_resource::<GameLog>().push(LogCategory::Combat, message.to_string());
+}
@@ -1,8 +1,13 @@
+use std::collections::HashSet;
 use specs::prelude::*;
-use super::{Viewshed, Monster, Position, WantsToMelee, RunState, Confusion};
-use rltk::{Point};
+use super::{Viewshed, Monster, Position, WantsToMelee, RunState, Confusion, Staggered, OwnedBy, Invisible, CanSeeInvisible, HeardNoise,
+            MovesThroughWalls, PackMember, Fleeing, Territory, Patrol, WantsToUseItem, UsedAbility, Webbed, invisibility_system::can_perceive, ethereal::{self, ThroughWalls}, pack_ai_system, turn_counter::TurnCounter};
+use rltk::{Point, RandomNumberGenerator};
 use crate::map::Map;
 
+/// An idle monster in its territory takes a step somewhere about one turn in this many.
+const WANDER_ODDS : i32 = 3;
+
 pub struct MonsterAI {}
 
 impl<'a> System<'a> for MonsterAI {
@@ -11,53 +16,195 @@ impl<'a> System<'a> for MonsterAI {
                         ReadExpect<'a, Point>,
                         ReadExpect<'a, Entity>,
                         ReadExpect<'a, RunState>,
+                        ReadExpect<'a, TurnCounter>,
                         Entities<'a>,
                         WriteStorage<'a, Viewshed>,
                         ReadStorage<'a, Monster>,
                         WriteStorage<'a, Position>,
                         WriteStorage<'a, WantsToMelee>,
-                        WriteStorage<'a, Confusion>);
+                        WriteStorage<'a, Confusion>,
+                        WriteStorage<'a, Staggered>,
+                        ReadStorage<'a, OwnedBy>,
+                        ReadStorage<'a, Invisible>,
+                        ReadStorage<'a, CanSeeInvisible>,
+                        WriteStorage<'a, HeardNoise>,
+                        ReadStorage<'a, MovesThroughWalls>,
+                        ReadStorage<'a, PackMember>,
+                        WriteStorage<'a, Fleeing>,
+                        ReadStorage<'a, Territory>,
+                        WriteStorage<'a, Patrol>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        ReadStorage<'a, WantsToUseItem>,
+                        ReadStorage<'a, UsedAbility>,
+                        ReadStorage<'a, Webbed>);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (mut map, player_pos, player_entity, runstate, entities, mut viewshed, monster, mut position, mut wants_to_melee, mut confused) = data;
+        let (mut map, player_pos, player_entity, runstate, turns, entities, mut viewshed, monster, mut position, mut wants_to_melee, mut confused,
+            mut staggered, owned, invisible, see_invisible, mut heard, walkers, members, mut fleeing,
+            territories, mut patrols, mut rng, using, used_ability, webbed) = data;
 
         if *runstate != RunState::MonsterTurn { return; }
 
+        // The player's minions are fair game too
+        let allies : Vec<(Entity, Point)> = (&entities, &owned, &position).join()
+            .filter(|(_e, o, _p)| o.owner == *player_entity)
+            .map(|(e, _o, p)| (e, Point::new(p.x, p.y)))
+            .collect();
+        // The tiles around the player that pack members are already making for this turn
+        let mut claimed : HashSet<usize> = HashSet::new();
+
         for (entity, mut viewshed,_monster,mut pos) in (&entities, &mut viewshed, &monster, &mut position).join() {
+            // Minions summoned by the player fight on the player's side
+            if owned.get(entity).map_or(false, |o| o.owner == *player_entity) { continue; }
+            // Drinking a potion, changing gear or using an ability takes the whole turn
+            if using.get(entity).is_some() || used_ability.get(entity).is_some() { continue; }
+
             let mut can_act = true;
 
             let is_confused = confused.get_mut(entity);
             if let Some(i_am_confused) = is_confused {
-                i_am_confused.turns -= 1;
+                i_am_confused.turns -= turns.elapsed();
                 if i_am_confused.turns < 1 {
                     confused.remove(entity);
                 }
                 can_act = false;
             }
 
+            if staggered.remove(entity).is_some() {
+                can_act = false;
+            }
+
+            // Caught in a web, it can still lash out at whatever is beside it but can't go anywhere
+            let stuck = webbed.get(entity).is_some();
+
+            // Something running scared keeps its distance, and only fights when cornered
+            if can_act && !stuck {
+                if let Some(fear) = fleeing.get_mut(entity) {
+                    fear.turns -= turns.elapsed();
+                    if fear.turns < 1 {
+                        fleeing.remove(entity);
+                    }
+                    if let Some(idx) = pack_ai_system::flee_step(&map, Point::new(pos.x, pos.y), *player_pos) {
+                        step(&mut map, entity, pos, viewshed, idx);
+                        continue;
+                    }
+                }
+            }
+
             if can_act {
+                // An invisible player can't be hunted by those who can't see it
+                let sees_player = can_perceive(entity, *player_entity, &invisible, &see_invisible);
+                // Whatever drifts through walls has to come out of them to strike
+                let in_wall = ethereal::in_wall(&map, &walkers, entity, pos.x, pos.y);
                 let distance = rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *player_pos);
-                if distance < 1.5 {
+                let adjacent_ally = allies.iter()
+                    .filter(|(e, _p)| can_perceive(entity, *e, &invisible, &see_invisible))
+                    .find(|(_e, p)| rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *p) < 1.5);
+                // What it's after: the player in sight, or else whatever it heard, unless that's off its home ground
+                let sighted = viewshed.visible_tiles.contains(&*player_pos);
+                let quarry = (if sighted { Some(*player_pos) } else { heard.get(entity).map(|noise| Point::new(noise.x, noise.y)) })
+                    .filter(|goal| in_territory(territories.get(entity), *goal));
+                let through_walls = walkers.get(entity).is_some();
+                if !sees_player {
+                    if let (Some((ally, _ally_pos)), false) = (adjacent_ally, in_wall) {
+                        wants_to_melee.insert(entity, WantsToMelee{ target: *ally }).expect("Unable to insert attack");
+                    } else if !stuck {
+                        idle(&mut map, entity, pos, viewshed, through_walls, patrols.get_mut(entity), territories.get(entity), &mut rng);
+                    }
+                }
+                else if distance < 1.5 && !in_wall {
                     wants_to_melee.insert(entity, WantsToMelee{ target: *player_entity }).expect("Unable to insert attack");
                 }
-                else if viewshed.visible_tiles.contains(&*player_pos) {
-                    // Path to the player
-                    let path = rltk::a_star_search(
-                        map.xy_idx(pos.x, pos.y),
-                        map.xy_idx(player_pos.x, player_pos.y),
-                        &*map
-                    );
-                    if path.success && path.steps.len()>1 {
-                        let mut idx = map.xy_idx(pos.x, pos.y);
-                        map.blocked[idx] = false;
-                        pos.x = path.steps[1] as i32 % map.width;
-                        pos.y = path.steps[1] as i32 / map.width;
-                        idx = map.xy_idx(pos.x, pos.y);
-                        map.blocked[idx] = true;
-                        viewshed.dirty = true;
+                else if let (Some((ally, _ally_pos)), false) = (adjacent_ally, in_wall) {
+                    wants_to_melee.insert(entity, WantsToMelee{ target: *ally }).expect("Unable to insert attack");
+                }
+                else if stuck {
+                    // Nothing within reach, and no way of getting closer until the web gives
+                }
+                else if distance < 1.5 {
+                    // Still in the wall beside the player: step out next to them, ready to strike next turn
+                    if let Some(idx) = ethereal::way_out(&map, Point::new(pos.x, pos.y), *player_pos) {
+                        step(&mut map, entity, pos, viewshed, idx);
                     }
                 }
+                else if let Some(goal) = quarry {
+                    if sighted {
+                        heard.remove(entity);
+                    }
+                    // A pack spreads out round its quarry instead of following each other in
+                    let goal = match members.get(entity) {
+                        Some(_) => pack_ai_system::flank_tile(&map, Point::new(pos.x, pos.y), goal, &mut claimed).unwrap_or(goal),
+                        None => goal
+                    };
+                    let path = walk_towards(&mut map, entity, pos, viewshed, goal, through_walls);
+                    if !path.success || path.steps.len() <= 2 {
+                        heard.remove(entity);
+                    }
+                }
+                else {
+                    // Nothing to chase, or it has gone off home ground: back to the rounds
+                    heard.remove(entity);
+                    idle(&mut map, entity, pos, viewshed, through_walls, patrols.get_mut(entity), territories.get(entity), &mut rng);
+                }
             }
         }
     }
+}
+
+fn in_territory(territory : Option<&Territory>, pt : Point) -> bool {
+    territory.map_or(true, |t| rltk::DistanceAlg::Pythagoras.distance2d(Point::new(t.home_x, t.home_y), pt) <= t.radius as f32)
+}
+
+/// What a monster does with nothing to hunt: walks its patrol, heads home if it has strayed from its
+/// territory, or now and then wanders about inside it. Anything else stays put.
+#[allow(clippy::too_many_arguments)]
+fn idle(map : &mut Map, entity : Entity, pos : &mut Position, viewshed : &mut Viewshed, through_walls : bool,
+        patrol : Option<&mut Patrol>, territory : Option<&Territory>, rng : &mut RandomNumberGenerator) {
+    let here = Point::new(pos.x, pos.y);
+    if let Some(patrol) = patrol {
+        if patrol.waypoints.is_empty() { return; }
+        if patrol.waypoints[patrol.next] == (here.x, here.y) {
+            patrol.next = (patrol.next + 1) % patrol.waypoints.len();
+        }
+        let (x, y) = patrol.waypoints[patrol.next];
+        // A waypoint that can't be reached right now is skipped
+        if !walk_towards(map, entity, pos, viewshed, Point::new(x, y), through_walls).success {
+            patrol.next = (patrol.next + 1) % patrol.waypoints.len();
+        }
+        return;
+    }
+
+    let territory = match territory { Some(t) => t, None => return };
+    if !in_territory(Some(territory), here) {
+        walk_towards(map, entity, pos, viewshed, Point::new(territory.home_x, territory.home_y), through_walls);
+    } else if rng.roll_dice(1, WANDER_ODDS) == 1 {
+        let spot = Point::new(here.x + rng.range(-1, 2), here.y + rng.range(-1, 2));
+        if spot != here && in_territory(Some(territory), spot) {
+            walk_towards(map, entity, pos, viewshed, spot, through_walls);
+        }
+    }
+}
+
+/// Takes one step along the shortest path to `goal`, through walls if the monster can go that way,
+/// and hands back the path it found.
+fn walk_towards(map : &mut Map, entity : Entity, pos : &mut Position, viewshed : &mut Viewshed, goal : Point, through_walls : bool) -> rltk::NavigationPath {
+    let start = map.xy_idx(pos.x, pos.y);
+    let end = map.xy_idx(goal.x, goal.y);
+    let path = if through_walls {
+        rltk::a_star_search(start, end, &ThroughWalls{ map: &*map })
+    } else {
+        rltk::a_star_search(start, end, &*map)
+    };
+    if path.success && path.steps.len() > 1 {
+        step(map, entity, pos, viewshed, path.steps[1]);
+    }
+    path
+}
+
+/// Moves a monster onto a neighbouring tile, keeping the map's index and its view up to date.
+fn step(map : &mut Map, entity : Entity, pos : &mut Position, viewshed : &mut Viewshed, idx : usize) {
+    pos.x = idx as i32 % map.width;
+    pos.y = idx as i32 / map.width;
+    map.move_entity(entity, idx);
+    viewshed.dirty = true;
 }
\ No newline at end of file
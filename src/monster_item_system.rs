@@ -0,0 +1,94 @@
+use specs::prelude::*;
+use rltk::Point;
+use super::{Monster, UsesItems, CombatStats, Position, Viewshed, Name, InBackpack, Equipped, Equippable, ProvidesHealing,
+            MeleePowerBonus, DefenseBonus, Durability, Confusion, Staggered, OwnedBy, WantsToUseItem, RunState,
+            durability::worn_bonus, gamelog::{GameLog, LogCategory}};
+
+/// Below this percentage of its hit points a monster reaches for a healing potion.
+const BADLY_HURT_PERCENT : i32 = 33;
+
+/// Lets monsters that use items spend their turn on them: a healing potion when badly hurt, or
+/// otherwise any gear in their pack better than what they're wearing. The items themselves are
+/// used just as the player's are, so monster gear counts in combat the same way.
+pub struct MonsterItemAI {}
+
+impl<'a> System<'a> for MonsterItemAI {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        ReadStorage<'a, Monster>,
+                        ReadStorage<'a, UsesItems>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Viewshed>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, InBackpack>,
+                        ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, Equippable>,
+                        ReadStorage<'a, ProvidesHealing>,
+                        ReadStorage<'a, MeleePowerBonus>,
+                        ReadStorage<'a, DefenseBonus>,
+                        ReadStorage<'a, Durability>,
+                        ReadStorage<'a, Confusion>,
+                        ReadStorage<'a, Staggered>,
+                        ReadStorage<'a, OwnedBy>,
+                        WriteStorage<'a, WantsToUseItem> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, runstate, player_entity, mut log, monsters, users, stats, positions, viewsheds, names, backpack,
+            equipped, equippable, healing, power_bonuses, defense_bonuses, durability, confused, staggered, owned, mut wants_use) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        let gear_score = |item : Entity| -> i32 {
+            power_bonuses.get(item).map_or(0, |b| worn_bonus(b.power, durability.get(item)))
+                + defense_bonuses.get(item).map_or(0, |b| worn_bonus(b.defense, durability.get(item)))
+        };
+        let player_viewshed = viewsheds.get(*player_entity);
+
+        for (entity, _monster, _user, stats, pos) in (&entities, &monsters, &users, &stats, &positions).join() {
+            if confused.get(entity).is_some() || staggered.get(entity).is_some() { continue; }
+            if owned.get(entity).map_or(false, |o| o.owner == *player_entity) { continue; }
+
+            let carried : Vec<Entity> = (&entities, &backpack).join()
+                .filter(|(_item, pack)| pack.owner == entity)
+                .map(|(item, _pack)| item)
+                .collect();
+
+            // Badly hurt, it drinks the strongest healing it has
+            let potion = if stats.hp * 100 < stats.max_hp * BADLY_HURT_PERCENT {
+                carried.iter().filter_map(|item| healing.get(*item).map(|h| (*item, h.heal_amount))).max_by_key(|(_item, amount)| *amount)
+            } else {
+                None
+            };
+
+            // Otherwise it swaps in whatever in its pack is the biggest step up over what it has on
+            let upgrade = carried.iter()
+                .filter_map(|item| equippable.get(*item).map(|e| (*item, e.slot)))
+                .map(|(item, slot)| {
+                    let current = (&entities, &equipped).join()
+                        .filter(|(_worn, e)| e.owner == entity && e.slot == slot)
+                        .map(|(worn, _e)| gear_score(worn))
+                        .max()
+                        .unwrap_or(0);
+                    (item, gear_score(item) - current)
+                })
+                .filter(|(_item, gain)| *gain > 0)
+                .max_by_key(|(_item, gain)| *gain);
+
+            let (item, verb) = match (potion, upgrade) {
+                (Some((item, _amount)), _) => (item, "drinks"),
+                (None, Some((item, _gain))) => (item, "equips"),
+                (None, None) => continue
+            };
+            wants_use.insert(entity, WantsToUseItem{ item, target: None }).expect("Unable to insert intent");
+
+            let in_view = player_viewshed.map_or(false, |v| v.visible_tiles.contains(&Point::new(pos.x, pos.y)));
+            if let (true, Some(name), Some(item_name)) = (in_view, names.get(entity), names.get(item)) {
+                log.push(LogCategory::Combat, format!("{} {} the {}.", name.name, verb, item_name.name));
+            }
+        }
+    }
+}
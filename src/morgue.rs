@@ -0,0 +1,74 @@
+use specs::prelude::*;
+use specs::shrev::ReaderId;
+use super::{CombatStats, Name, Map, gamelog::{GameLog, LogCategory}, turn_counter::TurnCounter, replay::Replay, run_stats::RunStats, undo::RunMode,
+            events::{GameEvents, GameEvent}, storage};
+
+const MORGUE_LOG_LINES : usize = 10;
+
+/// Dumps a summary of the finished run to morgue.txt when the player dies or wins, unless it was
+/// only a replay. It listens for the end of the run rather than being told, so the last turn's
+/// messages and statistics are in by the time it writes.
+#[derive(Default)]
+pub struct MorgueSystem {
+    reader : Option<ReaderId<GameEvent>>
+}
+
+impl<'a> System<'a> for MorgueSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, GameEvents>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, Replay>,
+                        ReadExpect<'a, TurnCounter>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, GameLog>,
+                        ReadExpect<'a, RunStats>,
+                        ReadExpect<'a, RunMode>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, CombatStats> );
+
+    fn setup(&mut self, world : &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(world.write_resource::<GameEvents>().register_reader());
+    }
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (events, player_entity, replay, turns, map, mut log, stats, mode, names, combat_stats) = data;
+
+        let mut outcome : Option<String> = None;
+        for event in events.read(self.reader.as_mut().expect("MorgueSystem was not set up")) {
+            match event {
+                GameEvent::PlayerDied => outcome = Some(format!("Killed in {}.", map.branch.level_name(map.depth))),
+                GameEvent::Victory => outcome = Some("Escaped the dungeon with the Amulet.".to_string()),
+                _ => {}
+            }
+        }
+        let outcome = match outcome {
+            Some(outcome) if !replay.is_playing() => outcome,
+            _ => return
+        };
+
+        let mut lines : Vec<String> = Vec::new();
+        if let Some(name) = names.get(*player_entity) {
+            lines.push(format!("Morgue file for {}", name.name));
+        }
+        if let Some(stats) = combat_stats.get(*player_entity) {
+            lines.push(format!("HP: {} / {}", stats.hp, stats.max_hp));
+        }
+        lines.push(outcome);
+        lines.push(format!("Depth: {}", map.branch.level_name(map.depth)));
+        lines.push(format!("Turns survived: {}", turns.turn));
+        lines.push(format!("Mode: {}", mode.title()));
+        lines.push(String::new());
+        lines.extend(stats.report());
+        lines.push(String::new());
+        lines.push("Last messages:".to_string());
+        for entry in log.entries.iter().rev().take(MORGUE_LOG_LINES).rev() {
+            lines.push(entry.line());
+        }
+
+        lines.push(String::new());
+        if let Err(err) = storage::write("morgue.txt", &lines.join("\n")) {
+            log.push(LogCategory::System, err);
+        }
+    }
+}
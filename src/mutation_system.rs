@@ -0,0 +1,178 @@
+use specs::prelude::*;
+use rltk::RandomNumberGenerator;
+use super::{Mutations, Mutation, Viewshed, Name, Map, RunState, difficulty, gamelog::{GameLog, LogCategory},
+            effects::{EffectQueue, EffectType, Targets}};
+
+/// Below this depth, the air itself is thick with chaos.
+const CHAOS_DEPTH : i32 = 6;
+/// Chance, as one in this many, that each turn spent that deep mutates the player.
+const EXPOSURE_ODDS : i32 = 1000;
+
+const ALL_MUTATIONS : [Mutation; 10] = [
+    Mutation::Claws, Mutation::ScaledHide, Mutation::NightEyes, Mutation::SlowMetabolism, Mutation::LongLegs,
+    Mutation::WitheredArm, Mutation::BrittleBones, Mutation::Cataracts, Mutation::Ravenous, Mutation::ClubFoot
+];
+
+impl Mutation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mutation::Claws => "Claws",
+            Mutation::ScaledHide => "Scaled Hide",
+            Mutation::NightEyes => "Night Eyes",
+            Mutation::SlowMetabolism => "Slow Metabolism",
+            Mutation::LongLegs => "Long Legs",
+            Mutation::WitheredArm => "Withered Arm",
+            Mutation::BrittleBones => "Brittle Bones",
+            Mutation::Cataracts => "Cataracts",
+            Mutation::Ravenous => "Ravenous Hunger",
+            Mutation::ClubFoot => "Club Foot"
+        }
+    }
+
+    /// What it does, for the character sheet.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Mutation::Claws => "+2 melee power",
+            Mutation::ScaledHide => "+2 defense",
+            Mutation::NightEyes => "+3 sight",
+            Mutation::SlowMetabolism => "meals last twice as long",
+            Mutation::LongLegs => "sprinting costs less",
+            Mutation::WitheredArm => "-2 melee power",
+            Mutation::BrittleBones => "-2 defense",
+            Mutation::Cataracts => "-3 sight",
+            Mutation::Ravenous => "meals last half as long",
+            Mutation::ClubFoot => "sprinting costs more"
+        }
+    }
+
+    pub fn beneficial(&self) -> bool {
+        matches!(self, Mutation::Claws | Mutation::ScaledHide | Mutation::NightEyes | Mutation::SlowMetabolism | Mutation::LongLegs)
+    }
+
+    /// The mutation that undoes this one; gaining one while having the other cancels both out.
+    pub fn opposite(&self) -> Mutation {
+        match self {
+            Mutation::Claws => Mutation::WitheredArm,
+            Mutation::ScaledHide => Mutation::BrittleBones,
+            Mutation::NightEyes => Mutation::Cataracts,
+            Mutation::SlowMetabolism => Mutation::Ravenous,
+            Mutation::LongLegs => Mutation::ClubFoot,
+            Mutation::WitheredArm => Mutation::Claws,
+            Mutation::BrittleBones => Mutation::ScaledHide,
+            Mutation::Cataracts => Mutation::NightEyes,
+            Mutation::Ravenous => Mutation::SlowMetabolism,
+            Mutation::ClubFoot => Mutation::LongLegs
+        }
+    }
+
+    pub fn melee_power(&self) -> i32 {
+        match self {
+            Mutation::Claws => 2,
+            Mutation::WitheredArm => -2,
+            _ => 0
+        }
+    }
+
+    pub fn defense(&self) -> i32 {
+        match self {
+            Mutation::ScaledHide => 2,
+            Mutation::BrittleBones => -2,
+            _ => 0
+        }
+    }
+
+    /// Tiles added to, or taken from, how far its owner can see.
+    pub fn sight(&self) -> i32 {
+        match self {
+            Mutation::NightEyes => 3,
+            Mutation::Cataracts => -3,
+            _ => 0
+        }
+    }
+
+    /// Extra stamina a sprint costs.
+    pub fn sprint_cost(&self) -> i32 {
+        match self {
+            Mutation::LongLegs => -1,
+            Mutation::ClubFoot => 1,
+            _ => 0
+        }
+    }
+
+    /// Percentage of the usual time a good meal keeps its owner well fed.
+    pub fn fed_percent(&self) -> i32 {
+        match self {
+            Mutation::SlowMetabolism => 200,
+            Mutation::Ravenous => 50,
+            _ => 100
+        }
+    }
+}
+
+impl Mutations {
+    /// Adds up one of the mutations' effects, such as `Mutation::defense`.
+    pub fn total(&self, effect : fn(&Mutation) -> i32) -> i32 {
+        self.list.iter().map(effect).sum()
+    }
+
+    pub fn scale_meal(&self, turns : i32) -> i32 {
+        self.list.iter().fold(turns, |turns, m| difficulty::scale(turns, m.fed_percent()))
+    }
+}
+
+/// Lets the chaos deep in the dungeon seep into the player, now and then, while they linger there.
+pub struct MutationSystem {}
+
+impl<'a> System<'a> for MutationSystem {
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteExpect<'a, EffectQueue> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, runstate, map, mut rng, mut effects) = data;
+
+        if *runstate != RunState::MonsterTurn || map.depth < CHAOS_DEPTH { return; }
+        if rng.roll_dice(1, EXPOSURE_ODDS) == 1 {
+            effects.add_effect(None, EffectType::Mutate{ beneficial: None }, Targets::Single{ target: *player_entity });
+        }
+    }
+}
+
+/// Gives the target a new mutation, good or bad as `beneficial` says, or either if it's None. One
+/// whose opposite they already have cancels that out instead.
+pub fn mutate(ecs : &mut World, target : Entity, beneficial : Option<bool>) {
+    let mut mutations = ecs.write_storage::<Mutations>();
+    let mut log = ecs.write_resource::<GameLog>();
+    let held : Vec<Mutation> = mutations.get(target).map_or(Vec::new(), |m| m.list.clone());
+    let candidates : Vec<Mutation> = ALL_MUTATIONS.iter()
+        .filter(|m| beneficial.map_or(true, |b| m.beneficial() == b) && !held.contains(m))
+        .copied()
+        .collect();
+    let name = ecs.read_storage::<Name>().get(target).map_or("Something".to_string(), |n| n.name.clone());
+    if candidates.is_empty() {
+        log.push(LogCategory::Items, format!("{} shudders, but nothing changes.", name));
+        return;
+    }
+
+    let roll = ecs.write_resource::<RandomNumberGenerator>().roll_dice(1, candidates.len() as i32);
+    let mutation = candidates[(roll - 1) as usize];
+    if held.contains(&mutation.opposite()) {
+        if let Some(mutated) = mutations.get_mut(target) { mutated.list.retain(|m| *m != mutation.opposite()); }
+        log.push(LogCategory::Items, format!("{}'s {} is gone!", name, mutation.opposite().name()));
+    } else {
+        match mutations.get_mut(target) {
+            Some(mutated) => mutated.list.push(mutation),
+            None => { mutations.insert(target, Mutations{ list: vec![mutation] }).expect("Unable to insert mutations"); }
+        }
+        log.push(LogCategory::Items, format!("{} mutates, gaining {}!", name, mutation.name()));
+    }
+    if mutations.get(target).map_or(false, |m| m.list.is_empty()) {
+        mutations.remove(target);
+    }
+
+    if mutation.sight() != 0 {
+        if let Some(viewshed) = ecs.write_storage::<Viewshed>().get_mut(target) { viewshed.dirty = true; }
+    }
+}
@@ -0,0 +1,134 @@
+use serde::{Serialize, Deserialize};
+use super::{controller::{PadBindings, PadAction}, palette::Palette, achievements::Profile, storage, ItemCategory};
+
+/// Settings picked from the options screen on the main menu. They belong to the player rather than
+/// the run, so they live in their own file instead of the save game.
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    /// Draws the map with sprites from the tileset, for every glyph the raws give a tile.
+    pub tile_graphics : bool,
+    /// Animates steps and missiles between tiles, instead of jumping straight to where they end up.
+    pub smooth_movement : bool,
+    /// Rattles the view when the player takes a heavy blow or something explodes.
+    pub screen_shake : bool,
+    /// Lights up whatever was just hurt, and the border around the view when it's the player.
+    pub hit_flashes : bool,
+    /// Works out who can see what with symmetric shadowcasting, so anything in view of the player can
+    /// see them back, instead of rltk's field of view.
+    pub symmetric_fov : bool,
+    /// Shows the whole level shrunk into a corner of the view. Toggled with Tab during play.
+    pub show_minimap : bool,
+    /// The colours everything is drawn in.
+    pub palette : Palette,
+    /// Which kinds of item the player picks up just by stepping on them.
+    pub auto_pickup : AutoPickup,
+    /// How loud sound effects and music play, in percent. Only heard in builds with the `audio` feature.
+    pub sound_volume : i32,
+    pub music_volume : i32,
+    /// Which controller button does what.
+    pub controller : PadBindings
+}
+
+/// One switch per category of item tagged in the raws.
+#[derive(Serialize, Deserialize, Copy, Clone)]
+#[serde(default)]
+pub struct AutoPickup {
+    pub gold : bool,
+    pub ammo : bool,
+    pub potions : bool
+}
+
+impl Default for AutoPickup {
+    fn default() -> AutoPickup {
+        AutoPickup{ gold: true, ammo: true, potions: false }
+    }
+}
+
+impl AutoPickup {
+    pub fn picks_up(&self, category : ItemCategory) -> bool {
+        match category {
+            ItemCategory::Gold => self.gold,
+            ItemCategory::Ammo => self.ammo,
+            ItemCategory::Potion => self.potions,
+            ItemCategory::Scroll => false
+        }
+    }
+}
+
+impl Default for Options {
+    fn default() -> Options {
+        Options{ tile_graphics: false, smooth_movement: true, screen_shake: true, hit_flashes: true, symmetric_fov: false, show_minimap: false, palette: Palette::Default, auto_pickup: AutoPickup::default(), sound_volume: 100, music_volume: 50, controller: PadBindings::default() }
+    }
+}
+
+impl Options {
+    /// Reads options.json, starting from the defaults if there isn't one yet. Settings added since
+    /// it was written take their defaults.
+    pub fn load() -> Options {
+        storage::read("options.json")
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let data = serde_json::to_string(self).expect("Unable to serialize options");
+        storage::write("options.json", &data)
+    }
+
+    pub fn graphics_name(&self) -> &'static str {
+        if self.tile_graphics { "Tiles" } else { "ASCII" }
+    }
+
+    pub fn movement_name(&self) -> &'static str {
+        if self.smooth_movement { "Smooth" } else { "Instant" }
+    }
+
+    pub fn fov_name(&self) -> &'static str {
+        if self.symmetric_fov { "Symmetric" } else { "Classic" }
+    }
+
+    /// The next volume up, going back round to silence after full.
+    pub fn next_volume(volume : i32) -> i32 {
+        if volume >= 100 { 0 } else { i32::min(100, volume + 25) }
+    }
+
+    pub fn on_off(setting : bool) -> &'static str {
+        if setting { "On" } else { "Off" }
+    }
+}
+
+/// One line of the options screen. The screen is drawn from `all()` and a pick is acted on by
+/// matching the choice, so the two can't fall out of step.
+#[derive(PartialEq, Eq, Copy, Clone)]
+pub enum OptionChoice { Graphics, Movement, ScreenShake, HitFlashes, FieldOfView, Colors, PickupGold, PickupAmmo, PickupPotions,
+    RememberIdentified, SoundVolume, MusicVolume, Pad(PadAction) }
+
+impl OptionChoice {
+    /// Every choice, in the order the screen lists them.
+    pub fn all() -> Vec<OptionChoice> {
+        let mut all = vec![OptionChoice::Graphics, OptionChoice::Movement, OptionChoice::ScreenShake, OptionChoice::HitFlashes,
+            OptionChoice::FieldOfView, OptionChoice::Colors, OptionChoice::PickupGold, OptionChoice::PickupAmmo, OptionChoice::PickupPotions,
+            OptionChoice::RememberIdentified, OptionChoice::SoundVolume, OptionChoice::MusicVolume];
+        all.extend(PadAction::ALL.iter().map(|action| OptionChoice::Pad(*action)));
+        all
+    }
+
+    pub fn label(self, options : &Options, profile : &Profile) -> String {
+        match self {
+            OptionChoice::Graphics => format!("Graphics: {}", options.graphics_name()),
+            OptionChoice::Movement => format!("Movement: {}", options.movement_name()),
+            OptionChoice::ScreenShake => format!("Screen shake: {}", Options::on_off(options.screen_shake)),
+            OptionChoice::HitFlashes => format!("Hit flashes: {}", Options::on_off(options.hit_flashes)),
+            OptionChoice::FieldOfView => format!("Field of view: {}", options.fov_name()),
+            OptionChoice::Colors => format!("Colors: {}", options.palette.name()),
+            OptionChoice::PickupGold => format!("Auto-pickup gold: {}", Options::on_off(options.auto_pickup.gold)),
+            OptionChoice::PickupAmmo => format!("Auto-pickup ammo: {}", Options::on_off(options.auto_pickup.ammo)),
+            OptionChoice::PickupPotions => format!("Auto-pickup potions: {}", Options::on_off(options.auto_pickup.potions)),
+            OptionChoice::RememberIdentified => format!("Remember identified: {}", Options::on_off(profile.remember_identifications)),
+            OptionChoice::SoundVolume => format!("Sound volume: {}%", options.sound_volume),
+            OptionChoice::MusicVolume => format!("Music volume: {}%", options.music_volume),
+            OptionChoice::Pad(action) => format!("Pad {}: {}", action.name(), options.controller.button(action).name())
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::collections::HashSet;
+use specs::prelude::*;
+use rltk::Point;
+use super::{Viewshed, Position, PackMember, Fleeing, HeardNoise, Name, RunState, Invisible, CanSeeInvisible, invisibility_system::can_perceive,
+            gamelog::{GameLog, LogCategory}};
+use crate::map::Map;
+
+/// How long a pack runs once its leader is dead.
+const FLEE_TURNS : i32 = 12;
+/// How much a tile next to the player is put off by each tile beside it another pack member is heading for.
+const CROWDING_PENALTY : f32 = 0.5;
+
+/// Keeps packs acting together: once any of them sees the player the rest come too, and when the
+/// leader dies they all run.
+pub struct PackAI {}
+
+impl<'a> System<'a> for PackAI {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, Point>,
+                        WriteExpect<'a, GameLog>,
+                        ReadStorage<'a, Viewshed>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Invisible>,
+                        ReadStorage<'a, CanSeeInvisible>,
+                        WriteStorage<'a, PackMember>,
+                        WriteStorage<'a, Fleeing>,
+                        WriteStorage<'a, HeardNoise> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, runstate, player_entity, player_pos, mut log, viewsheds, positions, names, invisible, see_invisible,
+            mut members, mut fleeing, mut heard) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        // A pack whose leader has fallen scatters
+        let scattered : Vec<Entity> = (&entities, &members).join()
+            .filter(|(_entity, member)| !entities.is_alive(member.leader) || positions.get(member.leader).is_none())
+            .map(|(entity, _member)| entity)
+            .collect();
+        let player_viewshed = viewsheds.get(*player_entity);
+        let mut seen_running : Option<String> = None;
+        for entity in scattered {
+            members.remove(entity);
+            fleeing.insert(entity, Fleeing{ turns: FLEE_TURNS }).expect("Unable to insert fleeing");
+            let in_view = positions.get(entity).zip(player_viewshed).map_or(false, |(pos, v)| v.visible_tiles.contains(&Point::new(pos.x, pos.y)));
+            if in_view { seen_running = names.get(entity).map(|n| n.name.clone()); }
+        }
+        if let Some(name) = seen_running {
+            log.push(LogCategory::Combat, format!("With its leader dead, the {} pack turns tail!", name.to_lowercase()));
+        }
+
+        // A pack that spots the player hunts as one
+        let alerted : HashSet<Entity> = (&entities, &members, &viewsheds).join()
+            .filter(|(entity, _member, viewshed)| viewshed.visible_tiles.contains(&*player_pos) && can_perceive(*entity, *player_entity, &invisible, &see_invisible))
+            .map(|(_entity, member, _viewshed)| member.leader)
+            .collect();
+        for (entity, member, viewshed) in (&entities, &members, &viewsheds).join() {
+            if alerted.contains(&member.leader) && !viewshed.visible_tiles.contains(&*player_pos) {
+                heard.insert(entity, HeardNoise{ x: player_pos.x, y: player_pos.y }).expect("Unable to insert noise");
+            }
+        }
+    }
+}
+
+/// Where a pack member closing on `target` should head: the free tile beside the target nearest
+/// to it, skipping those packmates already claimed and shying away from crowding them, so the pack
+/// spreads round the target instead of queueing along one path. Claims the tile it picks.
+pub fn flank_tile(map : &Map, from : Point, target : Point, claimed : &mut HashSet<usize>) -> Option<Point> {
+    let mut best : Option<(Point, f32)> = None;
+    for dy in -1 ..= 1 {
+        for dx in -1 ..= 1 {
+            let tile = Point::new(target.x + dx, target.y + dy);
+            if tile == target || tile.x < 1 || tile.y < 1 || tile.x > map.width - 2 || tile.y > map.height - 2 { continue; }
+            let idx = map.xy_idx(tile.x, tile.y);
+            if claimed.contains(&idx) || (map.is_blocked(idx) && tile != from) { continue; }
+            let crowding = claimed.iter()
+                .filter(|c| rltk::DistanceAlg::Pythagoras.distance2d(Point::new(**c as i32 % map.width, **c as i32 / map.width), tile) < 1.5)
+                .count() as f32;
+            let score = rltk::DistanceAlg::Pythagoras.distance2d(from, tile) + crowding * CROWDING_PENALTY;
+            if best.map_or(true, |(_tile, best_score)| score < best_score) {
+                best = Some((tile, score));
+            }
+        }
+    }
+    let (tile, _score) = best?;
+    claimed.insert(map.xy_idx(tile.x, tile.y));
+    Some(tile)
+}
+
+/// The step that takes a fleeing creature furthest from `threat`, if any step gets it further away.
+pub fn flee_step(map : &Map, from : Point, threat : Point) -> Option<usize> {
+    let here = rltk::DistanceAlg::Pythagoras.distance2d(from, threat);
+    let mut best : Option<(usize, f32)> = None;
+    for dy in -1 ..= 1 {
+        for dx in -1 ..= 1 {
+            let tile = Point::new(from.x + dx, from.y + dy);
+            if tile == from || tile.x < 1 || tile.y < 1 || tile.x > map.width - 2 || tile.y > map.height - 2 { continue; }
+            let idx = map.xy_idx(tile.x, tile.y);
+            if map.is_blocked(idx) { continue; }
+            let distance = rltk::DistanceAlg::Pythagoras.distance2d(tile, threat);
+            if distance > here && best.map_or(true, |(_idx, best_distance)| distance > best_distance) {
+                best = Some((idx, distance));
+            }
+        }
+    }
+    best.map(|(idx, _distance)| idx)
+}
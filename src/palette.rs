@@ -0,0 +1,75 @@
+use std::sync::atomic::{AtomicU8, Ordering};
+use rltk::RGB;
+use serde::{Serialize, Deserialize};
+
+/// The set of colours everything is drawn in, picked on the options screen. Colours are kept as
+/// they were written everywhere else and only changed here, on their way to the screen.
+#[derive(PartialEq, Eq, Copy, Clone, Serialize, Deserialize)]
+pub enum Palette { Default, Deuteranopia, HighContrast }
+
+impl Palette {
+    pub fn name(self) -> &'static str {
+        match self {
+            Palette::Default => "Default",
+            Palette::Deuteranopia => "Deuteranopia",
+            Palette::HighContrast => "High contrast"
+        }
+    }
+
+    pub fn next(self) -> Palette {
+        match self {
+            Palette::Default => Palette::Deuteranopia,
+            Palette::Deuteranopia => Palette::HighContrast,
+            Palette::HighContrast => Palette::Default
+        }
+    }
+}
+
+/// The palette in use. Drawing happens all over, often without the world to hand, so it lives here
+/// rather than in a resource.
+static ACTIVE : AtomicU8 = AtomicU8::new(Palette::Default as u8);
+
+pub fn set(palette : Palette) {
+    ACTIVE.store(palette as u8, Ordering::Relaxed);
+}
+
+fn active() -> Palette {
+    match ACTIVE.load(Ordering::Relaxed) {
+        1 => Palette::Deuteranopia,
+        2 => Palette::HighContrast,
+        _ => Palette::Default
+    }
+}
+
+/// A colour as the active palette shows it.
+pub fn color(rgb : RGB) -> RGB {
+    match active() {
+        Palette::Default => rgb,
+        Palette::Deuteranopia => daltonize(rgb),
+        Palette::HighContrast => RGB::from_f32(stretch(rgb.r), stretch(rgb.g), stretch(rgb.b))
+    }
+}
+
+/// One of rltk's named colours, as the active palette shows it.
+pub fn named(col : (u8, u8, u8)) -> RGB {
+    color(RGB::named(col))
+}
+
+/// Works out what someone without green-sensitive cones would lose of a colour, and puts that
+/// difference back into the red and blue they can tell apart, so red and green stop looking alike.
+fn daltonize(rgb : RGB) -> RGB {
+    let seen_r = 0.625 * rgb.r + 0.375 * rgb.g;
+    let seen_g = 0.7 * rgb.r + 0.3 * rgb.g;
+    let seen_b = 0.3 * rgb.g + 0.7 * rgb.b;
+    let (lost_r, lost_g, lost_b) = (rgb.r - seen_r, rgb.g - seen_g, rgb.b - seen_b);
+    RGB::from_f32(
+        rgb.r.clamp(0.0, 1.0),
+        (rgb.g + 0.7 * lost_r + lost_g).clamp(0.0, 1.0),
+        (rgb.b + 0.7 * lost_r + lost_b).clamp(0.0, 1.0)
+    )
+}
+
+/// Pushes a channel away from the middle, so dark goes black and bright goes full.
+fn stretch(channel : f32) -> f32 {
+    ((channel - 0.5) * 2.0 + 0.5).clamp(0.0, 1.0)
+}
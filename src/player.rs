@@ -1,11 +1,48 @@
-use rltk::{VirtualKeyCode, Rltk, Point, console};
+use rltk::{VirtualKeyCode, Point, console};
 use specs::prelude::*;
-use super::{Position, Player, State, Viewshed, RunState, CombatStats, WantsToMelee, Item, WantsToPickupItem};
+use super::{Position, Player, State, Viewshed, RunState, CombatStats, WantsToMelee, Item, WantsToPickupItem,
+            InBackpack, MacGuffin, Branch, Staggered, Pools, PowerAttack, Equipped, WeaponSpecial, SpecialAttack, Webbed,
+            OwnedBy, Recruitable, Companion, CompanionCommand, Experience, Name, Container, Locked, Key, Door, HeardNoise, Lever, Wired, Interactive, Butcherable, PropEffect, DotEffect, DamageType, Monster, BlocksTile, Renderable, Attributes, Skills, Resting, HungerClock, HungerState, Travelling, Launcher, Vendor, QuestGiver, Talks, Category, Hotbar, Faith, Mutations, Mutation, Skill,
+            gamesystem::attr_bonus, skill_training::LOCKPICK_XP, religion, raws, scores, ranged_combat_system::loaded_launcher, vendor, quests, dialogue, wizard::WizardMode, options::Options, undo, gui, input::Input, events::{self, GameEvent}, effects::{EffectQueue, EffectType, Targets}};
 use std::cmp::{min, max};
-use crate::gamelog::GameLog;
-use crate::map::Map;
+use crate::gamelog::{GameLog, LogCategory, LogFilter};
+use crate::map::{Map, TileType};
+
+const POWER_ATTACK_COST: i32 = 3;
+const SPRINT_COST: i32 = 2;
+const LOCKPICK_DIFFICULTY: i32 = 15;
+const NOISE_RADIUS: f32 = 12.0;
+/// However stealthy the player, a botched lock is heard at least this far off.
+const MIN_NOISE_RADIUS: f32 = 4.0;
+/// Resting gives up after this many turns even if not fully recovered.
+const MAX_REST_TURNS: i32 = 100;
 
 pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
+    if take_step(delta_x, delta_y, ecs) {
+        auto_pickup(ecs);
+    }
+}
+
+/// Picks up whatever is underfoot that the options say to take without asking.
+fn auto_pickup(ecs: &mut World) {
+    let rules = ecs.fetch::<Options>().auto_pickup;
+    let items : Vec<Entity> = {
+        let categories = ecs.read_storage::<Category>();
+        items_to_pick_up(ecs, false).into_iter()
+            .filter(|item| categories.get(*item).map_or(false, |c| rules.picks_up(c.category)))
+            .collect()
+    };
+    pick_up_items(ecs, &items);
+}
+
+/// Moves the player one tile, or acts on whatever stands there. True if they ended up on a new tile.
+fn take_step(delta_x: i32, delta_y: i32, ecs: &mut World) -> bool {
+    // Webs hold the player in place until they wear off, turn by turn
+    if ecs.read_storage::<Webbed>().get(*ecs.fetch::<Entity>()).is_some() {
+        ecs.write_resource::<GameLog>().push(LogCategory::Combat, "You struggle against the web.".to_string());
+        return false;
+    }
+
     let mut position = ecs.write_storage::<Position>();
     let player = ecs.write_storage::<Player>();
     let mut viewshed = ecs.write_storage::<Viewshed>();
@@ -15,31 +52,586 @@ pub fn try_move_player(delta_x: i32, delta_y: i32, ecs: &mut World) {
 
     let entities = ecs.entities();
     let mut wants_to_melee = ecs.write_storage::<WantsToMelee>();
+    let mut owned = ecs.write_storage::<OwnedBy>();
+    let mut recruitable = ecs.write_storage::<Recruitable>();
+    let mut swapped : Option<(Entity, Position)> = None;
+    let mut recruited : Option<(Entity, Entity)> = None;
+    let mut moved = false;
 
     for (entity, _player, pos, viewshed) in (&entities, &player, &mut position, &mut viewshed).join() {
-        if pos.x + delta_x < 1 || pos.x + delta_x > map.width-1 || pos.y + delta_y < 1 || pos.y + delta_y > map.height-1 { return; }
+        if pos.x + delta_x < 1 || pos.x + delta_x > map.width-1 || pos.y + delta_y < 1 || pos.y + delta_y > map.height-1 { return false; }
         let destination_idx = map.xy_idx(pos.x + delta_x, pos.y + delta_y);
 
-        for potential_target in map.tile_content[destination_idx].iter() {
+        for potential_target in map.entities_at(destination_idx).iter() {
+            // Walking into one of your own minions swaps places with it
+            if owned.get(*potential_target).map_or(false, |o| o.owner == entity) {
+                swapped = Some((*potential_target, pos.clone()));
+                pos.x += delta_x;
+                pos.y += delta_y;
+                ppos.x = pos.x;
+                ppos.y = pos.y;
+                viewshed.dirty = true;
+                moved = true;
+                break;
+            }
+            if recruitable.get(*potential_target).is_some() {
+                recruited = Some((entity, *potential_target));
+                break;
+            }
             let target = combat_stats.get(*potential_target);
             if let Some(_target) = target {
                 wants_to_melee.insert(entity, WantsToMelee{ target: *potential_target}).expect("Add target failed");
-                return;
+                return false;
             }
         }
-        if !map.blocked[destination_idx] {
-            pos.x = min(79 , max(0, pos.x + delta_x));
-            pos.y = min(49, max(0, pos.y + delta_y));
+        if swapped.is_none() && recruited.is_none() && !map.is_blocked(destination_idx) {
+            pos.x = min(map.width - 1, max(0, pos.x + delta_x));
+            pos.y = min(map.height - 1, max(0, pos.y + delta_y));
 
             ppos.x = pos.x;
             ppos.y = pos.y;
 
             viewshed.dirty = true;
+            moved = true;
+        }
+    }
+
+    if let Some((ally, old_pos)) = swapped {
+        position.insert(ally, old_pos).expect("Unable to move minion");
+    }
+    if let Some((player_entity, recruit)) = recruited {
+        recruitable.remove(recruit);
+        owned.insert(recruit, OwnedBy{ owner: player_entity, turns: None }).expect("Unable to insert owner");
+        ecs.write_storage::<Companion>().insert(recruit, Companion{ command: CompanionCommand::Follow, target: None }).expect("Unable to insert companion");
+        ecs.write_storage::<Experience>().insert(recruit, Experience{ level: 1, xp: 0 }).expect("Unable to insert experience");
+        if let Some(name) = ecs.read_storage::<Name>().get(recruit) {
+            ecs.write_resource::<GameLog>().push(LogCategory::System, format!("The {} joins you!", name.name));
+        }
+    }
+    moved
+}
+
+/// Spends stamina from the entity's pool, returning false if there isn't enough left.
+fn spend_stamina(ecs: &mut World, entity: Entity, cost: i32) -> bool {
+    let mut pools = ecs.write_storage::<Pools>();
+    match pools.get_mut(entity) {
+        Some(pools) if pools.stamina.current >= cost => {
+            pools.stamina.current -= cost;
+            pools.exerted = true;
+            true
+        }
+        _ => false
+    }
+}
+
+/// Holding shift turns a move into a sprint, or an attack into a power attack.
+fn move_or_exert(delta_x: i32, delta_y: i32, exert: bool, ecs: &mut World) {
+    if !exert {
+        try_move_player(delta_x, delta_y, ecs);
+        return;
+    }
+
+    let player_entity = *ecs.fetch::<Entity>();
+    let attacking = {
+        let ppos = ecs.fetch::<Point>();
+        let map = ecs.fetch::<Map>();
+        let combat_stats = ecs.read_storage::<CombatStats>();
+        let (x, y) = (ppos.x + delta_x, ppos.y + delta_y);
+        x > 0 && x < map.width && y > 0 && y < map.height &&
+            map.entities_at(map.xy_idx(x, y)).iter().any(|e| combat_stats.get(*e).is_some())
+    };
+
+    let sprint_cost = SPRINT_COST + ecs.read_storage::<Mutations>().get(player_entity).map_or(0, |m| m.total(Mutation::sprint_cost));
+
+    if attacking {
+        if spend_stamina(ecs, player_entity, POWER_ATTACK_COST) {
+            ecs.write_storage::<PowerAttack>().insert(player_entity, PowerAttack{}).expect("Unable to insert power attack");
+        } else {
+            ecs.write_resource::<GameLog>().push(LogCategory::Combat, "You are too exhausted for a power attack.".to_string());
+        }
+        try_move_player(delta_x, delta_y, ecs);
+    } else if spend_stamina(ecs, player_entity, sprint_cost) {
+        try_move_player(delta_x, delta_y, ecs);
+        // A sprint pulls up short of anyone in the way, rather than running into them
+        let ppos = *ecs.fetch::<Point>();
+        if combatant_at(ecs, ppos.x + delta_x, ppos.y + delta_y).is_none() {
+            try_move_player(delta_x, delta_y, ecs);
+        }
+    } else {
+        ecs.write_resource::<GameLog>().push(LogCategory::Combat, "You are too exhausted to sprint.".to_string());
+        try_move_player(delta_x, delta_y, ecs);
+    }
+}
+
+fn combatant_at(ecs: &World, x: i32, y: i32) -> Option<Entity> {
+    let map = ecs.fetch::<Map>();
+    if x < 1 || x > map.width - 1 || y < 1 || y > map.height - 1 { return None; }
+    let combat_stats = ecs.read_storage::<CombatStats>();
+    map.entities_at(map.xy_idx(x, y)).iter().find(|e| combat_stats.get(**e).is_some()).copied()
+}
+
+/// Ctrl + direction performs the wielded weapon's special attack, if it has one.
+fn try_weapon_special(delta_x: i32, delta_y: i32, ecs: &mut World) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    let special = {
+        let equipped = ecs.read_storage::<Equipped>();
+        let specials = ecs.read_storage::<WeaponSpecial>();
+        (&equipped, &specials).join().find(|(e, _)| e.owner == player_entity).map(|(_, s)| s.clone())
+    };
+    let special = match special {
+        Some(special) => special,
+        None => {
+            ecs.write_resource::<GameLog>().push(LogCategory::Combat, "Your weapon has no special technique.".to_string());
+            return RunState::AwaitingInput;
+        }
+    };
+
+    let ppos = *ecs.fetch::<Point>();
+    let (effects, targets) = match special.attack {
+        SpecialAttack::Cleave => {
+            let tiles : Vec<usize> = (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                .filter(|(dx, dy)| (*dx != 0 || *dy != 0) && combatant_at(ecs, ppos.x + dx, ppos.y + dy).is_some())
+                .map(|(dx, dy)| ecs.fetch::<Map>().xy_idx(ppos.x + dx, ppos.y + dy))
+                .collect();
+            (vec![EffectType::WeaponHit], if tiles.is_empty() { None } else { Some(Targets::Tiles{ tiles }) })
+        }
+        SpecialAttack::Knockback => {
+            let target = combatant_at(ecs, ppos.x + delta_x, ppos.y + delta_y);
+            (vec![EffectType::WeaponHit, EffectType::Knockback{ dx: delta_x, dy: delta_y }], target.map(|target| Targets::Single{ target }))
+        }
+        SpecialAttack::Lunge => {
+            let clear_path = {
+                let map = ecs.fetch::<Map>();
+                let (x, y) = (ppos.x + delta_x, ppos.y + delta_y);
+                x > 0 && x < map.width && y > 0 && y < map.height && !map.is_blocked(map.xy_idx(x, y))
+            };
+            let target = if clear_path { combatant_at(ecs, ppos.x + delta_x * 2, ppos.y + delta_y * 2) } else { None };
+            (vec![EffectType::WeaponHit], target.map(|target| Targets::Single{ target }))
+        }
+    };
+
+    let targets = match targets {
+        Some(targets) => targets,
+        None => {
+            ecs.write_resource::<GameLog>().push(LogCategory::Combat, "There is nothing there to strike.".to_string());
+            return RunState::AwaitingInput;
+        }
+    };
+    if !spend_stamina(ecs, player_entity, special.stamina_cost) {
+        ecs.write_resource::<GameLog>().push(LogCategory::Combat, "You are too exhausted for that technique.".to_string());
+        return RunState::AwaitingInput;
+    }
+
+    let mut queue = ecs.write_resource::<EffectQueue>();
+    for effect in effects {
+        queue.add_effect(Some(player_entity), effect, targets.clone());
+    }
+    RunState::PlayerTurn
+}
+
+fn container_at(ecs: &World, x: i32, y: i32) -> Option<Entity> {
+    let map = ecs.fetch::<Map>();
+    if x < 1 || x > map.width - 1 || y < 1 || y > map.height - 1 { return None; }
+    let containers = ecs.read_storage::<Container>();
+    map.entities_at(map.xy_idx(x, y)).iter().find(|e| containers.get(**e).is_some()).copied()
+}
+
+fn locked_door_at(ecs: &World, x: i32, y: i32) -> Option<Entity> {
+    let map = ecs.fetch::<Map>();
+    if x < 1 || x > map.width - 1 || y < 1 || y > map.height - 1 { return None; }
+    let doors = ecs.read_storage::<Door>();
+    let locks = ecs.read_storage::<Locked>();
+    map.entities_at(map.xy_idx(x, y)).iter().find(|e| doors.get(**e).is_some() && locks.get(**e).is_some()).copied()
+}
+
+fn vendor_at(ecs: &World, x: i32, y: i32) -> Option<Entity> {
+    let map = ecs.fetch::<Map>();
+    if x < 1 || x > map.width - 1 || y < 1 || y > map.height - 1 { return None; }
+    let vendors = ecs.read_storage::<Vendor>();
+    map.entities_at(map.xy_idx(x, y)).iter().find(|e| vendors.get(**e).is_some()).copied()
+}
+
+fn talker_at(ecs: &World, x: i32, y: i32) -> Option<Entity> {
+    let map = ecs.fetch::<Map>();
+    if x < 1 || x > map.width - 1 || y < 1 || y > map.height - 1 { return None; }
+    let talkers = ecs.read_storage::<Talks>();
+    map.entities_at(map.xy_idx(x, y)).iter().find(|e| talkers.get(**e).is_some()).copied()
+}
+
+fn quest_giver_at(ecs: &World, x: i32, y: i32) -> Option<Entity> {
+    let map = ecs.fetch::<Map>();
+    if x < 1 || x > map.width - 1 || y < 1 || y > map.height - 1 { return None; }
+    let givers = ecs.read_storage::<QuestGiver>();
+    map.entities_at(map.xy_idx(x, y)).iter().find(|e| givers.get(**e).is_some()).copied()
+}
+
+fn lever_at(ecs: &World, x: i32, y: i32) -> Option<Entity> {
+    let map = ecs.fetch::<Map>();
+    if x < 1 || x > map.width - 1 || y < 1 || y > map.height - 1 { return None; }
+    let levers = ecs.read_storage::<Lever>();
+    map.entities_at(map.xy_idx(x, y)).iter().find(|e| levers.get(**e).is_some()).copied()
+}
+
+/// Pulls a lever, setting off everything wired to it the first time.
+fn pull_lever(ecs: &mut World, lever: Entity) -> RunState {
+    let wire = ecs.write_storage::<Wired>().remove(lever);
+    match wire {
+        Some(wire) => {
+            if let Some(render) = ecs.write_storage::<Renderable>().get_mut(lever) {
+                render.glyph = rltk::to_cp437('\\');
+            }
+            ecs.write_resource::<GameLog>().push(LogCategory::System, "You pull the lever.".to_string());
+            let player_entity = *ecs.fetch::<Entity>();
+            let mut queue = ecs.write_resource::<EffectQueue>();
+            for target in wire.targets.iter() {
+                queue.add_effect(Some(player_entity), EffectType::Activate, Targets::Single{ target: *target });
+            }
+        }
+        None => ecs.write_resource::<GameLog>().push(LogCategory::System, "The lever won't budge.".to_string())
+    }
+    RunState::PlayerTurn
+}
+
+fn corpse_at(ecs: &World, x: i32, y: i32) -> Option<Entity> {
+    let map = ecs.fetch::<Map>();
+    let butcherable = ecs.read_storage::<Butcherable>();
+    map.entities_at(map.xy_idx(x, y)).iter().find(|e| butcherable.get(**e).is_some()).copied()
+}
+
+/// Carves a corpse up, leaving a cut of meat where it lay.
+fn butcher(ecs: &mut World, corpse: Entity) -> RunState {
+    let (x, y) = match ecs.read_storage::<Position>().get(corpse) {
+        Some(pos) => (pos.x, pos.y),
+        None => return RunState::AwaitingInput
+    };
+    let name = ecs.read_storage::<Name>().get(corpse).map_or("corpse".to_string(), |n| n.name.clone());
+    ecs.delete_entity(corpse).expect("Unable to delete corpse");
+    raws::spawn_named_entity(&raws::raws(), ecs, "Meat", x, y);
+    ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("You butcher the {}.", name));
+    RunState::PlayerTurn
+}
+
+fn interactive_at(ecs: &World, x: i32, y: i32) -> Option<Entity> {
+    let map = ecs.fetch::<Map>();
+    if x < 1 || x > map.width - 1 || y < 1 || y > map.height - 1 { return None; }
+    let interactive = ecs.read_storage::<Interactive>();
+    map.entities_at(map.xy_idx(x, y)).iter().find(|e| interactive.get(**e).is_some()).copied()
+}
+
+/// An altar to pray at, out in the dungeon or in the town temple.
+fn altar_at(ecs: &World, x: i32, y: i32) -> Option<Entity> {
+    let names = ecs.read_storage::<Name>();
+    interactive_at(ecs, x, y).filter(|prop| names.get(*prop).map_or(false, |n| n.name.ends_with("Altar")))
+}
+
+/// Uses a fountain, altar, campfire or the like. Altars and enchanters go on to ask which item to work on;
+/// an altar's use is only spent once something has been placed on it.
+fn interact(ecs: &mut World, prop: Entity) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    let name = ecs.read_storage::<Name>().get(prop).map_or("thing".to_string(), |n| n.name.clone());
+    let interaction = match ecs.read_storage::<Interactive>().get(prop) {
+        Some(interaction) => interaction.clone(),
+        None => return RunState::AwaitingInput
+    };
+    if interaction.uses == Some(0) {
+        ecs.write_resource::<GameLog>().push(LogCategory::System, format!("Nothing more comes of the {}.", name));
+        return RunState::AwaitingInput;
+    }
+    if !interaction.effects.contains(&PropEffect::Bless) {
+        spend_use(ecs, prop);
+    }
+    ecs.write_resource::<GameLog>().push(LogCategory::System, format!("You {} the {}.", interaction.verb, name));
+
+    let mut newrunstate = RunState::PlayerTurn;
+    let mut queue = ecs.write_resource::<EffectQueue>();
+    let me = Targets::Single{ target: player_entity };
+    for effect in interaction.effects.iter() {
+        match effect {
+            PropEffect::Heal(amount) => queue.add_effect(Some(prop), EffectType::Healing{ amount: *amount }, me.clone()),
+            PropEffect::Poison(damage) => {
+                let poison = DotEffect{ kind: DamageType::Poison, damage: *damage, turns: 5 };
+                queue.add_effect(Some(prop), EffectType::Dot{ effect: poison }, me.clone());
+            }
+            PropEffect::WellFed(turns) => queue.add_effect(Some(prop), EffectType::WellFed{ turns: *turns }, me.clone()),
+            PropEffect::Rest => queue.add_effect(Some(prop), EffectType::Rest, me.clone()),
+            PropEffect::Bless => newrunstate = RunState::ShowBlessItem{ altar: prop },
+            PropEffect::Enchant => newrunstate = RunState::ShowEnchantItem,
+            PropEffect::Repair => newrunstate = RunState::ShowRepairItem,
+            PropEffect::Cure(price) => queue.add_effect(Some(prop), EffectType::Cure{ kind: None, price: *price }, me.clone()),
+        }
+    }
+    newrunstate
+}
+
+/// Uses up one of a prop's limited uses, if it has any.
+pub fn spend_use(ecs: &mut World, prop: Entity) {
+    if let Some(uses) = ecs.write_storage::<Interactive>().get_mut(prop).and_then(|i| i.uses.as_mut()) {
+        *uses -= 1;
+    }
+}
+
+/// Something the player can do from where they stand, offered on the action menu.
+#[derive(PartialEq, Copy, Clone)]
+pub enum TileAction {
+    Descend,
+    Climb,
+    PickUp,
+    Open { container: Entity },
+    Unlock { door: Entity },
+    Pull { lever: Entity },
+    Use { prop: Entity },
+    Talk { npc: Entity },
+    Trade { vendor: Entity },
+    AskForWork { giver: Entity },
+    Butcher { corpse: Entity },
+    Pray
+}
+
+/// Everything the player could do on their tile or with what's next to it, labelled for the menu.
+pub fn tile_actions(ecs: &World) -> Vec<(String, TileAction)> {
+    let player_entity = *ecs.fetch::<Entity>();
+    let ppos = *ecs.fetch::<Point>();
+    let names = ecs.read_storage::<Name>();
+    let name_of = |entity: Entity| names.get(entity).map_or("thing".to_string(), |n| n.name.clone());
+    let mut actions : Vec<(String, TileAction)> = Vec::new();
+
+    {
+        let map = ecs.fetch::<Map>();
+        match map.tiles[map.xy_idx(ppos.x, ppos.y)] {
+            TileType::DownStairs | TileType::BranchEntrance => actions.push(("Go down".to_string(), TileAction::Descend)),
+            TileType::UpStairs => actions.push(("Go up".to_string(), TileAction::Climb)),
+            _ => {}
+        }
+    }
+
+    let items = items_to_pick_up(ecs, false);
+    match items.len() {
+        0 => {}
+        1 => actions.push((format!("Pick up the {}", name_of(items[0])), TileAction::PickUp)),
+        n => actions.push((format!("Pick up from {} items", n), TileAction::PickUp))
+    }
+
+    if let Some(corpse) = corpse_at(ecs, ppos.x, ppos.y) {
+        actions.push((format!("Butcher the {}", name_of(corpse)), TileAction::Butcher{ corpse }));
+    }
+
+    let neighbours : Vec<(i32, i32)> = (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (ppos.x + dx, ppos.y + dy))).collect();
+    for (x, y) in neighbours.iter() {
+        if let Some(container) = container_at(ecs, *x, *y) {
+            actions.push((format!("Open the {}", name_of(container)), TileAction::Open{ container }));
+        }
+        if let Some(door) = locked_door_at(ecs, *x, *y) {
+            actions.push((format!("Unlock the {}", name_of(door)), TileAction::Unlock{ door }));
+        }
+        if let Some(lever) = lever_at(ecs, *x, *y) {
+            actions.push((format!("Pull the {}", name_of(lever)), TileAction::Pull{ lever }));
+        }
+        if let Some(prop) = interactive_at(ecs, *x, *y) {
+            let verb = ecs.read_storage::<Interactive>().get(prop).map_or("use".to_string(), |i| i.verb.clone());
+            actions.push((capitalised(&format!("{} the {}", verb, name_of(prop))), TileAction::Use{ prop }));
+        }
+        if let Some(npc) = talker_at(ecs, *x, *y) {
+            actions.push((format!("Talk to the {}", name_of(npc)), TileAction::Talk{ npc }));
+        }
+        if let Some(vendor) = vendor_at(ecs, *x, *y) {
+            actions.push((format!("Trade with the {}", name_of(vendor)), TileAction::Trade{ vendor }));
+        }
+        if let Some(giver) = quest_giver_at(ecs, *x, *y) {
+            actions.push((format!("Ask the {} for work", name_of(giver)), TileAction::AskForWork{ giver }));
+        }
+    }
+
+    let at_altar = neighbours.iter().any(|(x, y)| altar_at(ecs, *x, *y).is_some());
+    if at_altar && ecs.read_storage::<Faith>().get(player_entity).is_some() {
+        actions.push(("Pray".to_string(), TileAction::Pray));
+    }
+    actions
+}
+
+/// The label with its first letter in upper case, whatever alphabet it is in.
+fn capitalised(label: &str) -> String {
+    let mut chars = label.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new()
+    }
+}
+
+/// Does what was picked from the action menu, just as its own key or a bump would.
+pub fn perform_action(ecs: &mut World, action: TileAction) -> RunState {
+    match action {
+        TileAction::Descend => {
+            if !try_next_level(ecs) { return RunState::AwaitingInput; }
+            if hostiles_adjacent(ecs) { RunState::Confirm{ action: gui::ConfirmAction::Descend } } else { RunState::NextLevel }
+        }
+        TileAction::Climb => try_previous_level(ecs),
+        TileAction::PickUp => get_item(ecs),
+        TileAction::Open{ container } => open_container(ecs, container),
+        TileAction::Unlock{ door } => open_door(ecs, door),
+        TileAction::Pull{ lever } => pull_lever(ecs, lever),
+        TileAction::Use{ prop } => interact(ecs, prop),
+        TileAction::Talk{ npc } => dialogue::start(npc),
+        TileAction::Trade{ vendor } => vendor::open_shop(ecs, vendor),
+        TileAction::AskForWork{ giver } => quests::talk(ecs, giver),
+        TileAction::Butcher{ corpse } => butcher(ecs, corpse),
+        TileAction::Pray => religion::pray(ecs)
+    }
+}
+
+/// Alerts every monster within earshot, sending them to investigate.
+fn make_noise(ecs: &mut World, x: i32, y: i32) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let stealth = ecs.read_storage::<Skills>().get(player_entity).map_or(0, |s| s.stealth);
+    let radius = f32::max(MIN_NOISE_RADIUS, NOISE_RADIUS - stealth as f32);
+    let entities = ecs.entities();
+    let monsters = ecs.read_storage::<Monster>();
+    let positions = ecs.read_storage::<Position>();
+    let mut heard = ecs.write_storage::<HeardNoise>();
+    for (entity, _monster, pos) in (&entities, &monsters, &positions).join() {
+        if rltk::DistanceAlg::Pythagoras.distance2d(Point::new(x, y), Point::new(pos.x, pos.y)) <= radius {
+            heard.insert(entity, HeardNoise{ x, y }).expect("Unable to insert noise");
+        }
+    }
+}
+
+/// Unlocks something with a matching carried key, or else tries to pick the lock.
+/// A failed attempt is noisy. Returns whether the lock is now open.
+fn try_unlock(ecs: &mut World, target: Entity) -> bool {
+    let lock_id = match ecs.read_storage::<Locked>().get(target) {
+        Some(lock) => lock.key,
+        None => return true
+    };
+    let player_entity = *ecs.fetch::<Entity>();
+    let name = ecs.read_storage::<Name>().get(target).map_or("lock".to_string(), |n| n.name.clone());
+    let key = {
+        let entities = ecs.entities();
+        let backpack = ecs.read_storage::<InBackpack>();
+        let keys = ecs.read_storage::<Key>();
+        (&entities, &backpack, &keys).join()
+            .find(|(_, pack, key)| pack.owner == player_entity && key.id == lock_id)
+            .map(|(e, _, _)| e)
+    };
+
+    if let Some(key) = key {
+        ecs.delete_entity(key).expect("Unable to delete key");
+        ecs.write_resource::<GameLog>().push(LogCategory::System, format!("You unlock the {} with your key.", name));
+    } else {
+        let quickness = ecs.read_storage::<Attributes>().get(player_entity).map_or(10, |a| a.quickness);
+        let skill = ecs.read_storage::<Skills>().get(player_entity).map_or(0, |s| s.lockpicking);
+        let roll = ecs.write_resource::<rltk::RandomNumberGenerator>().roll_dice(1, 20) + attr_bonus(quickness) + skill;
+        if roll < LOCKPICK_DIFFICULTY {
+            ecs.write_resource::<GameLog>().push(LogCategory::System, format!("You fail to pick the lock on the {}, rattling it loudly.", name));
+            let ppos = *ecs.fetch::<Point>();
+            make_noise(ecs, ppos.x, ppos.y);
+            return false;
         }
+        ecs.write_resource::<GameLog>().push(LogCategory::System, format!("You pick the lock on the {}.", name));
+        events::publish(ecs, GameEvent::SkillUsed{ by: player_entity, skill: Skill::Lockpicking, xp: LOCKPICK_XP });
+    }
+    ecs.write_storage::<Locked>().remove(target);
+    true
+}
+
+/// Opens a container, first unlocking it if need be.
+fn open_container(ecs: &mut World, container: Entity) -> RunState {
+    if !try_unlock(ecs, container) {
+        return RunState::PlayerTurn;
     }
+    RunState::ShowContainer{ container }
 }
 
-pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
+/// Unlocks a door and swings it open, leaving the way clear.
+fn open_door(ecs: &mut World, door: Entity) -> RunState {
+    if try_unlock(ecs, door) {
+        let name = ecs.read_storage::<Name>().get(door).map_or("door".to_string(), |n| n.name.clone());
+        let player_entity = *ecs.fetch::<Entity>();
+        events::publish(ecs, GameEvent::DoorOpened{ by: player_entity, door, name });
+        ecs.write_storage::<BlocksTile>().remove(door);
+        if let Some(render) = ecs.write_storage::<Renderable>().get_mut(door) {
+            render.glyph = rltk::to_cp437('\'');
+        }
+        ecs.write_storage::<Name>().insert(door, Name{ name: "Open Door".to_string() }).expect("Unable to insert name");
+    }
+    RunState::PlayerTurn
+}
+
+/// Opens the first container or locked door next to the player, or else pulls a lever or uses a prop.
+fn try_open(ecs: &mut World) -> RunState {
+    let ppos = *ecs.fetch::<Point>();
+    let neighbours : Vec<(i32, i32)> = (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (ppos.x + dx, ppos.y + dy))).collect();
+    let container = neighbours.iter().find_map(|(x, y)| container_at(ecs, *x, *y));
+    let door = neighbours.iter().find_map(|(x, y)| locked_door_at(ecs, *x, *y));
+    let lever = neighbours.iter().find_map(|(x, y)| lever_at(ecs, *x, *y));
+    let prop = neighbours.iter().find_map(|(x, y)| interactive_at(ecs, *x, *y));
+    match (container, door, lever, prop) {
+        (Some(container), _, _, _) => open_container(ecs, container),
+        (None, Some(door), _, _) => open_door(ecs, door),
+        (None, None, Some(lever), _) => pull_lever(ecs, lever),
+        (None, None, None, Some(prop)) => interact(ecs, prop),
+        (None, None, None, None) => {
+            ecs.write_resource::<GameLog>().push(LogCategory::System, "There is nothing here to open.".to_string());
+            RunState::AwaitingInput
+        }
+    }
+}
+
+fn player_move(delta_x: i32, delta_y: i32, ctx: &Input, ecs: &mut World) -> RunState {
+    if ctx.control {
+        return try_weapon_special(delta_x, delta_y, ecs);
+    }
+    // Bumping into a chest or barrel opens it
+    let ppos = *ecs.fetch::<Point>();
+    if let Some(container) = container_at(ecs, ppos.x + delta_x, ppos.y + delta_y) {
+        return open_container(ecs, container);
+    }
+    if let Some(door) = locked_door_at(ecs, ppos.x + delta_x, ppos.y + delta_y) {
+        return open_door(ecs, door);
+    }
+    if let Some(lever) = lever_at(ecs, ppos.x + delta_x, ppos.y + delta_y) {
+        return pull_lever(ecs, lever);
+    }
+    if let Some(prop) = interactive_at(ecs, ppos.x + delta_x, ppos.y + delta_y) {
+        return interact(ecs, prop);
+    }
+    // Townsfolk are talked to, unless shift is held to pick a fight with them
+    if !ctx.shift {
+        if let Some(npc) = talker_at(ecs, ppos.x + delta_x, ppos.y + delta_y) {
+            return dialogue::start(npc);
+        }
+        if let Some(shopkeeper) = vendor_at(ecs, ppos.x + delta_x, ppos.y + delta_y) {
+            return vendor::open_shop(ecs, shopkeeper);
+        }
+        if let Some(giver) = quest_giver_at(ecs, ppos.x + delta_x, ppos.y + delta_y) {
+            return quests::talk(ecs, giver);
+        }
+    }
+    move_or_exert(delta_x, delta_y, ctx.shift, ecs);
+    RunState::PlayerTurn
+}
+
+pub fn player_input(gs: &mut State, ctx: &mut Input) -> RunState {
+    // A fumble costs the player their next turn
+    let player_entity = *gs.ecs.fetch::<Entity>();
+    if gs.ecs.write_storage::<Staggered>().remove(player_entity).is_some() {
+        gs.ecs.write_resource::<GameLog>().push(LogCategory::Combat, "You regain your footing.".to_string());
+        return RunState::PlayerTurn;
+    }
+
+    // Resting carries on by itself until something interrupts it
+    if gs.ecs.read_storage::<Resting>().get(player_entity).is_some() {
+        return continue_rest(&mut gs.ecs, ctx.key.is_some());
+    }
+
+    // So does travelling to a marker
+    if gs.ecs.read_storage::<Travelling>().get(player_entity).is_some() {
+        return continue_travel(&mut gs.ecs, ctx);
+    }
+
+    // The number keys use what's bound to the hotbar, or with shift held bind something to it
+    if let Some(slot) = ctx.key.and_then(hotbar_slot) {
+        return if ctx.shift { RunState::AssignHotkey{ slot } } else { use_hotkey(gs, slot) };
+    }
+
     // Player movement
     match ctx.key {
         None => {return RunState::AwaitingInput} // No key was pressed
@@ -47,38 +639,104 @@ pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
             // Cardinal
             VirtualKeyCode::Left |
             VirtualKeyCode::Numpad4 |
-            VirtualKeyCode::A => try_move_player(-1, 0, &mut gs.ecs),
+            VirtualKeyCode::A => return player_move(-1, 0, ctx, &mut gs.ecs),
 
             VirtualKeyCode::Right |
             VirtualKeyCode::Numpad6 |
-            VirtualKeyCode::D => try_move_player(1, 0, &mut gs.ecs),
+            VirtualKeyCode::D => return player_move(1, 0, ctx, &mut gs.ecs),
 
             VirtualKeyCode::Up |
             VirtualKeyCode::Numpad8 |
-            VirtualKeyCode::W => try_move_player(0, -1, &mut gs.ecs),
+            VirtualKeyCode::W => return player_move(0, -1, ctx, &mut gs.ecs),
 
             VirtualKeyCode::Down |
             VirtualKeyCode::Numpad2 |
-            VirtualKeyCode::S => try_move_player(0, 1, &mut gs.ecs),
+            VirtualKeyCode::S => return player_move(0, 1, ctx, &mut gs.ecs),
 
             // Diagonals
             VirtualKeyCode::Numpad9 |
-            VirtualKeyCode::E => try_move_player(1, -1, &mut gs.ecs),
+            VirtualKeyCode::E => return player_move(1, -1, ctx, &mut gs.ecs),
 
             VirtualKeyCode::Numpad7 |
-            VirtualKeyCode::Q => try_move_player(-1, -1, &mut gs.ecs),
+            VirtualKeyCode::Q => return player_move(-1, -1, ctx, &mut gs.ecs),
 
             VirtualKeyCode::Numpad3 |
-            VirtualKeyCode::C => try_move_player(1, 1, &mut gs.ecs),
+            VirtualKeyCode::C => return player_move(1, 1, ctx, &mut gs.ecs),
 
             VirtualKeyCode::Numpad1 |
-            VirtualKeyCode::Y => try_move_player(-1, 1, &mut gs.ecs),
+            VirtualKeyCode::Y => return player_move(-1, 1, ctx, &mut gs.ecs),
+
+            // Stairs
+            VirtualKeyCode::Period => {
+                if ctx.shift {
+                    if try_next_level(&mut gs.ecs) {
+                        if hostiles_adjacent(&gs.ecs) {
+                            return RunState::Confirm{ action: gui::ConfirmAction::Descend };
+                        }
+                        return RunState::NextLevel;
+                    }
+                    return RunState::AwaitingInput;
+                }
+                // Wait a turn
+                return RunState::PlayerTurn;
+            }
+            VirtualKeyCode::Comma => {
+                if ctx.shift {
+                    return try_previous_level(&mut gs.ecs);
+                }
+                return RunState::AwaitingInput;
+            }
 
-            VirtualKeyCode::G => get_item(&mut gs.ecs),
-            VirtualKeyCode::I => return RunState::ShowInventory,
+            VirtualKeyCode::G => return get_item(&mut gs.ecs),
+            VirtualKeyCode::I if ctx.shift => return RunState::ShowDiscoveries,
+            VirtualKeyCode::I => return RunState::ShowInventory{ view: gui::InventoryView::default() },
+            VirtualKeyCode::K => return open_filtered_inventory(&mut gs.ecs, gui::InventoryFilter::Quaff),
+            VirtualKeyCode::B => return open_filtered_inventory(&mut gs.ecs, gui::InventoryFilter::Read),
+            VirtualKeyCode::Z if ctx.control => return undo::undo(&mut gs.ecs),
+            VirtualKeyCode::Z => return RunState::ShowZapItem,
             VirtualKeyCode::N => return RunState::ShowDropItem,
+            VirtualKeyCode::U if ctx.shift => return open_filtered_inventory(&mut gs.ecs, gui::InventoryFilter::Apply),
+            VirtualKeyCode::U => return RunState::ShowRemoveItem,
+            VirtualKeyCode::T if !ctx.shift => return RunState::ShowThrowItem,
+            VirtualKeyCode::V => return try_shoot(&mut gs.ecs),
+            VirtualKeyCode::O => return try_open(&mut gs.ecs),
+            VirtualKeyCode::P => return religion::pray(&mut gs.ecs),
+            VirtualKeyCode::X => return RunState::ShowCharacter,
+            VirtualKeyCode::J => return RunState::ShowJournal,
+            VirtualKeyCode::H => return RunState::ShowLog{ filter: LogFilter::default() },
+            VirtualKeyCode::Grave if gs.ecs.fetch::<WizardMode>().enabled => return RunState::WizardConsole,
+            VirtualKeyCode::R => return start_rest(&mut gs.ecs),
+            VirtualKeyCode::M if ctx.shift => return RunState::ShowBestiary{ selected: 0 },
+            VirtualKeyCode::M => return start_marker(&mut gs.ecs),
+            VirtualKeyCode::L => {
+                if gs.ecs.fetch::<Map>().markers.is_empty() {
+                    gs.ecs.write_resource::<GameLog>().push(LogCategory::System, "You haven't marked anywhere on this level.".to_string());
+                    return RunState::AwaitingInput;
+                }
+                return RunState::ShowMarkers;
+            }
+            VirtualKeyCode::Tab => {
+                let mut options = gs.ecs.write_resource::<Options>();
+                options.show_minimap = !options.show_minimap;
+                return RunState::AwaitingInput;
+            }
+            VirtualKeyCode::F => {
+                if gs.ecs.read_storage::<Companion>().is_empty() {
+                    gs.ecs.write_resource::<GameLog>().push(LogCategory::System, "You have no companions to command.".to_string());
+                    return RunState::AwaitingInput;
+                }
+                return RunState::ShowCompanionMenu;
+            }
+
+            VirtualKeyCode::Return | VirtualKeyCode::Space => {
+                if tile_actions(&gs.ecs).is_empty() {
+                    gs.ecs.write_resource::<GameLog>().push(LogCategory::System, "There is nothing to do here.".to_string());
+                    return RunState::AwaitingInput;
+                }
+                return RunState::ShowActions;
+            }
 
-            VirtualKeyCode::Escape => return RunState::SaveGame,
+            VirtualKeyCode::Escape => return RunState::Confirm{ action: gui::ConfirmAction::QuitRun },
 
             _ => {return RunState::AwaitingInput} // no valid key was pressed
         }
@@ -86,26 +744,368 @@ pub fn player_input(gs: &mut State, ctx: &mut Rltk) -> RunState {
     RunState::PlayerTurn
 }
 
-fn get_item(ecs: &mut World) {
-    let player_pos = ecs.fetch::<Point>();
-    let player_entity = ecs.fetch::<Entity>();
-    let entities = ecs.entities();
-    let items = ecs.read_storage::<Item>();
+fn hostiles_in_view(ecs: &World) -> bool {
+    let player_entity = *ecs.fetch::<Entity>();
+    let viewsheds = ecs.read_storage::<Viewshed>();
+    match viewsheds.get(player_entity) {
+        Some(viewshed) => hostile_in_view(player_entity, viewshed, &ecs.entities(), &ecs.read_storage::<Monster>(),
+            &ecs.read_storage::<Companion>(), &ecs.read_storage::<OwnedBy>(), &ecs.read_storage::<Position>()),
+        None => false
+    }
+}
+
+/// Whether a monster that isn't on the player's side stands anywhere within the given viewshed.
+/// Takes the storages directly so that systems can ask too.
+pub fn hostile_in_view(player: Entity, viewshed: &Viewshed, entities: &Entities, monsters: &ReadStorage<Monster>,
+                       companions: &ReadStorage<Companion>, owned: &ReadStorage<OwnedBy>, positions: &ReadStorage<Position>) -> bool {
+    (entities, monsters, positions).join()
+        .filter(|(e, _m, _p)| companions.get(*e).is_none() && owned.get(*e).map_or(true, |o| o.owner != player))
+        .any(|(_e, _m, p)| viewshed.visible_tiles.contains(&Point::new(p.x, p.y)))
+}
+
+/// Whether anything hostile is close enough to follow the player down the stairs.
+fn hostiles_adjacent(ecs: &World) -> bool {
+    let player_entity = *ecs.fetch::<Entity>();
+    let player_pos = *ecs.fetch::<Point>();
+    let monsters = ecs.read_storage::<Monster>();
+    let companions = ecs.read_storage::<Companion>();
+    let owned = ecs.read_storage::<OwnedBy>();
     let positions = ecs.read_storage::<Position>();
-    let mut gamelog = ecs.fetch_mut::<GameLog>();
+    (&ecs.entities(), &monsters, &positions).join()
+        .filter(|(e, _m, _p)| companions.get(*e).is_none() && owned.get(*e).map_or(true, |o| o.owner != player_entity))
+        .any(|(_e, _m, p)| (p.x - player_pos.x).abs() <= 1 && (p.y - player_pos.y).abs() <= 1)
+}
+
+/// Resting does a starving body no good.
+fn starving(ecs: &World, player_entity: Entity) -> bool {
+    ecs.read_storage::<HungerClock>().get(player_entity).map_or(false, |h| h.state == HungerState::Starving)
+}
 
-    let mut target_item : Option<Entity> = None;
-    for (item_entity, _item, position) in (&entities, &items, &positions).join() {
-        if position.x == player_pos.x && position.y == player_pos.y {
-            target_item = Some(item_entity);
+fn fully_rested(ecs: &World, player_entity: Entity) -> bool {
+    let healed = ecs.read_storage::<CombatStats>().get(player_entity).map_or(true, |s| s.hp >= s.max_hp);
+    let refreshed = ecs.read_storage::<Pools>().get(player_entity)
+        .map_or(true, |p| p.stamina.current >= p.stamina.max && p.mana.current >= p.mana.max);
+    healed && refreshed
+}
+
+/// Takes aim with the equipped bow or crossbow, provided the quiver holds something it can shoot.
+fn try_shoot(ecs: &mut World) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    match loaded_launcher(ecs, player_entity) {
+        Ok((launcher, _ammo)) => {
+            let range = ecs.read_storage::<Launcher>().get(launcher).map_or(0, |l| l.range);
+            RunState::ShowShootTargeting { range }
         }
+        Err(reason) => {
+            ecs.write_resource::<GameLog>().push(LogCategory::System, reason);
+            RunState::AwaitingInput
+        }
+    }
+}
+
+fn start_rest(ecs: &mut World) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    if hostiles_in_view(ecs) {
+        ecs.write_resource::<GameLog>().push(LogCategory::System, "You can't rest with enemies in view.".to_string());
+        return RunState::AwaitingInput;
+    }
+    if starving(ecs, player_entity) {
+        ecs.write_resource::<GameLog>().push(LogCategory::System, "You are too hungry to rest.".to_string());
+        return RunState::AwaitingInput;
+    }
+    if fully_rested(ecs, player_entity) {
+        ecs.write_resource::<GameLog>().push(LogCategory::System, "You are already fully rested.".to_string());
+        return RunState::AwaitingInput;
     }
 
-    match target_item {
-        None => gamelog.entries.push("There is nothing here to pick up.".to_string()),
-        Some(item) => {
+    let hp = ecs.read_storage::<CombatStats>().get(player_entity).map_or(0, |s| s.hp);
+    ecs.write_storage::<Resting>().insert(player_entity, Resting{ turns: 1, hp }).expect("Unable to insert resting");
+    ecs.write_resource::<GameLog>().push(LogCategory::System, "You settle down to rest.".to_string());
+    RunState::PlayerTurn
+}
+
+/// Passes another turn of rest, or stops if the player is disturbed or recovered.
+fn continue_rest(ecs: &mut World, key_pressed: bool) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    let hp = ecs.read_storage::<CombatStats>().get(player_entity).map_or(0, |s| s.hp);
+    let rest = ecs.read_storage::<Resting>().get(player_entity).cloned().expect("Not resting");
+
+    let interruption = if key_pressed {
+        Some("You stop resting.")
+    } else if hostiles_in_view(ecs) {
+        Some("You spot an enemy and stop resting!")
+    } else if starving(ecs, player_entity) {
+        Some("You are too hungry to rest any longer.")
+    } else if hp < rest.hp {
+        Some("Something hurts you, and you stop resting!")
+    } else if fully_rested(ecs, player_entity) {
+        Some("You feel fully rested.")
+    } else if rest.turns >= MAX_REST_TURNS {
+        Some("You can't rest any longer.")
+    } else {
+        None
+    };
+
+    match interruption {
+        Some(message) => {
+            ecs.write_storage::<Resting>().remove(player_entity);
+            ecs.write_resource::<GameLog>().push(LogCategory::System, message.to_string());
+            RunState::AwaitingInput
+        }
+        None => {
+            ecs.write_storage::<Resting>().insert(player_entity, Resting{ turns: rest.turns + 1, hp }).expect("Unable to insert resting");
+            RunState::PlayerTurn
+        }
+    }
+}
+
+/// The most markers a level can hold; one for each letter of the travel menu.
+const MAX_MARKERS : usize = 26;
+
+/// Asks for a name for the player's tile, offering its old one if it's already marked.
+fn start_marker(ecs: &mut World) -> RunState {
+    let ppos = *ecs.fetch::<Point>();
+    let existing = {
+        let map = ecs.fetch::<Map>();
+        let idx = map.xy_idx(ppos.x, ppos.y);
+        if map.markers.get(&idx).is_none() && map.markers.len() >= MAX_MARKERS {
+            ecs.write_resource::<GameLog>().push(LogCategory::System, "You can't keep track of any more places on this level.".to_string());
+            return RunState::AwaitingInput;
+        }
+        map.markers.get(&idx).cloned().unwrap_or_default()
+    };
+    ecs.write_resource::<gui::MarkerDraft>().name = existing;
+    RunState::NameMarker
+}
+
+/// Names the player's tile, so they can travel back to it later.
+pub fn place_marker(ecs: &mut World) {
+    let ppos = *ecs.fetch::<Point>();
+    let name = std::mem::take(&mut ecs.write_resource::<gui::MarkerDraft>().name);
+    let mut map = ecs.write_resource::<Map>();
+    let idx = map.xy_idx(ppos.x, ppos.y);
+    map.markers.insert(idx, name.clone());
+    ecs.write_resource::<GameLog>().push(LogCategory::System, format!("You mark this spot as {}.", name));
+}
+
+/// Sets off for a marked tile, one step a turn.
+pub fn start_travel(ecs: &mut World, destination: usize) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    if hostiles_in_view(ecs) {
+        ecs.write_resource::<GameLog>().push(LogCategory::System, "You can't travel with enemies in view.".to_string());
+        return RunState::AwaitingInput;
+    }
+    let (x, y) = {
+        let map = ecs.fetch::<Map>();
+        (destination as i32 % map.width, destination as i32 / map.width)
+    };
+    let hp = ecs.read_storage::<CombatStats>().get(player_entity).map_or(0, |s| s.hp);
+    ecs.write_storage::<Travelling>().insert(player_entity, Travelling{ x, y, hp }).expect("Unable to insert travelling");
+    RunState::AwaitingInput
+}
+
+/// Takes the next step towards the marker, or stops if the player is disturbed, stuck or there.
+fn continue_travel(ecs: &mut World, ctx: &Input) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    let ppos = *ecs.fetch::<Point>();
+    let hp = ecs.read_storage::<CombatStats>().get(player_entity).map_or(0, |s| s.hp);
+    let travel = ecs.read_storage::<Travelling>().get(player_entity).cloned().expect("Not travelling");
+
+    let next_step = {
+        let map = ecs.fetch::<Map>();
+        let path = rltk::a_star_search(map.xy_idx(ppos.x, ppos.y), map.xy_idx(travel.x, travel.y), &*map);
+        if path.success && path.steps.len() > 1 { Some(path.steps[1] as i32) } else { None }
+    };
+
+    let interruption = if ctx.key.is_some() {
+        Some("You stop travelling.")
+    } else if hostiles_in_view(ecs) {
+        Some("You spot an enemy and stop travelling!")
+    } else if hp < travel.hp {
+        Some("Something hurts you, and you stop travelling!")
+    } else if ppos.x == travel.x && ppos.y == travel.y {
+        Some("You arrive.")
+    } else if next_step.is_none() {
+        Some("You can't find a way there.")
+    } else {
+        None
+    };
+
+    match (interruption, next_step) {
+        (None, Some(step)) => {
+            ecs.write_storage::<Travelling>().insert(player_entity, Travelling{ hp, ..travel }).expect("Unable to insert travelling");
+            let width = ecs.fetch::<Map>().width;
+            player_move(step % width - ppos.x, step / width - ppos.y, ctx, ecs)
+        }
+        (message, _) => {
+            ecs.write_storage::<Travelling>().remove(player_entity);
+            ecs.write_resource::<GameLog>().push(LogCategory::System, message.unwrap_or_default().to_string());
+            RunState::AwaitingInput
+        }
+    }
+}
+
+/// Opens the inventory showing only what `filter` lets through, unless that's nothing at all.
+fn open_filtered_inventory(ecs: &mut World, filter: gui::InventoryFilter) -> RunState {
+    if !gui::has_items(ecs, filter) {
+        ecs.write_resource::<GameLog>().push(LogCategory::Items, filter.nothing().to_string());
+        return RunState::AwaitingInput;
+    }
+    RunState::ShowInventory{ view: gui::InventoryView::filtered(filter) }
+}
+
+/// How many items the hotbar holds, on the keys 1 to 9.
+const HOTBAR_SLOTS: usize = 9;
+
+fn hotbar_slot(key: VirtualKeyCode) -> Option<usize> {
+    match key {
+        VirtualKeyCode::Key1 => Some(0),
+        VirtualKeyCode::Key2 => Some(1),
+        VirtualKeyCode::Key3 => Some(2),
+        VirtualKeyCode::Key4 => Some(3),
+        VirtualKeyCode::Key5 => Some(4),
+        VirtualKeyCode::Key6 => Some(5),
+        VirtualKeyCode::Key7 => Some(6),
+        VirtualKeyCode::Key8 => Some(7),
+        VirtualKeyCode::Key9 => Some(8),
+        _ => None
+    }
+}
+
+/// Uses the first carried item with the name bound to `slot`, as if picked from the inventory.
+fn use_hotkey(gs: &mut State, slot: usize) -> RunState {
+    let player_entity = *gs.ecs.fetch::<Entity>();
+    let bound = gs.ecs.read_storage::<Hotbar>().get(player_entity).and_then(|h| h.slots.get(slot).cloned().flatten());
+    let bound = match bound {
+        Some(bound) => bound,
+        None => {
+            gs.ecs.write_resource::<GameLog>().push(LogCategory::System, format!("Nothing is bound to {}. Hold shift and press {} to bind something.", slot + 1, slot + 1));
+            return RunState::AwaitingInput;
+        }
+    };
+    let item = {
+        let entities = gs.ecs.entities();
+        let backpack = gs.ecs.read_storage::<InBackpack>();
+        let names = gs.ecs.read_storage::<Name>();
+        (&entities, &backpack, &names).join()
+            .find(|(_e, pack, name)| pack.owner == player_entity && name.name == bound)
+            .map(|(e, _pack, _name)| e)
+    };
+    match item {
+        Some(item) => gs.try_use_item(item),
+        None => {
+            gs.ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("You have no {} left.", bound));
+            RunState::AwaitingInput
+        }
+    }
+}
+
+/// Binds the kind of item `item` is to hotbar `slot`, or empties the slot if there's no item.
+pub fn bind_hotkey(ecs: &mut World, slot: usize, item: Option<Entity>) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let name = item.and_then(|item| ecs.read_storage::<Name>().get(item).map(|n| n.name.clone()));
+    let mut hotbars = ecs.write_storage::<Hotbar>();
+    let mut hotbar = hotbars.get(player_entity).cloned().unwrap_or_default();
+    hotbar.slots.resize(HOTBAR_SLOTS, None);
+    match &name {
+        Some(name) => ecs.write_resource::<GameLog>().push(LogCategory::System, format!("{} is now on key {}.", name, slot + 1)),
+        None => ecs.write_resource::<GameLog>().push(LogCategory::System, format!("Key {} is cleared.", slot + 1))
+    }
+    hotbar.slots[slot] = name;
+    hotbars.insert(player_entity, hotbar).expect("Unable to insert hotbar");
+}
+
+/// Picks up the only item underfoot straight away, or offers a menu when there are several.
+fn get_item(ecs: &mut World) -> RunState {
+    let items = items_to_pick_up(ecs, false);
+    match items.len() {
+        0 => {
+            ecs.fetch_mut::<GameLog>().push(LogCategory::Items, "There is nothing here to pick up.".to_string());
+            RunState::AwaitingInput
+        }
+        1 => {
+            let player_entity = *ecs.fetch::<Entity>();
             let mut pickup = ecs.write_storage::<WantsToPickupItem>();
-            pickup.insert(*player_entity, WantsToPickupItem{ collected_by: *player_entity, item }).expect("Unable to insert want to pickup");
+            pickup.insert(player_entity, WantsToPickupItem{ collected_by: player_entity, item: items[0] }).expect("Unable to insert want to pickup");
+            RunState::PlayerTurn
+        }
+        _ => {
+            ecs.write_resource::<gui::PickupSelection>().chosen.clear();
+            RunState::ShowPickup{ nearby: false }
+        }
+    }
+}
+
+/// The items lying on the player's tile, followed by those on the tiles around it if `nearby`.
+pub fn items_to_pick_up(ecs: &World, nearby: bool) -> Vec<Entity> {
+    let player_pos = *ecs.fetch::<Point>();
+    let entities = ecs.entities();
+    let items = ecs.read_storage::<Item>();
+    let positions = ecs.read_storage::<Position>();
+
+    let mut found : Vec<(Entity, i32)> = (&entities, &items, &positions).join()
+        .map(|(entity, _item, pos)| (entity, max((pos.x - player_pos.x).abs(), (pos.y - player_pos.y).abs())))
+        .filter(|(_entity, distance)| *distance == 0 || (nearby && *distance == 1))
+        .collect();
+    found.sort_by_key(|(_entity, distance)| *distance);
+    found.into_iter().map(|(entity, _distance)| entity).collect()
+}
+
+/// Sets the player reaching for each of `items`. Each intent goes on the item itself, so any
+/// number of them can be taken in the one turn.
+pub fn pick_up_items(ecs: &mut World, items: &[Entity]) {
+    let player_entity = *ecs.fetch::<Entity>();
+    let mut pickup = ecs.write_storage::<WantsToPickupItem>();
+    for item in items.iter() {
+        pickup.insert(*item, WantsToPickupItem{ collected_by: player_entity, item: *item }).expect("Unable to insert want to pickup");
+    }
+}
+pub fn try_next_level(ecs: &mut World) -> bool {
+    let player_pos = ecs.fetch::<Point>();
+    let map = ecs.fetch::<Map>();
+    let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+    if map.tiles[player_idx] == TileType::DownStairs || map.tiles[player_idx] == TileType::BranchEntrance {
+        true
+    } else {
+        let mut gamelog = ecs.fetch_mut::<GameLog>();
+        gamelog.push(LogCategory::System, "There is no way down from here.".to_string());
+        false
+    }
+}
+
+/// Climbs up a level, or wins the game when leaving the dungeon with the Amulet.
+pub fn try_previous_level(ecs: &mut World) -> RunState {
+    let leaving_dungeon;
+    {
+        let player_pos = ecs.fetch::<Point>();
+        let map = ecs.fetch::<Map>();
+        let player_idx = map.xy_idx(player_pos.x, player_pos.y);
+        if map.tiles[player_idx] != TileType::UpStairs {
+            let mut gamelog = ecs.fetch_mut::<GameLog>();
+            gamelog.push(LogCategory::System, "There is no way up from here.".to_string());
+            return RunState::AwaitingInput;
         }
+        leaving_dungeon = map.branch.parent() == Some(Branch::Overworld) && map.depth == map.branch.entry_depth() + 1;
+    }
+
+    if !leaving_dungeon {
+        return RunState::PreviousLevel;
+    }
+
+    let has_amulet;
+    {
+        let player_entity = ecs.fetch::<Entity>();
+        let backpack = ecs.read_storage::<InBackpack>();
+        let macguffins = ecs.read_storage::<MacGuffin>();
+        has_amulet = (&backpack, &macguffins).join().any(|(pack, _)| pack.owner == *player_entity);
+    }
+
+    if has_amulet {
+        ecs.fetch_mut::<GameLog>().push(LogCategory::System, "You step into the daylight, the Amulet in your hands!".to_string());
+        events::publish(ecs, GameEvent::Victory);
+        scores::record_score(ecs, true);
+        RunState::Victory
+    } else {
+        RunState::PreviousLevel
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,200 @@
+use specs::prelude::*;
+use rltk::RGB;
+use serde::{Serialize, Deserialize};
+use super::{Name, InBackpack, Purse, Experience, CombatStats, PendingLevelUps, RunState, gamesystem::gain_xp,
+            difficulty::Difficulty, gamelog::{GameLog, LogCategory}, raws};
+
+#[derive(PartialEq, Copy, Clone, Serialize, Deserialize, Debug)]
+pub enum QuestKind { Kill, Fetch }
+
+impl QuestKind {
+    pub fn from_name(name : &str) -> QuestKind {
+        match name {
+            "fetch" => QuestKind::Fetch,
+            _ => QuestKind::Kill
+        }
+    }
+}
+
+/// A quest the player has taken on, and how far along it is.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct QuestProgress {
+    pub name : String,
+    pub giver : String,
+    pub kind : QuestKind,
+    pub target : String,
+    pub needed : i32,
+    pub progress : i32,
+    pub turned_in : bool
+}
+
+impl QuestProgress {
+    pub fn is_complete(&self) -> bool {
+        self.progress >= self.needed
+    }
+
+    /// Counts one more kill or find towards the quest; true if that finished it.
+    fn advance(&mut self, kind : QuestKind, target : &str) -> bool {
+        if self.turned_in || self.kind != kind || self.target != target || self.is_complete() { return false; }
+        self.progress += 1;
+        self.is_complete()
+    }
+}
+
+/// Every quest the player has accepted this run.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct QuestLog {
+    pub quests : Vec<QuestProgress>
+}
+
+impl QuestLog {
+    pub fn has_taken(&self, name : &str) -> bool {
+        self.quests.iter().any(|q| q.name == name)
+    }
+
+    fn advance(&mut self, kind : QuestKind, target : &str) -> Vec<String> {
+        self.quests.iter_mut()
+            .filter_map(|q| if q.advance(kind, target) {
+                Some(format!("Quest complete: {}. Return to the {}.", q.name, q.giver))
+            } else { None })
+            .collect()
+    }
+
+    /// Notes a slain creature, returning a message for each quest that finished.
+    pub fn record_kill(&mut self, target : &str) -> Vec<String> {
+        self.advance(QuestKind::Kill, target)
+    }
+
+    /// Notes a picked up item, returning a message for each quest that finished.
+    pub fn record_pickup(&mut self, target : &str) -> Vec<String> {
+        self.advance(QuestKind::Fetch, target)
+    }
+}
+
+fn carried(ecs : &World, owner : Entity, target : &str) -> Vec<Entity> {
+    (&ecs.entities(), &ecs.read_storage::<InBackpack>(), &ecs.read_storage::<Name>()).join()
+        .filter(|(_e, pack, name)| pack.owner == owner && name.name == target)
+        .map(|(e, _pack, _name)| e)
+        .collect()
+}
+
+/// Quests the townsperson still has on offer, by name and description.
+pub fn offers(ecs : &World, giver : Entity) -> Vec<(String, String)> {
+    let giver_name = match ecs.read_storage::<Name>().get(giver) {
+        Some(name) => name.name.clone(),
+        None => return Vec::new()
+    };
+    let log = ecs.fetch::<QuestLog>();
+    raws::quests_from(&raws::raws(), &giver_name).iter()
+        .filter(|q| !log.has_taken(&q.name))
+        .map(|q| (q.name.clone(), q.description.clone()))
+        .collect()
+}
+
+/// Takes on the named quest. Items already in the backpack count towards a fetch quest.
+pub fn accept(ecs : &mut World, name : &str) {
+    let player = *ecs.fetch::<Entity>();
+    let progress = {
+        let raws = raws::raws();
+        let quest = match raws::get_quest(&raws, name) {
+            Some(quest) => quest,
+            None => return
+        };
+        let kind = QuestKind::from_name(&quest.kind);
+        let progress = if kind == QuestKind::Fetch { carried(ecs, player, &quest.target).len() as i32 } else { 0 };
+        QuestProgress{ name: quest.name.clone(), giver: quest.giver.clone(), kind, target: quest.target.clone(),
+            needed: quest.count, progress: i32::min(progress, quest.count), turned_in: false }
+    };
+    ecs.write_resource::<GameLog>().push(LogCategory::System, format!("You accept the quest: {}.", progress.name));
+    ecs.write_resource::<QuestLog>().quests.push(progress);
+}
+
+/// Hands out the rewards for a finished quest.
+fn reward(ecs : &mut World, player : Entity, name : &str) {
+    let (gold, xp, item) = {
+        let raws = raws::raws();
+        match raws::get_quest(&raws, name) {
+            Some(quest) => (quest.reward.gold.unwrap_or(0), quest.reward.xp.unwrap_or(0) * ecs.fetch::<Difficulty>().xp_percent() / 100,
+                quest.reward.item.clone()),
+            None => return
+        }
+    };
+    ecs.write_resource::<GameLog>().push_colored(LogCategory::System, format!("You turn in {}.", name), RGB::named(rltk::GOLD));
+
+    if gold > 0 {
+        if let Some(purse) = ecs.write_storage::<Purse>().get_mut(player) {
+            purse.gold += gold;
+        }
+        ecs.write_resource::<GameLog>().push(LogCategory::System, format!("You are paid {} gold.", gold));
+    }
+    if xp > 0 {
+        let mut experience = ecs.write_storage::<Experience>();
+        let mut stats = ecs.write_storage::<CombatStats>();
+        if let (Some(exp), Some(stats)) = (experience.get_mut(player), stats.get_mut(player)) {
+            let levels = gain_xp(exp, stats, xp);
+            if levels > 0 {
+                ecs.write_resource::<GameLog>().push_colored(LogCategory::System, format!("Welcome to level {}!", exp.level), RGB::named(rltk::GOLD));
+                let mut pending = ecs.write_storage::<PendingLevelUps>();
+                match pending.get_mut(player) {
+                    Some(owed) => owed.count += levels,
+                    None => { pending.insert(player, PendingLevelUps{ count: levels }).expect("Unable to insert level up"); }
+                }
+            }
+        }
+    }
+    if let Some(item) = item {
+        if raws::spawn_in_backpack(&raws::raws(), ecs, &item, player).is_some() {
+            ecs.write_resource::<GameLog>().push(LogCategory::System, format!("You are given the {}.", item));
+        }
+    }
+}
+
+/// Talks to a townsperson: finished quests are turned in, otherwise any new work is offered.
+pub fn talk(ecs : &mut World, giver : Entity) -> RunState {
+    let player = *ecs.fetch::<Entity>();
+    let giver_name = match ecs.read_storage::<Name>().get(giver) {
+        Some(name) => name.name.clone(),
+        None => return RunState::AwaitingInput
+    };
+
+    let active : Vec<QuestProgress> = ecs.fetch::<QuestLog>().quests.iter()
+        .filter(|q| q.giver == giver_name && !q.turned_in)
+        .cloned()
+        .collect();
+    let mut turned_in = false;
+    for quest in active.iter().filter(|q| q.is_complete()) {
+        // Fetched items have to actually be handed over
+        if quest.kind == QuestKind::Fetch {
+            let items = carried(ecs, player, &quest.target);
+            if (items.len() as i32) < quest.needed {
+                ecs.write_resource::<GameLog>().push(LogCategory::System, format!("The {} needs {} {}; you only have {}.",
+                    giver_name, quest.needed, quest.target, items.len()));
+                if let Some(q) = ecs.write_resource::<QuestLog>().quests.iter_mut().find(|q| q.name == quest.name) {
+                    q.progress = items.len() as i32;
+                }
+                continue;
+            }
+            for item in items.iter().take(quest.needed as usize) {
+                ecs.delete_entity(*item).expect("Unable to delete quest item");
+            }
+        }
+        if let Some(q) = ecs.write_resource::<QuestLog>().quests.iter_mut().find(|q| q.name == quest.name) {
+            q.turned_in = true;
+        }
+        reward(ecs, player, &quest.name);
+        turned_in = true;
+    }
+    if turned_in {
+        return RunState::AwaitingInput;
+    }
+
+    if !offers(ecs, giver).is_empty() {
+        return RunState::ShowQuestOffer{ giver };
+    }
+    let message = match active.iter().find(|q| !q.is_complete()) {
+        Some(quest) => format!("The {} reminds you: {} ({}/{}).", giver_name, quest.name, quest.progress, quest.needed),
+        None => format!("The {} has no more work for you.", giver_name)
+    };
+    ecs.write_resource::<GameLog>().push(LogCategory::System, message);
+    RunState::AwaitingInput
+}
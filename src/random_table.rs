@@ -0,0 +1,54 @@
+use rltk::RandomNumberGenerator;
+
+pub struct RandomEntry {
+    name : String,
+    weight : i32
+}
+
+impl RandomEntry {
+    pub fn new<S: ToString>(name: S, weight: i32) -> RandomEntry {
+        RandomEntry{ name: name.to_string(), weight }
+    }
+}
+
+#[derive(Default)]
+pub struct RandomTable {
+    entries : Vec<RandomEntry>,
+    total_weight : i32
+}
+
+impl RandomTable {
+    pub fn new() -> RandomTable {
+        RandomTable{ entries: Vec::new(), total_weight: 0 }
+    }
+
+    pub fn add<S: ToString>(mut self, name : S, weight: i32) -> RandomTable {
+        if weight > 0 {
+            self.total_weight += weight;
+            self.entries.push(RandomEntry::new(name.to_string(), weight));
+        }
+        self
+    }
+
+    /// Gives an entry a new weight, adding it if it wasn't there and dropping it if the weight isn't positive.
+    pub fn set<S: ToString>(mut self, name : S, weight : i32) -> RandomTable {
+        let name = name.to_string();
+        self.entries.retain(|entry| entry.name != name);
+        self.total_weight = self.entries.iter().map(|entry| entry.weight).sum();
+        self.add(name, weight)
+    }
+
+    pub fn roll(&self, rng : &mut RandomNumberGenerator) -> String {
+        if self.total_weight == 0 { return "None".to_string(); }
+        let mut roll = rng.roll_dice(1, self.total_weight) - 1;
+
+        for entry in self.entries.iter() {
+            if roll < entry.weight {
+                return entry.name.clone();
+            }
+            roll -= entry.weight;
+        }
+
+        "None".to_string()
+    }
+}
@@ -0,0 +1,152 @@
+use specs::prelude::*;
+use rltk::{Point, RandomNumberGenerator};
+use super::{WantsToShoot, Launcher, Ammo, Affixed, Equipped, EquipmentSlot, MeleePowerBonus, DefenseBonus, Durability, durability::{worn_bonus, wear_down}, Name, Position, CombatStats, SufferDamage,
+            DamageType, Attributes, Map, tile_walkable, gamesystem::attr_bonus, gamelog::{GameLog, LogCategory},
+            animation::{Animations, missile_glyph}, effects::{EffectQueue, EffectType, Targets}};
+
+/// A d20 roll plus the shooter's Quickness bonus must beat this plus the target's.
+const BASE_TO_HIT : i32 = 6;
+/// Chance (in 100) that a spent arrow or bolt survives to be picked up again.
+const AMMO_RECOVERY_CHANCE : i32 = 50;
+
+/// Finds what the entity would shoot with: its equipped launcher and matching quivered ammo.
+pub fn loaded_launcher(ecs : &World, shooter : Entity) -> Result<(Entity, Entity), String> {
+    let equipped = ecs.read_storage::<Equipped>();
+    let launchers = ecs.read_storage::<Launcher>();
+    let ammo = ecs.read_storage::<Ammo>();
+    let entities = ecs.entities();
+
+    let launcher = (&entities, &equipped, &launchers).join()
+        .find(|(_e, w, _l)| w.owner == shooter && w.slot == EquipmentSlot::Melee)
+        .map(|(e, _w, l)| (e, l));
+    let (launcher, kind) = match launcher {
+        Some((entity, l)) => (entity, &l.ammo),
+        None => return Err("You have nothing to shoot with.".to_string())
+    };
+    let quivered = (&entities, &equipped, &ammo).join()
+        .find(|(_e, w, a)| w.owner == shooter && w.slot == EquipmentSlot::Quiver && a.kind == *kind && a.count > 0)
+        .map(|(e, _w, _a)| e);
+    match quivered {
+        Some(quivered) => Ok((launcher, quivered)),
+        None => Err(format!("Your quiver holds no {}s.", kind))
+    }
+}
+
+pub struct RangedCombatSystem {}
+
+impl<'a> System<'a> for RangedCombatSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        ReadExpect<'a, Map>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToShoot>,
+                        ReadStorage<'a, Launcher>,
+                        WriteStorage<'a, Ammo>,
+                        ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, MeleePowerBonus>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, Affixed>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Attributes>,
+                        WriteStorage<'a, SufferDamage>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteExpect<'a, EffectQueue>,
+                        WriteExpect<'a, Animations>,
+                        ReadStorage<'a, DefenseBonus>,
+                        WriteStorage<'a, Durability>
+    );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut log, map, entities, mut wants_shoot, launchers, mut ammo, equipped, power_bonuses,
+            names, affixed, positions, combat_stats, attributes, mut suffer_damage, mut rng, mut effects, mut animations,
+            defense_bonuses, mut durability) = data;
+
+        // Launchers that landed a shot and the armor that took one, to be worn down afterwards
+        let mut wear : Vec<Entity> = Vec::new();
+
+        for (entity, shot) in (&entities, &wants_shoot).join() {
+            let origin = match positions.get(entity) {
+                Some(pos) => Point::new(pos.x, pos.y),
+                None => continue
+            };
+            let launcher = (&entities, &equipped, &launchers).join()
+                .find(|(_e, w, _l)| w.owner == entity && w.slot == EquipmentSlot::Melee)
+                .map(|(e, _w, l)| (e, l.ammo.clone()));
+            let (launcher, kind) = match launcher {
+                Some(launcher) => launcher,
+                None => continue
+            };
+            let quivered = (&entities, &equipped, &ammo).join()
+                .find(|(_e, w, a)| w.owner == entity && w.slot == EquipmentSlot::Quiver && a.kind == kind && a.count > 0)
+                .map(|(e, _w, _a)| e);
+            let quivered = match quivered {
+                Some(quivered) => quivered,
+                None => continue
+            };
+
+            // Every shot uses up one piece of ammunition
+            let ammo_name = names.get(quivered).unwrap().name.clone();
+            // Recovered ammunition is spawned afresh from the raws, which know it by its plain name
+            let raw_name = affixed.get(quivered).map_or_else(|| ammo_name.trim_start_matches("Blessed ").to_string(), |a| a.base.clone());
+            let ammo_damage = {
+                let stack = ammo.get_mut(quivered).unwrap();
+                stack.count -= 1;
+                stack.damage
+            };
+            if ammo.get(quivered).unwrap().count < 1 {
+                entities.delete(quivered).expect("Unable to delete spent ammo");
+                if entity == *player_entity {
+                    log.push(LogCategory::Items, format!("You have used up your {}.", ammo_name));
+                }
+            }
+
+            // Follow the flight path until it hits a creature or a wall
+            let mut landing = origin;
+            let mut victim : Option<Entity> = None;
+            let mut flight = vec![origin];
+            for step in rltk::line2d(rltk::LineAlg::Bresenham, origin, shot.target).iter().skip(1) {
+                if step.x < 0 || step.x >= map.width || step.y < 0 || step.y >= map.height { break; }
+                let idx = map.xy_idx(step.x, step.y);
+                if !tile_walkable(map.tiles[idx]) { break; }
+                landing = *step;
+                flight.push(landing);
+                victim = map.entities_at(idx).iter().find(|e| combat_stats.get(**e).is_some()).copied();
+                if victim.is_some() { break; }
+            }
+            animations.launch(flight, missile_glyph(origin, shot.target), rltk::RGB::named(rltk::BURLYWOOD));
+
+            let shooter_name = &names.get(entity).unwrap().name;
+            if let Some(victim) = victim {
+                let victim_name = &names.get(victim).unwrap().name;
+                let to_hit_bonus = attr_bonus(attributes.get(entity).map_or(10, |a| a.quickness));
+                let target_quickness = attr_bonus(attributes.get(victim).map_or(10, |a| a.quickness));
+                let natural_roll = rng.roll_dice(1, 20);
+                if natural_roll != 20 && (natural_roll == 1 || natural_roll + to_hit_bonus < BASE_TO_HIT + target_quickness) {
+                    log.push(LogCategory::Combat, format!("{}'s shot misses {}.", shooter_name, victim_name));
+                } else {
+                    let launcher_bonus = worn_bonus(power_bonuses.get(launcher).map_or(0, |b| b.power), durability.get(launcher));
+                    let mut defense = combat_stats.get(victim).map_or(0, |s| s.defense);
+                    for (item, worn, bonus) in (&entities, &equipped, &defense_bonuses).join() {
+                        if worn.owner != victim { continue; }
+                        defense += worn_bonus(bonus.defense, durability.get(item));
+                        wear.push(item);
+                    }
+                    wear.push(launcher);
+                    let damage = i32::max(1, ammo_damage + launcher_bonus - defense);
+                    SufferDamage::new_damage(&mut suffer_damage, victim, damage, DamageType::Physical, entity == *player_entity, Some(entity));
+                    log.push(LogCategory::Combat, format!("{} shoots {}, for {} hp.", shooter_name, victim_name, damage));
+                }
+            }
+
+            // Some of the ammunition can be gathered up again where it fell
+            if rng.roll_dice(1, 100) <= AMMO_RECOVERY_CHANCE {
+                effects.add_effect(Some(entity), EffectType::SpentAmmo{ name: raw_name }, Targets::Tile{ tile: map.xy_idx(landing.x, landing.y) });
+            }
+        }
+
+        wants_shoot.clear();
+        wear_down(&mut wear, &entities, &mut durability, &equipped, &names, &mut log);
+    }
+}
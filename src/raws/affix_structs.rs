@@ -0,0 +1,16 @@
+use serde::Deserialize;
+
+/// A prefix ("Flaming") or suffix ("of Frost") that can be rolled onto equipment.
+#[derive(Deserialize, Debug)]
+pub struct Affix {
+    pub name : String,
+    pub kind : String,
+    pub slot : String,
+    pub min_depth : i32,
+    pub weight : i32,
+    pub power_bonus : Option<i32>,
+    pub defense_bonus : Option<i32>,
+    pub bonus_damage : Option<i32>,
+    pub element : Option<String>,
+    pub confusion_chance : Option<i32>
+}
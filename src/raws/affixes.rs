@@ -0,0 +1,255 @@
+use rltk::RandomNumberGenerator;
+use specs::prelude::*;
+use crate::components::*;
+use crate::random_table::RandomTable;
+use crate::gamelog::{GameLog, LogCategory};
+use crate::events::{self, GameEvent};
+use crate::skill_training::ENCHANT_XP;
+use super::{RawMaster, Affix};
+
+/// Equipment found above this depth is always plain.
+const AFFIX_MIN_DEPTH : i32 = 2;
+const CONFUSE_ON_HIT_TURNS : i32 = 2;
+const ENCHANT_GOLD_COST : i32 = 50;
+const ENCHANT_FAIL_PER_UPGRADE : i32 = 10;
+const ENCHANT_MAX_FAIL_CHANCE : i32 = 60;
+const ENCHANT_FAIL_PER_CRAFTING : i32 = 5;
+/// Enchanters draw on affixes as if the item had been found this deep.
+const ENCHANT_AFFIX_DEPTH : i32 = 4;
+
+fn roll_affix<'a>(raws : &'a RawMaster, rng : &mut RandomNumberGenerator, kind : &str, slot : &str, depth : i32) -> Option<&'a Affix> {
+    let mut table = RandomTable::new();
+    for affix in raws.raws.affixes.iter() {
+        if affix.kind == kind && affix.slot == slot && affix.min_depth <= depth {
+            table = table.add(affix.name.clone(), affix.weight);
+        }
+    }
+
+    let name = table.roll(rng);
+    raws.raws.affixes.iter().find(|a| a.name == name)
+}
+
+/// Turns a plain piece of equipment into something like "Flaming Longsword of Frost +1".
+/// The deeper the item was found, the better the odds.
+pub fn roll_affixes(raws : &RawMaster, ecs : &mut World, item : Entity, depth : i32) {
+    if depth < AFFIX_MIN_DEPTH || ecs.read_storage::<Artifact>().get(item).is_some() { return; }
+    let slot = match ecs.read_storage::<Equippable>().get(item) {
+        Some(equippable) => equippable.slot,
+        None => return
+    };
+    let slot_name = match slot_name(slot) {
+        Some(slot_name) => slot_name,
+        None => return
+    };
+
+    let (prefix, suffix, enchantment) = {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let prefix = if rng.roll_dice(1, 100) <= depth * 5 { roll_affix(raws, &mut rng, "prefix", slot_name, depth) } else { None };
+        let suffix = if rng.roll_dice(1, 100) <= depth * 5 { roll_affix(raws, &mut rng, "suffix", slot_name, depth) } else { None };
+        let enchantment = if rng.roll_dice(1, 100) <= depth * 5 { rng.roll_dice(1, 1 + depth / 3) } else { 0 };
+        (prefix, suffix, enchantment)
+    };
+    if prefix.is_none() && suffix.is_none() && enchantment == 0 { return; }
+
+    add_enchantment(ecs, item, slot, enchantment);
+    for affix in [prefix, suffix].iter().flatten() {
+        apply_affix(ecs, item, affix);
+    }
+
+    let base = ecs.read_storage::<Name>().get(item).map_or(String::new(), |n| n.name.clone());
+    let affixed = Affixed{ base, prefix: prefix.map(|a| a.name.clone()), suffix: suffix.map(|a| a.name.clone()), enchantment };
+    rename(ecs, item, &affixed);
+    ecs.write_storage::<Affixed>().insert(item, affixed).expect("Insert fail");
+
+    let tier = if prefix.is_some() && suffix.is_some() { RarityTier::Legendary } else { RarityTier::Rare };
+    ecs.write_storage::<Rarity>().insert(item, Rarity{ tier }).expect("Insert fail");
+}
+
+/// Which affixes in the raws suit the slot. Ammunition takes none: it is spent one at a time and
+/// gathered up again by its plain name.
+fn slot_name(slot : EquipmentSlot) -> Option<&'static str> {
+    match slot {
+        EquipmentSlot::Melee => Some("weapon"),
+        EquipmentSlot::Shield => Some("shield"),
+        EquipmentSlot::Quiver => None,
+        EquipmentSlot::Head | EquipmentSlot::Torso | EquipmentSlot::Legs | EquipmentSlot::Feet | EquipmentSlot::Hands | EquipmentSlot::Cloak => Some("armor")
+    }
+}
+
+/// Raises a weapon's power or the defense of anything else.
+fn add_enchantment(ecs : &mut World, item : Entity, slot : EquipmentSlot, amount : i32) {
+    match slot {
+        EquipmentSlot::Melee => add_bonuses(ecs, item, amount, 0),
+        _ => add_bonuses(ecs, item, 0, amount)
+    }
+}
+
+fn add_bonuses(ecs : &mut World, item : Entity, power : i32, defense : i32) {
+    if power != 0 {
+        let mut power_bonuses = ecs.write_storage::<MeleePowerBonus>();
+        let base = power_bonuses.get(item).map_or(0, |b| b.power);
+        power_bonuses.insert(item, MeleePowerBonus{ power: base + power }).expect("Insert fail");
+    }
+    if defense != 0 {
+        let mut defense_bonuses = ecs.write_storage::<DefenseBonus>();
+        let base = defense_bonuses.get(item).map_or(0, |b| b.defense);
+        defense_bonuses.insert(item, DefenseBonus{ defense: base + defense }).expect("Insert fail");
+    }
+}
+
+/// Adds an affix's effects to whatever the item already has, so a prefix and a suffix with the
+/// same kind of effect stack; extra damage keeps the element it had first.
+fn apply_affix(ecs : &mut World, item : Entity, affix : &Affix) {
+    add_bonuses(ecs, item, affix.power_bonus.unwrap_or(0), affix.defense_bonus.unwrap_or(0));
+    if let Some(amount) = affix.bonus_damage {
+        let mut bonus_damage = ecs.write_storage::<BonusDamage>();
+        match bonus_damage.get_mut(item) {
+            Some(bonus) => bonus.amount += amount,
+            None => {
+                let element = affix.element.clone().unwrap_or_else(|| "magic".to_string());
+                bonus_damage.insert(item, BonusDamage{ amount, element }).expect("Insert fail");
+            }
+        }
+    }
+    if let Some(chance) = affix.confusion_chance {
+        let mut confusion = ecs.write_storage::<ConfuseOnHit>();
+        match confusion.get_mut(item) {
+            Some(confuse) => confuse.chance += chance,
+            None => { confusion.insert(item, ConfuseOnHit{ chance, turns: CONFUSE_ON_HIT_TURNS }).expect("Insert fail"); }
+        }
+    }
+}
+
+/// Rebuilds the item's name from its affixes, keeping any blessing.
+fn rename(ecs : &mut World, item : Entity, affixed : &Affixed) {
+    let mut names = ecs.write_storage::<Name>();
+    if let Some(name) = names.get_mut(item) {
+        let mut full_name = affixed.base.clone();
+        if let Some(prefix) = &affixed.prefix { full_name = format!("{} {}", prefix, full_name); }
+        if let Some(suffix) = &affixed.suffix { full_name = format!("{} {}", full_name, suffix); }
+        if affixed.enchantment > 0 { full_name = format!("{} +{}", full_name, affixed.enchantment); }
+        if name.name.starts_with("Blessed ") && !affixed.base.starts_with("Blessed ") {
+            full_name = format!("Blessed {}", full_name);
+        }
+        name.name = full_name;
+    }
+}
+
+/// Gold, reagents and failure chance (in 100) for the next enchantment; every
+/// upgrade already on the item makes the next one dearer and riskier, and each rank
+/// of the customer's crafting skill makes it a little safer.
+pub fn enchant_cost(ecs : &World, customer : Entity, item : Entity) -> (i32, i32, i32) {
+    let upgrades = ecs.read_storage::<Affixed>().get(item).map_or(0, |a| {
+        a.enchantment + if a.prefix.is_some() { 1 } else { 0 } + if a.suffix.is_some() { 1 } else { 0 }
+    });
+    let gold = ENCHANT_GOLD_COST * (upgrades + 1);
+    let reagents = 1 + upgrades / 2;
+    let crafting = ecs.read_storage::<Skills>().get(customer).map_or(0, |s| s.crafting);
+    let fail_chance = i32::max(0, i32::min(ENCHANT_MAX_FAIL_CHANCE, upgrades * ENCHANT_FAIL_PER_UPGRADE) - crafting * ENCHANT_FAIL_PER_CRAFTING);
+    (gold, reagents, fail_chance)
+}
+
+/// An enchanter's work: adds a prefix to plain gear, then a suffix, then raises its
+/// enchantment. Whatever the item already has stays on it, effects and name alike.
+/// Payment is taken whether or not the enchantment takes hold.
+pub fn enchant(raws : &RawMaster, ecs : &mut World, customer : Entity, item : Entity) {
+    let item_name = ecs.read_storage::<Name>().get(item).map_or("item".to_string(), |n| n.name.clone());
+    let slot = match ecs.read_storage::<Equippable>().get(item) {
+        Some(equippable) if ecs.read_storage::<Artifact>().get(item).is_none() && slot_name(equippable.slot).is_some() => equippable.slot,
+        _ => {
+            ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("The {} cannot be enchanted.", item_name));
+            return;
+        }
+    };
+
+    let (gold, reagents, fail_chance) = enchant_cost(ecs, customer, item);
+    let reagent_items : Vec<Entity> = {
+        let entities = ecs.entities();
+        let backpack = ecs.read_storage::<InBackpack>();
+        let reagent = ecs.read_storage::<Reagent>();
+        (&entities, &backpack, &reagent).join().filter(|(_e, pack, _r)| pack.owner == customer).map(|(e, _pack, _r)| e).collect()
+    };
+    let purse = ecs.read_storage::<Purse>().get(customer).map_or(0, |p| p.gold);
+    if purse < gold || (reagent_items.len() as i32) < reagents {
+        ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("Enchanting the {} needs {} gold and {} reagents.", item_name, gold, reagents));
+        return;
+    }
+
+    if let Some(purse) = ecs.write_storage::<Purse>().get_mut(customer) { purse.gold -= gold; }
+    for reagent in reagent_items.iter().take(reagents as usize) {
+        ecs.delete_entity(*reagent).expect("Unable to delete reagent");
+    }
+
+    if ecs.write_resource::<RandomNumberGenerator>().roll_dice(1, 100) <= fail_chance {
+        ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("The runes on the {} flare and fade. The enchantment fails.", item_name));
+        return;
+    }
+
+    // Plain gear starts its affixes from its own name, less any blessing, which `rename` puts back in front
+    let mut affixed = ecs.read_storage::<Affixed>().get(item).cloned().unwrap_or_else(|| Affixed{
+        base: item_name.trim_start_matches("Blessed ").to_string(), prefix: None, suffix: None, enchantment: 0
+    });
+    let kind = if affixed.prefix.is_none() { Some("prefix") } else if affixed.suffix.is_none() { Some("suffix") } else { None };
+    let affix = kind.and_then(|kind| {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        roll_affix(raws, &mut rng, kind, slot_name(slot).expect("Enchanting gear that takes no affixes"), ENCHANT_AFFIX_DEPTH)
+    });
+    match (kind, affix) {
+        (Some("prefix"), Some(affix)) => { apply_affix(ecs, item, affix); affixed.prefix = Some(affix.name.clone()); }
+        (Some(_), Some(affix)) => { apply_affix(ecs, item, affix); affixed.suffix = Some(affix.name.clone()); }
+        _ => { add_enchantment(ecs, item, slot, 1); affixed.enchantment += 1; }
+    }
+
+    rename(ecs, item, &affixed);
+    let tier = if affixed.prefix.is_some() && affixed.suffix.is_some() { RarityTier::Legendary } else { RarityTier::Rare };
+    ecs.write_storage::<Affixed>().insert(item, affixed).expect("Insert fail");
+    ecs.write_storage::<Rarity>().insert(item, Rarity{ tier }).expect("Insert fail");
+
+    let new_name = ecs.read_storage::<Name>().get(item).map_or("item".to_string(), |n| n.name.clone());
+    ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("The {} is now the {}!", item_name, new_name));
+    events::publish(ecs, GameEvent::SkillUsed{ by: customer, skill: Skill::Crafting, xp: ENCHANT_XP });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn world() -> World {
+        let mut ecs = World::new();
+        ecs.register::<Affixed>();
+        ecs.register::<Skills>();
+        ecs
+    }
+
+    #[test]
+    fn plain_gear_is_cheap_and_safe_to_enchant() {
+        let mut ecs = world();
+        let customer = ecs.create_entity().build();
+        let item = ecs.create_entity().build();
+        assert_eq!(enchant_cost(&ecs, customer, item), (ENCHANT_GOLD_COST, 1, 0));
+    }
+
+    #[test]
+    fn every_upgrade_makes_the_next_dearer_and_riskier() {
+        let mut ecs = world();
+        let customer = ecs.create_entity().build();
+        let item = ecs.create_entity()
+            .with(Affixed{ base: "Dagger".to_string(), prefix: Some("Keen".to_string()), suffix: Some("of Flame".to_string()), enchantment: 1 })
+            .build();
+        assert_eq!(enchant_cost(&ecs, customer, item), (ENCHANT_GOLD_COST * 4, 2, 3 * ENCHANT_FAIL_PER_UPGRADE));
+    }
+
+    #[test]
+    fn crafting_lowers_the_risk_but_never_below_zero() {
+        let mut ecs = world();
+        let item = ecs.create_entity()
+            .with(Affixed{ base: "Dagger".to_string(), prefix: None, suffix: None, enchantment: 10 })
+            .build();
+        let novice = ecs.create_entity().build();
+        assert_eq!(enchant_cost(&ecs, novice, item).2, ENCHANT_MAX_FAIL_CHANCE);
+        let crafter = ecs.create_entity().with(Skills{ crafting: 2, ..Default::default() }).build();
+        assert_eq!(enchant_cost(&ecs, crafter, item).2, ENCHANT_MAX_FAIL_CHANCE - 2 * ENCHANT_FAIL_PER_CRAFTING);
+        let master = ecs.create_entity().with(Skills{ crafting: 100, ..Default::default() }).build();
+        assert_eq!(enchant_cost(&ecs, master, item).2, 0);
+    }
+}
@@ -0,0 +1,33 @@
+use serde::Deserialize;
+
+/// A starting package for a new character, picked on the creation screen.
+#[derive(Deserialize, Debug)]
+pub struct Class {
+    pub name : String,
+    pub description : String,
+    pub hp : i32,
+    pub power : i32,
+    pub defense : i32,
+    pub attributes : ClassAttributes,
+    pub skills : ClassSkills,
+    pub gold : Option<i32>,
+    pub equipment : Vec<String>,
+    pub inventory : Vec<String>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ClassAttributes {
+    pub might : i32,
+    pub fitness : i32,
+    pub quickness : i32,
+    pub intelligence : i32
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ClassSkills {
+    pub melee : Option<i32>,
+    pub lockpicking : Option<i32>,
+    pub magic : Option<i32>,
+    pub stealth : Option<i32>,
+    pub crafting : Option<i32>
+}
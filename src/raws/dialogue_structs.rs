@@ -0,0 +1,23 @@
+use serde::Deserialize;
+
+/// A conversation with a townsperson. It opens on the first node.
+#[derive(Deserialize, Debug)]
+pub struct Dialogue {
+    pub name : String,
+    pub nodes : Vec<DialogueNode>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DialogueNode {
+    pub id : String,
+    pub text : String,
+    pub responses : Vec<DialogueResponse>
+}
+
+/// A reply the player can pick. It either leads to another node, or does something: "quests", "shop" or "end".
+#[derive(Deserialize, Debug)]
+pub struct DialogueResponse {
+    pub text : String,
+    pub next : Option<String>,
+    pub action : Option<String>
+}
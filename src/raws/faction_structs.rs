@@ -0,0 +1,10 @@
+use serde::Deserialize;
+
+/// How a group of creatures reacts to the player as their standing with it falls.
+#[derive(Deserialize, Debug)]
+pub struct FactionInfo {
+    pub name : String,
+    pub hostile_below : Option<i32>,
+    pub refuses_trade_below : Option<i32>,
+    pub allies : Option<Vec<String>>
+}
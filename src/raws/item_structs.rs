@@ -0,0 +1,120 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Deserialize, Debug)]
+pub struct Item {
+    pub name : String,
+    pub renderable : Option<Renderable>,
+    pub consumable : Option<Consumable>,
+    pub weapon : Option<Weapon>,
+    pub shield : Option<Shield>,
+    pub wearable : Option<Wearable>,
+    pub rarity : Option<String>,
+    pub value : Option<i32>,
+    pub weight : Option<i32>,
+    pub thrown : Option<Thrown>,
+    pub starts_fire : Option<i32>,
+    pub flammable : Option<bool>,
+    pub dot : Option<Dot>,
+    pub artifact : Option<ArtifactPowers>,
+    pub key : Option<bool>,
+    pub gold : Option<i32>,
+    pub reagent : Option<bool>,
+    pub wand : Option<Wand>,
+    pub ammo : Option<Ammo>,
+    pub unidentified_name : Option<String>,
+    pub category : Option<String>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Renderable {
+    pub glyph : String,
+    pub fg : String,
+    pub bg : String,
+    pub order : i32
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Consumable {
+    pub effects : HashMap<String, String>
+}
+
+/// Casts its effects each time it is zapped, until the charges run out.
+#[derive(Deserialize, Debug)]
+pub struct Wand {
+    pub charges : i32,
+    pub effects : HashMap<String, String>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Weapon {
+    pub power_bonus : i32,
+    pub crit_range : Option<i32>,
+    pub crit_multiplier : Option<i32>,
+    pub parry_chance : Option<i32>,
+    pub special : Option<WeaponSpecial>,
+    pub durability : Option<i32>,
+    pub hands : Option<String>,
+    pub launcher : Option<Launcher>
+}
+
+/// Makes a weapon shoot ammunition of the given kind instead of only striking in melee.
+#[derive(Deserialize, Debug)]
+pub struct Launcher {
+    pub ammo : String,
+    pub range : i32
+}
+
+/// A stack of arrows or bolts that goes in the quiver.
+#[derive(Deserialize, Debug)]
+pub struct Ammo {
+    pub kind : String,
+    pub damage : i32,
+    pub count : i32
+}
+
+#[derive(Deserialize, Debug)]
+pub struct WeaponSpecial {
+    pub attack : String,
+    pub stamina_cost : i32
+}
+
+/// A poison or bleed effect applied on hit.
+#[derive(Deserialize, Debug)]
+pub struct Dot {
+    pub kind : String,
+    pub damage : i32,
+    pub turns : i32,
+    pub chance : i32
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Thrown {
+    pub damage : i32
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Shield {
+    pub defense_bonus : i32,
+    pub block_chance : Option<i32>,
+    pub durability : Option<i32>
+}
+
+/// A piece of armor worn in one of the body slots.
+#[derive(Deserialize, Debug)]
+pub struct Wearable {
+    pub slot : String,
+    pub armor_class : i32,
+    pub durability : Option<i32>
+}
+
+/// Marks a unique item and the special powers it is always generated with.
+#[derive(Deserialize, Debug)]
+pub struct ArtifactPowers {
+    pub min_depth : i32,
+    pub weight : i32,
+    pub bonus_damage : Option<i32>,
+    pub element : Option<String>,
+    pub confusion_chance : Option<i32>,
+    pub regeneration : Option<i32>
+}
@@ -0,0 +1,16 @@
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct LootTable {
+    pub name : String,
+    pub drops : Vec<LootDrop>
+}
+
+/// One possible drop; `min_depth`/`max_depth` restrict it to part of the dungeon.
+#[derive(Deserialize, Debug)]
+pub struct LootDrop {
+    pub name : String,
+    pub weight : i32,
+    pub min_depth : Option<i32>,
+    pub max_depth : Option<i32>
+}
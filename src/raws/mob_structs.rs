@@ -0,0 +1,103 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use super::{Renderable, Dot};
+
+#[derive(Deserialize, Debug)]
+pub struct Mob {
+    pub name : String,
+    pub renderable : Option<Renderable>,
+    pub blocks_tile : bool,
+    pub stats : MobStats,
+    pub vision_range : i32,
+    pub loot_table : Option<String>,
+    pub dot : Option<Dot>,
+    pub resistances : Option<HashMap<String, i32>>,
+    pub vulnerabilities : Option<HashMap<String, i32>>,
+    pub attributes : Option<MobAttributes>,
+    pub abilities : Option<Vec<MobAbility>>,
+    pub boss : Option<MobBoss>,
+    pub recruitable : Option<bool>,
+    pub vendor : Option<MobVendor>,
+    pub quest_giver : Option<bool>,
+    pub dialogue : Option<String>,
+    pub faction : Option<String>,
+    pub invisible : Option<bool>,
+    pub see_invisible : Option<bool>,
+    pub moves_through_walls : Option<bool>,
+    pub pack : Option<MobPack>,
+    pub territory : Option<i32>,
+    pub patrol : Option<MobPatrol>,
+    pub carries : Option<i32>,
+    pub afflicts : Option<MobAffliction>
+}
+
+/// Each hit has a `chance` in 100 of passing on a "disease" or "curse".
+#[derive(Deserialize, Debug)]
+pub struct MobAffliction {
+    pub kind : String,
+    pub chance : i32
+}
+
+/// Walks a round of `waypoints` spots picked within `range` of where it spawned, and won't chase
+/// anything further away than that.
+#[derive(Deserialize, Debug)]
+pub struct MobPatrol {
+    pub waypoints : i32,
+    pub range : i32
+}
+
+/// Spawns with `min` to `max` of its kind in all, the first of them leading the rest.
+#[derive(Deserialize, Debug)]
+pub struct MobPack {
+    pub min : i32,
+    pub max : i32
+}
+
+/// A shopkeeper's wares are rolled from this loot table, `stock` items at a time.
+#[derive(Deserialize, Debug)]
+pub struct MobVendor {
+    pub table : String,
+    pub stock : i32
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MobStats {
+    pub max_hp : i32,
+    pub hp : i32,
+    pub power : i32,
+    pub defense : i32
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MobAttributes {
+    pub might : Option<i32>,
+    pub fitness : Option<i32>,
+    pub quickness : Option<i32>,
+    pub intelligence : Option<i32>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MobAbility {
+    pub kind : String,
+    pub chance : Option<i32>,
+    pub range : f32,
+    pub power : i32,
+    pub summon : Option<String>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MobBoss {
+    pub phases : Vec<MobBossPhase>,
+    pub loot : Option<String>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct MobBossPhase {
+    pub hp_percent : i32,
+    pub message : String,
+    pub abilities : Option<Vec<MobAbility>>,
+    pub summon : Option<String>,
+    pub summon_count : Option<i32>,
+    pub arena : Option<String>,
+    pub script : Option<String>
+}
@@ -0,0 +1,112 @@
+mod item_structs;
+mod mob_structs;
+mod loot_structs;
+mod affix_structs;
+mod prop_structs;
+mod class_structs;
+mod quest_structs;
+mod dialogue_structs;
+mod faction_structs;
+mod tile_structs;
+mod spawn_structs;
+mod rawmaster;
+mod affixes;
+mod mods;
+
+use serde::Deserialize;
+use std::sync::{Mutex, MutexGuard, OnceLock};
+pub use item_structs::*;
+pub use mob_structs::*;
+pub use loot_structs::*;
+pub use affix_structs::*;
+pub use prop_structs::*;
+pub use class_structs::*;
+pub use quest_structs::*;
+pub use dialogue_structs::*;
+pub use faction_structs::*;
+pub use tile_structs::*;
+pub use spawn_structs::*;
+pub use rawmaster::*;
+pub use affixes::{roll_affixes, enchant, enchant_cost};
+
+const RAW_FILE : &str = include_str!("../../raws/spawns.json");
+
+static RAWS : OnceLock<Mutex<RawMaster>> = OnceLock::new();
+
+/// Where the raws sit in the source tree, so debug builds can reload them while the game runs.
+#[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+const RAW_PATH : &str = concat!(env!("CARGO_MANIFEST_DIR"), "/raws/spawns.json");
+/// How often the watcher looks at the raws file.
+#[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+const WATCH_INTERVAL_MS : f32 = 1000.0;
+
+/// Everything in a raws file. Mods give only the parts they change, so each part can be left out.
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+pub struct Raws {
+    pub items : Vec<Item>,
+    pub mobs : Vec<Mob>,
+    pub loot_tables : Vec<LootTable>,
+    pub affixes : Vec<Affix>,
+    pub props : Vec<Prop>,
+    pub classes : Vec<Class>,
+    pub quests : Vec<Quest>,
+    pub dialogues : Vec<Dialogue>,
+    pub factions : Vec<FactionInfo>,
+    pub tiles : Vec<TileMapping>,
+    pub spawn_table : Vec<SpawnWeight>
+}
+
+/// Access to the game data, parsed from the embedded raws file on first use with any mods layered on top.
+pub fn raws() -> MutexGuard<'static, RawMaster> {
+    RAWS.get_or_init(|| {
+        let mut decoder : Raws = serde_json::from_str(RAW_FILE).expect("Unable to parse raws");
+        mods::apply_mods(&mut decoder);
+        Mutex::new(RawMaster::new(decoder))
+    }).lock().unwrap()
+}
+
+/// Watches the raws file in debug builds, and reloads the game data whenever it is saved, so
+/// content can be tweaked without restarting. Whatever spawns afterwards uses the new data; what
+/// is already in the world keeps what it was made with. Release and web builds never reload.
+#[derive(Default)]
+pub struct RawWatcher {
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    modified : Option<std::time::SystemTime>,
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    wait_ms : f32
+}
+
+impl RawWatcher {
+    #[cfg(not(all(debug_assertions, not(target_arch = "wasm32"))))]
+    pub fn poll(&mut self, _frame_time_ms : f32) -> Option<Result<(), String>> {
+        None
+    }
+
+    /// Reloads the raws if the file changed since the last look; says how that went if it did.
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    pub fn poll(&mut self, frame_time_ms : f32) -> Option<Result<(), String>> {
+        self.wait_ms -= frame_time_ms;
+        if self.wait_ms > 0.0 { return None; }
+        self.wait_ms = WATCH_INTERVAL_MS;
+
+        let modified = std::fs::metadata(RAW_PATH).and_then(|m| m.modified()).ok()?;
+        match self.modified.replace(modified) {
+            // The first look only notes the time; the embedded raws are already up to date
+            None => None,
+            Some(previous) if previous == modified => None,
+            Some(_) => Some(reload())
+        }
+    }
+}
+
+/// Parses the raws file afresh, with the mods on top, and swaps it in for the data in use.
+/// Broken JSON leaves the old data in place.
+#[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+fn reload() -> Result<(), String> {
+    let data = std::fs::read_to_string(RAW_PATH).map_err(|err| err.to_string())?;
+    let mut decoder : Raws = serde_json::from_str(&data).map_err(|err| err.to_string())?;
+    mods::apply_mods(&mut decoder);
+    *raws() = RawMaster::new(decoder);
+    Ok(())
+}
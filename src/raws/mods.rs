@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use super::{Raws, Item, Mob, LootTable, Affix, Prop, Class, Quest, Dialogue, FactionInfo, TileMapping, SpawnWeight, Renderable,
+            rawmaster::{ITEM_AMOUNT_EFFECTS, PROP_AMOUNT_EFFECTS}};
+use crate::components::EquipmentSlot;
+
+/// Where players drop their mods, next to the game.
+#[cfg(not(target_arch = "wasm32"))]
+const MODS_DIRECTORY : &str = "./mods";
+/// Lists the mods to load, one file name a line, in order. Without it every .json file is loaded,
+/// alphabetically.
+#[cfg(not(target_arch = "wasm32"))]
+const LOAD_ORDER : &str = "load_order.txt";
+
+/// Anything in the raws that a mod can replace by giving something else the same name.
+trait Named {
+    fn key(&self) -> String;
+}
+
+macro_rules! named_by_name {
+    ($($t:ty),*) => {
+        $(impl Named for $t {
+            fn key(&self) -> String { self.name.clone() }
+        })*
+    };
+}
+
+named_by_name!(Item, Mob, LootTable, Affix, Prop, Class, Quest, Dialogue, FactionInfo);
+
+impl Named for TileMapping {
+    fn key(&self) -> String { self.glyph.clone() }
+}
+
+impl Named for SpawnWeight {
+    fn key(&self) -> String { format!("{}/{}", self.name, self.branch.as_deref().unwrap_or("")) }
+}
+
+/// Adds a mod's entries to the list, each replacing whatever already had its name.
+fn merge<T : Named>(base : &mut Vec<T>, entries : Vec<T>) {
+    for entry in entries {
+        match base.iter().position(|existing| existing.key() == entry.key()) {
+            Some(i) => base[i] = entry,
+            None => base.push(entry)
+        }
+    }
+}
+
+impl Raws {
+    /// Layers a mod over the raws loaded so far.
+    fn merge(&mut self, other : Raws) {
+        merge(&mut self.items, other.items);
+        merge(&mut self.mobs, other.mobs);
+        merge(&mut self.loot_tables, other.loot_tables);
+        merge(&mut self.affixes, other.affixes);
+        merge(&mut self.props, other.props);
+        merge(&mut self.classes, other.classes);
+        merge(&mut self.quests, other.quests);
+        merge(&mut self.dialogues, other.dialogues);
+        merge(&mut self.factions, other.factions);
+        merge(&mut self.tiles, other.tiles);
+        merge(&mut self.spawn_table, other.spawn_table);
+    }
+}
+
+/// Merges every mod the player has installed into the raws. A mod that can't be read or parsed,
+/// or that holds values the game can't use, is skipped with a warning rather than stopping the game.
+pub fn apply_mods(raws : &mut Raws) {
+    for (name, data) in mod_files() {
+        let contents = serde_json::from_str::<Raws>(&data).map_err(|err| err.to_string())
+            .and_then(|contents| contents.check(raws).map(|_| contents));
+        match contents {
+            Ok(contents) => {
+                rltk::console::log(format!("Loaded mod {}", name));
+                raws.merge(contents);
+            }
+            Err(err) => rltk::console::log(format!("Warning: mod {} could not be loaded: {}", name, err))
+        }
+    }
+}
+
+impl Raws {
+    /// Looks over a mod's entries for anything that would only go wrong once it spawns: colours and
+    /// glyphs that can't be drawn, amounts that aren't numbers, unknown equipment slots, and classes
+    /// starting with gear that doesn't exist or can't be worn. `base` is what the mod is layered over.
+    fn check(&self, base : &Raws) -> Result<(), String> {
+        let renderables = self.items.iter().map(|i| (&i.name, &i.renderable))
+            .chain(self.mobs.iter().map(|m| (&m.name, &m.renderable)))
+            .chain(self.props.iter().map(|p| (&p.name, &p.renderable)));
+        for (name, renderable) in renderables {
+            if let Some(renderable) = renderable { check_renderable(name, renderable)?; }
+        }
+        for tile in self.tiles.iter() {
+            if tile.glyph.is_empty() { return Err("a tile mapping has no glyph".to_string()); }
+        }
+
+        for item in self.items.iter() {
+            let effects = item.consumable.iter().map(|c| &c.effects).chain(item.wand.iter().map(|w| &w.effects));
+            for effects in effects {
+                check_amounts(&item.name, effects, &ITEM_AMOUNT_EFFECTS)?;
+            }
+            if let Some(wearable) = &item.wearable {
+                if EquipmentSlot::from_name(&wearable.slot).is_none() {
+                    return Err(format!("{} is worn on unknown slot {}", item.name, wearable.slot));
+                }
+            }
+        }
+        for prop in self.props.iter() {
+            if let Some(interaction) = &prop.interact {
+                check_amounts(&prop.name, &interaction.effects, &PROP_AMOUNT_EFFECTS)?;
+            }
+        }
+
+        for class in self.classes.iter() {
+            for name in class.equipment.iter().chain(class.inventory.iter()) {
+                let item = self.items.iter().chain(base.items.iter()).find(|item| item.name == *name)
+                    .ok_or_else(|| format!("class {} starts with unknown item {}", class.name, name))?;
+                let equippable = item.weapon.is_some() || item.shield.is_some() || item.wearable.is_some() || item.ammo.is_some();
+                if class.equipment.contains(name) && !equippable {
+                    return Err(format!("class {} starts wearing {}, which can't be equipped", class.name, name));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn check_renderable(name : &str, renderable : &Renderable) -> Result<(), String> {
+    if renderable.glyph.is_empty() { return Err(format!("{} has no glyph", name)); }
+    for colour in [&renderable.fg, &renderable.bg].iter() {
+        if rltk::RGB::from_hex(colour).is_err() {
+            return Err(format!("{} has {} for a colour", name, colour));
+        }
+    }
+    Ok(())
+}
+
+fn check_amounts(name : &str, effects : &HashMap<String, String>, amounts : &[&str]) -> Result<(), String> {
+    for (effect, value) in effects.iter() {
+        if amounts.contains(&effect.as_str()) && value.trim().parse::<i32>().is_err() {
+            return Err(format!("{} has {} for its {} amount", name, value, effect));
+        }
+    }
+    Ok(())
+}
+
+/// The web build has nowhere to put mods.
+#[cfg(target_arch = "wasm32")]
+fn mod_files() -> Vec<(String, String)> {
+    Vec::new()
+}
+
+/// The contents of each mod file, in load order.
+#[cfg(not(target_arch = "wasm32"))]
+fn mod_files() -> Vec<(String, String)> {
+    let directory = std::path::Path::new(MODS_DIRECTORY);
+    let names : Vec<String> = match std::fs::read_to_string(directory.join(LOAD_ORDER)) {
+        Ok(order) => order.lines()
+            .map(|line| line.split('#').next().unwrap_or("").trim().to_string())
+            .filter(|line| !line.is_empty())
+            .collect(),
+        Err(_) => {
+            let mut names : Vec<String> = std::fs::read_dir(directory).into_iter().flatten().flatten()
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .filter(|name| name.ends_with(".json"))
+                .collect();
+            names.sort();
+            names
+        }
+    };
+    names.into_iter()
+        .filter_map(|name| match std::fs::read_to_string(directory.join(&name)) {
+            Ok(data) => Some((name, data)),
+            Err(err) => {
+                rltk::console::log(format!("Warning: mod {} could not be read: {}", name, err));
+                None
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,41 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+use super::Renderable;
+
+/// Scenery that isn't a creature or an item: traps, portals and the like.
+#[derive(Deserialize, Debug)]
+pub struct Prop {
+    pub name : String,
+    pub renderable : Option<Renderable>,
+    pub hidden : Option<bool>,
+    pub blocks_tile : Option<bool>,
+    pub entry_trigger : Option<EntryTrigger>,
+    pub locked : Option<bool>,
+    pub door : Option<bool>,
+    pub blocks_visibility : Option<bool>,
+    pub lever : Option<bool>,
+    pub portcullis : Option<bool>,
+    pub secret_passage : Option<bool>,
+    pub container : Option<PropContainer>,
+    pub interact : Option<Interaction>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EntryTrigger {
+    pub effects : HashMap<String, String>
+}
+
+/// Filled with `rolls` draws from a loot table when the prop is spawned.
+#[derive(Deserialize, Debug)]
+pub struct PropContainer {
+    pub loot_table : String,
+    pub rolls : i32
+}
+
+/// What happens when the player uses the prop; `verb` fills in "You <verb> the <name>."
+#[derive(Deserialize, Debug)]
+pub struct Interaction {
+    pub verb : String,
+    pub uses : Option<i32>,
+    pub effects : HashMap<String, String>
+}
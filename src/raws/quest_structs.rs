@@ -0,0 +1,20 @@
+use serde::Deserialize;
+
+/// A task a townsperson will hand out: slay `count` of a creature, or bring back `count` of an item.
+#[derive(Deserialize, Debug)]
+pub struct Quest {
+    pub name : String,
+    pub giver : String,
+    pub description : String,
+    pub kind : String,
+    pub target : String,
+    pub count : i32,
+    pub reward : QuestReward
+}
+
+#[derive(Deserialize, Debug)]
+pub struct QuestReward {
+    pub gold : Option<i32>,
+    pub xp : Option<i32>,
+    pub item : Option<String>
+}
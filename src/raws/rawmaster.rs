@@ -0,0 +1,784 @@
+use std::collections::HashMap;
+use rltk::{RandomNumberGenerator, RGB};
+use specs::prelude::*;
+use specs::saveload::{MarkedBuilder, SimpleMarker};
+use crate::components::*;
+use crate::random_table::RandomTable;
+use crate::unique_items::UniqueItems;
+use crate::identified_items::IdentifiedItems;
+use crate::tile_set::TileSet;
+use crate::Branch;
+use super::{Raws, Mob, Quest, DialogueNode, FactionInfo};
+use crate::reputation::{Reputation, Reaction};
+use crate::difficulty::{Difficulty, scale};
+
+pub struct RawMaster {
+    pub(super) raws : Raws,
+    item_index : HashMap<String, usize>,
+    mob_index : HashMap<String, usize>,
+    loot_index : HashMap<String, usize>,
+    prop_index : HashMap<String, usize>,
+    class_index : HashMap<String, usize>,
+    tile_index : HashMap<rltk::FontCharType, rltk::FontCharType>
+}
+
+impl RawMaster {
+    pub fn new(raws : Raws) -> RawMaster {
+        let mut item_index = HashMap::new();
+        for (i, item) in raws.items.iter().enumerate() {
+            item_index.insert(item.name.clone(), i);
+        }
+        let mut mob_index = HashMap::new();
+        for (i, mob) in raws.mobs.iter().enumerate() {
+            mob_index.insert(mob.name.clone(), i);
+        }
+        let mut loot_index = HashMap::new();
+        for (i, loot) in raws.loot_tables.iter().enumerate() {
+            loot_index.insert(loot.name.clone(), i);
+        }
+
+        let mut prop_index = HashMap::new();
+        for (i, prop) in raws.props.iter().enumerate() {
+            prop_index.insert(prop.name.clone(), i);
+        }
+
+        let mut class_index = HashMap::new();
+        for (i, class) in raws.classes.iter().enumerate() {
+            class_index.insert(class.name.clone(), i);
+        }
+
+        let mut tile_index = HashMap::new();
+        for tile in raws.tiles.iter() {
+            tile_index.insert(rltk::to_cp437(tile.glyph.chars().next().unwrap()), tile.tile);
+        }
+
+        RawMaster{ raws, item_index, mob_index, loot_index, prop_index, class_index, tile_index }
+    }
+}
+
+fn get_renderable_component(renderable : &super::Renderable) -> Renderable {
+    Renderable {
+        glyph: rltk::to_cp437(renderable.glyph.chars().next().unwrap()),
+        fg : RGB::from_hex(&renderable.fg).expect("Invalid RGB"),
+        bg : RGB::from_hex(&renderable.bg).expect("Invalid RGB"),
+        render_order : renderable.order
+    }
+}
+
+fn get_dot_component(dot : &super::Dot) -> InflictsDot {
+    InflictsDot{
+        effect : DotEffect{ kind: DamageType::from_name(&dot.kind), damage: dot.damage, turns: dot.turns },
+        chance : dot.chance
+    }
+}
+
+fn parse_abilities(abilities : &[super::MobAbility]) -> Vec<SpecialAbility> {
+    let mut list = Vec::new();
+    for ability in abilities.iter() {
+        let kind = match ability.kind.as_str() {
+            "web" => AbilityKind::Web,
+            "summon" => AbilityKind::Summon,
+            "self_destruct" => AbilityKind::SelfDestruct,
+            "life_drain" => AbilityKind::LifeDrain,
+            "steal" => AbilityKind::Steal,
+            "split" => AbilityKind::Split,
+            "breed" => AbilityKind::Breed,
+            _ => {
+                rltk::console::log(format!("Warning: mob ability {} not implemented.", ability.kind));
+                continue;
+            }
+        };
+        list.push(SpecialAbility{ kind, chance: ability.chance.unwrap_or(100), range: ability.range, power: ability.power, summon: ability.summon.clone() });
+    }
+    list
+}
+
+fn parse_category(category : &str) -> Option<ItemCategory> {
+    match category {
+        "gold" => Some(ItemCategory::Gold),
+        "ammo" => Some(ItemCategory::Ammo),
+        "potion" => Some(ItemCategory::Potion),
+        "scroll" => Some(ItemCategory::Scroll),
+        _ => {
+            rltk::console::log(format!("Warning: item category {} not implemented.", category));
+            None
+        }
+    }
+}
+
+fn parse_amount(effect_name : &str, value : &str) -> Option<i32> {
+    let amount = value.trim().parse::<i32>().ok();
+    if amount.is_none() {
+        rltk::console::log(format!("Warning: effect {} has {} for an amount, not a number.", effect_name, value));
+    }
+    amount
+}
+
+fn parse_affliction(name : &str) -> Option<AfflictionKind> {
+    let kind = AfflictionKind::from_name(name);
+    if kind.is_none() {
+        rltk::console::log(format!("Warning: affliction {} not implemented.", name));
+    }
+    kind
+}
+
+fn parse_rarity(rarity : &str) -> RarityTier {
+    match rarity {
+        "rare" => RarityTier::Rare,
+        "legendary" => RarityTier::Legendary,
+        "artifact" => RarityTier::Artifact,
+        _ => RarityTier::Common
+    }
+}
+
+/// Creates the item or monster called `key` at the given position.
+pub fn spawn_named_entity(raws : &RawMaster, ecs : &mut World, key : &str, x : i32, y : i32) -> Option<Entity> {
+    if raws.item_index.contains_key(key) {
+        return spawn_named_item(raws, ecs, key, x, y);
+    } else if raws.mob_index.contains_key(key) {
+        return spawn_named_mob(raws, ecs, key, x, y);
+    } else if raws.prop_index.contains_key(key) {
+        return spawn_named_prop(raws, ecs, key, x, y);
+    }
+
+    None
+}
+
+/// The tileset sprite drawn in place of a glyph, if it has one.
+pub fn tile_for(raws : &RawMaster, glyph : rltk::FontCharType) -> Option<rltk::FontCharType> {
+    raws.tile_index.get(&glyph).copied()
+}
+
+/// Looks up the exact raws name of an item, mob or prop, ignoring case.
+pub fn find_raw_name(raws : &RawMaster, name : &str) -> Option<String> {
+    raws.item_index.keys().chain(raws.mob_index.keys()).chain(raws.prop_index.keys())
+        .find(|key| key.eq_ignore_ascii_case(name))
+        .cloned()
+}
+
+/// Consumable and wand effects whose value is a number, as read by `with_effects`.
+pub(super) const ITEM_AMOUNT_EFFECTS : [&str; 12] = ["provides_healing", "ranged", "damage", "area_of_effect", "confusion",
+    "summon_turns", "charm", "invisibility", "see_invisible", "telepathy", "repair", "food"];
+/// Prop interaction effects whose value is a number.
+pub(super) const PROP_AMOUNT_EFFECTS : [&str; 4] = ["heal", "poison", "well_fed", "cure"];
+
+/// Adds the components for a consumable's or wand's effects.
+fn with_effects<'a>(mut eb : EntityBuilder<'a>, effects : &HashMap<String, String>) -> EntityBuilder<'a> {
+    let damage_type = effects.get("damage_type").map_or(DamageType::Magic, |t| DamageType::from_name(t));
+    for (effect_name, effect_value) in effects.iter() {
+        match effect_name.as_str() {
+            "provides_healing" => if let Some(heal_amount) = parse_amount(effect_name, effect_value) { eb = eb.with(ProvidesHealing{ heal_amount }) },
+            "ranged" => if let Some(range) = parse_amount(effect_name, effect_value) { eb = eb.with(Ranged{ range }) },
+            "damage" => if let Some(damage) = parse_amount(effect_name, effect_value) { eb = eb.with(InflictsDamage{ damage, damage_type }) },
+            "damage_type" => {}
+            "area_of_effect" => if let Some(radius) = parse_amount(effect_name, effect_value) { eb = eb.with(AreaOfEffect{ radius }) },
+            "confusion" => if let Some(turns) = parse_amount(effect_name, effect_value) { eb = eb.with(Confusion{ turns }) },
+            "summon" => {
+                let turns = effects.get("summon_turns").and_then(|t| parse_amount("summon_turns", t)).unwrap_or(0);
+                eb = eb.with(SummonsMinion{ name: effect_value.clone(), turns });
+            }
+            "summon_turns" => {}
+            "charm" => if let Some(turns) = parse_amount(effect_name, effect_value) { eb = eb.with(Charms{ turns }) },
+            "invisibility" => if let Some(turns) = parse_amount(effect_name, effect_value) { eb = eb.with(GrantsInvisibility{ turns }) },
+            "see_invisible" => if let Some(turns) = parse_amount(effect_name, effect_value) { eb = eb.with(GrantsSeeInvisible{ turns }) },
+            "telepathy" => if let Some(turns) = parse_amount(effect_name, effect_value) { eb = eb.with(GrantsTelepathy{ turns }) },
+            "blink" => eb = eb.with(Blink{}),
+            "town_portal" => eb = eb.with(TownPortal{}),
+            "repair" => if let Some(amount) = parse_amount(effect_name, effect_value) { eb = eb.with(Repairs{ amount }) },
+            "food" => if let Some(turns) = parse_amount(effect_name, effect_value) { eb = eb.with(ProvidesFood{ turns }) },
+            "recharge" => eb = eb.with(Recharges{}),
+            "cures" => if let Some(kind) = parse_affliction(effect_value) { eb = eb.with(Cures{ kind }) },
+            "mutate" => {
+                let beneficial = match effect_value.as_str() {
+                    "good" => Some(true),
+                    "bad" => Some(false),
+                    _ => None
+                };
+                eb = eb.with(Mutates{ beneficial });
+            }
+            "script" => eb = eb.with(Scripted{ script: effect_value.clone() }),
+            _ => rltk::console::log(format!("Warning: item effect {} not implemented.", effect_name))
+        }
+    }
+    eb
+}
+
+fn spawn_named_item(raws : &RawMaster, ecs : &mut World, key : &str, x : i32, y : i32) -> Option<Entity> {
+    let item_template = &raws.raws.items[raws.item_index[key]];
+    if item_template.artifact.is_some() {
+        let mut uniques = ecs.write_resource::<UniqueItems>();
+        if uniques.has_spawned(key) { return None; }
+        uniques.spawned.insert(key.to_string());
+    }
+
+    // Until the player has used one, a potion, scroll or wand only goes by what it looks like
+    let disguise = item_template.unidentified_name.as_ref()
+        .filter(|_| !ecs.fetch::<IdentifiedItems>().is_known(key));
+
+    let mut eb = ecs.create_entity().marked::<SimpleMarker<SerializeMe>>();
+    eb = eb.with(Position{ x, y });
+    if let Some(renderable) = &item_template.renderable {
+        eb = eb.with(get_renderable_component(renderable));
+    }
+    match disguise {
+        Some(disguise) => {
+            eb = eb.with(Name{ name : disguise.clone() });
+            eb = eb.with(Unidentified{ name : item_template.name.clone() });
+        }
+        None => eb = eb.with(Name{ name : item_template.name.clone() })
+    }
+    eb = eb.with(Item{});
+
+    if let Some(consumable) = &item_template.consumable {
+        eb = eb.with(Consumable{});
+        eb = with_effects(eb, &consumable.effects);
+    }
+
+    if let Some(wand) = &item_template.wand {
+        eb = eb.with(Charges{ current: wand.charges, max: wand.charges });
+        eb = with_effects(eb, &wand.effects);
+    }
+
+    if let Some(weapon) = &item_template.weapon {
+        eb = eb.with(Equippable{ slot: EquipmentSlot::Melee });
+        eb = eb.with(MeleePowerBonus{ power: weapon.power_bonus });
+        if item_template.artifact.is_none() {
+            let max = weapon.durability.unwrap_or(crate::durability::DEFAULT_DURABILITY);
+            eb = eb.with(Durability{ current: max, max });
+        }
+        if weapon.crit_range.is_some() || weapon.crit_multiplier.is_some() {
+            eb = eb.with(CriticalHit{ range: weapon.crit_range.unwrap_or(0), multiplier: weapon.crit_multiplier.unwrap_or(2) });
+        }
+        if let Some(chance) = weapon.parry_chance {
+            eb = eb.with(ParryChance{ chance });
+        }
+        match weapon.hands.as_deref() {
+            Some("two") => eb = eb.with(TwoHanded{}),
+            Some("off") => eb = eb.with(OffHand{}),
+            _ => {}
+        }
+        if let Some(launcher) = &weapon.launcher {
+            eb = eb.with(Launcher{ ammo: launcher.ammo.clone(), range: launcher.range });
+        }
+        if let Some(special) = &weapon.special {
+            let attack = match special.attack.as_str() {
+                "lunge" => Some(SpecialAttack::Lunge),
+                "cleave" => Some(SpecialAttack::Cleave),
+                "knockback" => Some(SpecialAttack::Knockback),
+                _ => {
+                    rltk::console::log(format!("Warning: weapon special {} not implemented.", special.attack));
+                    None
+                }
+            };
+            if let Some(attack) = attack {
+                eb = eb.with(WeaponSpecial{ attack, stamina_cost: special.stamina_cost });
+            }
+        }
+    }
+
+    if let Some(shield) = &item_template.shield {
+        eb = eb.with(Equippable{ slot: EquipmentSlot::Shield });
+        eb = eb.with(DefenseBonus{ defense: shield.defense_bonus });
+        if item_template.artifact.is_none() {
+            let max = shield.durability.unwrap_or(crate::durability::DEFAULT_DURABILITY);
+            eb = eb.with(Durability{ current: max, max });
+        }
+        if let Some(chance) = shield.block_chance {
+            eb = eb.with(BlockChance{ chance });
+        }
+    }
+
+    if let Some(ammo) = &item_template.ammo {
+        eb = eb.with(Equippable{ slot: EquipmentSlot::Quiver });
+        eb = eb.with(Ammo{ kind: ammo.kind.clone(), damage: ammo.damage, count: ammo.count });
+    }
+
+    if let Some(wearable) = &item_template.wearable {
+        let slot = EquipmentSlot::from_name(&wearable.slot).unwrap_or_else(|| {
+            rltk::console::log(format!("Warning: {} is worn on unknown slot {}, using torso.", item_template.name, wearable.slot));
+            EquipmentSlot::Torso
+        });
+        eb = eb.with(Equippable{ slot });
+        eb = eb.with(DefenseBonus{ defense: wearable.armor_class });
+        if item_template.artifact.is_none() {
+            let max = wearable.durability.unwrap_or(crate::durability::DEFAULT_DURABILITY);
+            eb = eb.with(Durability{ current: max, max });
+        }
+    }
+
+    if let Some(weight) = item_template.weight {
+        eb = eb.with(Weight{ weight });
+    }
+
+    if let Some(thrown) = &item_template.thrown {
+        eb = eb.with(Thrown{ damage: thrown.damage });
+    }
+
+    if let Some(turns) = item_template.starts_fire {
+        eb = eb.with(StartsFire{ turns });
+    }
+
+    if item_template.flammable.unwrap_or(false) {
+        eb = eb.with(Flammable{});
+    }
+
+    if item_template.key.unwrap_or(false) {
+        eb = eb.with(Key{ id: None });
+    }
+
+    if let Some(amount) = item_template.gold {
+        eb = eb.with(Gold{ amount });
+    }
+
+    if item_template.reagent.unwrap_or(false) {
+        eb = eb.with(Reagent{});
+    }
+
+    if let Some(category) = item_template.category.as_deref().and_then(parse_category) {
+        eb = eb.with(Category{ category });
+    }
+
+    if let Some(dot) = &item_template.dot {
+        eb = eb.with(get_dot_component(dot));
+    }
+
+    if let Some(rarity) = &item_template.rarity {
+        eb = eb.with(Rarity{ tier: parse_rarity(rarity) });
+    }
+
+    if let Some(gold) = item_template.value {
+        eb = eb.with(Value{ gold });
+    }
+
+    if let Some(powers) = &item_template.artifact {
+        eb = eb.with(Artifact{});
+        if let Some(amount) = powers.bonus_damage {
+            let element = powers.element.clone().unwrap_or_else(|| "magic".to_string());
+            eb = eb.with(BonusDamage{ amount, element });
+        }
+        if let Some(chance) = powers.confusion_chance {
+            eb = eb.with(ConfuseOnHit{ chance, turns: 3 });
+        }
+        if let Some(amount) = powers.regeneration {
+            eb = eb.with(Regeneration{ amount });
+        }
+    }
+
+    Some(eb.build())
+}
+
+fn spawn_named_prop(raws : &RawMaster, ecs : &mut World, key : &str, x : i32, y : i32) -> Option<Entity> {
+    let prop_template = &raws.raws.props[raws.prop_index[key]];
+
+    let mut eb = ecs.create_entity().marked::<SimpleMarker<SerializeMe>>();
+    eb = eb.with(Position{ x, y });
+    if let Some(renderable) = &prop_template.renderable {
+        eb = eb.with(get_renderable_component(renderable));
+    }
+    eb = eb.with(Name{ name : prop_template.name.clone() });
+    if prop_template.hidden.unwrap_or(false) {
+        eb = eb.with(Hidden{});
+    }
+    if prop_template.blocks_tile.unwrap_or(false) {
+        eb = eb.with(BlocksTile{});
+    }
+    if prop_template.locked.unwrap_or(false) {
+        eb = eb.with(Locked{ key: None });
+    }
+    if prop_template.door.unwrap_or(false) {
+        eb = eb.with(Door{});
+    }
+    if prop_template.blocks_visibility.unwrap_or(false) {
+        eb = eb.with(BlocksVisibility{});
+    }
+    if prop_template.lever.unwrap_or(false) {
+        eb = eb.with(Lever{});
+    }
+    if prop_template.portcullis.unwrap_or(false) {
+        eb = eb.with(Portcullis{});
+    }
+    if prop_template.secret_passage.unwrap_or(false) {
+        eb = eb.with(SecretPassage{});
+    }
+    if prop_template.container.is_some() {
+        eb = eb.with(Container{});
+    }
+    if let Some(interaction) = &prop_template.interact {
+        let mut effects : Vec<PropEffect> = Vec::new();
+        for (effect_name, effect_value) in interaction.effects.iter() {
+            match effect_name.as_str() {
+                "heal" => if let Some(amount) = parse_amount(effect_name, effect_value) { effects.push(PropEffect::Heal(amount)) },
+                "poison" => if let Some(amount) = parse_amount(effect_name, effect_value) { effects.push(PropEffect::Poison(amount)) },
+                "well_fed" => if let Some(amount) = parse_amount(effect_name, effect_value) { effects.push(PropEffect::WellFed(amount)) },
+                "rest" => effects.push(PropEffect::Rest),
+                "bless" => effects.push(PropEffect::Bless),
+                "enchant" => effects.push(PropEffect::Enchant),
+                "repair" => effects.push(PropEffect::Repair),
+                "cure" => if let Some(amount) = parse_amount(effect_name, effect_value) { effects.push(PropEffect::Cure(amount)) },
+                _ => rltk::console::log(format!("Warning: prop effect {} not implemented.", effect_name))
+            }
+        }
+        eb = eb.with(Interactive{ verb: interaction.verb.clone(), effects, uses: interaction.uses });
+    }
+    if let Some(trigger) = &prop_template.entry_trigger {
+        eb = eb.with(EntryTrigger{});
+        for effect_name in trigger.effects.keys() {
+            match effect_name.as_str() {
+                "teleport" => eb = eb.with(TeleportsRandomly{}),
+                "activate" => {} // wired up by the level builder
+                "script" => eb = eb.with(Scripted{ script: trigger.effects[effect_name].clone() }),
+                "afflict" => if let Some(kind) = parse_affliction(&trigger.effects[effect_name]) { eb = eb.with(Afflicts{ kind, chance: 100 }) },
+                _ => rltk::console::log(format!("Warning: prop effect {} not implemented.", effect_name))
+            }
+        }
+    }
+
+    Some(eb.build())
+}
+
+fn spawn_named_mob(raws : &RawMaster, ecs : &mut World, key : &str, x : i32, y : i32) -> Option<Entity> {
+    let mob_template = &raws.raws.mobs[raws.mob_index[key]];
+    // Creatures that belong to a faction only fight the player once it has turned on them
+    let hostile = mob_template.faction.as_ref().map_or(true, |f| faction_reaction(raws, f, &ecs.fetch::<Reputation>()) == Reaction::Hostile);
+    let toughness = if hostile { ecs.fetch::<Difficulty>().monster_percent() } else { 100 };
+
+    let mut eb = ecs.create_entity().marked::<SimpleMarker<SerializeMe>>();
+    eb = eb.with(Position{ x, y });
+    if let Some(renderable) = &mob_template.renderable {
+        eb = eb.with(get_renderable_component(renderable));
+    }
+    eb = eb.with(Name{ name : mob_template.name.clone() });
+    if mob_template.invisible.unwrap_or(false) {
+        eb = eb.with(Invisible{ turns: None });
+    }
+    if mob_template.see_invisible.unwrap_or(false) {
+        eb = eb.with(CanSeeInvisible{ turns: None });
+    }
+    if let Some(afflicts) = &mob_template.afflicts {
+        if let Some(kind) = parse_affliction(&afflicts.kind) {
+            eb = eb.with(Afflicts{ kind, chance: afflicts.chance });
+        }
+    }
+    if mob_template.moves_through_walls.unwrap_or(false) {
+        eb = eb.with(MovesThroughWalls{});
+    }
+    if let Some(radius) = mob_template.territory.or(mob_template.patrol.as_ref().map(|p| p.range)) {
+        eb = eb.with(Territory{ home_x: x, home_y: y, radius });
+    }
+    if mob_template.recruitable.unwrap_or(false) {
+        eb = eb.with(Recruitable{});
+    } else if let Some(vendor) = &mob_template.vendor {
+        eb = eb.with(Vendor{ table: vendor.table.clone(), stock: vendor.stock, restock_turn: 0 });
+    } else if mob_template.quest_giver.unwrap_or(false) {
+        eb = eb.with(QuestGiver{});
+    } else if hostile {
+        eb = eb.with(Monster{});
+    }
+    if let Some(faction) = &mob_template.faction {
+        eb = eb.with(Faction{ name: faction.clone() });
+    }
+    if let Some(dialogue) = &mob_template.dialogue {
+        eb = eb.with(Talks{ dialogue: dialogue.clone() });
+    }
+    if mob_template.blocks_tile {
+        eb = eb.with(BlocksTile{});
+    }
+    eb = eb.with(CombatStats{
+        max_hp : scale(mob_template.stats.max_hp, toughness),
+        hp : scale(mob_template.stats.hp, toughness),
+        power : scale(mob_template.stats.power, toughness),
+        defense : mob_template.stats.defense
+    });
+    eb = eb.with(Viewshed{ visible_tiles : TileSet::default(), range: mob_template.vision_range, dirty: true });
+    let mut attributes = Attributes::default();
+    if let Some(attr) = &mob_template.attributes {
+        attributes.might = attr.might.unwrap_or(attributes.might);
+        attributes.fitness = attr.fitness.unwrap_or(attributes.fitness);
+        attributes.quickness = attr.quickness.unwrap_or(attributes.quickness);
+        attributes.intelligence = attr.intelligence.unwrap_or(attributes.intelligence);
+    }
+    let stamina = crate::gamesystem::stamina_at_fitness(attributes.fitness);
+    let mana = crate::gamesystem::mana_at_intelligence(attributes.intelligence);
+    eb = eb.with(attributes);
+    eb = eb.with(Pools{ stamina: Pool{ current: stamina, max: stamina }, mana: Pool{ current: mana, max: mana }, exerted: false });
+    // Whatever an item user is going to drop, it carries from the start instead
+    if mob_template.carries.is_some() {
+        eb = eb.with(UsesItems{});
+    } else if let Some(loot) = &mob_template.loot_table {
+        eb = eb.with(LootTable{ table: loot.clone() });
+    }
+    if let Some(dot) = &mob_template.dot {
+        eb = eb.with(get_dot_component(dot));
+    }
+    if let Some(abilities) = &mob_template.abilities {
+        eb = eb.with(SpecialAbilities{ abilities: parse_abilities(abilities) });
+    }
+    if let Some(boss) = &mob_template.boss {
+        let phases = boss.phases.iter().map(|phase| BossPhase{
+            hp_percent : phase.hp_percent,
+            message : phase.message.clone(),
+            abilities : phase.abilities.as_deref().map(parse_abilities),
+            summon : phase.summon.clone(),
+            summon_count : phase.summon_count.unwrap_or(1),
+            arena : phase.arena.clone(),
+            script : phase.script.clone()
+        }).collect();
+        eb = eb.with(Boss{ phases, phase: 0, loot: boss.loot.clone() });
+    }
+    if mob_template.resistances.is_some() || mob_template.vulnerabilities.is_some() {
+        let mut percent = HashMap::new();
+        for (kind, amount) in mob_template.resistances.iter().flatten() {
+            percent.insert(DamageType::from_name(kind), *amount);
+        }
+        for (kind, amount) in mob_template.vulnerabilities.iter().flatten() {
+            *percent.entry(DamageType::from_name(kind)).or_insert(0) -= *amount;
+        }
+        eb = eb.with(Resistances{ percent });
+    }
+
+    Some(eb.build())
+}
+
+/// Stocks a freshly spawned container from its prop's loot table.
+pub fn fill_container(raws : &RawMaster, ecs : &mut World, container : Entity, depth : i32) {
+    let key = match ecs.read_storage::<Name>().get(container) {
+        Some(name) => name.name.clone(),
+        None => return
+    };
+    let stock = match raws.prop_index.get(&key).and_then(|idx| raws.raws.props[*idx].container.as_ref()) {
+        Some(stock) => stock,
+        None => return
+    };
+
+    let mut contents : Vec<String> = Vec::new();
+    {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        for _i in 0..stock.rolls {
+            if let Some(item) = get_item_drop(raws, &mut rng, &stock.loot_table, depth) {
+                contents.push(item);
+            }
+        }
+    }
+    for name in contents.iter() {
+        if let Some(item) = spawn_named_item(raws, ecs, name, 0, 0) {
+            ecs.write_storage::<Position>().remove(item);
+            ecs.write_storage::<InContainer>().insert(item, InContainer{ container }).expect("Unable to insert container entry");
+            super::roll_affixes(raws, ecs, item, depth);
+        }
+    }
+}
+
+/// Packs a creature that uses items with `carries` rolls on its loot table, which it keeps in its
+/// backpack until it uses them or dies.
+pub fn fill_pack(raws : &RawMaster, ecs : &mut World, mob : Entity, depth : i32) {
+    let key = match ecs.read_storage::<Name>().get(mob) {
+        Some(name) => name.name.clone(),
+        None => return
+    };
+    let (table, rolls) = match get_mob(raws, &key).and_then(|m| m.loot_table.as_ref().zip(m.carries)) {
+        Some(stock) => stock,
+        None => return
+    };
+
+    let mut contents : Vec<String> = Vec::new();
+    {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        for _i in 0..rolls {
+            if let Some(item) = get_item_drop(raws, &mut rng, table, depth) {
+                contents.push(item);
+            }
+        }
+    }
+    for name in contents.iter() {
+        if let Some(item) = spawn_in_backpack(raws, ecs, name, mob) {
+            super::roll_affixes(raws, ecs, item, depth);
+        }
+    }
+}
+
+/// Replaces a shopkeeper's wares with a fresh roll from their stock table.
+pub fn stock_vendor(raws : &RawMaster, ecs : &mut World, vendor : Entity, depth : i32) {
+    let (table, stock) = match ecs.read_storage::<Vendor>().get(vendor) {
+        Some(v) => (v.table.clone(), v.stock),
+        None => return
+    };
+
+    let old_stock : Vec<Entity> = (&ecs.entities(), &ecs.read_storage::<InBackpack>()).join()
+        .filter(|(_e, pack)| pack.owner == vendor)
+        .map(|(e, _pack)| e)
+        .collect();
+    for item in old_stock.iter() {
+        ecs.delete_entity(*item).expect("Unable to delete old stock");
+    }
+
+    let mut wares : Vec<String> = Vec::new();
+    {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        for _i in 0..stock {
+            if let Some(item) = get_item_drop(raws, &mut rng, &table, depth) {
+                wares.push(item);
+            }
+        }
+    }
+    for name in wares.iter() {
+        if let Some(item) = spawn_named_item(raws, ecs, name, 0, 0) {
+            ecs.write_storage::<Position>().remove(item);
+            ecs.write_storage::<InBackpack>().insert(item, InBackpack{ owner: vendor }).expect("Unable to insert backpack entry");
+        }
+    }
+}
+
+/// Spawns a named item straight into the owner's backpack.
+pub fn spawn_in_backpack(raws : &RawMaster, ecs : &mut World, key : &str, owner : Entity) -> Option<Entity> {
+    let item = spawn_named_item(raws, ecs, key, 0, 0)?;
+    ecs.write_storage::<Position>().remove(item);
+    ecs.write_storage::<InBackpack>().insert(item, InBackpack{ owner }).expect("Unable to insert backpack entry");
+    Some(item)
+}
+
+/// Every quest the named townsperson hands out, in the order they appear in the raws.
+pub fn quests_from<'a>(raws : &'a RawMaster, giver : &str) -> Vec<&'a Quest> {
+    raws.raws.quests.iter().filter(|q| q.giver == giver).collect()
+}
+
+/// Every item with an appearance to hide behind, as (appearance, real name) pairs.
+pub fn disguised_items(raws : &RawMaster) -> Vec<(String, String)> {
+    raws.raws.items.iter().filter_map(|item| item.unidentified_name.as_ref().map(|disguise| (disguise.clone(), item.name.clone()))).collect()
+}
+
+/// The raws entry for a creature, by name.
+pub fn get_mob<'a>(raws : &'a RawMaster, name : &str) -> Option<&'a Mob> {
+    raws.mob_index.get(name).map(|i| &raws.raws.mobs[*i])
+}
+
+pub fn get_quest<'a>(raws : &'a RawMaster, name : &str) -> Option<&'a Quest> {
+    raws.raws.quests.iter().find(|q| q.name == name)
+}
+
+/// A node of the named conversation, by its position in the raws.
+pub fn dialogue_node<'a>(raws : &'a RawMaster, dialogue : &str, node : usize) -> Option<&'a DialogueNode> {
+    raws.raws.dialogues.iter().find(|d| d.name == dialogue).and_then(|d| d.nodes.get(node))
+}
+
+/// Where the node with the given id sits within the named conversation.
+pub fn dialogue_node_index(raws : &RawMaster, dialogue : &str, id : &str) -> Option<usize> {
+    raws.raws.dialogues.iter().find(|d| d.name == dialogue).and_then(|d| d.nodes.iter().position(|n| n.id == id))
+}
+
+pub fn get_faction<'a>(raws : &'a RawMaster, name : &str) -> Option<&'a FactionInfo> {
+    raws.raws.factions.iter().find(|f| f.name == name)
+}
+
+/// How members of the faction treat the player, given the player's standing with it.
+pub fn faction_reaction(raws : &RawMaster, name : &str, reputation : &Reputation) -> Reaction {
+    let faction = match get_faction(raws, name) {
+        Some(faction) => faction,
+        None => return Reaction::Friendly
+    };
+    let standing = reputation.standing(name);
+    if faction.hostile_below.map_or(false, |limit| standing < limit) {
+        Reaction::Hostile
+    } else if faction.refuses_trade_below.map_or(false, |limit| standing < limit) {
+        Reaction::Unwelcome
+    } else {
+        Reaction::Friendly
+    }
+}
+
+/// Names and blurbs of every playable class, in the order they appear in the raws.
+pub fn class_list(raws : &RawMaster) -> Vec<(String, String)> {
+    raws.raws.classes.iter().map(|c| (c.name.clone(), c.description.clone())).collect()
+}
+
+/// Gives a new character the stats, skills and starting kit of their class. Fails, leaving the
+/// character untouched, if there is no such class.
+pub fn apply_class(raws : &RawMaster, ecs : &mut World, player : Entity, key : &str) -> Result<(), String> {
+    let class = match raws.class_index.get(key) {
+        Some(idx) => &raws.raws.classes[*idx],
+        None => return Err(format!("There is no {} class.", key))
+    };
+    let attributes = Attributes{
+        might: class.attributes.might,
+        fitness: class.attributes.fitness,
+        quickness: class.attributes.quickness,
+        intelligence: class.attributes.intelligence
+    };
+    let stamina = crate::gamesystem::stamina_at_fitness(attributes.fitness);
+    let mana = crate::gamesystem::mana_at_intelligence(attributes.intelligence);
+    ecs.write_storage::<CombatStats>().insert(player, CombatStats{ max_hp: class.hp, hp: class.hp, defense: class.defense, power: class.power })
+        .expect("Unable to insert stats");
+    ecs.write_storage::<Attributes>().insert(player, attributes).expect("Unable to insert attributes");
+    ecs.write_storage::<Pools>().insert(player, Pools{ stamina: Pool{ current: stamina, max: stamina }, mana: Pool{ current: mana, max: mana }, exerted: false })
+        .expect("Unable to insert pools");
+    ecs.write_storage::<Skills>().insert(player, Skills{
+        melee: class.skills.melee.unwrap_or(0),
+        lockpicking: class.skills.lockpicking.unwrap_or(0),
+        magic: class.skills.magic.unwrap_or(0),
+        stealth: class.skills.stealth.unwrap_or(0),
+        crafting: class.skills.crafting.unwrap_or(0)
+    }).expect("Unable to insert skills");
+    ecs.write_storage::<SkillTraining>().insert(player, SkillTraining::default()).expect("Unable to insert skill training");
+    ecs.write_storage::<Purse>().insert(player, Purse{ gold: class.gold.unwrap_or(0) }).expect("Unable to insert purse");
+
+    // Gear that can't be worn goes in the pack instead, and gear that doesn't exist is left behind
+    for (name, worn) in class.equipment.iter().map(|n| (n, true)).chain(class.inventory.iter().map(|n| (n, false))) {
+        if !raws.item_index.contains_key(name) {
+            rltk::console::log(format!("Warning: class {} starts with unknown item {}.", key, name));
+            continue;
+        }
+        if let Some(item) = spawn_named_item(raws, ecs, name, 0, 0) {
+            ecs.write_storage::<Position>().remove(item);
+            let slot = ecs.read_storage::<Equippable>().get(item).map(|e| e.slot);
+            match slot {
+                Some(slot) if worn => {
+                    ecs.write_storage::<Equipped>().insert(item, Equipped{ owner: player, slot }).expect("Unable to equip starting gear");
+                }
+                _ => {
+                    if worn { rltk::console::log(format!("Warning: class {} starts wearing {}, which can't be equipped.", key, name)); }
+                    ecs.write_storage::<InBackpack>().insert(item, InBackpack{ owner: player }).expect("Unable to insert backpack entry");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Rolls on a loot table, only considering drops allowed at this depth.
+pub fn get_item_drop(raws : &RawMaster, rng : &mut RandomNumberGenerator, table : &str, depth : i32) -> Option<String> {
+    let loot_table = &raws.raws.loot_tables[*raws.loot_index.get(table)?];
+
+    let mut rt = RandomTable::new();
+    for drop in loot_table.drops.iter() {
+        if depth >= drop.min_depth.unwrap_or(0) && depth <= drop.max_depth.unwrap_or(i32::MAX) {
+            rt = rt.add(drop.name.clone(), drop.weight);
+        }
+    }
+
+    let result = rt.roll(rng);
+    if result == "None" { None } else { Some(result) }
+}
+
+/// Applies the spawn weights given in the raws, which mods use to rebalance the built-in tables.
+pub fn apply_spawn_weights(raws : &RawMaster, table : RandomTable, branch : Branch, depth : i32) -> RandomTable {
+    let mut table = table;
+    for spawn in raws.raws.spawn_table.iter() {
+        if spawn.branch.as_ref().map_or(true, |b| b.eq_ignore_ascii_case(branch.name())) {
+            table = table.set(spawn.name.clone(), spawn.weight + spawn.per_depth.unwrap_or(0) * depth);
+        }
+    }
+    table
+}
+
+/// Adds every artifact that could still appear at this depth to a spawn table.
+pub fn add_artifacts(raws : &RawMaster, ecs : &World, table : RandomTable, depth : i32) -> RandomTable {
+    let uniques = ecs.fetch::<UniqueItems>();
+    let mut table = table;
+    for item in raws.raws.items.iter() {
+        if let Some(powers) = &item.artifact {
+            if powers.min_depth <= depth && !uniques.has_spawned(&item.name) {
+                table = table.add(item.name.clone(), powers.weight);
+            }
+        }
+    }
+    table
+}
@@ -0,0 +1,11 @@
+use serde::Deserialize;
+
+/// Overrides how often something turns up in the spawn tables: `weight` plus `per_depth` for every
+/// level down, on every branch or only the one named. A weight of zero or less takes it out.
+#[derive(Deserialize, Debug)]
+pub struct SpawnWeight {
+    pub name : String,
+    pub weight : i32,
+    pub per_depth : Option<i32>,
+    pub branch : Option<String>
+}
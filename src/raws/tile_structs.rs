@@ -0,0 +1,8 @@
+use serde::Deserialize;
+
+/// Swaps a glyph for a sprite from the tileset when tile graphics are switched on.
+#[derive(Deserialize, Debug)]
+pub struct TileMapping {
+    pub glyph : String,
+    pub tile : u16
+}
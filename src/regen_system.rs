@@ -0,0 +1,80 @@
+use specs::prelude::*;
+use super::{CombatStats, Attributes, Viewshed, Position, Monster, Companion, OwnedBy, Pools, SufferDamage, DamageOverTime,
+            WellFed, HungerClock, HungerState, FastHealing, Equipped, Regeneration, RunState, gamesystem::attr_bonus, player::hostile_in_view, turn_counter::TurnCounter};
+
+/// Turns between points of natural healing for someone of average Fitness.
+const BASE_REGEN_INTERVAL : i32 = 10;
+const MIN_REGEN_INTERVAL : i32 = 2;
+
+/// Slowly heals the player while out of combat. Fitness and the Fast Healing perk shorten the wait,
+/// a good meal and regenerating gear heal more each time, and poison or bleeding stop it altogether.
+/// Mana trickles back regardless, sooner for a higher Intelligence. Going hungry makes both wait
+/// twice as long, and a starving body recovers neither.
+pub struct RegenerationSystem {}
+
+impl<'a> System<'a> for RegenerationSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, TurnCounter>,
+                        WriteStorage<'a, CombatStats>,
+                        ReadStorage<'a, Attributes>,
+                        ReadStorage<'a, Viewshed>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Monster>,
+                        ReadStorage<'a, Companion>,
+                        ReadStorage<'a, OwnedBy>,
+                        WriteStorage<'a, Pools>,
+                        ReadStorage<'a, SufferDamage>,
+                        ReadStorage<'a, DamageOverTime>,
+                        ReadStorage<'a, WellFed>,
+                        ReadStorage<'a, HungerClock>,
+                        ReadStorage<'a, FastHealing>,
+                        ReadStorage<'a, Equipped>,
+                        ReadStorage<'a, Regeneration> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, runstate, player_entity, turns, mut combat_stats, attributes, viewsheds, positions, monsters,
+            companions, owned, mut pools, suffer_damage, damage_over_time, well_fed, hunger, fast_healing, equipped, regeneration) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        let player = *player_entity;
+        let slowdown = match hunger.get(player).map_or(HungerState::Normal, |h| h.state) {
+            HungerState::WellFed | HungerState::Normal => 1,
+            HungerState::Hungry => 2,
+            HungerState::Starving => return
+        };
+        let intelligence = attr_bonus(attributes.get(player).map_or(10, |a| a.intelligence));
+        let mana_interval = i32::max(MIN_REGEN_INTERVAL, BASE_REGEN_INTERVAL - intelligence * 2) * slowdown;
+        if turns.turn % mana_interval == 0 {
+            if let Some(pools) = pools.get_mut(player) {
+                pools.mana.current = i32::min(pools.mana.max, pools.mana.current + 1);
+            }
+        }
+
+        // No healing while hurt, fighting or in sight of an enemy
+        if suffer_damage.get(player).is_some() || damage_over_time.get(player).is_some() { return; }
+        if pools.get(player).map_or(false, |p| p.exerted) { return; }
+        if let Some(viewshed) = viewsheds.get(player) {
+            if hostile_in_view(player, viewshed, &entities, &monsters, &companions, &owned, &positions) { return; }
+        }
+
+        let fitness = attr_bonus(attributes.get(player).map_or(10, |a| a.fitness));
+        let mut interval = BASE_REGEN_INTERVAL - fitness * 2;
+        if fast_healing.get(player).is_some() { interval /= 2; }
+        let interval = i32::max(MIN_REGEN_INTERVAL, interval) * slowdown;
+        if turns.turn % interval != 0 { return; }
+
+        let mut amount = 1;
+        if well_fed.get(player).is_some() { amount += 1; }
+        for (worn, regen) in (&equipped, &regeneration).join() {
+            if worn.owner == player { amount += regen.amount; }
+        }
+
+        if let Some(stats) = combat_stats.get_mut(player) {
+            stats.hp = i32::min(stats.max_hp, stats.hp + amount);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use specs::prelude::*;
+use rltk::RGB;
+use super::{Deity, Faith, CombatStats, DamageOverTime, Monster, OwnedBy, Position, Viewshed, DamageType, RunState,
+    gamelog::{GameLog, LogCategory}, turn_counter::TurnCounter, effects::{EffectQueue, EffectType, Targets}};
+
+pub const KILL_PIETY : i32 = 1;
+pub const SACRIFICE_PIETY : i32 = 5;
+const BOON_PIETY : i32 = 30;
+const PRAYER_COOLDOWN : i32 = 200;
+const IMPATIENCE_PENALTY : i32 = 10;
+const SMITE_DAMAGE : i32 = 10;
+
+impl Deity {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Deity::Tyrus => "Tyrus",
+            Deity::Mirel => "Mirel"
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Deity::Tyrus => "Tyrus, Lord of Battle",
+            Deity::Mirel => "Mirel, the Gentle Hand"
+        }
+    }
+
+    /// What the god asks of its followers, shown when choosing one.
+    pub fn description(&self) -> &'static str {
+        match self {
+            Deity::Tyrus => "Rewards every kill; smites your foes when pleased.",
+            Deity::Mirel => "Prizes sacrifices twice as much; heals you when pleased."
+        }
+    }
+}
+
+/// A word for how pleased the god is, for the character sheet.
+pub fn piety_rating(piety : i32) -> &'static str {
+    match piety {
+        p if p < 0 => "Angered",
+        p if p < BOON_PIETY / 2 => "Indifferent",
+        p if p < BOON_PIETY => "Pleased",
+        _ => "Favoured"
+    }
+}
+
+/// Raises (or lowers) the follower's standing with their god, if they have one.
+pub fn add_piety(ecs : &World, follower : Entity, amount : i32) {
+    if let Some(faith) = ecs.write_storage::<Faith>().get_mut(follower) {
+        faith.piety += amount;
+    }
+}
+
+/// Sacrificing an item is worth double to Mirel.
+pub fn sacrifice_value(deity : Deity) -> i32 {
+    match deity {
+        Deity::Tyrus => SACRIFICE_PIETY,
+        Deity::Mirel => SACRIFICE_PIETY * 2
+    }
+}
+
+/// The player asks their god for help. A well-disposed god grants a boon and spends
+/// the piety; an angry one punishes; praying too often only annoys.
+pub fn pray(ecs : &mut World) -> RunState {
+    let player_entity = *ecs.fetch::<Entity>();
+    let turn = ecs.fetch::<TurnCounter>().turn;
+    let faith = match ecs.read_storage::<Faith>().get(player_entity) {
+        Some(faith) => faith.clone(),
+        None => {
+            ecs.write_resource::<GameLog>().push(LogCategory::System, "You have no god to pray to.".to_string());
+            return RunState::AwaitingInput;
+        }
+    };
+    let god = faith.deity.name();
+    if let Some(faith) = ecs.write_storage::<Faith>().get_mut(player_entity) {
+        faith.last_prayer = Some(turn);
+    }
+
+    if faith.last_prayer.map_or(false, |last| turn - last < PRAYER_COOLDOWN) {
+        add_piety(ecs, player_entity, -IMPATIENCE_PENALTY);
+        ecs.write_resource::<GameLog>().push(LogCategory::System, format!("{} is annoyed by your impatience.", god));
+    } else if faith.piety < 0 {
+        let damage = 5 - faith.piety / 5;
+        ecs.write_resource::<GameLog>().push_colored(LogCategory::System, format!("{} punishes your faithlessness!", god), RGB::named(rltk::RED));
+        ecs.write_resource::<EffectQueue>().add_effect(None, EffectType::Damage{ amount: damage, kind: DamageType::Magic }, Targets::Single{ target: player_entity });
+    } else if faith.piety >= BOON_PIETY {
+        add_piety(ecs, player_entity, -BOON_PIETY);
+        match faith.deity {
+            Deity::Tyrus => smite(ecs, player_entity),
+            Deity::Mirel => {
+                if let Some(stats) = ecs.write_storage::<CombatStats>().get_mut(player_entity) {
+                    stats.hp = stats.max_hp;
+                }
+                ecs.write_storage::<DamageOverTime>().remove(player_entity);
+                ecs.write_resource::<GameLog>().push_colored(LogCategory::System, "Mirel's warmth washes over you, closing every wound.".to_string(), RGB::named(rltk::GOLD));
+            }
+        }
+    } else {
+        ecs.write_resource::<GameLog>().push(LogCategory::System, "Your prayer goes unanswered.".to_string());
+    }
+    RunState::PlayerTurn
+}
+
+/// Lightning strikes every hostile monster the player can see.
+fn smite(ecs : &mut World, player_entity : Entity) {
+    let targets : Vec<Entity> = {
+        let entities = ecs.entities();
+        let monsters = ecs.read_storage::<Monster>();
+        let owned = ecs.read_storage::<OwnedBy>();
+        let positions = ecs.read_storage::<Position>();
+        let viewsheds = ecs.read_storage::<Viewshed>();
+        let visible = match viewsheds.get(player_entity) {
+            Some(viewshed) => &viewshed.visible_tiles,
+            None => return
+        };
+        (&entities, &monsters, &positions).join()
+            .filter(|(e, _m, _p)| owned.get(*e).map_or(true, |o| o.owner != player_entity))
+            .filter(|(_e, _m, p)| visible.contains(&rltk::Point::new(p.x, p.y)))
+            .map(|(e, _m, _p)| e)
+            .collect()
+    };
+
+    ecs.write_resource::<GameLog>().push_colored(LogCategory::System, "Tyrus hurls lightning at your enemies!".to_string(), RGB::named(rltk::GOLD));
+    let mut queue = ecs.write_resource::<EffectQueue>();
+    for target in targets {
+        queue.add_effect(Some(player_entity), EffectType::Damage{ amount: SMITE_DAMAGE, kind: DamageType::Magic }, Targets::Single{ target });
+    }
+}
@@ -0,0 +1,161 @@
+use rltk::VirtualKeyCode;
+use serde::{Serialize, Deserialize};
+use super::{challenge::Challenge, input::{Input, key_from_code}, storage};
+
+/// The speeds playback can run at, in game ticks per frame.
+const SPEEDS : [usize; 5] = [1, 2, 4, 8, 16];
+
+/// The input the player gave on one tick. Ticks where they did nothing aren't stored.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InputFrame {
+    pub tick : u64,
+    pub key : Option<u32>,
+    pub shift : bool,
+    pub control : bool,
+    pub mouse : (i32, i32),
+    pub click : bool
+}
+
+/// Everything needed to play a run again: the seed it started from, whether it was a daily
+/// challenge, and what the player pressed when.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Recording {
+    pub seed : u64,
+    pub challenge : Challenge,
+    pub frames : Vec<InputFrame>
+}
+
+impl Recording {
+    /// Reads back the last finished run from replay.json.
+    pub fn load() -> Option<Recording> {
+        let data = storage::read("replay.json")?;
+        serde_json::from_str(&data).ok()
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let data = serde_json::to_string(self).expect("Unable to serialize replay");
+        storage::write("replay.json", &data)
+    }
+}
+
+pub fn does_replay_exist() -> bool {
+    storage::exists("replay.json")
+}
+
+#[derive(PartialEq, Copy, Clone)]
+enum Mode { Idle, Recording, Playing { next : usize, speed : usize, paused : bool } }
+
+/// Records the current run's input, or feeds a recorded run back in place of the player's. The game
+/// has to do exactly the same thing given the same seed and input for this to work, so nothing that
+/// changes the world may depend on the clock or anything else outside the game.
+pub struct Replay {
+    recording : Recording,
+    mode : Mode,
+    tick : u64
+}
+
+impl Default for Replay {
+    fn default() -> Replay {
+        Replay{ recording: Recording::default(), mode: Mode::Idle, tick: 0 }
+    }
+}
+
+/// What the player asked for with the playback controls this frame.
+pub enum Playback {
+    /// Run this many game ticks.
+    Run(usize),
+    Stop
+}
+
+impl Replay {
+    /// Starts recording a new run that begins from `seed`.
+    pub fn record(&mut self, seed : u64, challenge : Challenge) {
+        self.recording = Recording{ seed, challenge, frames: Vec::new() };
+        self.mode = Mode::Recording;
+        self.tick = 0;
+    }
+
+    /// Saves the recording of a run that has just ended.
+    pub fn finish(&mut self) -> Result<(), String> {
+        if self.mode != Mode::Recording { return Ok(()); }
+        self.mode = Mode::Idle;
+        self.recording.save()
+    }
+
+    /// Starts playing a recording back from its first tick.
+    pub fn play(&mut self, recording : Recording) {
+        self.recording = recording;
+        self.mode = Mode::Playing{ next: 0, speed: 0, paused: false };
+        self.tick = 0;
+    }
+
+    /// Stops recording or playing, throwing away whatever hasn't been saved.
+    pub fn stop(&mut self) {
+        self.mode = Mode::Idle;
+    }
+
+    pub fn is_playing(&self) -> bool {
+        matches!(self.mode, Mode::Playing{ .. })
+    }
+
+    /// Handles the keys that steer playback: + and - change the speed, SPACE pauses and ESCAPE
+    /// stops. Outside playback there's always exactly one tick to run.
+    pub fn controls(&mut self, ctx : &Input) -> Playback {
+        let (speed, paused) = match &mut self.mode {
+            Mode::Playing{ speed, paused, .. } => (speed, paused),
+            _ => return Playback::Run(1)
+        };
+        match ctx.key {
+            Some(VirtualKeyCode::Escape) => return Playback::Stop,
+            Some(VirtualKeyCode::Space) => *paused = !*paused,
+            Some(VirtualKeyCode::Equals) | Some(VirtualKeyCode::Add) => *speed = usize::min(*speed + 1, SPEEDS.len() - 1),
+            Some(VirtualKeyCode::Minus) | Some(VirtualKeyCode::Subtract) => *speed = speed.saturating_sub(1),
+            _ => {}
+        }
+        if *paused { Playback::Run(0) } else { Playback::Run(SPEEDS[*speed]) }
+    }
+
+    /// Called at the start of every game tick. While recording, notes down the player's input; while
+    /// playing, replaces it with what was recorded for this tick.
+    pub fn capture(&mut self, ctx : &mut Input) {
+        match &mut self.mode {
+            Mode::Idle => {}
+            Mode::Recording => {
+                let key = ctx.key.and_then(|key| key_from_code(key as u32)).map(|key| key as u32);
+                if key.is_some() || ctx.left_click {
+                    self.recording.frames.push(InputFrame{
+                        tick: self.tick, key, shift: ctx.shift, control: ctx.control,
+                        mouse: ctx.mouse_pos, click: ctx.left_click
+                    });
+                }
+            }
+            Mode::Playing{ next, .. } => {
+                ctx.key = None;
+                ctx.shift = false;
+                ctx.control = false;
+                ctx.left_click = false;
+                if let Some(frame) = self.recording.frames.get(*next).filter(|f| f.tick == self.tick) {
+                    ctx.key = frame.key.and_then(key_from_code);
+                    ctx.shift = frame.shift;
+                    ctx.control = frame.control;
+                    ctx.left_click = frame.click;
+                    ctx.mouse_pos = frame.mouse;
+                    *next += 1;
+                }
+            }
+        }
+        self.tick += 1;
+    }
+
+    /// A line saying how playback is going, if it is.
+    pub fn status(&self) -> Option<String> {
+        match self.mode {
+            Mode::Playing{ speed, paused, next } => {
+                let state = if paused { "paused".to_string() } else { format!("{}x", SPEEDS[speed]) };
+                let done = if next >= self.recording.frames.len() { ", finished" } else { "" };
+                Some(format!("Replay {}{} (+/- speed, SPACE pause, ESCAPE stop)", state, done))
+            }
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,147 @@
+use specs::prelude::*;
+use specs::shrev::ReaderId;
+use rltk::RandomNumberGenerator;
+use super::{SpecialAbilities, AbilityKind, CombatStats, Monster, Name, Position, OwnedBy, BlocksTile, RunState, Map, tile_walkable,
+            events::{GameEvents, GameEvent}, gamelog::{GameLog, LogCategory}, raws};
+
+/// However much they split or breed, no more than this many of one kind share a level.
+const BROOD_LIMIT : usize = 10;
+
+/// A newborn waiting to be placed beside its parent.
+pub struct Birth {
+    pub parent : Entity,
+    pub offspring : String,
+    /// Split off from the parent, taking half its hit points, rather than bred.
+    pub split : bool
+}
+
+/// Creatures that split or bred this turn, to be brought into the world once the systems are done.
+#[derive(Default)]
+pub struct Births {
+    pub pending : Vec<Birth>
+}
+
+/// Splits creatures that took a heavy enough blow, and lets breeders breed once a turn.
+#[derive(Default)]
+pub struct ReproductionSystem {
+    reader : Option<ReaderId<GameEvent>>
+}
+
+impl<'a> System<'a> for ReproductionSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, GameEvents>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteExpect<'a, Births>,
+                        ReadStorage<'a, SpecialAbilities>,
+                        ReadStorage<'a, CombatStats>,
+                        ReadStorage<'a, Monster>,
+                        ReadStorage<'a, OwnedBy>,
+                        ReadStorage<'a, Name> );
+
+    fn setup(&mut self, world : &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(world.write_resource::<GameEvents>().register_reader());
+    }
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, events, runstate, player_entity, mut rng, mut births, abilities, stats, monsters, owned, names) = data;
+
+        let offspring_of = |entity : Entity, summon : &Option<String>| -> Option<String> {
+            summon.clone().or_else(|| names.get(entity).map(|n| n.name.clone()))
+        };
+
+        // A hard enough blow that doesn't kill splits the creature in two
+        for event in events.read(self.reader.as_mut().expect("ReproductionSystem was not set up")) {
+            if let GameEvent::Damaged{ target, amount, .. } = event {
+                if stats.get(*target).map_or(true, |s| s.hp < 2) { continue; }
+                let split = abilities.get(*target)
+                    .and_then(|a| a.abilities.iter().find(|a| a.kind == AbilityKind::Split && *amount >= a.power));
+                if let Some(split) = split {
+                    if rng.roll_dice(1, 100) > split.chance { continue; }
+                    if let Some(offspring) = offspring_of(*target, &split.summon) {
+                        births.pending.push(Birth{ parent: *target, offspring, split: true });
+                    }
+                }
+            }
+        }
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        // Breeders breed whether or not anyone is watching
+        for (entity, abilities, _monster) in (&entities, &abilities, &monsters).join() {
+            if owned.get(entity).map_or(false, |o| o.owner == *player_entity) { continue; }
+            for breed in abilities.abilities.iter().filter(|a| a.kind == AbilityKind::Breed) {
+                if rng.roll_dice(1, 100) > breed.chance { continue; }
+                if let Some(offspring) = offspring_of(entity, &breed.summon) {
+                    births.pending.push(Birth{ parent: entity, offspring, split: false });
+                }
+            }
+        }
+    }
+}
+
+/// Places this turn's newborns on a free tile next to their parents, while there's room for more
+/// of their kind on the level.
+pub fn bring_forth(ecs : &mut World) {
+    let pending : Vec<Birth> = ecs.write_resource::<Births>().pending.drain(..).collect();
+    for birth in pending {
+        let parent_hp = ecs.read_storage::<CombatStats>().get(birth.parent).map_or(0, |s| s.hp);
+        let parent_pos = ecs.read_storage::<Position>().get(birth.parent).map(|p| (p.x, p.y));
+        let (x, y) = match parent_pos {
+            Some(pos) if parent_hp > 0 && (!birth.split || parent_hp > 1) => pos,
+            _ => continue
+        };
+
+        let brood = (&ecs.read_storage::<Name>(), &ecs.read_storage::<Position>()).join()
+            .filter(|(name, _pos)| name.name == birth.offspring)
+            .count();
+        if brood >= BROOD_LIMIT { continue; }
+
+        let tile = {
+            let map = ecs.fetch::<Map>();
+            let mut free : Vec<usize> = Vec::new();
+            for dy in -1 ..= 1 {
+                for dx in -1 ..= 1 {
+                    let (tx, ty) = (x + dx, y + dy);
+                    if tx < 1 || ty < 1 || tx > map.width - 2 || ty > map.height - 2 { continue; }
+                    let idx = map.xy_idx(tx, ty);
+                    if tile_walkable(map.tiles[idx]) && !map.is_blocked(idx) { free.push(idx); }
+                }
+            }
+            if free.is_empty() { continue; }
+            let roll = ecs.write_resource::<RandomNumberGenerator>().roll_dice(1, free.len() as i32);
+            free[(roll - 1) as usize]
+        };
+
+        let width = ecs.fetch::<Map>().width;
+        let child = match raws::spawn_named_entity(&raws::raws(), ecs, &birth.offspring, tile as i32 % width, tile as i32 / width) {
+            Some(child) => child,
+            None => continue
+        };
+        let blocks = ecs.read_storage::<BlocksTile>().get(child).is_some();
+        ecs.write_resource::<Map>().index_entity(child, tile, blocks);
+
+        // What splits off takes half the parent's wounds with it, and is no bigger than what it took
+        if birth.split {
+            let mut stats = ecs.write_storage::<CombatStats>();
+            if let Some(parent) = stats.get_mut(birth.parent) { parent.hp -= parent_hp / 2; }
+            if let Some(child) = stats.get_mut(child) {
+                child.hp = parent_hp / 2;
+                child.max_hp = parent_hp / 2;
+            }
+        }
+
+        let seen = {
+            let map = ecs.fetch::<Map>();
+            map.visible_tiles[map.xy_idx(x, y)]
+        };
+        if seen {
+            let parent_name = ecs.read_storage::<Name>().get(birth.parent).map_or(birth.offspring.clone(), |n| n.name.clone());
+            let message = if birth.split { format!("The {} splits in two!", parent_name) } else { format!("The {} spawns another {}!", parent_name, birth.offspring) };
+            ecs.write_resource::<GameLog>().push(LogCategory::Combat, message);
+        }
+    }
+}
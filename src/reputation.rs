@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use super::raws;
+
+/// Standing lost with a faction, and its allies, every time the player harms one of its members.
+const OFFENCE_PENALTY : i32 = 10;
+/// The side charmed creatures fight on for as long as the charm lasts.
+pub const PLAYER_FACTION : &str = "Player";
+
+/// How a faction's members treat the player.
+#[derive(PartialEq, Copy, Clone, Debug)]
+pub enum Reaction { Friendly, Unwelcome, Hostile }
+
+/// The player's standing with each faction this run. Everyone starts out at zero.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Reputation {
+    pub standing : HashMap<String, i32>
+}
+
+impl Reputation {
+    pub fn standing(&self, faction : &str) -> i32 {
+        self.standing.get(faction).copied().unwrap_or(0)
+    }
+
+    fn adjust(&mut self, faction : &str, amount : i32) {
+        *self.standing.entry(faction.to_string()).or_insert(0) += amount;
+    }
+
+    /// The player has hurt a member of the faction: it, and everyone allied to it, think less of them.
+    /// Returns the names of factions whose reaction changed because of it.
+    pub fn offend(&mut self, faction : &str) -> Vec<String> {
+        // The player can't fall out with their own side
+        if faction == PLAYER_FACTION { return Vec::new(); }
+        let raws = raws::raws();
+        let mut offended = vec![faction.to_string()];
+        if let Some(allies) = raws::get_faction(&raws, faction).and_then(|f| f.allies.as_ref()) {
+            offended.extend(allies.iter().cloned());
+        }
+
+        let mut changed = Vec::new();
+        for name in offended.iter() {
+            let before = raws::faction_reaction(&raws, name, self);
+            self.adjust(name, -OFFENCE_PENALTY);
+            if raws::faction_reaction(&raws, name, self) != before {
+                changed.push(name.clone());
+            }
+        }
+        changed
+    }
+
+    pub fn reaction(&self, faction : &str) -> Reaction {
+        raws::faction_reaction(&raws::raws(), faction, self)
+    }
+}
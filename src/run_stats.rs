@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use specs::prelude::*;
+use specs::shrev::ReaderId;
+use serde::{Serialize, Deserialize};
+use super::{Map, Name, turn_counter::TurnCounter, events::{GameEvents, GameEvent}};
+
+/// What damage is put down to when nobody dealt it, like fire or poison.
+const NO_SOURCE : &str = "Other causes";
+
+/// How long the player spent on one level, and how much of it they saw.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LevelStats {
+    pub name : String,
+    pub turns : i32,
+    pub explored : i32
+}
+
+/// The run in numbers, for the game over screen and the morgue file. Saved with the game.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct RunStats {
+    /// Damage the player dealt, by what they dealt it to.
+    pub damage_dealt : HashMap<String, i32>,
+    /// Damage the player took, by whatever dealt it.
+    pub damage_taken : HashMap<String, i32>,
+    pub kills : HashMap<String, i32>,
+    /// Potions, scrolls and the like the player used up, by their real names.
+    pub items_used : HashMap<String, i32>,
+    /// Every level the player has been on, in the order they first got there.
+    pub levels : Vec<LevelStats>
+}
+
+impl RunStats {
+    pub fn total_dealt(&self) -> i32 {
+        self.damage_dealt.values().sum()
+    }
+
+    pub fn total_taken(&self) -> i32 {
+        self.damage_taken.values().sum()
+    }
+
+    pub fn total_kills(&self) -> i32 {
+        self.kills.values().sum()
+    }
+
+    pub fn tiles_explored(&self) -> i32 {
+        self.levels.iter().map(|level| level.explored).sum()
+    }
+
+    /// Whatever hurt the player most over the run, and by how much.
+    pub fn worst_source(&self) -> Option<(&String, i32)> {
+        self.damage_taken.iter().max_by_key(|(name, amount)| (**amount, std::cmp::Reverse(*name))).map(|(name, amount)| (name, *amount))
+    }
+
+    /// A few lines summing the run up.
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("You killed {} foes and dealt {} damage.", self.total_kills(), self.total_dealt()),
+            format!("You took {} damage, used {} items and explored {} tiles.", self.total_taken(), self.items_used.values().sum::<i32>(), self.tiles_explored())
+        ];
+        if let Some((name, amount)) = self.worst_source() {
+            lines.push(format!("Hurt most by: {} ({} damage)", name, amount));
+        }
+        lines
+    }
+
+    /// Everything, broken down, for the morgue file.
+    pub fn report(&self) -> Vec<String> {
+        let mut lines = vec!["Statistics:".to_string()];
+        lines.extend(self.summary());
+        section(&mut lines, "Damage dealt to", &self.damage_dealt);
+        section(&mut lines, "Damage taken from", &self.damage_taken);
+        section(&mut lines, "Kills", &self.kills);
+        section(&mut lines, "Items used", &self.items_used);
+        if !self.levels.is_empty() {
+            lines.push(String::new());
+            lines.push("Levels:".to_string());
+            for level in self.levels.iter() {
+                lines.push(format!("  {}: {} turns, {} tiles explored", level.name, level.turns, level.explored));
+            }
+        }
+        lines
+    }
+
+    fn level(&mut self, name : String) -> &mut LevelStats {
+        let index = match self.levels.iter().position(|level| level.name == name) {
+            Some(index) => index,
+            None => {
+                self.levels.push(LevelStats{ name, turns: 0, explored: 0 });
+                self.levels.len() - 1
+            }
+        };
+        &mut self.levels[index]
+    }
+}
+
+/// Adds one count per line, biggest first, under a heading; nothing at all if there's nothing to count.
+fn section(lines : &mut Vec<String>, heading : &str, counts : &HashMap<String, i32>) {
+    if counts.is_empty() { return; }
+    let mut sorted : Vec<(&String, &i32)> = counts.iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    lines.push(String::new());
+    lines.push(format!("{}:", heading));
+    for (name, count) in sorted {
+        lines.push(format!("  {}: {}", name, count));
+    }
+}
+
+fn add(counts : &mut HashMap<String, i32>, name : &str, amount : i32) {
+    *counts.entry(name.to_string()).or_insert(0) += amount;
+}
+
+/// Keeps the run's statistics up to date from the events each tick, and notes the time spent and
+/// tiles seen on the current level.
+#[derive(Default)]
+pub struct RunStatsSystem {
+    reader : Option<ReaderId<GameEvent>>,
+    /// The turn counter when this last ran; None until it has, so a loaded game starts counting afresh.
+    last_turn : Option<i32>
+}
+
+impl<'a> System<'a> for RunStatsSystem {
+    type SystemData = ( ReadExpect<'a, GameEvents>,
+                        WriteExpect<'a, RunStats>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, Map>,
+                        ReadExpect<'a, TurnCounter>,
+                        ReadStorage<'a, Name> );
+
+    fn setup(&mut self, world : &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(world.write_resource::<GameEvents>().register_reader());
+    }
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (events, mut stats, player_entity, map, turns, names) = data;
+
+        // Whatever died this tick is already gone, so the damage that killed it waits for its name
+        let mut unnamed : HashMap<Entity, i32> = HashMap::new();
+        for event in events.read(self.reader.as_mut().expect("RunStatsSystem was not set up")) {
+            match event {
+                GameEvent::Damaged{ target, amount, by, .. } if *target == *player_entity => {
+                    let source = by.and_then(|by| names.get(by)).map(|n| n.name.as_str()).unwrap_or(NO_SOURCE);
+                    add(&mut stats.damage_taken, source, *amount);
+                }
+                GameEvent::Damaged{ target, amount, from_player: true, .. } => {
+                    match names.get(*target) {
+                        Some(name) => add(&mut stats.damage_dealt, &name.name, *amount),
+                        None => *unnamed.entry(*target).or_insert(0) += *amount
+                    }
+                }
+                GameEvent::Died{ entity, name, foe } => {
+                    if let Some(amount) = unnamed.remove(entity) {
+                        add(&mut stats.damage_dealt, name, amount);
+                    }
+                    if *foe { add(&mut stats.kills, name, 1); }
+                }
+                GameEvent::ItemConsumed{ name, by_player: true, .. } => add(&mut stats.items_used, name, 1),
+                _ => {}
+            }
+        }
+
+        // Undoing a turn can wind the counter back, which isn't time spent anywhere
+        let elapsed = i32::max(0, turns.turn - self.last_turn.unwrap_or(turns.turn));
+        self.last_turn = Some(turns.turn);
+        let explored = map.revealed_tiles.iter().filter(|revealed| **revealed).count() as i32;
+        let level = stats.level(map.branch.level_name(map.depth));
+        level.turns += elapsed;
+        level.explored = i32::max(level.explored, explored);
+    }
+}
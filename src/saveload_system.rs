@@ -1,10 +1,19 @@
-use std::fs;
-use std::fs::File;
-use std::path::Path;
 use specs::{Builder, Entity, Join, World, WorldExt};
 use specs::saveload::{SimpleMarker, SimpleMarkerAllocator, SerializeComponents, DeserializeComponents, MarkedBuilder};
 use crate::SerializeMe;
 use crate::components::*;
+use crate::turn_counter::TurnCounter;
+use crate::dungeon::MasterDungeonMap;
+use crate::unique_items::UniqueItems;
+use crate::identified_items::IdentifiedItems;
+use crate::quests::QuestLog;
+use crate::reputation::Reputation;
+use crate::difficulty::Difficulty;
+use crate::achievements::RunRecord;
+use crate::run_stats::RunStats;
+use crate::challenge::Challenge;
+use crate::undo::RunMode;
+use crate::storage;
 use specs::error::NoError;
 
 macro_rules! serialize_individually {
@@ -37,42 +46,74 @@ macro_rules! deserialize_individually {
     };
 }
 
-#[cfg(target_arch = "wasm32")]
-pub fn save_game(_ecs : &mut World) {
+const SAVE_FILE : &str = "savegame.json";
+
+pub fn save_game(ecs : &mut World) -> Result<(), String> {
+    let data = serialize_world(ecs);
+    storage::write(SAVE_FILE, &data)
 }
 
-#[cfg(not(target_arch = "wasm32"))]
-pub fn save_game(ecs : &mut World) {
+/// Writes out everything about the run, as a saved game or a forgiving mode snapshot holds it.
+pub fn serialize_world(ecs : &mut World) -> String {
     // Create helper
     let mapcopy = ecs.get_mut::<super::map::Map>().unwrap().clone();
+    let turns = *ecs.fetch::<TurnCounter>();
+    let dungeon = ecs.fetch::<MasterDungeonMap>().clone();
+    let uniques = ecs.fetch::<UniqueItems>().clone();
+    let identified = ecs.fetch::<IdentifiedItems>().clone();
+    let quests = ecs.fetch::<QuestLog>().clone();
+    let reputation = ecs.fetch::<Reputation>().clone();
+    let difficulty = *ecs.fetch::<Difficulty>();
+    let record = *ecs.fetch::<RunRecord>();
+    let challenge = ecs.fetch::<Challenge>().clone();
+    let stats = ecs.fetch::<RunStats>().clone();
+    let mode = *ecs.fetch::<RunMode>();
     let savehelper = ecs
         .create_entity()
-        .with(SerializationHelper{ map : mapcopy })
+        .with(SerializationHelper{ map : mapcopy, turns, dungeon, uniques, identified, quests, reputation, difficulty, record, challenge, stats, mode })
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
 
     // Actually serialize
-    {
+    let data = {
         let data = ( ecs.entities(), ecs.read_storage::<SimpleMarker<SerializeMe>>() );
 
-        let writer = File::create("./savegame.json").unwrap();
-        let mut serializer = serde_json::Serializer::new(writer);
+        let mut serializer = serde_json::Serializer::new(Vec::new());
         serialize_individually!(ecs, serializer, data, Position, Player, Monster,
             Name, BlocksTile, CombatStats, SufferDamage, WantsToMelee, Item, Consumable, Ranged, InflictsDamage,
             AreaOfEffect, Confusion, ProvidesHealing, InBackpack, WantsToPickupItem,
-            WantsToDropItem, SerializationHelper, Renderable, Viewshed, WantsToUseItem
+            WantsToDropItem, WantsToRemoveItem, SerializationHelper, Renderable, Viewshed, WantsToUseItem, MacGuffin,
+            OtherLevelPosition, Decay, Rarity, LootTable, Equippable, Equipped, MeleePowerBonus, DefenseBonus,
+            BonusDamage, ConfuseOnHit, Artifact, WantsToThrowItem, Thrown, Weight,
+            Burning, Flammable, Butcherable, StartsFire, Resistances, DamageOverTime, InflictsDot,
+            Attributes, CriticalHit, Staggered, BlockChance, ParryChance, ReactionCooldown,
+            Pools, PowerAttack, WeaponSpecial, SpecialAbilities, Webbed, Boss, OwnedBy, SummonsMinion,
+            Companion, Recruitable, Experience, Charmed, Charms, FactionOverride, Invisible, CanSeeInvisible,
+            GrantsInvisibility, GrantsSeeInvisible, Telepathy, GrantsTelepathy,
+            Hidden, EntryTrigger, TeleportsRandomly, Blink, TownPortal, PortalLink,
+            Container, InContainer, Locked, Key, Door, BlocksVisibility, HeardNoise, Lever, Wired, Portcullis, SecretPassage,
+            Interactive, WellFed, HungerClock, ProvidesFood, Faith, Skills, Resting, Travelling, PendingLevelUps, KeenEye, FastHealing, FarSight, Regeneration,
+            Affixed, Purse, Gold, Reagent, Durability, Repairs, Charges, Recharges, Unidentified, TwoHanded, OffHand, Launcher, Ammo, WantsToShoot, Vendor, Value, QuestGiver, Talks, Faction, Category, Hotbar, Scripted, MovesThroughWalls, PackMember, Fleeing, Territory, Patrol, UsesItems, Afflictions, Afflicts, Cures, Mutations, Mutates, SkillTraining
         );
-    }
+        String::from_utf8(serializer.into_inner()).expect("Save game is not valid UTF-8")
+    };
 
     // Clean up
     ecs.delete_entity(savehelper).expect("Crash on cleanup");
+    data
 }
 
 pub fn does_save_exist() -> bool {
-    Path::new("./savegame.json").exists()
+    storage::exists(SAVE_FILE)
 }
 
 pub fn load_game(ecs: &mut World) {
+    let data = storage::read(SAVE_FILE).expect("Unable to read save game");
+    deserialize_world(ecs, &data);
+}
+
+/// Replaces everything in the world with a run written out by `serialize_world`.
+pub fn deserialize_world(ecs : &mut World, data : &str) {
     {
         // Delete everything
         let mut to_delete = Vec::new();
@@ -84,8 +125,7 @@ pub fn load_game(ecs: &mut World) {
         }
     }
 
-    let data = fs::read_to_string("./savegame.json").unwrap();
-    let mut de = serde_json::Deserializer::from_str(&data);
+    let mut de = serde_json::Deserializer::from_str(data);
 
     {
         let mut d = (&mut ecs.entities(), &mut ecs.write_storage::<SimpleMarker<SerializeMe>>(), &mut ecs.write_resource::<SimpleMarkerAllocator<SerializeMe>>());
@@ -93,7 +133,18 @@ pub fn load_game(ecs: &mut World) {
         deserialize_individually!(ecs, de, d, Position, Player, Monster,
             Name, BlocksTile, CombatStats, SufferDamage, WantsToMelee, Item, Consumable, Ranged, InflictsDamage,
             AreaOfEffect, Confusion, ProvidesHealing, InBackpack, WantsToPickupItem,
-            WantsToDropItem, SerializationHelper, Renderable, Viewshed, WantsToUseItem
+            WantsToDropItem, WantsToRemoveItem, SerializationHelper, Renderable, Viewshed, WantsToUseItem, MacGuffin,
+            OtherLevelPosition, Decay, Rarity, LootTable, Equippable, Equipped, MeleePowerBonus, DefenseBonus,
+            BonusDamage, ConfuseOnHit, Artifact, WantsToThrowItem, Thrown, Weight,
+            Burning, Flammable, Butcherable, StartsFire, Resistances, DamageOverTime, InflictsDot,
+            Attributes, CriticalHit, Staggered, BlockChance, ParryChance, ReactionCooldown,
+            Pools, PowerAttack, WeaponSpecial, SpecialAbilities, Webbed, Boss, OwnedBy, SummonsMinion,
+            Companion, Recruitable, Experience, Charmed, Charms, FactionOverride, Invisible, CanSeeInvisible,
+            GrantsInvisibility, GrantsSeeInvisible, Telepathy, GrantsTelepathy,
+            Hidden, EntryTrigger, TeleportsRandomly, Blink, TownPortal, PortalLink,
+            Container, InContainer, Locked, Key, Door, BlocksVisibility, HeardNoise, Lever, Wired, Portcullis, SecretPassage,
+            Interactive, WellFed, HungerClock, ProvidesFood, Faith, Skills, Resting, Travelling, PendingLevelUps, KeenEye, FastHealing, FarSight, Regeneration,
+            Affixed, Purse, Gold, Reagent, Durability, Repairs, Charges, Recharges, Unidentified, TwoHanded, OffHand, Launcher, Ammo, WantsToShoot, Vendor, Value, QuestGiver, Talks, Faction, Category, Hotbar, Scripted, MovesThroughWalls, PackMember, Fleeing, Territory, Patrol, UsesItems, Afflictions, Afflicts, Cures, Mutations, Mutates, SkillTraining
         );
     }
 
@@ -106,7 +157,18 @@ pub fn load_game(ecs: &mut World) {
         for (e,h) in (&entities, &helper).join() {
             let mut worldmap = ecs.write_resource::<super::map::Map>();
             *worldmap = h.map.clone();
-            worldmap.tile_content = vec![Vec::new(); super::map::MAP_COUNT];
+            worldmap.reset_index();
+            *ecs.write_resource::<TurnCounter>() = h.turns;
+            *ecs.write_resource::<MasterDungeonMap>() = h.dungeon.clone();
+            *ecs.write_resource::<UniqueItems>() = h.uniques.clone();
+            *ecs.write_resource::<IdentifiedItems>() = h.identified.clone();
+            *ecs.write_resource::<QuestLog>() = h.quests.clone();
+            *ecs.write_resource::<Reputation>() = h.reputation.clone();
+            *ecs.write_resource::<Difficulty>() = h.difficulty;
+            *ecs.write_resource::<RunRecord>() = h.record;
+            *ecs.write_resource::<Challenge>() = h.challenge.clone();
+            *ecs.write_resource::<RunStats>() = h.stats.clone();
+            *ecs.write_resource::<RunMode>() = h.mode;
             deleteme = Some(e);
         }
         for (e,_p,pos) in (&entities, &player, &position).join() {
@@ -120,5 +182,5 @@ pub fn load_game(ecs: &mut World) {
 }
 
 pub fn delete_save() {
-    if Path::new("./savegame.json").exists() { std::fs::remove_file("./savegame.json").expect("Unable to delete file"); }
+    storage::remove(SAVE_FILE);
 }
\ No newline at end of file
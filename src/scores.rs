@@ -0,0 +1,171 @@
+use specs::prelude::*;
+use serde::{Serialize, Deserialize};
+use super::{Name, Purse, gamelog::{GameLog, LogCategory}, turn_counter::TurnCounter, achievements::RunRecord, challenge::Challenge, replay::Replay, undo::RunMode, storage};
+
+/// How many runs the table keeps; the lowest scoring fall off the bottom.
+const MAX_SCORES : usize = 100;
+
+/// How one run ended up.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Score {
+    pub name : String,
+    pub depth : i32,
+    pub kills : i32,
+    pub gold : i32,
+    pub turns : i32,
+    pub victory : bool,
+    /// The day of the daily challenge this run was, if it was one.
+    #[serde(default)]
+    pub daily : Option<String>,
+    /// Whether the run was played in forgiving mode, able to take turns back.
+    #[serde(default)]
+    pub forgiving : bool
+}
+
+impl Score {
+    /// A single number to rank runs by: going deep counts for most, and escaping for more still.
+    pub fn points(&self) -> i32 {
+        self.depth * 100 + self.kills * 10 + self.gold + if self.victory { 5000 } else { 0 }
+    }
+}
+
+/// Which column the high score screen is ordered by, best first.
+#[derive(PartialEq, Copy, Clone)]
+pub enum ScoreSort { Points, Depth, Kills, Gold, Turns }
+
+impl ScoreSort {
+    pub fn next(self) -> ScoreSort {
+        match self {
+            ScoreSort::Points => ScoreSort::Depth,
+            ScoreSort::Depth => ScoreSort::Kills,
+            ScoreSort::Kills => ScoreSort::Gold,
+            ScoreSort::Gold => ScoreSort::Turns,
+            ScoreSort::Turns => ScoreSort::Points
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ScoreSort::Points => "Score",
+            ScoreSort::Depth => "Depth",
+            ScoreSort::Kills => "Kills",
+            ScoreSort::Gold => "Gold",
+            ScoreSort::Turns => "Turns"
+        }
+    }
+
+    /// What the column is ranked by, higher being better; so the quickest runs rank first by turns.
+    fn key(self, score : &Score) -> i32 {
+        match self {
+            ScoreSort::Points => score.points(),
+            ScoreSort::Depth => score.depth,
+            ScoreSort::Kills => score.kills,
+            ScoreSort::Gold => score.gold,
+            ScoreSort::Turns => -score.turns
+        }
+    }
+}
+
+/// What the high score screen is showing: which runs, and in what order.
+#[derive(PartialEq, Copy, Clone)]
+pub struct ScoreView {
+    pub sort : ScoreSort,
+    /// Today's daily challenge rather than ordinary runs.
+    pub daily : bool
+}
+
+impl Default for ScoreView {
+    fn default() -> ScoreView {
+        ScoreView{ sort: ScoreSort::Points, daily: false }
+    }
+}
+
+/// Every finished run, kept in scores.json between games.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct ScoreTable {
+    pub scores : Vec<Score>
+}
+
+impl ScoreTable {
+    pub fn load() -> ScoreTable {
+        storage::read("scores.json")
+            .and_then(|data| serde_json::from_str(&data).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let data = serde_json::to_string(self).expect("Unable to serialize scores");
+        storage::write("scores.json", &data)
+    }
+
+    /// The scores ordered by one column, best first; ties go to the higher score overall. Ordinary
+    /// runs and each day's challenge are ranked separately, so `daily` picks which to list.
+    pub fn sorted(&self, sort : ScoreSort, daily : Option<&str>) -> Vec<&Score> {
+        let mut scores : Vec<&Score> = self.scores.iter().filter(|s| s.daily.as_deref() == daily).collect();
+        scores.sort_by(|a, b| sort.key(b).cmp(&sort.key(a)).then(b.points().cmp(&a.points())));
+        scores
+    }
+
+    fn add(&mut self, score : Score) {
+        self.scores.push(score);
+        self.scores.sort_by_key(|s| -s.points());
+        self.scores.truncate(MAX_SCORES);
+    }
+}
+
+/// Adds the run that just ended to the table and saves it, unless it was only a replay.
+pub fn record_score(ecs : &World, victory : bool) {
+    if ecs.fetch::<Replay>().is_playing() { return; }
+    let player_entity = *ecs.fetch::<Entity>();
+    let record = *ecs.fetch::<RunRecord>();
+    let daily = ecs.fetch::<Challenge>().date.clone();
+    let score = Score{
+        name : ecs.read_storage::<Name>().get(player_entity).map_or("Nameless".to_string(), |n| n.name.clone()),
+        depth : record.deepest,
+        kills : record.kills,
+        gold : ecs.read_storage::<Purse>().get(player_entity).map_or(0, |p| p.gold),
+        turns : ecs.fetch::<TurnCounter>().turn,
+        victory,
+        daily,
+        forgiving : *ecs.fetch::<RunMode>() == RunMode::Forgiving
+    };
+    let mut table = ecs.write_resource::<ScoreTable>();
+    table.add(score);
+    if let Err(err) = table.save() {
+        ecs.write_resource::<GameLog>().push(LogCategory::System, err);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(name : &str, depth : i32, turns : i32, daily : Option<&str>) -> Score {
+        Score{ name: name.to_string(), depth, kills: 0, gold: 0, turns, victory: false, daily: daily.map(|d| d.to_string()), forgiving: false }
+    }
+
+    fn names(scores : Vec<&Score>) -> Vec<&str> {
+        scores.iter().map(|s| s.name.as_str()).collect()
+    }
+
+    #[test]
+    fn fewest_turns_rank_first() {
+        let table = ScoreTable{ scores: vec![score("slow", 3, 900, None), score("quick", 1, 200, None), score("middling", 2, 500, None)] };
+        assert_eq!(names(table.sorted(ScoreSort::Turns, None)), vec!["quick", "middling", "slow"]);
+        assert_eq!(ScoreSort::Turns.key(&table.scores[1]), -200);
+    }
+
+    #[test]
+    fn ties_are_broken_by_points() {
+        let table = ScoreTable{ scores: vec![score("shallow", 1, 300, None), score("deep", 4, 300, None)] };
+        assert_eq!(names(table.sorted(ScoreSort::Turns, None)), vec!["deep", "shallow"]);
+        assert_eq!(names(table.sorted(ScoreSort::Depth, None)), vec!["deep", "shallow"]);
+    }
+
+    #[test]
+    fn daily_runs_are_ranked_apart() {
+        let table = ScoreTable{ scores: vec![score("ordinary", 5, 100, None), score("daily", 2, 100, Some("2026-10-16"))] };
+        assert_eq!(names(table.sorted(ScoreSort::Points, None)), vec!["ordinary"]);
+        assert_eq!(names(table.sorted(ScoreSort::Points, Some("2026-10-16"))), vec!["daily"]);
+    }
+}
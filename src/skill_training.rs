@@ -0,0 +1,176 @@
+use std::collections::HashSet;
+use specs::prelude::*;
+use specs::shrev::ReaderId;
+use rltk::{Point, RGB};
+use super::{Skills, Skill, SkillTraining, Monster, Position, Viewshed, OwnedBy, HeardNoise, Invisible, CanSeeInvisible, RunState, Map, Branch,
+            invisibility_system::can_perceive, gamelog::{GameLog, LogCategory}, events::{GameEvents, GameEvent}};
+
+pub const LOCKPICK_XP : i32 = 20;
+pub const REPAIR_XP : i32 = 10;
+pub const ENCHANT_XP : i32 = 25;
+const IDENTIFY_XP : i32 = 15;
+const SNEAK_XP : i32 = 10;
+/// How close the player has to slip by a monster, unseen, for it to count as sneaking past.
+const SNEAK_DISTANCE : f32 = 3.0;
+/// No skill trains past this rank by use alone.
+const MAX_RANK : i32 = 10;
+
+impl Skill {
+    /// Every skill that improves with use, in the order the character sheet lists them.
+    pub const ALL : [Skill; 4] = [Skill::Lockpicking, Skill::Magic, Skill::Stealth, Skill::Crafting];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Skill::Lockpicking => "Lockpicking",
+            Skill::Magic => "Magic",
+            Skill::Stealth => "Stealth",
+            Skill::Crafting => "Crafting"
+        }
+    }
+}
+
+impl Skills {
+    pub fn rank(&self, skill : Skill) -> i32 {
+        match skill {
+            Skill::Lockpicking => self.lockpicking,
+            Skill::Magic => self.magic,
+            Skill::Stealth => self.stealth,
+            Skill::Crafting => self.crafting
+        }
+    }
+
+    fn rank_mut(&mut self, skill : Skill) -> &mut i32 {
+        match skill {
+            Skill::Lockpicking => &mut self.lockpicking,
+            Skill::Magic => &mut self.magic,
+            Skill::Stealth => &mut self.stealth,
+            Skill::Crafting => &mut self.crafting
+        }
+    }
+}
+
+impl SkillTraining {
+    pub fn xp(&self, skill : Skill) -> i32 {
+        match skill {
+            Skill::Lockpicking => self.lockpicking,
+            Skill::Magic => self.magic,
+            Skill::Stealth => self.stealth,
+            Skill::Crafting => self.crafting
+        }
+    }
+
+    fn xp_mut(&mut self, skill : Skill) -> &mut i32 {
+        match skill {
+            Skill::Lockpicking => &mut self.lockpicking,
+            Skill::Magic => &mut self.magic,
+            Skill::Stealth => &mut self.stealth,
+            Skill::Crafting => &mut self.crafting
+        }
+    }
+}
+
+/// Experience it takes to go from `rank` to the next one.
+pub fn xp_to_next(rank : i32) -> i32 {
+    100 * (rank + 1)
+}
+
+/// Notices the player slipping past monsters close by without being seen, once per monster a level.
+#[derive(Default)]
+pub struct SneakSystem {
+    /// Monsters already snuck past, so lurking beside one doesn't count again and again.
+    passed : HashSet<Entity>,
+    /// The branch and depth of the level these were on; None until the system has run.
+    level : Option<(Branch, i32)>
+}
+
+impl<'a> System<'a> for SneakSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, Point>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, GameEvents>,
+                        ReadStorage<'a, Monster>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Viewshed>,
+                        ReadStorage<'a, OwnedBy>,
+                        ReadStorage<'a, HeardNoise>,
+                        ReadStorage<'a, Invisible>,
+                        ReadStorage<'a, CanSeeInvisible> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, player_entity, player_pos, runstate, map, mut events, monsters, positions, viewsheds, owned, heard,
+            invisible, see_invisible) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+        if self.level != Some((map.branch, map.depth)) {
+            self.passed.clear();
+            self.level = Some((map.branch, map.depth));
+        }
+
+        for (entity, _monster, pos, viewshed) in (&entities, &monsters, &positions, &viewsheds).join() {
+            if self.passed.contains(&entity) || heard.get(entity).is_some() { continue; }
+            if owned.get(entity).map_or(false, |o| o.owner == *player_entity) { continue; }
+            if rltk::DistanceAlg::Pythagoras.distance2d(*player_pos, Point::new(pos.x, pos.y)) > SNEAK_DISTANCE { continue; }
+            // Walking up in plain sight is no sneaking, even if it turns its back later
+            self.passed.insert(entity);
+            let spotted = viewshed.visible_tiles.contains(&*player_pos) && can_perceive(entity, *player_entity, &invisible, &see_invisible);
+            if !spotted {
+                events.single_write(GameEvent::SkillUsed{ by: *player_entity, skill: Skill::Stealth, xp: SNEAK_XP });
+            }
+        }
+    }
+}
+
+/// Puts the experience from skills used this tick towards their next ranks, and raises any that
+/// have earned it.
+#[derive(Default)]
+pub struct SkillTrainingSystem {
+    reader : Option<ReaderId<GameEvent>>
+}
+
+impl<'a> System<'a> for SkillTrainingSystem {
+    type SystemData = ( ReadExpect<'a, GameEvents>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, Skills>,
+                        WriteStorage<'a, SkillTraining> );
+
+    fn setup(&mut self, world : &mut World) {
+        Self::SystemData::setup(world);
+        self.reader = Some(world.write_resource::<GameEvents>().register_reader());
+    }
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (events, player_entity, mut log, mut skills, mut training) = data;
+
+        for event in events.read(self.reader.as_mut().expect("SkillTrainingSystem was not set up")) {
+            let (by, skill, xp) = match event {
+                GameEvent::SkillUsed{ by, skill, xp } => (*by, *skill, *xp),
+                GameEvent::ItemIdentified{ .. } => (*player_entity, Skill::Magic, IDENTIFY_XP),
+                _ => continue
+            };
+            // A character from an older save starts training from nothing
+            if skills.get(by).is_some() && training.get(by).is_none() {
+                training.insert(by, SkillTraining::default()).expect("Unable to insert skill training");
+            }
+            let (skills, training) = match (skills.get_mut(by), training.get_mut(by)) {
+                (Some(skills), Some(training)) => (skills, training),
+                _ => continue
+            };
+
+            let rank = skills.rank_mut(skill);
+            if *rank >= MAX_RANK { continue; }
+            let earned = training.xp_mut(skill);
+            *earned += xp;
+            while *earned >= xp_to_next(*rank) && *rank < MAX_RANK {
+                *earned -= xp_to_next(*rank);
+                *rank += 1;
+                if by == *player_entity {
+                    log.push_colored(LogCategory::System, format!("Your {} skill rises to {}!", skill.name(), *rank), RGB::named(rltk::GOLD));
+                }
+            }
+        }
+    }
+}
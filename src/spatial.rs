@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use specs::prelude::*;
+
+/// Where every positioned entity stands, and which tiles are blocked by terrain or by something
+/// standing there. It lives on the map and is only changed through these methods, so the per-tile
+/// lists and blocking flags can't drift apart. `MapIndexingSystem` keeps it up to date as
+/// positions change; anything that moves an entity mid-turn should also tell it straight away.
+#[derive(Default, Clone)]
+pub struct SpatialIndex {
+    solid : Vec<bool>,
+    blockers : Vec<u16>,
+    content : Vec<Vec<Entity>>,
+    locations : HashMap<u32, (Entity, usize, bool)>,
+    populated : bool
+}
+
+impl SpatialIndex {
+    pub fn new(tile_count : usize) -> SpatialIndex {
+        SpatialIndex{
+            solid : vec![false; tile_count],
+            blockers : vec![0; tile_count],
+            content : vec![Vec::new(); tile_count],
+            locations : HashMap::new(),
+            populated : false
+        }
+    }
+
+    /// Whether every entity has been added since the index was made, or the map loaded.
+    pub fn is_populated(&self) -> bool {
+        self.populated
+    }
+
+    /// Forgets every entity, ready for them all to be added again.
+    pub fn clear_entities(&mut self) {
+        for content in self.content.iter_mut() {
+            content.clear();
+        }
+        for count in self.blockers.iter_mut() {
+            *count = 0;
+        }
+        self.locations.clear();
+        self.populated = true;
+    }
+
+    /// Marks a tile as blocked (or not) by the terrain itself.
+    pub fn set_solid(&mut self, idx : usize, solid : bool) {
+        self.solid[idx] = solid;
+    }
+
+    pub fn entities_at(&self, idx : usize) -> &[Entity] {
+        &self.content[idx]
+    }
+
+    /// Whether the terrain or anything standing on the tile stops others moving in.
+    pub fn is_blocked(&self, idx : usize) -> bool {
+        self.solid[idx] || self.blockers[idx] > 0
+    }
+
+    /// Whether something standing on the tile stops others moving in, whatever the terrain.
+    pub fn is_occupied(&self, idx : usize) -> bool {
+        self.blockers[idx] > 0
+    }
+
+    /// Puts an entity on a tile, moving it there if it was already somewhere else.
+    pub fn insert(&mut self, entity : Entity, idx : usize, blocks : bool) {
+        if let Some((old, old_idx, old_blocks)) = self.locations.get(&entity.id()).copied() {
+            if old == entity && old_idx == idx && old_blocks == blocks { return; }
+            self.remove(entity.id());
+        }
+        self.content[idx].push(entity);
+        if blocks { self.blockers[idx] += 1; }
+        self.locations.insert(entity.id(), (entity, idx, blocks));
+    }
+
+    /// Takes whichever entity has this id off the map, if it was on it.
+    pub fn remove(&mut self, id : u32) {
+        if let Some((entity, idx, blocks)) = self.locations.remove(&id) {
+            self.content[idx].retain(|e| *e != entity);
+            if blocks { self.blockers[idx] -= 1; }
+        }
+    }
+
+    /// Moves an entity already on the map to another tile, still blocking it if it did before.
+    pub fn move_entity(&mut self, entity : Entity, idx : usize) {
+        if let Some((known, _old_idx, blocks)) = self.locations.get(&entity.id()).copied() {
+            if known == entity {
+                self.insert(entity, idx, blocks);
+            }
+        }
+    }
+}
@@ -1,15 +1,34 @@
 use rltk::{RGB, RandomNumberGenerator};
 use specs::prelude::*;
-use rand::random;
 use specs::saveload::{MarkedBuilder, SimpleMarker};
-use super::{CombatStats, Player, Renderable, Name, Position, Viewshed, Monster, BlocksTile, Rect, MAP_WIDTH, Item, Consumable, ProvidesHealing, Ranged, InflictsDamage, Confusion, AreaOfEffect, SerializeMe};
+use super::{Locked, Key, Wired, Lever, EntryTrigger, Experience, Player, Renderable, Name, Position, Viewshed, tile_set::TileSet, Rect, Item, SerializeMe, MacGuffin, Map, TileType,
+            Branch, random_table::RandomTable, tile_walkable, Decay, Burning, Flammable, Monster, HeardNoise, CombatStats, PackMember, Patrol, difficulty::Difficulty, turn_counter::TurnCounter, raws,
+            HungerClock, HungerState, hunger_system};
 
-const MAX_MONSTER: i32 = 4;
-const MAX_ITEMS: i32 = 3;
+const MAX_SPAWNS: i32 = 4;
+const SPAWN_CHUNK_SIZE: i32 = 10;
+const CORPSE_DECAY_TURNS: i32 = 200;
+/// Chance (in 100) that a spawn is rolled as if the level were deeper than it is.
+const OUT_OF_DEPTH_CHANCE: i32 = 4;
+/// How much deeper an out-of-depth spawn comes from.
+const OUT_OF_DEPTH_LEVELS: i32 = 3;
+/// Total monster hit points a level can hold, per player level, and still feel like a fair fight.
+const FAIR_DANGER_PER_LEVEL: i32 = 60;
+/// Every turn, a wandering monster turns up with a chance of (1 + depth / 2) in this.
+const WANDERER_ODDS: i32 = 150;
+/// A level stops attracting wanderers once this many monsters, plus two per depth, are about.
+const POPULATION_BUDGET: i32 = 10;
+/// Attempts at finding an out-of-sight tile for a wanderer before giving up for the turn.
+const WANDERER_PLACEMENT_TRIES: i32 = 20;
+/// How far from its leader the rest of a pack is placed.
+const PACK_RADIUS: i32 = 2;
+/// Attempts at finding each waypoint of a patrol route.
+const PATROL_TRIES: i32 = 10;
 
-/// Spawns the player and returns his/her entity object.
-pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
-    ecs
+/// Spawns the player, outfitted for the chosen class, and returns his/her entity object. Nothing is
+/// spawned if the class can't be found.
+pub fn player(ecs: &mut World, player_x: i32, player_y: i32, name: &str, class: &str) -> Result<Entity, String> {
+    let player = ecs
         .create_entity()
         .with(Position { x: player_x, y: player_y })
         .with(Renderable {
@@ -19,178 +38,448 @@ pub fn player(ecs: &mut World, player_x: i32, player_y: i32) -> Entity {
             render_order: 0
         })
         .with(Player {})
-        .with(Viewshed { visible_tiles: Vec::new(), range: 8, dirty: true })
-        .with(Name { name: "Player".to_string() })
-        .with(CombatStats { max_hp: 30, hp: 30, defense: 2, power: 5 })
+        .with(Viewshed { visible_tiles: TileSet::default(), range: 8, dirty: true })
+        .with(Name { name: name.to_string() })
+        .with(Experience { level: 1, xp: 0 })
+        .with(HungerClock { state: HungerState::Normal, duration: hunger_system::NORMAL_TURNS })
         .marked::<SimpleMarker<SerializeMe>>()
-        .build()
+        .build();
+
+    if let Err(err) = raws::apply_class(&raws::raws(), ecs, player, class) {
+        ecs.delete_entity(player).expect("Unable to delete player");
+        return Err(err);
+    }
+    Ok(player)
 }
 
-/// Spawns a random monster at a given location
-pub fn random_monster(ecs: &mut World, x: i32, y: i32) {
-    let roll: i32;
-    {
-        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        roll = rng.roll_dice(1, 2);
+/// The creatures that live on a given level; every branch has its own inhabitants.
+fn monster_table(branch: Branch, depth: i32) -> RandomTable {
+    match branch {
+        Branch::Dungeon => RandomTable::new()
+            .add("Goblin", 10)
+            .add("Orc", 1 + depth)
+            .add("Goblin Shaman", depth - 1)
+            .add("Phantom", depth - 3)
+            .add("Cutpurse", depth - 2)
+            .add("Spectre", depth - 5),
+        Branch::Caverns => RandomTable::new()
+            .add("Giant Spider", 8)
+            .add("Goblin", 4)
+            .add("Orc", depth)
+            .add("Spore Pod", 4)
+            .add("Vampire Bat", depth)
+            .add("Green Slime", depth - 1),
+        Branch::Sewers => RandomTable::new()
+            .add("Rat", 12)
+            .add("Kobold", 6 + depth)
+            .add("Cutpurse", depth)
+            .add("Giant Roach", 4),
+        Branch::Fortress => RandomTable::new()
+            .add("Orc", 10)
+            .add("Orc Guard", 2 + depth),
+        Branch::Overworld => RandomTable::new()
+            .add("Wolf", 8)
+            .add("Bandit", 4)
+    }
+}
+
+/// Weighted spawn table for a given level: its monsters, plus the loot and furniture found anywhere,
+/// adjusted by any weights the raws give.
+fn spawn_table(branch: Branch, depth: i32) -> RandomTable {
+    let table = monster_table(branch, depth)
+        .add("Health Potion", 7)
+        .add("Rations", 5)
+        .add("Fireball Scroll", 2 + depth)
+        .add("Confusion Scroll", 2 + depth)
+        .add("Magic Missile Scroll", 4)
+        .add("Greater Health Potion", depth - 2)
+        .add("Scroll of Inferno", depth - 5)
+        .add("Scroll of Summoning", depth - 1)
+        .add("Scroll of Domination", depth - 2)
+        .add("Potion of Invisibility", depth - 1)
+        .add("Potion of True Sight", depth - 2)
+        .add("Potion of Telepathy", depth - 2)
+        .add("Scroll of Detect Monsters", 2)
+        .add("Scroll of Blinking", depth)
+        .add("Scroll of Town Portal", 2)
+        .add("Scroll of Recharging", depth - 2)
+        .add("Wand of Magic Missile", depth)
+        .add("Wand of Confusion", depth - 1)
+        .add("Wand of Fire", depth - 3)
+        .add("Teleport Trap", depth - 1)
+        .add("Spike Trap", depth)
+        .add("Cursed Rune", depth - 3)
+        .add("Barrel", 3)
+        .add("Chest", 1 + depth / 2)
+        .add("Locked Chest", depth - 1)
+        .add("Iron Key", 2)
+        .add("Gold Coins", 4)
+        .add("Repair Kit", 2)
+        .add("Arcane Dust", 1 + depth / 2)
+        .add("Fountain", 2)
+        .add("Stagnant Fountain", 1)
+        .add("Altar", depth / 3)
+        .add("Campfire", 1)
+        .add("Dagger", 3)
+        .add("Flask of Oil", 3)
+        .add("Shield", 3)
+        .add("Leather Cap", 2)
+        .add("Leather Armor", 2)
+        .add("Leather Leggings", 2)
+        .add("Boots", 2)
+        .add("Gloves", 2)
+        .add("Cloak", 1 + depth / 3)
+        .add("Iron Helm", depth - 1)
+        .add("Chainmail", depth - 2)
+        .add("Longsword", depth - 1)
+        .add("Mace", depth)
+        .add("Greataxe", depth - 2)
+        .add("Shortbow", 2)
+        .add("Crossbow", depth - 1)
+        .add("Arrows", 3)
+        .add("Barbed Arrows", depth - 2)
+        .add("Bolts", depth)
+        .add("Tower Shield", depth - 1);
+    raws::apply_spawn_weights(&raws::raws(), table, branch, depth)
+}
+
+/// Populates a freshly built level, leaving the arrival area empty.
+pub fn spawn_level(ecs: &mut World, map: &Map) {
+    if map.branch != Branch::Overworld && !map.rooms.is_empty() {
+        for room in map.rooms.iter().skip(1) {
+            spawn_room(ecs, map, room);
+        }
+        return;
     }
-    match roll {
-        1 => { orc(ecs, x, y) }
-        _ => { goblin(ecs, x, y) }
+
+    // Open maps are split into square chunks instead, keeping the town or the stairs clear
+    let safe_zone = match map.rooms.first() {
+        Some(town) if map.branch == Branch::Overworld => {
+            townsfolk(ecs, town);
+            *town
+        }
+        _ => {
+            let (x, y) = map.arrival_point();
+            Rect::new(x - SPAWN_CHUNK_SIZE / 2, y - SPAWN_CHUNK_SIZE / 2, SPAWN_CHUNK_SIZE, SPAWN_CHUNK_SIZE)
+        }
+    };
+    for chunk_y in 0 .. (map.height / SPAWN_CHUNK_SIZE) + 1 {
+        for chunk_x in 0 .. (map.width / SPAWN_CHUNK_SIZE) + 1 {
+            let mut area : Vec<usize> = Vec::new();
+            for y in chunk_y * SPAWN_CHUNK_SIZE .. i32::min((chunk_y + 1) * SPAWN_CHUNK_SIZE, map.height) {
+                for x in chunk_x * SPAWN_CHUNK_SIZE .. i32::min((chunk_x + 1) * SPAWN_CHUNK_SIZE, map.width) {
+                    let idx = map.xy_idx(x, y);
+                    if tile_walkable(map.tiles[idx]) && map.tiles[idx] != TileType::BranchEntrance && !safe_zone.contains(x, y) {
+                        area.push(idx);
+                    }
+                }
+            }
+
+            spawn_region(ecs, map, &area);
+        }
+    }
+}
+
+fn spawn_room(ecs: &mut World, map: &Map, room: &Rect) {
+    let mut possible_targets : Vec<usize> = Vec::new();
+    for y in room.y1 + 1 ..= room.y2 {
+        for x in room.x1 + 1 ..= room.x2 {
+            let idx = map.xy_idx(x, y);
+            if map.tiles[idx] == TileType::Floor {
+                possible_targets.push(idx);
+            }
+        }
     }
+
+    spawn_region(ecs, map, &possible_targets);
 }
 
-fn orc(ecs: &mut World, x: i32, y: i32) { monster(ecs, x, y, rltk::to_cp437('o'), "Orc"); }
+fn spawn_region(ecs: &mut World, map: &Map, area: &[usize]) {
+    // Built before the artifacts are added, as both need the raws
+    let (table, deep_table) = (spawn_table(map.branch, map.depth), spawn_table(map.branch, map.depth + OUT_OF_DEPTH_LEVELS));
+    let spawn_table = raws::add_artifacts(&raws::raws(), ecs, table, map.depth);
+    let deep_table = raws::add_artifacts(&raws::raws(), ecs, deep_table, map.depth + OUT_OF_DEPTH_LEVELS);
+    let mut spawn_points : Vec<(usize, String)> = Vec::new();
+    let mut areas : Vec<usize> = Vec::from(area);
 
-fn goblin(ecs: &mut World, x: i32, y: i32) { monster(ecs, x, y, rltk::to_cp437('g'), "Goblin"); }
+    {
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let density = ecs.fetch::<Difficulty>().spawn_percent();
+        let num_spawns = i32::min(areas.len() as i32, (rng.roll_dice(1, MAX_SPAWNS + 3) + (map.depth - 1) - 3) * density / 100);
 
-fn monster<S: ToString>(ecs: &mut World, x: i32, y: i32, glyph: rltk::FontCharType, name: S) {
-    ecs.create_entity()
-        .with(Position { x, y })
-        .with(Renderable {
-            glyph,
-            fg: RGB::named(rltk::RED),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 1
-        })
-        .with(Viewshed { visible_tiles: Vec::new(), range: 8, dirty: true })
-        .with(Monster {})
-        .with(Name { name: name.to_string() })
-        .with(BlocksTile {})
-        .with(CombatStats { max_hp: 16, hp: 16, defense: 1, power: 4 })
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+        for _i in 0..num_spawns {
+            let array_index = if areas.len() == 1 { 0usize } else { (rng.roll_dice(1, areas.len() as i32) - 1) as usize };
+            let map_idx = areas[array_index];
+            // Once in a while something turns up that belongs much further down
+            let table = if rng.roll_dice(1, 100) <= OUT_OF_DEPTH_CHANCE { &deep_table } else { &spawn_table };
+            spawn_points.push((map_idx, table.roll(&mut rng)));
+            areas.remove(array_index);
+        }
+    }
+
+    let raws = raws::raws();
+    let mut taken : Vec<usize> = spawn_points.iter().map(|(idx, _name)| *idx).collect();
+    for (idx, name) in spawn_points.iter() {
+        let x = *idx as i32 % map.width;
+        let y = *idx as i32 / map.width;
+        if let Some(entity) = raws::spawn_named_entity(&raws, ecs, name, x, y) {
+            raws::roll_affixes(&raws, ecs, entity, map.depth);
+            raws::fill_container(&raws, ecs, entity, map.depth);
+            raws::fill_pack(&raws, ecs, entity, map.depth);
+            spawn_pack(&raws, ecs, map, entity, name, &mut taken);
+            plan_patrol(&raws, ecs, map, entity, name);
+        }
+    }
 }
 
-pub fn spawn_room(ecs: &mut World, room: &Rect) {
-    let mut monster_spawn_points: Vec<usize> = Vec::new();
-    let mut item_spawn_points: Vec<usize> = Vec::new();
+/// Picks out a patrol route for a creature whose raws give it one: where it starts, then a few
+/// open spots in range of it.
+fn plan_patrol(raws: &raws::RawMaster, ecs: &mut World, map: &Map, entity: Entity, name: &str) {
+    let (stops, range) = match raws::get_mob(raws, name).and_then(|mob| mob.patrol.as_ref()) {
+        Some(patrol) => (patrol.waypoints, patrol.range),
+        None => return
+    };
+    let (x, y) = match ecs.read_storage::<Position>().get(entity) {
+        Some(pos) => (pos.x, pos.y),
+        None => return
+    };
 
+    let mut waypoints = vec![(x, y)];
     {
         let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        let num_monsters = rng.roll_dice(1, MAX_MONSTER + 2) - 3;
-        let num_items = rng.roll_dice(1, MAX_ITEMS + 2) - 3;
-
-        for _i in 0..num_monsters {
-            let mut added = false;
-            while !added {
-                let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-                let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-                let idx = (y * MAP_WIDTH) + x;
-                if !monster_spawn_points.contains(&idx) {
-                    monster_spawn_points.push(idx);
-                    added = true;
-                }
+        for _i in 0..stops * PATROL_TRIES {
+            if waypoints.len() > stops as usize { break; }
+            let (wx, wy) = (x + rng.range(-range, range + 1), y + rng.range(-range, range + 1));
+            if wx < 1 || wy < 1 || wx > map.width - 2 || wy > map.height - 2 { continue; }
+            let idx = map.xy_idx(wx, wy);
+            if !tile_walkable(map.tiles[idx]) || waypoints.contains(&(wx, wy)) { continue; }
+            if rltk::DistanceAlg::Pythagoras.distance2d(rltk::Point::new(x, y), rltk::Point::new(wx, wy)) > range as f32 { continue; }
+            waypoints.push((wx, wy));
+        }
+    }
+    let next = 1 % waypoints.len();
+    ecs.write_storage::<Patrol>().insert(entity, Patrol{ waypoints, next }).expect("Unable to insert patrol");
+}
+
+/// Gathers the rest of a pack around a creature whose raws say it runs with one, and puts it in
+/// charge of them. Packs are smaller if there isn't room around it.
+fn spawn_pack(raws: &raws::RawMaster, ecs: &mut World, map: &Map, leader: Entity, name: &str, taken: &mut Vec<usize>) {
+    let size = match raws::get_mob(raws, name).and_then(|mob| mob.pack.as_ref()) {
+        Some(pack) => ecs.write_resource::<RandomNumberGenerator>().range(pack.min, pack.max + 1),
+        None => return
+    };
+    let (x, y) = match ecs.read_storage::<Position>().get(leader) {
+        Some(pos) => (pos.x, pos.y),
+        None => return
+    };
+
+    let mut spots : Vec<(i32, i32)> = Vec::new();
+    for dy in -PACK_RADIUS ..= PACK_RADIUS {
+        for dx in -PACK_RADIUS ..= PACK_RADIUS {
+            let (px, py) = (x + dx, y + dy);
+            if px < 1 || py < 1 || px > map.width - 2 || py > map.height - 2 { continue; }
+            let idx = map.xy_idx(px, py);
+            if tile_walkable(map.tiles[idx]) && !map.is_blocked(idx) && map.tiles[idx] != TileType::BranchEntrance && !taken.contains(&idx) {
+                spots.push((px, py));
             }
         }
+    }
 
-        for _i in 0 .. num_items {
-            let mut added = false;
-            while !added {
-                let x = (room.x1 + rng.roll_dice(1, i32::abs(room.x2 - room.x1))) as usize;
-                let y = (room.y1 + rng.roll_dice(1, i32::abs(room.y2 - room.y1))) as usize;
-                let idx = (y * MAP_WIDTH) + x;
-                if !item_spawn_points.contains(&idx) {
-                    item_spawn_points.push(idx);
-                    added = true;
-                }
+    ecs.write_storage::<PackMember>().insert(leader, PackMember{ leader }).expect("Unable to insert pack member");
+    for (px, py) in spots.into_iter().take((size - 1).max(0) as usize) {
+        if let Some(member) = raws::spawn_named_entity(raws, ecs, name, px, py) {
+            ecs.write_storage::<PackMember>().insert(member, PackMember{ leader }).expect("Unable to insert pack member");
+            raws::fill_pack(raws, ecs, member, map.depth);
+            taken.push(map.xy_idx(px, py));
+        }
+    }
+}
+
+/// Sizes up the monsters on the level against the player's experience, for the log line shown on arrival.
+pub fn level_feeling(ecs: &World) -> String {
+    let danger : i32 = (&ecs.read_storage::<Monster>(), &ecs.read_storage::<Position>(), &ecs.read_storage::<CombatStats>()).join()
+        .map(|(_m, _p, stats)| stats.max_hp)
+        .sum();
+    let player_level = ecs.read_storage::<Experience>().get(*ecs.fetch::<Entity>()).map_or(1, |e| e.level);
+    let fair = FAIR_DANGER_PER_LEVEL * player_level;
+    if danger >= fair * 3 {
+        "You feel a sense of dread...".to_string()
+    } else if danger >= fair * 2 {
+        "You feel nervous about this place.".to_string()
+    } else if danger * 2 >= fair {
+        "This place does not seem too dangerous.".to_string()
+    } else {
+        "This seems a quiet, peaceful place.".to_string()
+    }
+}
+
+/// Now and then brings a monster onto the level somewhere out of sight, drawn by the player's
+/// presence. Levels that are already crowded, and the overworld with its town, are left alone.
+pub fn wandering_monster(ecs: &mut World) {
+    let (branch, depth) = {
+        let map = ecs.fetch::<Map>();
+        (map.branch, map.depth)
+    };
+    if branch == Branch::Overworld { return; }
+    if ecs.write_resource::<RandomNumberGenerator>().roll_dice(1, WANDERER_ODDS) > 1 + depth / 2 { return; }
+
+    let population = (&ecs.read_storage::<Monster>(), &ecs.read_storage::<Position>()).join().count() as i32;
+    if population >= POPULATION_BUDGET + depth * 2 { return; }
+
+    let spot = {
+        let map = ecs.fetch::<Map>();
+        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
+        let mut spot = None;
+        for _i in 0..WANDERER_PLACEMENT_TRIES {
+            let x = rng.roll_dice(1, map.width - 2);
+            let y = rng.roll_dice(1, map.height - 2);
+            let idx = map.xy_idx(x, y);
+            if tile_walkable(map.tiles[idx]) && !map.is_blocked(idx) && !map.visible_tiles[idx] && map.tiles[idx] != TileType::BranchEntrance {
+                spot = Some((x, y));
+                break;
             }
         }
+        spot
+    };
+    let (x, y) = match spot {
+        Some(spot) => spot,
+        None => return
+    };
+
+    let name = monster_table(branch, depth).roll(&mut ecs.write_resource::<RandomNumberGenerator>());
+    let wanderer = {
+        let raws = raws::raws();
+        let wanderer = raws::spawn_named_entity(&raws, ecs, &name, x, y);
+        if let Some(wanderer) = wanderer { raws::fill_pack(&raws, ecs, wanderer, depth); }
+        wanderer
+    };
+    if let Some(wanderer) = wanderer {
+        let target = *ecs.fetch::<rltk::Point>();
+        ecs.write_storage::<HeardNoise>().insert(wanderer, HeardNoise{ x: target.x, y: target.y }).expect("Unable to insert noise");
     }
-    for idx in monster_spawn_points.iter() {
-        let x = *idx % MAP_WIDTH;
-        let y = *idx / MAP_WIDTH;
-        random_monster(ecs, x as i32, y as i32);
+}
+
+/// The people of town: would-be companions in the corners, shopkeepers, quest givers, the temple and the guards.
+fn townsfolk(ecs: &mut World, town: &Rect) {
+    let raws = raws::raws();
+    raws::spawn_named_entity(&raws, ecs, "Dog", town.x1 + 2, town.y1 + 2);
+    raws::spawn_named_entity(&raws, ecs, "Hireling", town.x2 - 2, town.y1 + 2);
+    let (x, y) = town.center();
+    raws::spawn_named_entity(&raws, ecs, "Campfire", x, y + 2);
+    raws::spawn_named_entity(&raws, ecs, "Enchanter's Table", town.x2 - 2, town.y2 - 2);
+    raws::spawn_named_entity(&raws, ecs, "Blacksmith's Anvil", town.x1 + 2, town.y2 - 2);
+    raws::spawn_named_entity(&raws, ecs, "Temple Altar", x, town.y2 - 2);
+    raws::spawn_named_entity(&raws, ecs, "Armorer", x - 4, town.y1 + 2);
+    raws::spawn_named_entity(&raws, ecs, "Alchemist", x, town.y1 + 2);
+    raws::spawn_named_entity(&raws, ecs, "Trader", x + 4, town.y1 + 2);
+    raws::spawn_named_entity(&raws, ecs, "Village Elder", x - 4, town.y2 - 2);
+    raws::spawn_named_entity(&raws, ecs, "Huntress", x + 4, town.y2 - 2);
+    raws::spawn_named_entity(&raws, ecs, "Town Guard", town.x1 + 2, y);
+    raws::spawn_named_entity(&raws, ecs, "Town Guard", town.x2 - 2, y);
+}
+
+/// Places the inhabitants of a prefab level exactly where its builder asked.
+pub fn spawn_list(ecs: &mut World, map: &Map, spawns: &[(usize, String)]) {
+    let raws = raws::raws();
+    for (idx, name) in spawns.iter() {
+        let x = *idx as i32 % map.width;
+        let y = *idx as i32 / map.width;
+        if let Some(entity) = raws::spawn_named_entity(&raws, ecs, name, x, y) {
+            raws::fill_container(&raws, ecs, entity, map.depth);
+            raws::fill_pack(&raws, ecs, entity, map.depth);
+            plan_patrol(&raws, ecs, map, entity, name);
+        }
     }
+}
 
-    for idx in item_spawn_points.iter() {
-        let x = *idx % MAP_WIDTH;
-        let y = *idx / MAP_WIDTH;
-        random_item(ecs, x as i32, y as i32);
+/// Connects the levers and pressure plates on each wire's first tile to whatever stands on its second.
+pub fn wire_mechanisms(ecs: &mut World, map: &Map, wires: &[(usize, usize)]) {
+    let entities = ecs.entities();
+    let positions = ecs.read_storage::<Position>();
+    let levers = ecs.read_storage::<Lever>();
+    let triggers = ecs.read_storage::<EntryTrigger>();
+    let mut wired = ecs.write_storage::<Wired>();
+
+    let placed : Vec<(Entity, usize)> = (&entities, &positions).join().map(|(e, pos)| (e, map.xy_idx(pos.x, pos.y))).collect();
+    for (from_idx, to_idx) in wires.iter() {
+        let targets : Vec<Entity> = placed.iter().filter(|(_, idx)| idx == to_idx).map(|(e, _)| *e).collect();
+        let switches = placed.iter().filter(|(e, idx)| idx == from_idx && (levers.get(*e).is_some() || triggers.get(*e).is_some()));
+        for (switch, _) in switches {
+            let switch = *switch;
+            match wired.get_mut(switch) {
+                Some(wire) => wire.targets.extend(targets.iter()),
+                None => { wired.insert(switch, Wired{ targets: targets.clone() }).expect("Unable to insert wire"); }
+            }
+        }
     }
 }
 
-fn health_potion(ecs: &mut World, x: i32, y: i32) {
-    ecs.create_entity()
-        .with(Position{ x,y })
-        .with(Renderable{
-            glyph: rltk::to_cp437('i'),
-            fg: RGB::named(rltk::MAGENTA),
-            bg: RGB::named(rltk::BLACK),
-            render_order: 2
-        })
-        .with(Name{ name: "Health Potion".to_string() })
-        .with(Item{})
-        .with(Consumable{})
-        .with(ProvidesHealing{ heal_amount: 8})
-        .marked::<SimpleMarker<SerializeMe>>()
-        .build();
+/// Places a locked door and the one key that opens it.
+pub fn locked_door(ecs: &mut World, map: &Map, door_idx: usize, key_idx: usize) {
+    let lock_id = map.branch.level_key(map.depth) * map.tile_count() as i32 + door_idx as i32;
+    let (door, key) = {
+        let raws = raws::raws();
+        let door = raws::spawn_named_entity(&raws, ecs, "Locked Door", door_idx as i32 % map.width, door_idx as i32 / map.width);
+        let key = raws::spawn_named_entity(&raws, ecs, "Door Key", key_idx as i32 % map.width, key_idx as i32 / map.width);
+        (door, key)
+    };
+    if let Some(door) = door {
+        ecs.write_storage::<Locked>().insert(door, Locked{ key: Some(lock_id) }).expect("Unable to insert lock");
+    }
+    if let Some(key) = key {
+        ecs.write_storage::<Key>().insert(key, Key{ id: Some(lock_id) }).expect("Unable to insert key");
+        ecs.write_storage::<Name>().insert(key, Name{ name: format!("Key ({})", map.branch.level_name(map.depth)) }).expect("Unable to insert name");
+    }
 }
 
-fn magic_missile_scroll(ecs: &mut World, x: i32, y: i32) {
+/// Spawns the Amulet the player has to retrieve from the deepest level.
+pub fn amulet(ecs: &mut World, x: i32, y: i32) {
     ecs.create_entity()
         .with(Position{ x, y })
         .with(Renderable{
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::CYAN),
+            glyph: rltk::to_cp437('"'),
+            fg: RGB::named(rltk::GOLD),
             bg: RGB::named(rltk::BLACK),
             render_order: 2
         })
-        .with(Name{ name : "Magic Missile Scroll".to_string() })
+        .with(Name{ name : "Amulet of Yala".to_string() })
         .with(Item{})
-        .with(Consumable{})
-        .with(Ranged{ range: 6 })
-        .with(InflictsDamage{ damage: 8 })
+        .with(MacGuffin{})
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
 }
 
-fn confusion_scroll(ecs: &mut World, x: i32, y: i32) {
+/// Sets a tile alight for the given number of turns.
+pub fn fire(ecs: &mut World, x: i32, y: i32, turns: i32) {
     ecs.create_entity()
         .with(Position{ x, y })
         .with(Renderable{
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::PINK),
-            bg: RGB::named(rltk::BLACK),
+            glyph: rltk::to_cp437('^'),
+            fg: RGB::named(rltk::ORANGE),
+            bg: RGB::named(rltk::DARK_RED),
             render_order: 2
         })
-        .with(Name{ name : "Confusion Scroll".to_string() })
-        .with(Item{})
-        .with(Consumable{})
-        .with(Ranged{ range: 6 })
-        .with(Confusion{ turns: 4 })
+        .with(Name{ name : "Fire".to_string() })
+        .with(Burning{ turns })
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
 }
 
-fn fireball_scroll(ecs: &mut World, x: i32, y: i32) {
+/// Leaves the remains of a slain creature on the floor; they rot away after a while.
+pub fn corpse(ecs: &mut World, name: &str, x: i32, y: i32) {
+    let expires_at = ecs.fetch::<TurnCounter>().turns_from_now(CORPSE_DECAY_TURNS);
     ecs.create_entity()
         .with(Position{ x, y })
         .with(Renderable{
-            glyph: rltk::to_cp437(')'),
-            fg: RGB::named(rltk::ORANGE),
+            glyph: rltk::to_cp437('%'),
+            fg: RGB::named(rltk::DARK_RED),
             bg: RGB::named(rltk::BLACK),
-            render_order: 2
+            render_order: 3
         })
-        .with(Name{ name : "Fireball Scroll".to_string() })
-        .with(Item{})
-        .with(Consumable{})
-        .with(Ranged{ range: 6 })
-        .with(InflictsDamage{ damage: 20 })
-        .with(AreaOfEffect{ radius: 3 })
+        .with(Name{ name : format!("{} corpse", name) })
+        .with(Decay{ expires_at })
+        .with(Flammable{})
+        .with(Butcherable{})
         .marked::<SimpleMarker<SerializeMe>>()
         .build();
 }
-
-fn random_item(ecs: &mut World, x: i32, y: i32) {
-    let roll :i32;
-    {
-        let mut rng = ecs.write_resource::<RandomNumberGenerator>();
-        roll = rng.roll_dice(1, 4);
-    }
-    match roll {
-        1 => { health_potion(ecs, x, y) }
-        2 => { fireball_scroll(ecs, x, y) }
-        3 => { confusion_scroll(ecs, x, y) }
-        _ => { magic_missile_scroll(ecs, x, y) }
-    }
-}
\ No newline at end of file
@@ -0,0 +1,94 @@
+use specs::prelude::*;
+use rltk::{Point, RandomNumberGenerator};
+use super::{SpecialAbilities, AbilityKind, Monster, Position, Viewshed, WantsToMelee, Confusion, Staggered, Name, Map,
+            RunState, OwnedBy, UsedAbility, Invisible, CanSeeInvisible, WantsToUseItem, InBackpack, invisibility_system::can_perceive, gamelog::{GameLog, LogCategory}, events::{GameEvents, GameEvent}, effects::{EffectQueue, EffectType, Targets}};
+
+/// Lets monsters use their raws-defined abilities during their turn, in place of moving or attacking.
+/// Runs ahead of the monster AI, which leaves alone anything that used an ability.
+pub struct SpecialAbilitySystem {}
+
+impl<'a> System<'a> for SpecialAbilitySystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Point>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, Map>,
+                        WriteExpect<'a, RandomNumberGenerator>,
+                        WriteExpect<'a, EffectQueue>,
+                        WriteExpect<'a, GameLog>,
+                        WriteExpect<'a, GameEvents>,
+                        ReadStorage<'a, SpecialAbilities>,
+                        ReadStorage<'a, Monster>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Viewshed>,
+                        ReadStorage<'a, Confusion>,
+                        ReadStorage<'a, Staggered>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, WantsToMelee>,
+                        WriteStorage<'a, UsedAbility>,
+                        ReadStorage<'a, OwnedBy>,
+                        ReadStorage<'a, Invisible>,
+                        ReadStorage<'a, CanSeeInvisible>,
+                        ReadStorage<'a, WantsToUseItem>,
+                        ReadStorage<'a, InBackpack> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, runstate, player_pos, player_entity, map, mut rng, mut effects, mut log, mut events, abilities, monsters,
+            positions, viewsheds, confused, staggered, names, mut wants_melee, mut used_ability, owned, invisible, see_invisible, using, backpack) = data;
+
+        used_ability.clear();
+        if *runstate != RunState::MonsterTurn { return; }
+
+        for (entity, abilities, _monster, pos, viewshed) in (&entities, &abilities, &monsters, &positions, &viewsheds).join() {
+            if confused.get(entity).is_some() || staggered.get(entity).is_some() || using.get(entity).is_some() { continue; }
+            if owned.get(entity).map_or(false, |o| o.owner == *player_entity) { continue; }
+            if !viewshed.visible_tiles.contains(&*player_pos) { continue; }
+            if !can_perceive(entity, *player_entity, &invisible, &see_invisible) { continue; }
+
+            let distance = rltk::DistanceAlg::Pythagoras.distance2d(Point::new(pos.x, pos.y), *player_pos);
+            for ability in abilities.abilities.iter() {
+                if ability.kind.is_passive() || distance > ability.range { continue; }
+                if rng.roll_dice(1, 100) > ability.chance { continue; }
+
+                let name = names.get(entity).map_or("Something", |n| n.name.as_str());
+                match ability.kind {
+                    AbilityKind::Web => {
+                        log.push(LogCategory::Combat, format!("{} spits a web!", name));
+                        effects.add_effect(Some(entity), EffectType::Web{ turns: ability.power }, Targets::Single{ target: *player_entity });
+                    }
+                    AbilityKind::LifeDrain => {
+                        effects.add_effect(Some(entity), EffectType::LifeDrain{ amount: ability.power }, Targets::Single{ target: *player_entity });
+                    }
+                    AbilityKind::Summon => {
+                        let free_tile = (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy)))
+                            .map(|(dx, dy)| (pos.x + dx, pos.y + dy))
+                            .filter(|(x, y)| *x > 0 && *x < map.width - 1 && *y > 0 && *y < map.height - 1)
+                            .map(|(x, y)| map.xy_idx(x, y))
+                            .find(|idx| !map.is_blocked(*idx));
+                        match (free_tile, &ability.summon) {
+                            (Some(tile), Some(summon)) => {
+                                log.push(LogCategory::Combat, format!("{} calls for help!", name));
+                                effects.add_effect(Some(entity), EffectType::Summon{ name: summon.clone(), turns: if ability.power > 0 { Some(ability.power) } else { None } }, Targets::Tile{ tile });
+                            }
+                            _ => continue
+                        }
+                    }
+                    AbilityKind::Steal => {
+                        // Nothing loose in the player's pack means nothing worth the grab
+                        if !backpack.join().any(|pack| pack.owner == *player_entity) { continue; }
+                        effects.add_effect(Some(entity), EffectType::Steal{ flee_turns: ability.power }, Targets::Single{ target: *player_entity });
+                    }
+                    AbilityKind::SelfDestruct | AbilityKind::Split | AbilityKind::Breed => {}
+                }
+
+                events.single_write(GameEvent::AbilityUsed{ by: entity, name: name.to_string(), ability: ability.kind });
+
+                // Using an ability takes the monster's whole turn
+                wants_melee.remove(entity);
+                used_ability.insert(entity, UsedAbility{}).expect("Unable to insert used ability");
+                break;
+            }
+        }
+    }
+}
@@ -0,0 +1,43 @@
+use specs::prelude::*;
+use super::{Pools, WellFed, FastHealing, RunState, turn_counter::TurnCounter, gamelog::{GameLog, LogCategory}};
+
+/// Entities that walked or rested this turn get a point of stamina back, plus one each for being well fed or a fast healer.
+pub struct StaminaSystem {}
+
+impl<'a> System<'a> for StaminaSystem {
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Entity>,
+                        ReadExpect<'a, TurnCounter>,
+                        WriteExpect<'a, GameLog>,
+                        WriteStorage<'a, Pools>,
+                        WriteStorage<'a, WellFed>,
+                        ReadStorage<'a, FastHealing> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, runstate, player_entity, turns, mut log, mut pools, mut well_fed, fast_healing) = data;
+
+        if *runstate != RunState::MonsterTurn { return; }
+
+        for (entity, pools) in (&entities, &mut pools).join() {
+            if !pools.exerted {
+                let regen = 1 + if well_fed.get(entity).is_some() { 1 } else { 0 }
+                    + if fast_healing.get(entity).is_some() { 1 } else { 0 };
+                pools.stamina.current = i32::min(pools.stamina.max, pools.stamina.current + regen);
+            }
+            pools.exerted = false;
+        }
+
+        let mut hungry : Vec<Entity> = Vec::new();
+        for (entity, fed) in (&entities, &mut well_fed).join() {
+            fed.turns -= turns.elapsed();
+            if fed.turns < 1 { hungry.push(entity); }
+        }
+        for entity in hungry {
+            well_fed.remove(entity);
+            if entity == *player_entity {
+                log.push(LogCategory::System, "You are no longer well fed.".to_string());
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+/// Reads one of the game's files, if it has been written.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read(name : &str) -> Option<String> {
+    std::fs::read_to_string(path(name)).ok()
+}
+
+/// Writes one of the game's files, or says why it couldn't be written.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write(name : &str, data : &str) -> Result<(), String> {
+    std::fs::write(path(name), data).map_err(|err| format!("{} could not be saved: {}", name, err))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn exists(name : &str) -> bool {
+    std::path::Path::new(&path(name)).exists()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn remove(name : &str) {
+    if exists(name) { std::fs::remove_file(path(name)).expect("Unable to delete file"); }
+}
+
+/// On the desktop the files sit in the working directory.
+#[cfg(not(target_arch = "wasm32"))]
+fn path(name : &str) -> String {
+    format!("./{}", name)
+}
+
+/// The browser has no files to write, so the web build keeps them in its local storage instead,
+/// under the same names. Storage can be switched off or full, in which case nothing is kept.
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read(name : &str) -> Option<String> {
+    local_storage()?.get_item(name).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write(name : &str, data : &str) -> Result<(), String> {
+    let storage = local_storage().ok_or(format!("{} could not be saved: the browser's storage is switched off", name))?;
+    storage.set_item(name, data).map_err(|_| format!("{} could not be saved: the browser's storage is full", name))
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn exists(name : &str) -> bool {
+    read(name).is_some()
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn remove(name : &str) {
+    if let Some(storage) = local_storage() {
+        storage.remove_item(name).expect("Unable to delete from local storage");
+    }
+}
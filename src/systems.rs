@@ -0,0 +1,105 @@
+use specs::prelude::*;
+use super::{VisibilitySystem, PackAI, MonsterItemAI, MonsterAI, AllyAI, SpecialAbilitySystem, MapIndexingSystem, TriggerSystem, MeleeCombatSystem,
+            ThrowSystem, RangedCombatSystem, FireSystem, DamageOverTimeSystem, CooldownSystem, MinionSystem, InvisibilitySystem,
+            RegenerationSystem, StaminaSystem, HungerSystem, AfflictionSystem, MutationSystem, SneakSystem, DoorSystem, DamageSystem, BossSystem, ItemCollectionSystem, ItemUseSystem, ItemDropSystem,
+            ItemRemoveSystem, DecaySystem, ArtifactSystem, ParticleSpawnSystem, ReproductionSystem, effects, events, achievements, bestiary, identified_items, run_stats, morgue, fire_system, reproduction_system, skill_training};
+
+/// Every system that runs each tick, in two dispatchers either side of the effects queue, plus
+/// the listeners that react to the events published along the way.
+/// Each system names the ones it has to wait for; anything else is free to run alongside it.
+/// To add a system, put it in the phase it belongs to and list what it depends on.
+pub struct Systems {
+    actions : Dispatcher<'static, 'static>,
+    upkeep : Dispatcher<'static, 'static>,
+    reactions : Dispatcher<'static, 'static>
+}
+
+impl Systems {
+    pub fn new() -> Systems {
+        // Everyone decides what to do, then acts on it
+        let actions = DispatcherBuilder::new()
+            .with(DoorSystem{}, "doors", &[])
+            .with(VisibilitySystem{}, "visibility", &["doors"])
+            .with(PackAI{}, "pack_ai", &["visibility"])
+            .with(MonsterItemAI{}, "monster_items", &["visibility"])
+            .with(SpecialAbilitySystem{}, "special_abilities", &["visibility", "monster_items"])
+            .with(MonsterAI{}, "monster_ai", &["visibility", "pack_ai", "monster_items", "special_abilities"])
+            .with(AllyAI{}, "ally_ai", &["visibility"])
+            .with(MapIndexingSystem::default(), "map_indexing", &["monster_ai", "ally_ai", "special_abilities"])
+            .with(TriggerSystem::default(), "triggers", &["map_indexing"])
+            .with(MeleeCombatSystem{}, "melee", &["map_indexing"])
+            .with(ThrowSystem{}, "throws", &["map_indexing"])
+            .with(RangedCombatSystem{}, "ranged_combat", &["map_indexing"])
+            .build();
+
+        // The consequences: damage, timers, items changing hands and finally the particles they all asked for
+        let upkeep = DispatcherBuilder::new()
+            .with(FireSystem{}, "fire", &[])
+            .with(DamageOverTimeSystem{}, "damage_over_time", &["fire"])
+            .with(CooldownSystem{}, "cooldowns", &[])
+            .with(MinionSystem{}, "minions", &[])
+            .with(InvisibilitySystem{}, "invisibility", &[])
+            .with(RegenerationSystem{}, "regeneration", &[])
+            .with(StaminaSystem{}, "stamina", &[])
+            .with(HungerSystem{}, "hunger", &["stamina"])
+            .with(AfflictionSystem{}, "afflictions", &[])
+            .with(MutationSystem{}, "mutations", &[])
+            .with(SneakSystem::default(), "sneaking", &[])
+            .with(DamageSystem{}, "damage", &["fire", "damage_over_time", "regeneration"])
+            .with(BossSystem{}, "bosses", &["damage"])
+            .with(ReproductionSystem::default(), "reproduction", &["damage"])
+            .with(ItemCollectionSystem{}, "item_collection", &["damage"])
+            .with(ItemUseSystem{}, "item_use", &["item_collection"])
+            .with(ItemDropSystem{}, "item_drop", &["item_use"])
+            .with(ItemRemoveSystem{}, "item_remove", &["item_drop"])
+            .with(DecaySystem{}, "decay", &["item_remove"])
+            .with(ArtifactSystem{}, "artifacts", &["item_remove"])
+            .with_barrier()
+            .with(ParticleSpawnSystem{}, "particles", &[])
+            .build();
+
+        // Everything that listens for gameplay events, once the dead have been cleared away
+        let reactions = DispatcherBuilder::new()
+            .with(events::EventLogSystem::default(), "event_log", &[])
+            .with(achievements::AchievementSystem::default(), "achievements", &["event_log"])
+            .with(bestiary::BestiarySystem::default(), "bestiary", &["event_log"])
+            .with(identified_items::IdentificationMemorySystem::default(), "identification_memory", &["achievements"])
+            .with(run_stats::RunStatsSystem::default(), "run_stats", &[])
+            .with(skill_training::SkillTrainingSystem::default(), "skill_training", &["event_log"])
+            .with(morgue::MorgueSystem::default(), "morgue", &["event_log", "run_stats"])
+            .build();
+
+        Systems{ actions, upkeep, reactions }
+    }
+
+    /// Lets the systems register whatever they need with the world, once every component is registered.
+    pub fn setup(&mut self, ecs : &mut World) {
+        self.actions.setup(ecs);
+        self.upkeep.setup(ecs);
+        self.reactions.setup(ecs);
+    }
+
+    pub fn run(&mut self, ecs : &mut World) {
+        Systems::dispatch(&mut self.actions, ecs);
+        effects::run_effects_queue(ecs);
+        Systems::dispatch(&mut self.upkeep, ecs);
+        fire_system::spawn_fires(ecs);
+        reproduction_system::bring_forth(ecs);
+    }
+
+    /// Hands this tick's events to whoever is listening for them.
+    pub fn react(&mut self, ecs : &mut World) {
+        Systems::dispatch(&mut self.reactions, ecs);
+    }
+
+    // Browsers have no threads to spread the systems over, so the web build runs them one at a time
+    #[cfg(not(target_arch = "wasm32"))]
+    fn dispatch(dispatcher : &mut Dispatcher<'static, 'static>, ecs : &World) {
+        dispatcher.dispatch(ecs);
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    fn dispatch(dispatcher : &mut Dispatcher<'static, 'static>, ecs : &World) {
+        dispatcher.dispatch_seq(ecs);
+    }
+}
@@ -0,0 +1,88 @@
+use specs::prelude::*;
+use rltk::Point;
+use super::{WantsToThrowItem, Thrown, Weight, Name, Position, InBackpack, Equipped, CombatStats, SufferDamage, DamageType,
+            Map, tile_walkable, StartsFire, Renderable, fire_system::Ignitions, gamelog::{GameLog, LogCategory}, animation::Animations};
+
+pub struct ThrowSystem {}
+
+impl<'a> System<'a> for ThrowSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        ReadExpect<'a, Map>,
+                        Entities<'a>,
+                        WriteStorage<'a, WantsToThrowItem>,
+                        ReadStorage<'a, Thrown>,
+                        ReadStorage<'a, Weight>,
+                        ReadStorage<'a, Name>,
+                        WriteStorage<'a, Position>,
+                        WriteStorage<'a, InBackpack>,
+                        WriteStorage<'a, Equipped>,
+                        ReadStorage<'a, CombatStats>,
+                        WriteStorage<'a, SufferDamage>,
+                        ReadStorage<'a, StartsFire>,
+                        WriteExpect<'a, Ignitions>,
+                        ReadStorage<'a, Renderable>,
+                        WriteExpect<'a, Animations>
+    );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (player_entity, mut log, map, entities, mut wants_throw, thrown, weights, names, mut positions,
+            mut backpack, mut equipped, combat_stats, mut suffer_damage, starts_fire, mut ignitions, renderables, mut animations) = data;
+
+        for (entity, throw) in (&entities, &wants_throw).join() {
+            let origin = match positions.get(entity) {
+                Some(pos) => Point::new(pos.x, pos.y),
+                None => continue
+            };
+
+            // Follow the flight path until it hits a creature or a wall
+            let mut landing = origin;
+            let mut victim : Option<Entity> = None;
+            let mut flight = vec![origin];
+            for step in rltk::line2d(rltk::LineAlg::Bresenham, origin, throw.target).iter().skip(1) {
+                if step.x < 0 || step.x >= map.width || step.y < 0 || step.y >= map.height { break; }
+                let idx = map.xy_idx(step.x, step.y);
+                if !tile_walkable(map.tiles[idx]) { break; }
+                landing = *step;
+                flight.push(landing);
+                victim = map.entities_at(idx).iter().find(|e| combat_stats.get(**e).is_some()).copied();
+                if victim.is_some() { break; }
+            }
+            let (glyph, fg) = renderables.get(throw.item).map_or((rltk::to_cp437('*'), rltk::RGB::named(rltk::WHITE)), |r| (r.glyph, r.fg));
+            animations.launch(flight, glyph, fg);
+
+            backpack.remove(throw.item);
+            equipped.remove(throw.item);
+            positions.insert(throw.item, Position{ x: landing.x, y: landing.y }).expect("Unable to insert position");
+
+            let item_name = &names.get(throw.item).unwrap().name;
+            match victim {
+                Some(victim) => {
+                    // Improvised missiles hurt according to how heavy they are
+                    let damage = match thrown.get(throw.item) {
+                        Some(thrown) => thrown.damage,
+                        None => i32::max(1, weights.get(throw.item).map_or(1, |w| w.weight / 2))
+                    };
+                    SufferDamage::new_damage(&mut suffer_damage, victim, damage, DamageType::Physical, entity == *player_entity, Some(entity));
+                    if entity == *player_entity {
+                        log.push(LogCategory::Combat, format!("You throw the {} at {}, for {} hp.", item_name, names.get(victim).unwrap().name, damage));
+                    }
+                }
+                None => {
+                    if entity == *player_entity {
+                        log.push(LogCategory::Combat, format!("You throw the {}.", item_name));
+                    }
+                }
+            }
+
+            // Flasks of oil shatter and set the landing tile alight
+            if let Some(fire) = starts_fire.get(throw.item) {
+                ignitions.ignite(landing, fire.turns);
+                entities.delete(throw.item).expect("Delete failed");
+            }
+        }
+
+        wants_throw.clear();
+    }
+}
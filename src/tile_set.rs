@@ -0,0 +1,54 @@
+use rltk::Point;
+use serde::{Serialize, Deserialize};
+
+/// A set of map tiles around a centre point, one bit each. Used for what a viewshed can see, so
+/// looking a tile up doesn't mean searching a list, and recomputing reuses the same storage.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct TileSet {
+    centre : Option<Point>,
+    left : i32,
+    top : i32,
+    size : i32,
+    bits : Vec<u64>
+}
+
+impl TileSet {
+    /// Empties the set, making room for every tile within `range` of `centre`.
+    pub fn reset(&mut self, centre : Point, range : i32) {
+        self.centre = Some(centre);
+        self.left = centre.x - range;
+        self.top = centre.y - range;
+        self.size = range * 2 + 1;
+        let words = ((self.size * self.size) as usize + 63) / 64;
+        self.bits.clear();
+        self.bits.resize(words, 0);
+    }
+
+    /// Where the set was last centred, if it has been filled in at all.
+    pub fn centre(&self) -> Option<Point> {
+        self.centre
+    }
+
+    fn bit(&self, point : &Point) -> Option<usize> {
+        let (x, y) = (point.x - self.left, point.y - self.top);
+        if x < 0 || x >= self.size || y < 0 || y >= self.size { return None; }
+        Some((y * self.size + x) as usize)
+    }
+
+    /// Adds a tile; anything outside the range given to `reset` is ignored.
+    pub fn insert(&mut self, point : Point) {
+        if let Some(bit) = self.bit(&point) {
+            self.bits[bit / 64] |= 1 << (bit % 64);
+        }
+    }
+
+    pub fn contains(&self, point : &Point) -> bool {
+        self.bit(point).map_or(false, |bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Point> + '_ {
+        (0 .. self.size * self.size)
+            .filter(move |bit| self.bits[*bit as usize / 64] & (1 << (*bit as usize % 64)) != 0)
+            .map(move |bit| Point::new(self.left + bit % self.size, self.top + bit / self.size))
+    }
+}
@@ -0,0 +1,86 @@
+use std::collections::HashSet;
+use specs::prelude::*;
+use super::{Map, Position, Name, EntryTrigger, Hidden, TeleportsRandomly, Scripted, PortalLink, CombatStats, RunState, Wired, Afflicts,
+    gamelog::{GameLog, LogCategory}, dungeon::LevelTeleport, effects::{EffectQueue, EffectType, Targets}};
+
+/// Fires traps and portals for whoever has just stepped onto them: the player on their turn, everyone else on the monsters' turn.
+#[derive(Default)]
+pub struct TriggerSystem {
+    /// Who was standing on which trigger when last checked, so that staying put doesn't set it off again.
+    standing : HashSet<(Entity, Entity)>
+}
+
+impl<'a> System<'a> for TriggerSystem {
+    #[allow(clippy::type_complexity)]
+    type SystemData = ( Entities<'a>,
+                        ReadExpect<'a, Map>,
+                        ReadExpect<'a, RunState>,
+                        ReadExpect<'a, Entity>,
+                        WriteExpect<'a, GameLog>,
+                        WriteExpect<'a, EffectQueue>,
+                        WriteExpect<'a, LevelTeleport>,
+                        ReadStorage<'a, Position>,
+                        ReadStorage<'a, Name>,
+                        ReadStorage<'a, EntryTrigger>,
+                        WriteStorage<'a, Hidden>,
+                        ReadStorage<'a, TeleportsRandomly>,
+                        ReadStorage<'a, Scripted>,
+                        ReadStorage<'a, PortalLink>,
+                        ReadStorage<'a, CombatStats>,
+                        WriteStorage<'a, Wired>,
+                        ReadStorage<'a, Afflicts> );
+
+    fn run(&mut self, data : Self::SystemData) {
+        let (entities, map, runstate, player_entity, mut log, mut effects, mut level_teleport, positions, names,
+            triggers, mut hidden, teleports, scripted, portals, combat_stats, mut wired, afflicts) = data;
+
+        let players_turn = match *runstate {
+            RunState::PlayerTurn => true,
+            RunState::MonsterTurn => false,
+            _ => return
+        };
+
+        // Only those whose turn it is can have stepped on or off a trigger since the last check
+        let player = *player_entity;
+        let (was_standing, others) : (HashSet<_>, HashSet<_>) = self.standing.drain()
+            .partition(|(_trigger, victim)| (*victim == player) == players_turn);
+        self.standing = others;
+
+        for (trigger_entity, _trigger, pos) in (&entities, &triggers, &positions).join() {
+            let idx = map.xy_idx(pos.x, pos.y);
+            for victim in map.entities_at(idx).iter() {
+                if combat_stats.get(*victim).is_none() || (*victim == *player_entity) != players_turn { continue; }
+                self.standing.insert((trigger_entity, *victim));
+                if was_standing.contains(&(trigger_entity, *victim)) { continue; }
+
+                if let Some(portal) = portals.get(trigger_entity) {
+                    if *victim == *player_entity {
+                        log.push(LogCategory::System, "You step through the portal.".to_string());
+                        level_teleport.destination = Some((portal.branch, portal.depth, rltk::Point::new(portal.x, portal.y)));
+                        entities.delete(trigger_entity).expect("Unable to delete portal");
+                    }
+                    continue;
+                }
+
+                hidden.remove(trigger_entity);
+                if let Some(name) = names.get(trigger_entity) {
+                    log.push(LogCategory::System, format!("{} triggers!", name.name));
+                }
+                if teleports.get(trigger_entity).is_some() {
+                    effects.add_effect(Some(trigger_entity), EffectType::TeleportRandom, Targets::Single{ target: *victim });
+                }
+                if let Some(script) = scripted.get(trigger_entity) {
+                    effects.add_effect(Some(trigger_entity), EffectType::Script{ source: script.script.clone() }, Targets::Single{ target: *victim });
+                }
+                if let Some(afflicts) = afflicts.get(trigger_entity) {
+                    effects.add_effect(Some(trigger_entity), EffectType::Afflict{ kind: afflicts.kind }, Targets::Single{ target: *victim });
+                }
+                if let Some(wire) = wired.remove(trigger_entity) {
+                    for target in wire.targets.iter() {
+                        effects.add_effect(Some(trigger_entity), EffectType::Activate, Targets::Single{ target: *target });
+                    }
+                }
+            }
+        }
+    }
+}
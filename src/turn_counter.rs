@@ -0,0 +1,31 @@
+use serde::{Serialize, Deserialize};
+
+/// Global game clock. Turns pass every time the monsters have their go.
+#[derive(Default, Serialize, Deserialize, Clone, Copy)]
+pub struct TurnCounter {
+    pub turn : i32,
+    /// How far the clock moved on at the start of this round of monster turns.
+    #[serde(skip)]
+    elapsed : i32
+}
+
+impl TurnCounter {
+    pub fn advance_by(&mut self, turns : i32) {
+        self.turn += turns;
+        self.elapsed = turns;
+    }
+
+    /// Turns that have passed this round, which is what timed statuses and hunger count down by.
+    pub fn elapsed(&self) -> i32 {
+        self.elapsed
+    }
+
+    /// Returns the turn number `turns` turns from now, used to schedule effects.
+    pub fn turns_from_now(&self, turns : i32) -> i32 {
+        self.turn + turns
+    }
+
+    pub fn has_passed(&self, turn : i32) -> bool {
+        self.turn >= turn
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::VecDeque;
+use specs::prelude::*;
+use serde::{Serialize, Deserialize};
+use super::{Map, Branch, RunState, challenge::Challenge, replay::Replay, saveload_system,
+            gamelog::{GameLog, LogCategory}};
+
+/// How many of the player's turns forgiving mode keeps a copy of the world for.
+const SNAPSHOTS : usize = 5;
+
+/// Whether the run lets the player take a turn back, picked when creating the character. Saved
+/// with the game, and marked on the run's score and morgue file.
+#[derive(PartialEq, Eq, Copy, Clone, Serialize, Deserialize, Default)]
+pub enum RunMode { #[default] Standard, Forgiving }
+
+impl RunMode {
+    pub const ALL : [RunMode; 2] = [RunMode::Standard, RunMode::Forgiving];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            RunMode::Standard => "Standard",
+            RunMode::Forgiving => "Forgiving"
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            RunMode::Standard => "Every move is final.",
+            RunMode::Forgiving => "Take back one turn per level with Ctrl+Z; the run is marked as forgiving."
+        }
+    }
+}
+
+/// Forgiving mode's copies of the world from the start of the player's last few turns on this
+/// level, as the save game layer writes them, and the levels whose one undo has been spent.
+#[derive(Default)]
+pub struct Snapshots {
+    level : Option<(Branch, i32)>,
+    turns : VecDeque<String>,
+    undone : Vec<(Branch, i32)>
+}
+
+/// Whether this run can be taken back: only in forgiving mode, and never in the daily challenge.
+pub fn is_forgiving(ecs : &World) -> bool {
+    *ecs.fetch::<RunMode>() == RunMode::Forgiving && !ecs.fetch::<Challenge>().is_daily()
+}
+
+/// Keeps a copy of the world as the player is about to act. A new level starts a fresh history.
+pub fn take_snapshot(ecs : &mut World) {
+    if !is_forgiving(ecs) || ecs.fetch::<Replay>().is_playing() { return; }
+    let level = {
+        let map = ecs.fetch::<Map>();
+        (map.branch, map.depth)
+    };
+    let data = saveload_system::serialize_world(ecs);
+    let mut snapshots = ecs.write_resource::<Snapshots>();
+    if snapshots.level != Some(level) {
+        snapshots.level = Some(level);
+        snapshots.turns.clear();
+    }
+    snapshots.turns.push_back(data);
+    while snapshots.turns.len() > SNAPSHOTS {
+        snapshots.turns.pop_front();
+    }
+}
+
+/// Puts the world back as it was before the player's last turn, once per level. The dice aren't
+/// wound back with it, and a run that has been rewound can no longer be recorded for replay.
+pub fn undo(ecs : &mut World) -> RunState {
+    if !is_forgiving(ecs) {
+        ecs.write_resource::<GameLog>().push(LogCategory::System, "Undo is only available in forgiving mode.".to_string());
+        return RunState::AwaitingInput;
+    }
+    let restore = {
+        let mut snapshots = ecs.write_resource::<Snapshots>();
+        let level = snapshots.level;
+        if level.map_or(false, |level| snapshots.undone.contains(&level)) {
+            Err("You have already undone a turn on this level.")
+        } else if snapshots.turns.len() < 2 {
+            Err("There is nothing to undo.")
+        } else {
+            // The newest copy is the world as it is now; the one before it is where the last turn began
+            snapshots.turns.pop_back();
+            let data = snapshots.turns.pop_back().expect("No snapshot to undo to");
+            snapshots.undone.extend(level);
+            Ok(data)
+        }
+    };
+    match restore {
+        Err(reason) => ecs.write_resource::<GameLog>().push(LogCategory::System, reason.to_string()),
+        Ok(data) => {
+            saveload_system::deserialize_world(ecs, &data);
+            ecs.write_resource::<Replay>().stop();
+            ecs.write_resource::<GameLog>().push(LogCategory::System, "You take back your last move.".to_string());
+        }
+    }
+    RunState::AwaitingInput
+}
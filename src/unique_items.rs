@@ -0,0 +1,15 @@
+use std::collections::HashSet;
+use serde::{Serialize, Deserialize};
+
+/// Tracks which artifacts have already been generated (and seen) this run.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct UniqueItems {
+    pub spawned : HashSet<String>,
+    pub seen : HashSet<String>
+}
+
+impl UniqueItems {
+    pub fn has_spawned(&self, name : &str) -> bool {
+        self.spawned.contains(name)
+    }
+}
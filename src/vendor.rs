@@ -0,0 +1,95 @@
+use specs::prelude::*;
+use super::{Vendor, Value, Ammo, Affixed, Attributes, InBackpack, Name, Purse, Map, RunState, gui::VendorMode,
+            gamesystem::attr_bonus, gamelog::{GameLog, LogCategory}, turn_counter::TurnCounter, Faction,
+            reputation::{Reputation, Reaction}, raws};
+
+/// Turns between a shopkeeper's deliveries of fresh stock.
+pub const RESTOCK_TURNS : i32 = 300;
+/// Shopkeepers pay this percentage of an item's worth, before haggling.
+const SELL_PERCENT : i32 = 40;
+/// Each point of Intelligence bonus knocks this much (in 100) off prices, and adds half as much to offers.
+const HAGGLE_PERCENT : i32 = 5;
+/// Extra worth of every affix and point of enchantment on a piece of gear.
+const UPGRADE_VALUE : i32 = 30;
+
+/// What the item is worth: its base value, plus its upgrades, times the size of a stack.
+fn item_value(ecs : &World, item : Entity) -> i32 {
+    let base = match ecs.read_storage::<Value>().get(item) {
+        Some(value) => value.gold,
+        None => return 0
+    };
+    let upgrades = ecs.read_storage::<Affixed>().get(item)
+        .map_or(0, |a| a.enchantment + a.prefix.is_some() as i32 + a.suffix.is_some() as i32);
+    let count = ecs.read_storage::<Ammo>().get(item).map_or(1, |a| a.count);
+    (base + upgrades * UPGRADE_VALUE) * count
+}
+
+fn haggling(ecs : &World, customer : Entity) -> i32 {
+    attr_bonus(ecs.read_storage::<Attributes>().get(customer).map_or(10, |a| a.intelligence))
+}
+
+pub fn buy_price(ecs : &World, customer : Entity, item : Entity) -> i32 {
+    let percent = i32::max(50, 100 - haggling(ecs, customer) * HAGGLE_PERCENT);
+    i32::max(1, item_value(ecs, item) * percent / 100)
+}
+
+/// What the shopkeeper offers for the item; worthless things fetch nothing.
+pub fn sell_price(ecs : &World, customer : Entity, item : Entity) -> i32 {
+    let percent = i32::min(80, i32::max(10, SELL_PERCENT + haggling(ecs, customer) * HAGGLE_PERCENT / 2));
+    item_value(ecs, item) * percent / 100
+}
+
+/// Greets the customer, unless the town has turned against them. A delivery of fresh wares comes first, if one is due.
+pub fn open_shop(ecs : &mut World, vendor : Entity) -> RunState {
+    let faction = ecs.read_storage::<Faction>().get(vendor).map(|f| f.name.clone());
+    if faction.map_or(false, |f| ecs.fetch::<Reputation>().reaction(&f) != Reaction::Friendly) {
+        let name = ecs.read_storage::<Name>().get(vendor).map_or("shopkeeper".to_string(), |n| n.name.clone());
+        ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("The {} refuses to deal with you.", name));
+        return RunState::AwaitingInput;
+    }
+    let due = {
+        let turns = ecs.fetch::<TurnCounter>();
+        ecs.read_storage::<Vendor>().get(vendor).map_or(false, |v| turns.has_passed(v.restock_turn))
+    };
+    if due {
+        let depth = ecs.fetch::<Map>().depth;
+        raws::stock_vendor(&raws::raws(), ecs, vendor, depth);
+        let restock_turn = ecs.fetch::<TurnCounter>().turns_from_now(RESTOCK_TURNS);
+        if let Some(v) = ecs.write_storage::<Vendor>().get_mut(vendor) {
+            v.restock_turn = restock_turn;
+        }
+    }
+    RunState::ShowVendor{ vendor, mode: VendorMode::Buy }
+}
+
+pub fn buy(ecs : &mut World, customer : Entity, item : Entity) {
+    let price = buy_price(ecs, customer, item);
+    let name = ecs.read_storage::<Name>().get(item).map_or("item".to_string(), |n| n.name.clone());
+    let paid = match ecs.write_storage::<Purse>().get_mut(customer) {
+        Some(purse) if purse.gold >= price => {
+            purse.gold -= price;
+            true
+        }
+        _ => false
+    };
+    if !paid {
+        ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("You can't afford the {} ({} gold).", name, price));
+        return;
+    }
+    ecs.write_storage::<InBackpack>().insert(item, InBackpack{ owner: customer }).expect("Unable to insert backpack entry");
+    ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("You buy the {} for {} gold.", name, price));
+}
+
+pub fn sell(ecs : &mut World, customer : Entity, vendor : Entity, item : Entity) {
+    let price = sell_price(ecs, customer, item);
+    let name = ecs.read_storage::<Name>().get(item).map_or("item".to_string(), |n| n.name.clone());
+    if price < 1 {
+        ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("Nobody wants your {}.", name));
+        return;
+    }
+    if let Some(purse) = ecs.write_storage::<Purse>().get_mut(customer) {
+        purse.gold += price;
+    }
+    ecs.write_storage::<InBackpack>().insert(item, InBackpack{ owner: vendor }).expect("Unable to insert backpack entry");
+    ecs.write_resource::<GameLog>().push(LogCategory::Items, format!("You sell the {} for {} gold.", name, price));
+}
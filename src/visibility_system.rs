@@ -1,25 +1,56 @@
+use std::collections::HashMap;
 use rltk::{field_of_view};
 use specs::prelude::*;
-use crate::map::Map;
-use super::{Viewshed, Position, Player, Point};
+use crate::map::{Map, Remembered};
+use super::{Viewshed, Position, Player, Point, FarSight, Mutations, Mutation, Renderable, Hidden, Invisible, CanSeeInvisible, ParticleLifetime,
+            level_up::FAR_SIGHT_BONUS, options::Options, fov::symmetric_field_of_view};
+
+/// Marks every viewshed out of date, for when the map changes in a way that could open or close
+/// lines of sight.
+pub fn invalidate_viewsheds(ecs : &World) {
+    for viewshed in (&mut ecs.write_storage::<Viewshed>()).join() {
+        viewshed.dirty = true;
+    }
+}
 
 pub struct VisibilitySystem {}
 
 impl<'a> System<'a> for VisibilitySystem {
+    #[allow(clippy::type_complexity)]
     type SystemData = ( WriteExpect<'a, Map>,
                         Entities<'a>,
                         WriteStorage<'a, Viewshed>,
                         WriteStorage<'a, Position>,
-                        ReadStorage<'a, Player>);
+                        ReadStorage<'a, Player>,
+                        ReadStorage<'a, FarSight>,
+                        ReadStorage<'a, Mutations>,
+                        ReadStorage<'a, Renderable>,
+                        ReadStorage<'a, Hidden>,
+                        ReadStorage<'a, Invisible>,
+                        ReadStorage<'a, CanSeeInvisible>,
+                        ReadStorage<'a, ParticleLifetime>,
+                        ReadExpect<'a, Options>);
 
     fn run(&mut self, data : Self::SystemData) {
-        let (mut map, entities, mut viewshed, pos, player) = data;
+        let (mut map, entities, mut viewshed, pos, player, far_sight, mutations, renderables, hidden, invisible, see_invisible, particles, options) = data;
 
         for (ent,viewshed, pos) in (&entities, &mut viewshed, &pos).join() {
+            // Nobody's view changes unless they moved, or something marked it out of date
+            let centre = Point::new(pos.x, pos.y);
+            if !viewshed.dirty && viewshed.visible_tiles.centre() == Some(centre) { continue; }
             viewshed.dirty = false;
-            viewshed.visible_tiles.clear();
-            viewshed.visible_tiles = field_of_view(Point::new(pos.x, pos.y), viewshed.range, &*map);
-            viewshed.visible_tiles.retain(|p| p.x >= 0 && p.x < map.width && p.y >= 0 && p.y < map.height );
+            let range = viewshed.range + if far_sight.get(ent).is_some() { FAR_SIGHT_BONUS } else { 0 }
+                + mutations.get(ent).map_or(0, |m| m.total(Mutation::sight));
+            let range = i32::max(1, range);
+            let visible = if options.symmetric_fov {
+                symmetric_field_of_view(centre, range, &map)
+            } else {
+                field_of_view(centre, range, &*map)
+            };
+            viewshed.visible_tiles.reset(centre, range);
+            for p in visible.into_iter().filter(|p| p.x >= 0 && p.x < map.width && p.y >= 0 && p.y < map.height) {
+                viewshed.visible_tiles.insert(p);
+            }
 
             // If this is the player, reveal what they can see
             let _p : Option<&Player> = player.get(ent);
@@ -32,5 +63,25 @@ impl<'a> System<'a> for VisibilitySystem {
                 }
             }
         }
+
+        // Remember the top thing on each tile in view, so it can still be shown once out of sight
+        let sees_invisible = (&player, &see_invisible).join().next().is_some();
+        let mut seen : HashMap<usize, &Renderable> = HashMap::new();
+        for (ent, pos, render) in (&entities, &pos, &renderables).join() {
+            if player.get(ent).is_some() || particles.get(ent).is_some() || hidden.get(ent).is_some() { continue; }
+            if invisible.get(ent).is_some() && !sees_invisible { continue; }
+            if pos.x < 0 || pos.x >= map.width || pos.y < 0 || pos.y >= map.height { continue; }
+            let idx = map.xy_idx(pos.x, pos.y);
+            if !map.visible_tiles[idx] { continue; }
+            if seen.get(&idx).map_or(true, |top| render.render_order < top.render_order) {
+                seen.insert(idx, render);
+            }
+        }
+        let map = &mut *map;
+        let visible = &map.visible_tiles;
+        map.remembered.retain(|idx, _| !visible[*idx]);
+        for (idx, render) in seen {
+            map.remembered.insert(idx, Remembered{ glyph: render.glyph, fg: render.fg });
+        }
     }
-}
\ No newline at end of file
+}
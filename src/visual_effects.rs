@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use specs::prelude::*;
+use rltk::Point;
+use super::options::Options;
+
+/// How long a struck entity's glyph stays lit up.
+const FLASH_MS : f32 = 150.0;
+/// How long the border stays red after the player is hurt.
+const BORDER_FLASH_MS : f32 = 200.0;
+/// How long the view shakes for, after a big hit or an explosion.
+const SHAKE_MS : f32 = 250.0;
+/// How often the shaking view jumps to a new offset.
+const SHAKE_STEP_MS : f32 = 40.0;
+/// A hit taking at least this share of the player's health (in 100) shakes the view.
+const BIG_HIT_PERCENT : i32 = 20;
+
+/// Short-lived effects drawn over the view when things get hurt. Like the animations, they only
+/// change how the map looks, and the options can turn each kind off.
+#[derive(Default)]
+pub struct VisualEffects {
+    flashes : HashMap<Entity, f32>,
+    border_ms : f32,
+    shake_ms : f32
+}
+
+impl VisualEffects {
+    /// Records damage dealt to an entity; hurting the player can also flash the border and shake the view.
+    pub fn hit(&mut self, entity : Entity, is_player : bool, damage : i32, max_hp : i32) {
+        self.flashes.insert(entity, FLASH_MS);
+        if is_player {
+            self.border_ms = BORDER_FLASH_MS;
+            if damage * 100 >= max_hp * BIG_HIT_PERCENT {
+                self.shake();
+            }
+        }
+    }
+
+    /// Shakes the view, for explosions and anything else that should rattle the screen.
+    pub fn shake(&mut self) {
+        self.shake_ms = SHAKE_MS;
+    }
+
+    /// Whether the entity was just hit, and should be drawn lit up.
+    pub fn flashing(&self, entity : Entity) -> bool {
+        self.flashes.contains_key(&entity)
+    }
+
+    /// Whether the border around the view should be drawn red.
+    pub fn border_flashing(&self) -> bool {
+        self.border_ms > 0.0
+    }
+
+    /// How far the view is knocked from where it belongs this frame.
+    pub fn shake_offset(&self) -> Point {
+        if self.shake_ms <= 0.0 { return Point::new(0, 0); }
+        // Step around a small fixed pattern rather than roll dice, so the game's own RNG is untouched
+        const PATTERN : [(i32, i32); 4] = [(1, 0), (-1, 1), (0, -1), (-1, 0)];
+        let (x, y) = PATTERN[(self.shake_ms / SHAKE_STEP_MS) as usize % PATTERN.len()];
+        Point::new(x, y)
+    }
+}
+
+/// Counts every effect down by a frame, dropping whatever the options have turned off.
+pub fn update(ecs : &World, frame_time_ms : f32) {
+    let options = ecs.fetch::<Options>();
+    let mut effects = ecs.write_resource::<VisualEffects>();
+
+    if !options.hit_flashes {
+        effects.flashes.clear();
+        effects.border_ms = 0.0;
+    }
+    if !options.screen_shake {
+        effects.shake_ms = 0.0;
+    }
+
+    effects.flashes.retain(|_entity, remaining| {
+        *remaining -= frame_time_ms;
+        *remaining > 0.0
+    });
+    effects.border_ms = f32::max(0.0, effects.border_ms - frame_time_ms);
+    effects.shake_ms = f32::max(0.0, effects.shake_ms - frame_time_ms);
+}
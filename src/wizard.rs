@@ -0,0 +1,158 @@
+use specs::prelude::*;
+use rltk::Point;
+use super::{Position, Viewshed, CombatStats, Attributes, Purse, Map, TileType, Branch, tile_walkable, gamelog::{GameLog, LogCategory}, raws};
+
+/// Debug mode, switched on with the `--wizard` command line flag. The console is opened with the backquote key.
+#[derive(Default)]
+pub struct WizardMode {
+    pub enabled : bool,
+    pub god_mode : bool,
+    pub input : String
+}
+
+const HELP : &str = "Commands: spawn <name>, give <name>, reveal, teleport <x> <y>, stairs, set <stat> <value>, god";
+
+fn log(ecs : &World, message : String) {
+    ecs.write_resource::<GameLog>().push(LogCategory::System, message);
+}
+
+/// Whether the name reads with "an" in front of it rather than "a".
+fn takes_an(name : &str) -> bool {
+    name.starts_with(|c : char| "aeiouAEIOU".contains(c))
+}
+
+/// Carries out a line typed into the wizard console, reporting back through the log.
+pub fn run_command(ecs : &mut World, command : &str) {
+    let words : Vec<&str> = command.split_whitespace().collect();
+    match words.as_slice() {
+        ["spawn", name @ ..] if !name.is_empty() => spawn(ecs, &name.join(" ")),
+        ["give", name @ ..] if !name.is_empty() => give(ecs, &name.join(" ")),
+        ["reveal"] => {
+            let mut map = ecs.write_resource::<Map>();
+            for tile in map.revealed_tiles.iter_mut() {
+                *tile = true;
+            }
+        }
+        ["teleport", x, y] => match (x.parse::<i32>(), y.parse::<i32>()) {
+            (Ok(x), Ok(y)) => teleport(ecs, x, y),
+            _ => log(ecs, "Teleport where?".to_string())
+        },
+        ["stairs"] => {
+            let stairs = {
+                let map = ecs.fetch::<Map>();
+                map.branch_exits.iter()
+                    .find(|(_, branch)| **branch == Branch::Dungeon)
+                    .map(|(idx, _)| (*idx as i32 % map.width, *idx as i32 / map.width))
+                    .or_else(|| map.find_tile(TileType::DownStairs))
+            };
+            match stairs {
+                Some((x, y)) => teleport(ecs, x, y),
+                None => log(ecs, "There is no way down from this level.".to_string())
+            }
+        }
+        ["set", stat, value] => match value.parse::<i32>() {
+            Ok(value) => set_stat(ecs, stat, value),
+            Err(_) => log(ecs, format!("{} is not a number.", value))
+        },
+        ["god"] => {
+            let god_mode = {
+                let mut wizard = ecs.write_resource::<WizardMode>();
+                wizard.god_mode = !wizard.god_mode;
+                wizard.god_mode
+            };
+            log(ecs, format!("God mode {}.", if god_mode { "on" } else { "off" }));
+        }
+        _ => log(ecs, HELP.to_string())
+    }
+}
+
+/// Spawns anything from the raws next to the player. Names are matched regardless of case.
+fn spawn(ecs : &mut World, name : &str) {
+    let ppos = *ecs.fetch::<Point>();
+    let spot = {
+        let map = ecs.fetch::<Map>();
+        (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (ppos.x + dx, ppos.y + dy)))
+            .filter(|(x, y)| (*x != ppos.x || *y != ppos.y) && *x > 0 && *x < map.width - 1 && *y > 0 && *y < map.height - 1)
+            .find(|(x, y)| { let idx = map.xy_idx(*x, *y); tile_walkable(map.tiles[idx]) && !map.is_blocked(idx) })
+    };
+    let (x, y) = spot.unwrap_or((ppos.x, ppos.y));
+
+    let spawned = {
+        let raws = raws::raws();
+        match raws::find_raw_name(&raws, name) {
+            Some(key) => raws::spawn_named_entity(&raws, ecs, &key, x, y).map(|_| key),
+            None => None
+        }
+    };
+    match spawned {
+        Some(key) => log(ecs, format!("You conjure {} {}.", if takes_an(&key) { "an" } else { "a" }, key)),
+        None => log(ecs, format!("There is nothing called '{}' in the raws.", name))
+    }
+}
+
+/// Puts an item from the raws straight into the player's backpack.
+fn give(ecs : &mut World, name : &str) {
+    let player = *ecs.fetch::<Entity>();
+    let given = {
+        let raws = raws::raws();
+        match raws::find_raw_name(&raws, name) {
+            Some(key) => raws::spawn_in_backpack(&raws, ecs, &key, player).map(|_| key),
+            None => None
+        }
+    };
+    match given {
+        Some(key) => log(ecs, format!("{} {} appears in your pack.", if takes_an(&key) { "An" } else { "A" }, key)),
+        None => log(ecs, format!("There is no item called '{}' in the raws.", name))
+    }
+}
+
+fn teleport(ecs : &mut World, x : i32, y : i32) {
+    let open = {
+        let map = ecs.fetch::<Map>();
+        x > 0 && x < map.width - 1 && y > 0 && y < map.height - 1 && tile_walkable(map.tiles[map.xy_idx(x, y)])
+    };
+    if !open {
+        log(ecs, format!("You can't teleport to {}, {}.", x, y));
+        return;
+    }
+    let player = *ecs.fetch::<Entity>();
+    if let Some(pos) = ecs.write_storage::<Position>().get_mut(player) {
+        pos.x = x;
+        pos.y = y;
+    }
+    {
+        let mut map = ecs.write_resource::<Map>();
+        let idx = map.xy_idx(x, y);
+        map.move_entity(player, idx);
+    }
+    if let Some(vs) = ecs.write_storage::<Viewshed>().get_mut(player) {
+        vs.dirty = true;
+    }
+    *ecs.write_resource::<Point>() = Point::new(x, y);
+}
+
+fn set_stat(ecs : &mut World, stat : &str, value : i32) {
+    let player = *ecs.fetch::<Entity>();
+    let found = {
+        let mut stats = ecs.write_storage::<CombatStats>();
+        let mut attributes = ecs.write_storage::<Attributes>();
+        let mut purses = ecs.write_storage::<Purse>();
+        match stat {
+            "hp" => stats.get_mut(player).map(|s| s.hp = value).is_some(),
+            "maxhp" => stats.get_mut(player).map(|s| s.max_hp = value).is_some(),
+            "power" => stats.get_mut(player).map(|s| s.power = value).is_some(),
+            "defense" => stats.get_mut(player).map(|s| s.defense = value).is_some(),
+            "might" => attributes.get_mut(player).map(|a| a.might = value).is_some(),
+            "fitness" => attributes.get_mut(player).map(|a| a.fitness = value).is_some(),
+            "quickness" => attributes.get_mut(player).map(|a| a.quickness = value).is_some(),
+            "intelligence" => attributes.get_mut(player).map(|a| a.intelligence = value).is_some(),
+            "gold" => purses.get_mut(player).map(|p| p.gold = value).is_some(),
+            _ => false
+        }
+    };
+    if found {
+        log(ecs, format!("Your {} is now {}.", stat, value));
+    } else {
+        log(ecs, "Stats: hp, maxhp, power, defense, might, fitness, quickness, intelligence, gold".to_string());
+    }
+}